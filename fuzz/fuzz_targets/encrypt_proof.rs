@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pok3r_verify::EncryptProof;
+
+fuzz_target!(|data: &[u8]| {
+    // every element of a parsed proof was read from the input, so it is no
+    // larger than the input (ids may lose leading zero bytes on the way)
+    if let Ok(proof) = EncryptProof::from_bytes(data) {
+        assert!(proof.to_bytes().len() <= data.len());
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/message.rs"]
+#[allow(dead_code)]
+mod message;
+
+use message::*;
+
+fuzz_target!(|data: &[u8]| {
+    // a frame that decodes must survive being sent on again
+    if let Ok(msg) = decode_eval_net_msg(data, MAX_MESSAGE_SIZE) {
+        let frame = serde_json::to_vec(&msg).unwrap();
+        assert!(decode_eval_net_msg(&frame, usize::MAX).is_ok());
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pok3r_verify::PermutationProof;
+
+fuzz_target!(|data: &[u8]| {
+    // the layout has no redundancy, so whatever parses must serialize back
+    // to exactly the input
+    if let Ok(proof) = PermutationProof::from_bytes(data) {
+        assert_eq!(proof.to_bytes(), data);
+    }
+});
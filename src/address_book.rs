@@ -1,5 +1,6 @@
 use std::{fmt, collections::HashMap};
 
+#[derive(Clone)]
 pub struct Pok3rPeer {
     // base58 encoding of ed25519 pub key
     pub peer_id: Pok3rPeerId,
@@ -17,6 +18,40 @@ pub type Pok3rPeerId = String;
 
 pub type Pok3rAddrBook = HashMap<Pok3rPeerId, Pok3rPeer>;
 
+#[derive(Debug, PartialEq)]
+pub enum AddrBookError {
+    /// The same peer id appears more than once, which would give one
+    /// party two node ids.
+    DuplicatePeer {
+        /// The repeated peer id.
+        peer_id: Pok3rPeerId,
+    },
+    /// The address book has no peers at all.
+    Empty,
+}
+
+/// address book of peer_ids, with node ids 0, 1, ... assigned in list order
+pub fn build_addr_book(peer_ids: &[Pok3rPeerId]) -> Result<Pok3rAddrBook, AddrBookError> {
+    if peer_ids.is_empty() {
+        return Err(AddrBookError::Empty);
+    }
+
+    let mut output: Pok3rAddrBook = HashMap::new();
+    for (node_id, peer_id) in peer_ids.iter().enumerate() {
+        if output.contains_key(peer_id) {
+            return Err(AddrBookError::DuplicatePeer { peer_id: peer_id.clone() });
+        }
+
+        let peer = Pok3rPeer {
+            peer_id: peer_id.clone(),
+            node_id: node_id as u64,
+        };
+        output.insert(peer_id.clone(), peer);
+    }
+
+    Ok(output)
+}
+
 pub fn get_node_id_via_peer_id(
     addr_book: &Pok3rAddrBook, 
     peer_id: &Pok3rPeerId) -> Option<u64> {
@@ -26,11 +61,30 @@ pub fn get_node_id_via_peer_id(
     }
 }
 
-// pub fn get_peer_id_via_node_id(addr_book: &Pok3rAddrBook, node_id: u64) -> Option<Pok3rPeerId> {
-//     for (id, peer) in addr_book.iter() {
-//         if peer.node_id == node_id {
-//             return Some(id.clone());
-//         }
-//     }
-//     return None;
-// }
\ No newline at end of file
+pub fn get_peer_id_via_node_id(addr_book: &Pok3rAddrBook, node_id: u64) -> Option<Pok3rPeerId> {
+    for (id, peer) in addr_book.iter() {
+        if peer.node_id == node_id {
+            return Some(id.clone());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_addr_book_rejects_duplicate_peer() {
+        let peer_ids = vec![
+            String::from("12D3KooWPjceQrSwdWXPyLLeABRXmuqt69Rg3sBYbU1Nft9HyQ6X"),
+            String::from("12D3KooWH3uVF6wv47WnArKHk5p6cvgCJEb74UTmxztmQDc298L3"),
+            String::from("12D3KooWPjceQrSwdWXPyLLeABRXmuqt69Rg3sBYbU1Nft9HyQ6X"),
+        ];
+        assert_eq!(build_addr_book(&peer_ids).err(), Some(AddrBookError::DuplicatePeer {
+            peer_id: String::from("12D3KooWPjceQrSwdWXPyLLeABRXmuqt69Rg3sBYbU1Nft9HyQ6X")
+        }));
+
+        assert_eq!(build_addr_book(&[]).err(), Some(AddrBookError::Empty));
+    }
+}
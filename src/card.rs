@@ -0,0 +1,192 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// number of cards in a standard deck; the roots of unity ω^0 .. ω^51
+/// stand for cards, the remaining powers of ω are padding
+pub const NUM_CARDS: usize = 52;
+
+/// Rank of a card, in poker order (Two lowest, Ace highest)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rank {
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+    Ace,
+}
+
+/// Suit of a card; suits carry no value in poker, the order only
+/// makes Card totally ordered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Suit {
+    Clubs,
+    Diamonds,
+    Hearts,
+    Spades,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CardError {
+    /// The index does not correspond to one of the 52 cards.
+    IndexOutOfRange { index: usize },
+    /// The string is not a rank character followed by a suit character.
+    InvalidCard { card: String },
+}
+
+pub const RANKS: [Rank; 13] = [
+    Rank::Two, Rank::Three, Rank::Four, Rank::Five, Rank::Six, Rank::Seven, Rank::Eight,
+    Rank::Nine, Rank::Ten, Rank::Jack, Rank::Queen, Rank::King, Rank::Ace,
+];
+
+pub const SUITS: [Suit; 4] = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
+
+impl Rank {
+    /// 2 for Two up to 14 for Ace
+    pub fn value(self) -> u8 {
+        self as u8 + 2
+    }
+
+    /// like value, but with the Ace played low (as in the wheel A-2-3-4-5)
+    pub fn low_value(self) -> u8 {
+        match self {
+            Rank::Ace => 1,
+            _ => self.value(),
+        }
+    }
+
+    fn to_char(self) -> char {
+        b"23456789TJQKA"[self as usize] as char
+    }
+
+    fn from_char(c: char) -> Option<Rank> {
+        RANKS.iter().copied().find(|r| r.to_char() == c.to_ascii_uppercase())
+    }
+}
+
+impl Suit {
+    fn to_char(self) -> char {
+        b"cdhs"[self as usize] as char
+    }
+
+    fn from_char(c: char) -> Option<Suit> {
+        SUITS.iter().copied().find(|s| s.to_char() == c.to_ascii_lowercase())
+    }
+}
+
+/// Card is ordered by rank first, so sorting a hand sorts it in poker order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Card {
+    pub rank: Rank,
+    pub suit: Suit,
+}
+
+impl Card {
+    pub fn new(rank: Rank, suit: Suit) -> Self {
+        Card { rank, suit }
+    }
+
+    /// the card encoded by ω^index
+    pub fn from_index(index: usize) -> Result<Card, CardError> {
+        if index >= NUM_CARDS {
+            return Err(CardError::IndexOutOfRange { index });
+        }
+
+        Ok(Card { rank: RANKS[index % 13], suit: SUITS[index / 13] })
+    }
+
+    /// the exponent i such that ω^i encodes this card
+    pub fn to_index(self) -> usize {
+        (self.suit as usize) * 13 + (self.rank as usize)
+    }
+
+    /// all 52 cards, in index order
+    pub fn deck() -> Vec<Card> {
+        (0..NUM_CARDS).map(|i| Card::from_index(i).unwrap()).collect()
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.rank.to_char(), self.suit.to_char())
+    }
+}
+
+impl FromStr for Card {
+    type Err = CardError;
+
+    /// parses cards written as in Display, e.g. "As" or "Td"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CardError::InvalidCard { card: s.to_string() };
+
+        let mut chars = s.chars();
+        let (r, su) = match (chars.next(), chars.next(), chars.next()) {
+            (Some(r), Some(su), None) => (r, su),
+            _ => return Err(invalid()),
+        };
+
+        let rank = Rank::from_char(r).ok_or_else(invalid)?;
+        let suit = Suit::from_char(su).ok_or_else(invalid)?;
+        Ok(Card { rank, suit })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_rank_order() {
+        assert!(Rank::Ace > Rank::King);
+        assert!(Rank::Two < Rank::Three);
+        assert_eq!(Rank::Ace.value(), 14);
+        assert_eq!(Rank::Ace.low_value(), 1);
+        assert_eq!(Rank::King.low_value(), Rank::King.value());
+
+        let ace_of_clubs = Card::new(Rank::Ace, Suit::Clubs);
+        let king_of_spades = Card::new(Rank::King, Suit::Spades);
+        assert!(ace_of_clubs > king_of_spades);
+
+        let mut hand = [ace_of_clubs, Card::new(Rank::Two, Suit::Hearts), king_of_spades];
+        hand.sort();
+        assert_eq!(hand.iter().map(|c| c.rank).collect::<Vec<Rank>>(), vec![Rank::Two, Rank::King, Rank::Ace]);
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        assert_eq!(Card::new(Rank::Ace, Suit::Spades).to_string(), "As");
+        assert_eq!(Card::new(Rank::Ten, Suit::Diamonds).to_string(), "Td");
+        assert_eq!("9h".parse::<Card>(), Ok(Card::new(Rank::Nine, Suit::Hearts)));
+
+        for card in Card::deck() {
+            assert_eq!(card.to_string().parse::<Card>(), Ok(card));
+        }
+
+        assert!("1s".parse::<Card>().is_err());
+        assert!("Ax".parse::<Card>().is_err());
+        assert!("Ass".parse::<Card>().is_err());
+        assert!("".parse::<Card>().is_err());
+    }
+
+    #[test]
+    fn test_index_bijection() {
+        let deck = Card::deck();
+        assert_eq!(deck.len(), NUM_CARDS);
+        assert_eq!(deck.iter().collect::<HashSet<&Card>>().len(), NUM_CARDS);
+
+        for (i, card) in deck.iter().enumerate() {
+            assert_eq!(card.to_index(), i);
+            assert_eq!(Card::from_index(i), Ok(*card));
+        }
+
+        assert_eq!(Card::from_index(NUM_CARDS), Err(CardError::IndexOutOfRange { index: NUM_CARDS }));
+    }
+}
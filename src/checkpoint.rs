@@ -0,0 +1,100 @@
+use std::{fs, io, path::Path};
+
+use pok3r_verify::format::*;
+use crate::common::*;
+use crate::evaluator::{F, Share};
+
+/// what a party needs to continue a run after shuffle_deck or after
+/// compute_permutation_argument instead of starting over: its shares of
+/// the cards, the commitment to them once made, and where its gate and
+/// operation counters stood, so that fresh wires and reveals after
+/// resuming get the same names on every party.
+/// It holds this party's shares, so it must be kept as private as they are
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    /// wire handles of the cards, by position
+    pub handles: Vec<String>,
+    /// this party's shares of the cards, by position
+    pub shares: Vec<Share>,
+    /// the permutation argument, if the run got that far
+    pub perm_proof: Option<PermutationProof>,
+    pub(crate) gate_counter: u64,
+    pub(crate) op_counter: u64,
+}
+
+impl Checkpoint {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes);
+        write_element(&mut bytes, &self.gate_counter);
+        write_element(&mut bytes, &self.op_counter);
+
+        write_len(&mut bytes, self.handles.len());
+        for handle in &self.handles {
+            write_bytes(&mut bytes, handle.as_bytes());
+        }
+        write_len(&mut bytes, self.shares.len());
+        for share in Share::raw(&self.shares) {
+            write_element(&mut bytes, &share);
+        }
+
+        match &self.perm_proof {
+            Some(proof) => {
+                write_element(&mut bytes, &true);
+                write_bytes(&mut bytes, &proof.to_bytes());
+            },
+            None => write_element(&mut bytes, &false),
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofFormatError> {
+        let reader = &mut &bytes[..];
+        read_header(reader)?;
+        let gate_counter = read_element(reader)?;
+        let op_counter = read_element(reader)?;
+
+        let handles = (0..read_len(reader)?)
+            .map(|_| {
+                let bytes = read_bytes(reader)?;
+                String::from_utf8(bytes.to_vec()).map_err(|_| ProofFormatError::Malformed)
+            })
+            .collect::<Result<Vec<String>, ProofFormatError>>()?;
+        let shares = Share::from_raw(&read_vec::<F>(reader)?);
+        if shares.len() != handles.len() {
+            return Err(ProofFormatError::Malformed);
+        }
+
+        let perm_proof = match read_element(reader)? {
+            true => Some(PermutationProof::from_bytes(read_bytes(reader)?)?),
+            false => None,
+        };
+
+        finish(reader, Checkpoint { handles, shares, perm_proof, gate_counter, op_counter })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Checkpoint::from_bytes(&fs::read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err)))
+    }
+
+    /// hash of everything in the checkpoint the parties must agree on,
+    /// i.e. all of it but the shares
+    pub fn public_fingerprint(&self) -> [u8; 16] {
+        let mut bytes = Vec::new();
+        write_element(&mut bytes, &self.gate_counter);
+        write_element(&mut bytes, &self.op_counter);
+        for handle in &self.handles {
+            write_bytes(&mut bytes, handle.as_bytes());
+        }
+        if let Some(proof) = &self.perm_proof {
+            bytes.extend_from_slice(&proof.to_bytes());
+        }
+        fingerprint_of(&bytes)
+    }
+}
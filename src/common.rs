@@ -1,125 +1,180 @@
-use ark_serialize::CanonicalSerialize;
+use ark_ec::{pairing::Pairing, AffineRepr};
 use num_bigint::BigUint;
-use serde::{Serialize, Deserialize};
+use pok3r_verify::format::*;
 use crate::evaluator::*;
+use crate::kzg::UniversalParams;
 
-pub const PERM_SIZE: usize = 256;
+pub use crate::message::{EvalNetMsg, MAX_MESSAGE_SIZE};
+pub use pok3r_verify::encryption::{EncryptProof, EncryptTranscript};
+pub use pok3r_verify::format::{ProofFormatError, PROOF_FORMAT_VERSION};
+pub use pok3r_verify::permutation::{permutation_challenge_1, permutation_challenge_2, PermutationProof};
+pub use pok3r_verify::sigma::*;
+
+pub use pok3r_verify::PERM_SIZE;
 pub const DECK_SIZE: usize = 255;
 pub const LOG_PERM_SIZE: usize = 8;
-pub const NUM_SAMPLES: usize = 2000;
-
-/// EvalNetMsg represents the types of messages that
-/// we expect to flow between the evaluator and networkd
-#[derive(Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum EvalNetMsg {
-    ConnectionEstablished { success: bool },
-    Greeting { message: String },
-    PublishValue { 
-        sender: String,
-        handle: String,
-        value: String
-    },
-    PublishBatchValue { 
-        sender: String,
-        handles: Vec<String>,
-        values: Vec<String>
-    },
-    SendTriple { 
-        sender: String, 
-        receiver: String, 
-        handle_a: String, 
-        share_a: String,
-        handle_b: String,
-        share_b: String,
-        handle_c: String,
-        share_c: String,
-    },
+/// batched rounds a party has in flight at once, unless set with
+/// Evaluator::with_max_inflight; it also bounds what a peer may have
+/// buffered with us, see Evaluator::max_pending_per_peer
+pub const DEFAULT_MAX_INFLIGHT: usize = 64;
+/// rounds of values a peer may have buffered with us on top of the ones
+/// its batched rounds account for, e.g. single values it published
+pub const PENDING_SLACK_ROUNDS: usize = 4;
+/// how long dropping an Evaluator waits for its networking daemon to stop
+pub const NETWORK_SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// how long wait_until_ready waits for the Pongs of one round of pings
+/// before pinging the parties that did not answer again
+pub const PING_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// the protocol parameters this build supports; parties compare these
+/// before a game instead of failing halfway through it
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct Capabilities {
+    pub curve: &'static str,
+    pub deck_sizes: Vec<usize>,
+    pub perm_size: usize,
+    /// the utils::HashConfig choices for the Fiat-Shamir hash of combined
+    /// proofs, see prove_deal_in and Verifier::with_hash; their field
+    /// elements can be absorbed big-endian, see Verifier::with_field_encoding
+    pub hashes: Vec<&'static str>,
+    /// the SharingScheme choices
+    pub sharing_schemes: Vec<&'static str>,
+    pub proof_format_version: u8,
 }
 
-/// PermutationProof is a structure for the permutation proofs
-pub struct PermutationProof {
-    pub y1: F,
-    pub y2: F,
-    pub y3: F,
-    pub y4: F,
-    pub y5: F,
-    pub pi_1: G1,
-    pub pi_2: G1,
-    pub pi_3: G1,
-    pub pi_4: G1,
-    pub pi_5: G1,
-    pub f_com: G1,
-    pub q_com: G1,
-    pub t_com: G1,
+impl Capabilities {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
 }
 
-/// EncryptProof is a structure for the encryptions and attached proofs
-/// produced by encrypt_and_prove and verified by local_verify_encryption_proof
-pub struct EncryptProof {
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        curve: "bls12-377",
+        deck_sizes: vec![DECK_SIZE],
+        perm_size: PERM_SIZE,
+        hashes: vec!["sha256", "keccak256", "blake2b"],
+        sharing_schemes: vec!["additive", "shamir"],
+        proof_format_version: PROOF_FORMAT_VERSION,
+    }
+}
+
+/// fingerprint of an SRS, over its size and the elements that fix its
+/// trapdoor, so two different SRSs (almost surely) differ in it
+pub fn srs_fingerprint(pp: &UniversalParams<Curve>) -> [u8; 16] {
+    let mut bytes = Vec::new();
+    write_len(&mut bytes, pp.powers_of_g.len());
+    write_len(&mut bytes, pp.powers_of_h.len());
+    for g in pp.powers_of_g.iter().take(2) {
+        write_element(&mut bytes, g);
+    }
+    for h in pp.powers_of_h.iter().take(2) {
+        write_element(&mut bytes, h);
+    }
+    fingerprint_of(&bytes)
+}
+
+/// CombinedProof is a structure for a permutation proof and an encryption
+/// proof over the same shuffled deck, whose challenges are drawn from one
+/// CombinedTranscript: first the permutation argument's, then the
+/// encryption proof's. Neither proof verifies on its own
+#[derive(Clone)]
+pub struct CombinedProof {
+    pub perm: PermutationProof,
+    pub enc: EncryptProof,
+}
+
+impl CombinedProof {
+    /// the shared transcript absorbs f_com only once, for the permutation
+    /// argument, so the encryption proof must be about that same commitment
+    pub fn is_consistent(&self) -> bool {
+        self.perm.f_com == self.enc.card_commitment
+    }
+}
+
+/// GameBundle is a structure holding everything needed to check a game's
+/// deal after the fact: the committed deck, both proofs about it, and the
+/// public key and ids the cards were encrypted under
+#[derive(Clone)]
+pub struct GameBundle {
+    pub f_com: G1,
+    pub perm_proof: PermutationProof,
+    pub enc_proof: EncryptProof,
     pub pk: G2,
     pub ids: Vec<BigUint>,
-    pub card_commitment: G1,
-    pub masked_commitments: Vec<G1>,
-    pub masked_evals: Vec<F>,
-    pub eval_proofs: Vec<G1>,
-    pub ciphertexts: Vec<(G2,Gt)>,
-    pub sigma_proof: Option<SigmaProof>,
 }
 
-impl EncryptProof {
+impl GameBundle {
+    /// takes f_com, pk and ids from the proofs
+    pub fn new(perm_proof: PermutationProof, enc_proof: EncryptProof) -> Self {
+        GameBundle {
+            f_com: perm_proof.f_com,
+            pk: enc_proof.pk,
+            ids: enc_proof.ids.clone(),
+            perm_proof,
+            enc_proof,
+        }
+    }
+
+    /// whether f_com, pk and ids agree with what the proofs are about,
+    /// so checking the proofs checks the bundle's own fields
+    pub fn is_consistent(&self) -> bool {
+        self.f_com == self.perm_proof.f_com
+            && self.f_com == self.enc_proof.card_commitment
+            && self.pk == self.enc_proof.pk
+            && self.ids == self.enc_proof.ids
+    }
+
+    /// the proofs are nested as length-prefixed to_bytes outputs, each
+    /// with its own header
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
+        write_header(&mut bytes);
 
-        let mut pk_bytes = Vec::new();
-        self.pk.serialize_uncompressed(&mut pk_bytes).unwrap();
-        bytes.extend_from_slice(&pk_bytes);
-
-        for id in &self.ids {
-            bytes.extend_from_slice(&id.to_bytes_be());
+        write_element(&mut bytes, &self.f_com);
+        write_element(&mut bytes, &self.pk);
+        write_len(&mut bytes, self.ids.len());
+        for id in self.ids.iter() {
+            write_bytes(&mut bytes, &id.to_bytes_be());
         }
 
-        let mut card_commitment_bytes = Vec::new();
-        self.card_commitment.serialize_uncompressed(&mut card_commitment_bytes).unwrap();
-        bytes.extend_from_slice(&card_commitment_bytes);
+        write_bytes(&mut bytes, &self.perm_proof.to_bytes());
+        write_bytes(&mut bytes, &self.enc_proof.to_bytes());
 
-        for masked_commitment in &self.masked_commitments {
-            let mut masked_commitment_bytes = Vec::new();
-            masked_commitment.serialize_uncompressed(&mut masked_commitment_bytes).unwrap();
-            bytes.extend_from_slice(&masked_commitment_bytes);
-        }
+        bytes
+    }
 
-        for masked_eval in &self.masked_evals {
-            let mut masked_eval_bytes = Vec::new();
-            masked_eval.serialize_uncompressed(&mut masked_eval_bytes).unwrap();
-            bytes.extend_from_slice(&masked_eval_bytes);
-        }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofFormatError> {
+        let reader = &mut &bytes[..];
+        read_header(reader)?;
 
-        for eval_proof in &self.eval_proofs {
-            let mut eval_proof_bytes = Vec::new();
-            eval_proof.serialize_uncompressed(&mut eval_proof_bytes).unwrap();
-            bytes.extend_from_slice(&eval_proof_bytes);
+        let f_com = read_element(reader)?;
+        let pk = read_element(reader)?;
+        let mut ids = Vec::new();
+        for _ in 0..read_len(reader)? {
+            ids.push(BigUint::from_bytes_be(read_bytes(reader)?));
         }
 
-        for (ciphertext1, ciphertext2) in &self.ciphertexts {
-            let mut ciphertext1_bytes = Vec::new();
-            ciphertext1.serialize_uncompressed(&mut ciphertext1_bytes).unwrap();
-            bytes.extend_from_slice(&ciphertext1_bytes);
-
-            let mut ciphertext2_bytes = Vec::new();
-            ciphertext2.serialize_uncompressed(&mut ciphertext2_bytes).unwrap();
-            bytes.extend_from_slice(&ciphertext2_bytes);
-        }
+        let perm_proof = PermutationProof::from_bytes(read_bytes(reader)?)?;
+        let enc_proof = EncryptProof::from_bytes(read_bytes(reader)?)?;
 
-        bytes
+        let bundle = GameBundle { f_com, perm_proof, enc_proof, pk, ids };
+        finish(reader, bundle)
     }
 }
 
-/// SigmaProof is a structure for the sigma protocol proof
-pub struct SigmaProof {
-    pub a1: G1,
-    pub a2: G2,
-    pub a3: Gt,
-    pub x: F,
-    pub y: F,
-}
\ No newline at end of file
+/// PairingProof is a structure tying a revealed g2^x to a G1 commitment
+/// g1^x, checked with the single pairing equation e(com_g1, g2) == e(g1, g2^x)
+#[derive(Clone)]
+pub struct PairingProof {
+    pub com_g1: G1,
+    pub value_g2: G2,
+}
+
+impl PairingProof {
+    pub fn verify(&self) -> bool {
+        let lhs = <Curve as Pairing>::pairing(self.com_g1, G2::generator());
+        let rhs = <Curve as Pairing>::pairing(G1::generator(), self.value_g2);
+        lhs == rhs
+    }
+}
@@ -1,6 +1,5 @@
-
-use ark_ec::{Group, pairing::*};
-use ark_poly::DenseUVPolynomial;
+use ark_ec::Group;
+use ark_poly::{DenseUVPolynomial, Polynomial};
 use ark_poly::univariate::DenseOrSparsePolynomial;
 use ark_poly::univariate::DensePolynomial;
 use ark_std::UniformRand;
@@ -8,36 +7,350 @@ use ark_ff::{Field, /* FftField */ };
 use ark_serialize::{CanonicalSerialize, CanonicalDeserialize};
 use ark_ec::{pairing::Pairing, CurveGroup, AffineRepr};
 use ark_std::{Zero, One};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+use std::{fs::File, io::{self, BufRead, BufReader, Write}, path::Path, thread, time::{Duration, Instant}};
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use std::ops::*;
 use futures::{prelude::*, channel::*};
 use ark_std::io::Cursor;
 //use rand::{rngs::StdRng, SeedableRng};
 use sha2::{Sha256, Digest};
 use num_bigint::BigUint;
-use rand::{rngs::StdRng, SeedableRng};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
 
 use crate::address_book::*;
+use crate::checkpoint::Checkpoint;
 use crate::common::*;
 use crate::kzg::UniversalParams;
 use crate::utils;
 
-pub type Curve = ark_bls12_377::Bls12_377;
-//type KZG = KZG10::<Curve, DensePolynomial<<Curve as Pairing>::ScalarField>>;
-pub type F = ark_bls12_377::Fr;
-pub type G1 = <Curve as Pairing>::G1Affine;
-pub type G2 = <Curve as Pairing>::G2Affine;
-pub type Gt = PairingOutput<Curve>;
+pub use pok3r_verify::{Curve, F, G1, G2, Gt};
+
+/// this party's share of a wire: under additive sharing, a summand of
+/// the value, and under Shamir sharing, a point on its polynomial. It is
+/// no value by itself, and only opening the wire gives the plaintext F.
+/// Share arithmetic, e.g. interpolating a share polynomial, unwraps it
+/// explicitly with Share::raw
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Share(F);
+
+impl Share {
+    /// the field elements of shares, for local arithmetic on them whose
+    /// results are shares again
+    pub fn raw(shares: &[Share]) -> Vec<F> {
+        shares.iter().map(|share| share.0).collect()
+    }
+
+    /// shares this party kept as field elements, e.g. in a Checkpoint
+    pub(crate) fn from_raw(values: &[F]) -> Vec<Share> {
+        values.iter().map(|value| Share(*value)).collect()
+    }
+}
 
 macro_rules! send_over_network {
-    ($msg:expr, $tx:expr) => {
-        let r = $tx.send($msg).await;
-        if let Err(err) = r {
-            eprint!("evaluator error {:?}", err);
+    ($msg:expr, $evaluator:expr) => {
+        let msg = $msg;
+        $evaluator.messages_sent += 1;
+        $evaluator.log_to_transcript(TranscriptDirection::Sent, &msg);
+        match $evaluator.outbox.as_mut() {
+            Some(outbox) => outbox.push(msg),
+            None => {
+                let r = $evaluator.tx.send(msg).await;
+                if let Err(err) = r {
+                    eprint!("evaluator error {:?}", err);
+                }
+            },
         }
     };
 }
 
+#[derive(Debug, PartialEq)]
+pub enum EvaluatorError {
+    /// A party revealed a share that does not open the commitment
+    /// it broadcast earlier for the same wire.
+    CommitmentMismatch {
+        /// The peer id of the party whose reveal was inconsistent.
+        sender: Pok3rPeerId,
+    },
+
+    /// This node's peer id is not a key of the address book, so it
+    /// has no node id and cannot take part in the protocol.
+    SelfNotInAddrBook {
+        /// The peer id this node was started with.
+        id: Pok3rPeerId,
+        /// All peer ids the address book does contain, sorted.
+        known_ids: Vec<Pok3rPeerId>,
+    },
+
+    /// The run was aborted through a CancellationToken before it finished.
+    Cancelled,
+
+    /// The shared product polynomial checked by check_share_poly_mult does
+    /// not evaluate to the product of its factors at the random point.
+    ProductCheckFailed {
+        /// The label the check was run under.
+        label: String,
+    },
+
+    /// The shared polynomial checked under label does not vanish on the
+    /// subgroup, e.g. d(X) of a permutation argument over a deck that is
+    /// not a permutation of the cards.
+    VanishingCheckFailed {
+        /// The label the polynomial was committed under.
+        label: String,
+    },
+
+    /// A party uses an SRS with another fingerprint than this one, so the
+    /// sum of commitments from all parties would commit to nothing.
+    SrsMismatch {
+        /// The peer id of the first such party, by peer id.
+        sender: Pok3rPeerId,
+    },
+
+    /// A party resumed from a checkpoint of another point of the run, or
+    /// of another run, so the parties' wires no longer belong together.
+    CheckpointMismatch {
+        /// The peer id of the first such party, by peer id.
+        sender: Pok3rPeerId,
+    },
+
+    /// A party derived other Fiat-Shamir challenges from what should be
+    /// the same transcript, e.g. because it serializes an element differently.
+    ChallengeMismatch {
+        /// The label the challenges were checked under.
+        label: String,
+        /// The peer id of the first such party, by peer id.
+        sender: Pok3rPeerId,
+    },
+
+    /// A party sent two different values under the same name, so which
+    /// one it meant to contribute is ambiguous.
+    DuplicateContribution {
+        /// The operation key the values were sent under.
+        name: String,
+        /// The peer id of the party that sent both.
+        sender: Pok3rPeerId,
+    },
+
+    /// Some parties did not answer a ping before the timeout, e.g. as
+    /// their connection is half-open, so no round with them would finish.
+    PeersUnreachable {
+        /// The node ids of the parties that did not answer.
+        node_ids: Vec<usize>,
+    },
+
+    /// A position to open is not a position of the deck.
+    PositionOutOfRange {
+        /// The position asked for.
+        position: usize,
+        /// The number of cards in the deck.
+        deck_size: usize,
+    },
+
+    /// The combined opening of a deck position does not open the deck
+    /// commitment, so some party contributed a bad card share or proof.
+    OpeningMismatch {
+        /// The position whose opening failed.
+        position: usize,
+    },
+
+    /// The permutation argument commits to another deck than the one
+    /// committed to right after the shuffle, e.g. as it was resumed from
+    /// the checkpoint of another deck.
+    DeckCommitmentMismatch,
+
+    /// TripleSource::Distributed was asked for without Shamir sharing
+    /// of 2 * threshold < n, which its resharing of products needs.
+    UnsupportedTripleSource {
+        /// The sharing scheme the evaluator was set up with.
+        sharing: SharingScheme,
+        /// The number of parties.
+        num_parties: usize,
+    },
+
+    /// Shamir sharing was asked for with a threshold of at least the
+    /// number of parties, so no set of parties could open a wire.
+    UnsupportedSharingScheme {
+        /// The sharing scheme asked for.
+        sharing: SharingScheme,
+        /// The number of parties.
+        num_parties: usize,
+    },
+
+    /// The sharing scheme was switched after the triple source was
+    /// picked, whose triples depend on the scheme.
+    SharingSchemeAfterTripleSource,
+
+    /// Under Shamir sharing, ran hands out the random wires dealt ahead
+    /// of time by deal_random_shares, and none of them are left.
+    RandomPoolEmpty,
+
+    /// The deck has no multiplicative subgroup of exactly its size to
+    /// interpolate over, e.g. as it is no power of two.
+    Domain(utils::DomainError),
+}
+
+/// CancellationToken aborts a protocol run from outside, e.g. when a
+/// player disconnects: once cancelled, the evaluator's operations that
+/// return a Result fail with Err(EvaluatorError::Cancelled) as soon as they
+/// wait for a message, as do futures wrapped in cancellable; the others
+/// panic rather than wait forever. The channel towards the networking
+/// daemon is closed as well, which stops the daemon
+#[derive(Clone)]
+pub struct CancellationToken {
+    trigger: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    cancelled: future::Shared<oneshot::Receiver<()>>,
+    /// how many cancellable calls are running with this token
+    guards: Arc<AtomicUsize>,
+    tx: mpsc::UnboundedSender<EvalNetMsg>,
+}
+
+impl CancellationToken {
+    fn new(tx: mpsc::UnboundedSender<EvalNetMsg>) -> Self {
+        let (trigger, cancelled) = oneshot::channel();
+        CancellationToken {
+            trigger: Arc::new(Mutex::new(Some(trigger))),
+            cancelled: cancelled.shared(),
+            guards: Arc::new(AtomicUsize::new(0)),
+            tx,
+        }
+    }
+
+    pub fn cancel(&self) {
+        if let Some(trigger) = self.trigger.lock().unwrap().take() {
+            let _ = trigger.send(());
+        }
+        self.tx.close_channel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.trigger.lock().unwrap().is_none()
+    }
+}
+
+/// runs fut (typically a protocol step borrowing the evaluator) until it
+/// completes or token is cancelled, whichever comes first; on cancellation
+/// fut is dropped, releasing the evaluator
+pub async fn cancellable<T>(
+    token: &CancellationToken, 
+    fut: impl Future<Output = T>
+) -> Result<T, EvaluatorError> {
+    struct Guard<'a>(&'a AtomicUsize);
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) { self.0.fetch_sub(1, Ordering::SeqCst); }
+    }
+    token.guards.fetch_add(1, Ordering::SeqCst);
+    let _guard = Guard(&token.guards);
+
+    futures::pin_mut!(fut);
+    // check for cancellation first, so fut is not polled once cancelled
+    match future::select(token.cancelled.clone(), fut).await {
+        future::Either::Left(_) => Err(EvaluatorError::Cancelled),
+        future::Either::Right((output, _)) => Ok(output),
+    }
+}
+
+/// how an operation that cannot fail ends once the run was cancelled:
+/// under cancellable it waits to be dropped, as the cancellable around it
+/// resolves to Err(Cancelled); otherwise it panics, rather than waiting
+/// forever for messages that will never arrive
+async fn expect_running<T>(token: CancellationToken, result: Result<T, EvaluatorError>) -> T {
+    match result {
+        Ok(output) => output,
+        Err(_) if token.guards.load(Ordering::SeqCst) > 0 => {
+            // e.g. the network is gone, but the token was not cancelled yet
+            token.cancel();
+            future::pending().await
+        },
+        Err(err) => panic!("the run failed outside of cancellable: {:?}", err),
+    }
+}
+
+/// which way a message of a recorded transcript went
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TranscriptDirection {
+    Sent,
+    Received,
+}
+
+/// one line of a transcript written by Evaluator::record_transcript
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    /// microseconds since the recording started
+    pub elapsed_micros: u64,
+    pub direction: TranscriptDirection,
+    pub msg: EvalNetMsg,
+}
+
+/// parses a transcript written by Evaluator::record_transcript
+pub fn read_transcript(path: impl AsRef<Path>) -> io::Result<Vec<TranscriptEntry>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+/// number of beaver triples mult_auto fetches at once
+pub const TRIPLE_POOL_SIZE: usize = 64;
+/// most values published in one PublishBatchValue message
+const BATCH_ROUND_SIZE: usize = 256;
+/// values per round of batch_add_gt_elements_from_all_parties; a Gt
+/// element is large, and a round must fit in one message
+const GT_ROUND_SIZE: usize = 64;
+
+/// how wire values are split into shares among the parties
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SharingScheme {
+    /// shares sum to the value; opening needs every party (n-of-n)
+    #[default]
+    Additive,
+    /// party i holds p(i + 1) for a random p of degree threshold with
+    /// p(0) the value; any threshold + 1 parties can open a wire
+    Shamir { threshold: usize },
+}
+
+/// where beaver() and beaver_batch() get their triples from
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TripleSource {
+    /// a dealer everyone trusts hands out the triples; it is simulated by a
+    /// common seed, so triples cost no communication, but whoever knows the
+    /// seed knows every a, b and c, and so every value a mult masks with them
+    #[default]
+    Dealer,
+    /// the parties make the triples among themselves: no coalition of up to
+    /// threshold parties learns a, b or c. Costs two rounds per beaver_batch,
+    /// and needs Shamir sharing with 2 * threshold < n
+    Distributed,
+}
+
+/// multi-scalar multiplications this party has computed locally, counting
+/// commitments, eval proofs and secret-shared exponentiations
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MsmStats {
+    /// number of MSMs
+    pub count: usize,
+    /// number of scalars (and bases) over all MSMs
+    pub total_scalars: usize,
+}
+
+/// the long running stages of the protocol, as reported by Progress
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProgressPhase {
+    Shuffle,
+    Permutation,
+    Encryption,
+}
+
+/// step out of total steps of phase are done; steps of a phase only grow
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub phase: ProgressPhase,
+    pub step: usize,
+    pub total: usize,
+}
 
 pub struct Evaluator {
     /// local peer id
@@ -52,8 +365,56 @@ pub struct Evaluator {
     wire_shares: HashMap<String, F>,
     /// stores incoming messages indexed by identifier and then by peer id
     mailbox: HashMap<String, HashMap<String, String>>,
+    /// number of values each peer has waiting in the mailbox
+    pending_per_peer: HashMap<Pok3rPeerId, usize>,
+    /// identifiers some peer sent two different values for, and that peer
+    conflicts: HashMap<String, Pok3rPeerId>,
     /// keep track of gates
-    gate_counter: u64
+    gate_counter: u64,
+    /// keep track of group-element reveals, see operation_key
+    op_counter: u64,
+    /// how wire values are shared, and so how many shares open a wire
+    sharing: SharingScheme,
+    /// where beaver triples come from
+    triple_source: TripleSource,
+    /// beaver triples made ahead of time for mult_auto
+    triple_pool: VecDeque<(String, String, String)>,
+    /// number of times triple_pool was refilled
+    triple_pool_refills: usize,
+    /// random wires dealt by deal_random_shares, which ran hands out under
+    /// Shamir sharing
+    random_pool: VecDeque<String>,
+    /// keys shared with each other party, see pair_keys
+    pair_keys: Option<HashMap<usize, [u8; 32]>>,
+    /// source of this party's local randomness (shares from ran, commitment nonces)
+    rng: Box<dyn RngCore + Send>,
+    /// MSMs computed so far, see msm_stats
+    msm_stats: MsmStats,
+    /// number of messages this party has sent to the networkd
+    messages_sent: usize,
+    /// messages sent but not yet handed to the networkd, if sends are
+    /// buffered, see with_send_buffering
+    outbox: Option<Vec<EvalNetMsg>>,
+    /// whether the prover re-checks its own proofs, see with_sanity_checks
+    sanity_checks: bool,
+    /// where record_transcript logs messages to, and when it started
+    transcript: Option<(io::LineWriter<File>, Instant)>,
+    /// nonces of the wires committed with commit_wire and not revealed yet
+    commit_nonces: HashMap<String, [u8; 32]>,
+    /// aborts this party's run and stops its networking daemon
+    cancel_token: CancellationToken,
+    /// where report_progress sends Progress to, see subscribe_progress
+    progress: Option<mpsc::UnboundedSender<Progress>>,
+    /// thread of the networking daemon, joined on drop, see with_network_thread
+    network_thread: Option<thread::JoinHandle<()>>,
+    /// how many batched rounds may await their peers' values at once
+    max_inflight: usize,
+    /// batched rounds sent whose values are not all collected yet
+    inflight_rounds: usize,
+    /// the most rounds that were ever in flight at once
+    peak_inflight_rounds: usize,
+    /// number of ping_all calls, which tells their Pongs apart
+    ping_counter: u64,
 }
 
 impl Evaluator {
@@ -62,7 +423,15 @@ impl Evaluator {
         addr_book: Pok3rAddrBook,
         tx: mpsc::UnboundedSender<EvalNetMsg>, 
         mut rx: mpsc::UnboundedReceiver<EvalNetMsg>
-    ) -> Self {
+    ) -> Result<Self, EvaluatorError> {
+        // without an entry in the address book we have no node id,
+        // so fail before waiting on the network
+        if get_node_id_via_peer_id(&addr_book, id).is_none() {
+            let mut known_ids: Vec<Pok3rPeerId> = addr_book.keys().cloned().collect();
+            known_ids.sort();
+            return Err(EvaluatorError::SelfNotInAddrBook { id: id.clone(), known_ids });
+        }
+
         // we expect the first message from the 
         // networkd to be a connection established;
         // so, here we will loop till we get that
@@ -80,32 +449,393 @@ impl Evaluator {
             }
         }
 
-        Evaluator {
+        let cancel_token = CancellationToken::new(tx.clone());
+
+        Ok(Evaluator {
             id: id.clone(), 
             addr_book, 
             tx, 
             rx,
             wire_shares: HashMap::new(),
             mailbox: HashMap::new(),
-            gate_counter: 0
+            pending_per_peer: HashMap::new(),
+            conflicts: HashMap::new(),
+            gate_counter: 0,
+            op_counter: 0,
+            sharing: SharingScheme::Additive,
+            triple_source: TripleSource::Dealer,
+            triple_pool: VecDeque::new(),
+            triple_pool_refills: 0,
+            random_pool: VecDeque::new(),
+            pair_keys: None,
+            rng: Box::new(StdRng::from_entropy()),
+            msm_stats: MsmStats::default(),
+            messages_sent: 0,
+            outbox: None,
+            transcript: None,
+            sanity_checks: cfg!(debug_assertions),
+            commit_nonces: HashMap::new(),
+            cancel_token,
+            progress: None,
+            network_thread: None,
+            max_inflight: DEFAULT_MAX_INFLIGHT,
+            inflight_rounds: 0,
+            peak_inflight_rounds: 0,
+            ping_counter: 0,
+        })
+    }
+
+    /// replaces the OS-seeded randomness source, e.g. with a seeded rng
+    /// so that tests produce the same shares on every run. beaver() is not
+    /// affected under TripleSource::Dealer, as its triples come from the
+    /// (simulated) common dealer.
+    pub fn with_rng(mut self, rng: impl RngCore + Send + 'static) -> Self {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// hands over the thread running the networking daemon behind tx and rx,
+    /// so that dropping the evaluator stops the daemon and joins the thread
+    /// instead of leaving it blocked on its channel
+    pub fn with_network_thread(mut self, handle: thread::JoinHandle<()>) -> Self {
+        self.network_thread = Some(handle);
+        self
+    }
+
+    /// switches how wires are shared; all parties must pick the same scheme
+    /// before creating any wire, as shares of different schemes do not mix
+    pub fn with_sharing_scheme(mut self, sharing: SharingScheme) -> Result<Self, EvaluatorError> {
+        let n = self.num_parties();
+        if let SharingScheme::Shamir { threshold } = sharing {
+            if threshold >= n {
+                return Err(EvaluatorError::UnsupportedSharingScheme { sharing, num_parties: n });
+            }
+        }
+        if self.triple_source != TripleSource::Dealer {
+            return Err(EvaluatorError::SharingSchemeAfterTripleSource);
+        }
+        self.sharing = sharing;
+        Ok(self)
+    }
+
+    /// switches where beaver triples come from; all parties must pick the
+    /// same source, see TripleSource for what each one trusts. Distributed
+    /// triples need the sharing scheme to be picked first, as Shamir with
+    /// 2 * threshold < n
+    pub fn with_triple_source(mut self, triple_source: TripleSource) -> Result<Self, EvaluatorError> {
+        if triple_source == TripleSource::Distributed {
+            let n = self.num_parties();
+            match self.sharing {
+                SharingScheme::Shamir { threshold } if 2 * threshold < n => (),
+                sharing => return Err(EvaluatorError::UnsupportedTripleSource { sharing, num_parties: n }),
+            }
+        }
+        self.triple_source = triple_source;
+        Ok(self)
+    }
+
+    /// turns the prover-side sanity checks (e.g. that d(X) of the permutation
+    /// argument vanishes) on or off; they cost extra rounds, so by default
+    /// they only run in builds with debug assertions
+    pub fn with_sanity_checks(mut self, enabled: bool) -> Self {
+        self.sanity_checks = enabled;
+        self
+    }
+
+    pub fn sanity_checks(&self) -> bool {
+        self.sanity_checks
+    }
+
+    /// caps how many batched rounds of e.g. batch_output_wire are in
+    /// flight at once: round k + limit is only published once the values
+    /// of round k came in from all peers, which bounds the messages
+    /// buffered for (and by) every peer. DEFAULT_MAX_INFLIGHT by default;
+    /// all parties must pick the same limit, as values a peer sends beyond
+    /// max_pending_per_peer are dropped
+    pub fn with_max_inflight(mut self, limit: usize) -> Self {
+        assert!(limit > 0, "at least one round must be allowed in flight");
+        self.max_inflight = limit;
+        self
+    }
+
+    /// queues the messages this party sends until flush, so that the shares
+    /// of a batch are handed to the networkd together; they are flushed at
+    /// the latest when the evaluator waits on its peers
+    pub fn with_send_buffering(mut self, enabled: bool) -> Self {
+        assert!(
+            self.outbox.as_ref().map_or(true, Vec::is_empty),
+            "queued messages must be flushed before send buffering is changed"
+        );
+        self.outbox = enabled.then(Vec::new);
+        self
+    }
+
+    /// most values a peer may have buffered with us before they are
+    /// collected: an honest peer publishes at most max_inflight rounds
+    /// ahead of us, and we collect at most max_inflight rounds behind
+    /// what we published, so anything beyond (plus some slack) is a flood
+    pub fn max_pending_per_peer(&self) -> usize {
+        (2 * self.max_inflight + PENDING_SLACK_ROUNDS) * BATCH_ROUND_SIZE
+    }
+
+    /// the most batched rounds that were in flight at once so far
+    pub fn peak_inflight_rounds(&self) -> usize {
+        self.peak_inflight_rounds
+    }
+
+    /// number of messages this party has sent so far
+    pub fn messages_sent(&self) -> usize {
+        self.messages_sent
+    }
+
+    /// a channel of Progress events for e.g. a UI; sending never blocks
+    /// the protocol, and events are dropped while nobody subscribed. A
+    /// new subscription replaces the previous one
+    pub fn subscribe_progress(&mut self) -> mpsc::UnboundedReceiver<Progress> {
+        let (tx, rx) = mpsc::unbounded();
+        self.progress = Some(tx);
+        rx
+    }
+
+    /// tells the subscriber, if any, that step out of total is done
+    pub fn report_progress(&self, phase: ProgressPhase, step: usize, total: usize) {
+        if let Some(tx) = &self.progress {
+            // a dropped receiver just means nobody is watching any more
+            let _ = tx.unbounded_send(Progress { phase, step, total });
+        }
+    }
+
+    /// hands the messages queued by with_send_buffering to the networking
+    /// daemon and waits until they are dispatched; call it before a
+    /// synchronization point that another party waits on, e.g. before
+    /// awaiting anything but the evaluator, so no share is left queued
+    pub async fn flush(&mut self) {
+        let queued = self.outbox.as_mut().map(std::mem::take).unwrap_or_default();
+        for msg in queued {
+            if let Err(err) = self.tx.send(msg).await {
+                eprint!("evaluator error {:?}", err);
+            }
+        }
+
+        if let Err(err) = self.tx.flush().await {
+            eprint!("evaluator error {:?}", err);
+        }
+    }
+
+    /// from now on, writes every message this party sends or receives to
+    /// path, one JSON TranscriptEntry per line; see replay_transcript
+    pub fn record_transcript(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = File::create(path)?;
+        self.transcript = Some((io::LineWriter::new(file), Instant::now()));
+        Ok(())
+    }
+
+    /// disconnects this party from the network and instead delivers the
+    /// messages it received in a transcript of record_transcript, in their
+    /// recorded order. Rerunning the recorded steps with the same rng seed
+    /// then reproduces the run without any live peers. Returns the receiver
+    /// of everything the replaying party sends, to compare against the
+    /// recorded sends; a replay that diverges waits forever for a message.
+    pub fn replay_transcript(&mut self, path: impl AsRef<Path>) -> io::Result<mpsc::UnboundedReceiver<EvalNetMsg>> {
+        let (replay_tx, replay_rx) = mpsc::unbounded::<EvalNetMsg>();
+        for entry in read_transcript(path)? {
+            if entry.direction == TranscriptDirection::Received {
+                replay_tx.unbounded_send(entry.msg).expect("receiver is alive");
+            }
+        }
+
+        let (sent_tx, sent_rx) = mpsc::unbounded::<EvalNetMsg>();
+        self.rx = replay_rx;
+        self.tx = sent_tx;
+        self.cancel_token = CancellationToken::new(self.tx.clone());
+        Ok(sent_rx)
+    }
+
+    fn log_to_transcript(&mut self, direction: TranscriptDirection, msg: &EvalNetMsg) {
+        if let Some((writer, start)) = self.transcript.as_mut() {
+            let entry = TranscriptEntry {
+                elapsed_micros: start.elapsed().as_micros() as u64,
+                direction,
+                msg: msg.clone(),
+            };
+            let r = serde_json::to_writer(&mut *writer, &entry)
+                .map_err(io::Error::from)
+                .and_then(|_| writer.write_all(b"\n"));
+            if let Err(err) = r {
+                eprint!("transcript error {:?}", err);
+            }
+        }
+    }
+
+    /// whether this party adds public constants to its share: under additive
+    /// sharing only party 0 does, under Shamir sharing every party does
+    fn adds_public_constants(&self) -> bool {
+        match self.sharing {
+            SharingScheme::Additive => self.node_id() == 0,
+            SharingScheme::Shamir { .. } => true,
         }
     }
 
-    pub async fn test_networking(&mut self) {
-        let greeting = EvalNetMsg::Greeting { message: format!("Hello from {}", self.id) };
-        send_over_network!(greeting, self.tx);
+    /// this party's share of a Shamir sharing of value dealt from rng,
+    /// which the (simulated) dealer seeds identically for all parties
+    fn deal_shamir_share(&self, value: &F, threshold: usize, rng: &mut StdRng) -> F {
+        utils::compute_shamir_shares(value, threshold, self.num_parties(), rng)[self.node_id()]
+    }
+
+    /// MSMs this party has computed so far
+    pub fn msm_stats(&self) -> MsmStats {
+        self.msm_stats
+    }
+
+    fn record_msm(&mut self, num_scalars: usize) {
+        self.msm_stats.count += 1;
+        self.msm_stats.total_scalars += num_scalars;
+    }
+
+    /// utils::commit_poly, counted in msm_stats
+    pub fn commit_poly(&mut self, pp: &UniversalParams<Curve>, f: &DensePolynomial<F>) -> G1 {
+        self.record_msm(f.coeffs.len());
+        utils::commit_poly(pp, f)
+    }
+
+    /// a handle that aborts this party's run from another task,
+    /// see cancellable
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+
+    /// aborts this party's run, see CancellationToken
+    pub fn cancel(&self) {
+        self.cancel_token.cancel();
+    }
+
+    /// this party's node id; party 0 is the one adding public constants
+    pub fn node_id(&self) -> usize {
+        // new() rejects ids missing from the address book
+        get_node_id_via_peer_id(&self.addr_book, &self.id).unwrap() as usize
+    }
+
+    /// number of parties in the protocol, this one included
+    pub fn num_parties(&self) -> usize {
+        self.addr_book.len()
+    }
+
+    /// peer ids of all parties, this one included, ordered by node id
+    pub fn peer_ids(&self) -> Vec<Pok3rPeerId> {
+        let mut peers: Vec<&Pok3rPeer> = self.addr_book.values().collect();
+        peers.sort_by_key(|p| p.node_id);
+        peers.into_iter().map(|p| p.peer_id.clone()).collect()
+    }
+
+    /// checks all-to-all connectivity for however many parties the address
+    /// book holds: waits for a greeting from every other party, then opens
+    /// a random value, which needs a share from everyone
+    pub async fn test_networking(&mut self) -> Result<(), EvaluatorError> {
+        let greeting = EvalNetMsg::Greeting {
+            sender: self.id.clone(),
+            message: format!("Hello from {}", self.id)
+        };
+        send_over_network!(greeting, self);
 
         //we expect greetings from all other players
-        let num_other_parties = self.addr_book.len() - 1;
-        for _ in 0..num_other_parties {
-            let msg: EvalNetMsg = self.rx.select_next_some().await;
+        let mut waiting_on: HashSet<String> = self.addr_book.keys()
+            .filter(|id| **id != self.id)
+            .cloned()
+            .collect();
+        while !waiting_on.is_empty() {
+            let msg = self.next_message().await;
+            let msg: EvalNetMsg = expect_running(self.cancel_token.clone(), msg).await;
             match msg {
-                EvalNetMsg::Greeting { message } => { println!("evaluator received: {:?}", message); },
-                _ => continue,
+                EvalNetMsg::Greeting { sender, message } => {
+                    println!("evaluator received: {:?}", message);
+                    waiting_on.remove(&sender);
+                },
+                // a faster peer may already be in the round below
+                _ => self.process_next_message(&msg),
+            }
+        }
+
+        let h_r = self.ran()?;
+        let _ = self.output_wire(&h_r).await;
+        println!("all {} parties connected", self.num_parties());
+        Ok(())
+    }
+
+    /// pings every other party and waits up to timeout for their Pongs;
+    /// returns (node id, whether it answered) for each of them. A party
+    /// only answers while it handles messages, i.e. inside a round or a
+    /// ping_all of its own
+    pub async fn ping_all(&mut self, timeout: Duration) -> Result<Vec<(usize, bool)>, EvaluatorError> {
+        self.ping_counter += 1;
+        let nonce = format!("ping{}", self.ping_counter);
+        let ping = EvalNetMsg::Ping { sender: self.id.clone(), nonce: nonce.clone() };
+        send_over_network!(ping, self);
+
+        let mut waiting_on: HashSet<String> = self.addr_book.keys()
+            .filter(|id| **id != self.id)
+            .cloned()
+            .collect();
+        let deadline = Instant::now() + timeout;
+        while !waiting_on.is_empty() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let msg = match async_std::future::timeout(remaining, self.next_message()).await {
+                Ok(msg) => msg?,
+                Err(_) => break,
+            };
+            match msg {
+                EvalNetMsg::Pong { sender, receiver, nonce: pong_nonce }
+                    if receiver == self.id && pong_nonce == nonce => {
+                    waiting_on.remove(&sender);
+                },
+                _ => self.process_next_message(&msg),
+            }
+        }
+
+        Ok(self.peer_ids()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, peer_id)| *peer_id != self.id)
+            .map(|(node_id, peer_id)| (node_id, !waiting_on.contains(&peer_id)))
+            .collect())
+    }
+
+    /// pings the other parties every PING_INTERVAL until all of them have
+    /// answered, so a dead peer shows up here and not as a round that
+    /// never finishes; fails with the ones still silent after timeout
+    pub async fn wait_until_ready(&mut self, timeout: Duration) -> Result<(), EvaluatorError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let unreachable: Vec<usize> = self.ping_all(PING_INTERVAL).await?
+                .into_iter()
+                .filter(|(_, answered)| !answered)
+                .map(|(node_id, _)| node_id)
+                .collect();
+            if unreachable.is_empty() {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(EvaluatorError::PeersUnreachable { node_ids: unreachable });
             }
         }
     }
 
+    /// the mailbox key of the next group-element reveal: unique to the
+    /// operation, as every party reveals in the same order, with label
+    /// kept only so transcripts stay readable. Two reveals given the same
+    /// (or no, i.e. empty) label thus never mix each other's shares
+    fn operation_key(&mut self, label: &str) -> String {
+        self.op_counter += 1;
+        if label.is_empty() {
+            format!("op{}", self.op_counter)
+        } else {
+            format!("op{}/{}", self.op_counter, label)
+        }
+    }
+
+    fn operation_keys(&mut self, labels: &[String]) -> Vec<String> {
+        labels.iter().map(|l| self.operation_key(l)).collect()
+    }
+
     fn compute_fresh_wire_label(&mut self) -> String {
         self.gate_counter += 1;
 
@@ -122,58 +852,95 @@ impl Evaluator {
     
 
     /// asks the pre-processor to generate an additive sharing of a random value
-    /// returns a string handle, which can be used to access the share in future
-    pub fn ran(&mut self) -> String {
-        let r = F::rand(&mut rand::thread_rng());
+    /// returns a string handle, which can be used to access the share in future;
+    /// under Shamir sharing the wire comes from the ones dealt ahead of time
+    /// by deal_random_shares, as a share no party picks alone needs a round,
+    /// and it fails with RandomPoolEmpty once they are used up
+    pub fn ran(&mut self) -> Result<String, EvaluatorError> {
+        if let SharingScheme::Shamir { .. } = self.sharing {
+            return self.random_pool
+                .pop_front()
+                .ok_or(EvaluatorError::RandomPoolEmpty);
+        }
 
         let handle = self.compute_fresh_wire_label();
+        let r = F::rand(&mut self.rng);
         self.wire_shares.insert(handle.clone(), r);
-        handle
+        Ok(handle)
     }
 
-    /// returns shares of a random element in {1, ω, ..., ω^63}
-    pub async fn ran_64(&mut self, h_a: &String) -> String {
-        let h_c =  self.compute_fresh_wire_label();
+    /// count handles as by ran
+    fn batch_ran(&mut self, count: usize) -> Result<Vec<String>, EvaluatorError> {
+        (0..count).map(|_| self.ran()).collect()
+    }
 
-        let h_a_exp_64 = self.exp(h_a).await;
-        let a_exp_64 = self.output_wire(&h_a_exp_64).await;
-    
-        if a_exp_64 == F::from(0) {
-            panic!("Highly improbable event occurred. Abort!");
+    /// under Shamir sharing, deals count random wires for ran() to hand out:
+    /// every party deals a sharing of its own random value, and each wire is
+    /// their sum, which no party knows unless all of them collude. Under
+    /// additive sharing ran() needs no rounds, and this does nothing
+    pub async fn deal_random_shares(&mut self, count: usize) {
+        if let SharingScheme::Shamir { .. } = self.sharing {
+            let handles: Vec<String> = (0..count).map(|_| self.compute_fresh_wire_label()).collect();
+            self.deal_random_sharings(&handles).await;
+            self.random_pool.extend(handles);
         }
-    
-        let mut l = a_exp_64;
-        for _ in 0..6 {
-            l = utils::compute_root(&l);
+    }
+
+    /// like ran(), but the shared value is guaranteed to be nonzero, so it
+    /// is safe to use as an inversion mask
+    pub async fn ran_nonzero(&mut self) -> Result<String, EvaluatorError> {
+        Ok(self.batch_ran_nonzero(1).await?.pop().unwrap())
+    }
+
+    /// len handles as by ran_nonzero: every [r] is multiplied by a fresh
+    /// random [s] and r·s opened, which reveals only that r (and s) is
+    /// nonzero; a zero product is thrown away and drawn again
+    pub async fn batch_ran_nonzero(&mut self, len: usize) -> Result<Vec<String>, EvaluatorError> {
+        let mut output: Vec<String> = Vec::with_capacity(len);
+
+        // the products are public, so every party retries the same ones
+        while output.len() < len {
+            let missing = len - output.len();
+            let h_rs = self.batch_ran(missing)?;
+            let h_ss = self.batch_ran(missing)?;
+
+            let h_rs_mult_ss = self.batch_mult(&h_rs, &h_ss).await;
+            let rs_mult_ss = self.batch_output_wire(&h_rs_mult_ss).await;
+
+            output.extend(h_rs
+                .into_iter()
+                .zip(rs_mult_ss)
+                .filter(|(_, r_mult_s)| !r_mult_s.is_zero())
+                .map(|(h_r, _)| h_r));
         }
 
-        let share_c = self.get_wire(h_a) / l;
-        self.wire_shares.insert(h_c.clone(), share_c);
-        h_c
+        Ok(output)
     }
 
-    pub async fn batch_ran_64(&mut self, len: usize) -> Vec<String> {
-        let mut h_c = Vec::new();
-        let h_as = (0..len)
-            .into_iter()
-            .map(|_| self.ran())
-            .collect::<Vec<String>>();
+    /// returns shares of a random element in {1, ω, ..., ω^(PERM_SIZE-1)}
+    pub async fn ran_64(&mut self, h_a: &String) -> String {
+        self.ran_64_batch(std::slice::from_ref(h_a)).await.remove(0)
+    }
 
-        let h_a_exp_64s = self.batch_exp(&h_as).await;
+    /// ran_64 of every handle in a_handles, taking the rounds of one call:
+    /// the a^PERM_SIZE are computed and opened together
+    pub async fn ran_64_batch(&mut self, a_handles: &[String]) -> Vec<String> {
+        let h_a_exp_64s = self.batch_exp(a_handles).await;
         let a_exp_64s = self.batch_output_wire(&h_a_exp_64s).await;
 
-        for i in 0..len {
-            if a_exp_64s[i] == F::from(0) {
+        let mut h_c = Vec::new();
+        for (h_a, a_exp_64) in a_handles.iter().zip(a_exp_64s) {
+            if a_exp_64 == F::from(0) {
                 panic!("Highly improbable event occurred. Abort!");
             }
-        
-            let mut l = a_exp_64s[i];
+
+            let mut l = a_exp_64;
             for _ in 0..LOG_PERM_SIZE {
                 l = utils::compute_root(&l);
             }
 
             let handle = self.compute_fresh_wire_label();
-            let share_c = self.get_wire(&h_as[i]) / l;
+            let share_c = self.local_share(h_a) / l;
             self.wire_shares.insert(handle.clone(), share_c);
             h_c.push(handle);
         }
@@ -181,78 +948,145 @@ impl Evaluator {
         h_c
     }
 
+    pub async fn batch_ran_64(&mut self, len: usize) -> Result<Vec<String>, EvaluatorError> {
+        let h_as = self.batch_ran(len)?;
+
+        Ok(self.ran_64_batch(&h_as).await)
+    }
+
     /// outputs the wire label denoting the [x] + [y]
     pub fn add(&mut self, 
         handle_x: &String, 
         handle_y: &String) -> String {
         let handle =  self.compute_fresh_wire_label();
 
-        let share_x = self.get_wire(handle_x);
-        let share_y = self.get_wire(handle_y);
+        let share_x = self.local_share(handle_x);
+        let share_y = self.local_share(handle_y);
 
         self.wire_shares.insert(handle.clone(), share_x + share_y);
         handle
     }
+
+    /// outputs the wire label denoting [x] - [y]
+    pub fn sub(&mut self, 
+        handle_x: &String, 
+        handle_y: &String) -> String {
+        let handle =  self.compute_fresh_wire_label();
+
+        let share_x = self.local_share(handle_x);
+        let share_y = self.local_share(handle_y);
+
+        self.wire_shares.insert(handle.clone(), share_x - share_y);
+        handle
+    }
+
+    /// outputs the wire label denoting -[x]; every party negates its own
+    /// share, under either sharing scheme
+    pub fn negate(&mut self, handle_x: &String) -> String {
+        let handle =  self.compute_fresh_wire_label();
+
+        let share_x = self.local_share(handle_x);
+
+        self.wire_shares.insert(handle.clone(), -share_x);
+        handle
+    }
     
     pub async fn inv(&mut self, 
         handle_in: &String
-    ) -> String {
+    ) -> Result<String, EvaluatorError> {
+        Ok(self.batch_inv(std::slice::from_ref(handle_in)).await?.remove(0))
+    }
+
+    /// inv of every handle in input_handles, which must all be nonzero
+    pub async fn batch_inv(&mut self, 
+        input_handles: &[String]
+    ) -> Result<Vec<String>, EvaluatorError> {
         // goal: compute inv([s])
-        // step 1: invoke ran_p to obtain [r]
+        // step 1: invoke ran to obtain [r]
         // step 2: invoke mult to get [q] = [r . s]
         // step 3: reconstruct q = r . s
         // step 4: return [r] / q
-        
-        let handle_r = self.ran();
-        let handle_out = self.compute_fresh_wire_label();
-        
-        let handle_r_mult_s = self.mult(
-            handle_in, 
-            &handle_r).await;
-        //reconstruct the padded wires in the clear
-        let r_mult_s = self.output_wire(&handle_r_mult_s).await;
+        // a zero q means r was zero, as s is not; only those are drawn again
 
-        let q_inv = F::from(1) / r_mult_s;
-        let wire_out = q_inv * self.get_wire(&handle_r);
+        let mut output: Vec<Option<String>> = vec![None; input_handles.len()];
+        let mut pending: Vec<usize> = (0..input_handles.len()).collect();
+
+        for attempt in 0..2 {
+            if pending.is_empty() {
+                break;
+            }
+            let handles: Vec<String> = pending.iter().map(|&i| input_handles[i].clone()).collect();
+            let rand_handles = self.batch_ran(pending.len())?;
+
+            let masked_handles = self.batch_mult(&handles, &rand_handles).await;
+            let masked_values = self.batch_output_wire(&masked_handles).await;
+
+            let mut retry = vec![];
+            for ((i, h_r), q) in pending.into_iter().zip(rand_handles).zip(masked_values) {
+                let q_inv = match q.inverse() {
+                    Some(q_inv) => q_inv,
+                    None => {
+                        retry.push(i);
+                        continue;
+                    },
+                };
+                let handle_out = self.compute_fresh_wire_label();
+                let wire_out = q_inv * self.local_share(&h_r);
+                self.wire_shares.insert(handle_out.clone(), wire_out);
+                output[i] = Some(handle_out);
+            }
+            pending = retry;
 
-        self.wire_shares.insert(handle_out.clone(), wire_out);
+            // two zero products of the same s and fresh masks: s is zero
+            if attempt == 1 && !pending.is_empty() {
+                panic!("inverting a wire that holds zero");
+            }
+        }
 
-        handle_out
+        Ok(output.into_iter().map(Option::unwrap).collect())
     }
 
-    pub async fn batch_inv(&mut self, 
-        input_handles: &[String]
-    ) -> Vec<String> {
-        // goal: compute inv([s])
-        // step 1: invoke ran_p to obtain [r]
-        // step 2: invoke mult to get [q] = [r . s]
-        // step 3: reconstruct q = r . s
-        // step 4: return [r] / q
-        
-        let rand_handles: Vec<String> = (0..input_handles.len())
-            .into_iter()
-            .map(|_| self.ran())
-            .collect();
+    /// evaluates the PRF y = g^{1 / (sk + x)} on a public input x,
+    /// where [sk] is a shared key; only y is revealed
+    pub async fn oprf_eval(&mut self, 
+        sk: &String, 
+        input: F
+    ) -> Result<G1, EvaluatorError> {
+        let denom = self.clear_add(sk, input);
+        let t = self.inv(&denom).await?;
+        Ok(self.output_wire_in_exponent(&t).await)
+    }
 
-        let masked_handles = self.batch_mult(
-            input_handles, 
-            &rand_handles
-        ).await;
-        
-        let masked_values = self.batch_output_wire(&masked_handles).await;
+    /// batched oprf_eval over many public inputs, using a single inversion round
+    pub async fn batch_oprf_eval(&mut self, 
+        sk: &String, 
+        inputs: &[F]
+    ) -> Result<Vec<G1>, EvaluatorError> {
+        let denoms = inputs
+            .iter()
+            .map(|x| self.clear_add(sk, *x))
+            .collect::<Vec<String>>();
 
-        let mut output: Vec<String> = vec![];
-        for i in 0..input_handles.len() {
-            let q_inv = F::from(1) / masked_values[i];
-            let wire_out = q_inv * self.get_wire(&rand_handles[i]);
+        self.batch_oprf_eval_on_denominators(&denoms).await
+    }
 
-            let handle_out = self.compute_fresh_wire_label();
-            self.wire_shares.insert(handle_out.clone(), wire_out);
+    /// batched PRF evaluation on secret-shared inputs [x_i], revealing only
+    /// y_i = g^{1 / (sk + x_i)}
+    pub async fn batch_oprf_eval_shared(&mut self, 
+        sk: &String, 
+        input_handles: &[String]
+    ) -> Result<Vec<G1>, EvaluatorError> {
+        let denoms = input_handles
+            .iter()
+            .map(|h| self.add(h, sk))
+            .collect::<Vec<String>>();
 
-            output.push(handle_out);
-        }
+        self.batch_oprf_eval_on_denominators(&denoms).await
+    }
 
-        output
+    async fn batch_oprf_eval_on_denominators(&mut self, denoms: &[String]) -> Result<Vec<G1>, EvaluatorError> {
+        let t_is = self.batch_inv(denoms).await?;
+        Ok(self.batch_output_wire_in_exponent(&t_is).await)
     }
 
     // Adds [x] to y in the clear and outputs handle to the resulting share
@@ -262,12 +1096,11 @@ impl Evaluator {
     ) -> String {
         let handle_out = self.compute_fresh_wire_label();
 
-        let x = self.get_wire(&handle_x);
+        let x = self.local_share(handle_x);
 
-        let my_id = get_node_id_via_peer_id(&self.addr_book, &self.id).unwrap();
-        let clear_add_share: F = match my_id {
-            0 => {x + y}
-            _ => {x}
+        let clear_add_share: F = match self.adds_public_constants() {
+            true => {x + y}
+            false => {x}
         };
 
         self.wire_shares.insert(handle_out.clone(), clear_add_share);
@@ -275,20 +1108,31 @@ impl Evaluator {
         handle_out
     }
 
-    // Scales [x] by scalar and outputs handle to the resulting share
-    pub fn scale(&mut self, 
-        handle_in: &String, 
-        scalar: F
+    /// multiplies [x] by the public constant c, with no interaction:
+    /// every party scales its own share. The result goes to a fresh wire,
+    /// whose handle is returned; the wire [x] itself is left unchanged.
+    pub fn clear_mul(&mut self, 
+        handle_x: &String, 
+        c: F
     ) -> String {
         let handle_out = self.compute_fresh_wire_label();
 
-        let x = self.get_wire(handle_in);
+        let x = self.local_share(handle_x);
 
-        self.wire_shares.insert(handle_out.clone(), x * scalar);
+        self.wire_shares.insert(handle_out.clone(), x * c);
 
         handle_out
     }
 
+    /// same as clear_mul: returns a fresh handle to [scalar * x] and does not
+    /// modify [x]
+    pub fn scale(&mut self, 
+        handle_in: &String, 
+        scalar: F
+    ) -> String {
+        self.clear_mul(handle_in, scalar)
+    }
+
     /// given: triple ([a], [b], [c]) and inputs ([x], [y])
     /// reveals: x + a, y + b
     /// computes [x.y] = (x+a).(y+b) - (x+a).[b] - (y+b).[a] + [c]
@@ -297,11 +1141,43 @@ impl Evaluator {
         handle_x: &String, 
         handle_y: &String
     ) -> String {
-        let (h_a, h_b, h_c) = self.beaver().await;
+        let triple = self.beaver().await;
+        self.mult_with_triple(handle_x, handle_y, triple).await
+    }
+
+    /// same as mult, but the triple comes from a pool kept by the evaluator,
+    /// which is refilled with TRIPLE_POOL_SIZE triples whenever it runs dry;
+    /// nothing is prefetched, so the call that finds the pool empty waits
+    /// for the refill inline before it multiplies
+    pub async fn mult_auto(&mut self, 
+        handle_x: &String, 
+        handle_y: &String
+    ) -> String {
+        let triple = self.next_pooled_triple().await;
+        self.mult_with_triple(handle_x, handle_y, triple).await
+    }
+
+    async fn next_pooled_triple(&mut self) -> (String, String, String) {
+        if self.triple_pool.is_empty() {
+            // from the dealer a refill is local; distributed, it costs
+            // the two rounds of beaver_batch once per TRIPLE_POOL_SIZE triples
+            let triples = self.beaver_batch(TRIPLE_POOL_SIZE).await;
+            self.triple_pool.extend(triples);
+            self.triple_pool_refills += 1;
+        }
+
+        // hand out triples in the order they were made
+        self.triple_pool.pop_front().unwrap()
+    }
 
-        let share_a = self.get_wire(&h_a);
-        let share_b = self.get_wire(&h_b);
-        let share_c = self.get_wire(&h_c);
+    async fn mult_with_triple(&mut self, 
+        handle_x: &String, 
+        handle_y: &String, 
+        (h_a, h_b, h_c): (String, String, String)
+    ) -> String {
+        let share_a = self.local_share(&h_a);
+        let share_b = self.local_share(&h_b);
+        let share_c = self.local_share(&h_c);
 
         // our strategy would be to re-use other components
         //construct adder gates for the padded wires
@@ -314,16 +1190,15 @@ impl Evaluator {
 
         let handle = self.compute_fresh_wire_label();
         
-        //only one party should add the constant term
-        let my_id = get_node_id_via_peer_id(&self.addr_book, &self.id).unwrap();
-        let share_x_mul_y: F = match my_id {
-            0 => {
+        //only one party should add the constant term (all, under Shamir)
+        let share_x_mul_y: F = match self.adds_public_constants() {
+            true => {
                 x_plus_a * y_plus_b 
                 - x_plus_a * share_b 
                 - y_plus_b * share_a 
                 + share_c
             },
-            _ => {
+            false => {
                 F::from(0)
                 - x_plus_a * share_b 
                 - y_plus_b * share_a 
@@ -350,12 +1225,12 @@ impl Evaluator {
         let mut x_plus_a_handles: Vec<String> = Vec::new();
         let mut y_plus_b_handles: Vec<String> = Vec::new();
 
-        for i in 0..len {
-            let (h_a, h_b, h_c) = self.beaver().await;
+        let triples = self.beaver_batch(len).await;
+        for (i, (h_a, h_b, h_c)) in triples.into_iter().enumerate() {
 
-            bookkeeping_a.push(self.get_wire(&h_a));
-            bookkeeping_b.push(self.get_wire(&h_b));
-            bookkeeping_c.push(self.get_wire(&h_c));
+            bookkeeping_a.push(self.local_share(&h_a));
+            bookkeeping_b.push(self.local_share(&h_b));
+            bookkeeping_c.push(self.local_share(&h_c));
 
             let handle_x_plus_a = self.add(&x_handles[i], &h_a);
             let handle_y_plus_b = self.add(&y_handles[i], &h_b);
@@ -379,20 +1254,20 @@ impl Evaluator {
         let x_plus_a_and_y_plus_b = self.batch_output_wire(&batch_handles).await;
 
         let mut output: Vec<String> = vec![];
-        let my_id = get_node_id_via_peer_id(&self.addr_book, &self.id).unwrap();
+        let adds_public_constants = self.adds_public_constants();
         for i in 0..len {
             let x_plus_a_reconstructed = x_plus_a_and_y_plus_b[i];
             let y_plus_b_reconstructed = x_plus_a_and_y_plus_b[x_plus_a_handles.len() + i];
 
-            //only one party should add the constant term
-            let share_x_mul_y: F = match my_id {
-                0 => {
+            //only one party should add the constant term (all, under Shamir)
+            let share_x_mul_y: F = match adds_public_constants {
+                true => {
                     x_plus_a_reconstructed * y_plus_b_reconstructed 
                     - x_plus_a_reconstructed * bookkeeping_b[i] 
                     - y_plus_b_reconstructed * bookkeeping_a[i]  
                     + bookkeeping_c[i]
                 },
-                _ => {
+                false => {
                     F::from(0)
                     - x_plus_a_reconstructed * bookkeeping_b[i] 
                     - y_plus_b_reconstructed* bookkeeping_a[i] 
@@ -412,10 +1287,9 @@ impl Evaluator {
     pub fn fixed_wire_handle(&mut self, value: F) -> String {
         let handle = self.compute_fresh_wire_label();
         
-        let my_id = get_node_id_via_peer_id(&self.addr_book, &self.id).unwrap();
-        let share: F = match my_id {
-            0 => value,
-            _ => F::from(0)
+        let share: F = match self.adds_public_constants() {
+            true => value,
+            false => F::from(0)
         };
 
         self.wire_shares.insert(handle.clone(), share);
@@ -442,29 +1316,69 @@ impl Evaluator {
 
     }
 
-    /// Should multiply two polynomials with shared coefficients to get a larger degree polynomial with shared coefficients
+    /// [f(X)] + [g(X)] for polynomials with shared coefficients; like add,
+    /// this is local, as is adding or subtracting a sharing of any other
+    /// linear combination of f and g
+    pub fn share_poly_add(&self, 
+        f_poly_share: &DensePolynomial<F>, 
+        g_poly_share: &DensePolynomial<F>
+    ) -> DensePolynomial<F> {
+        f_poly_share + g_poly_share
+    }
+
+    /// [f(X)] - [g(X)] for polynomials with shared coefficients
+    pub fn share_poly_sub(&self, 
+        f_poly_share: &DensePolynomial<F>, 
+        g_poly_share: &DensePolynomial<F>
+    ) -> DensePolynomial<F> {
+        f_poly_share - g_poly_share
+    }
+
+    /// -[f(X)] for a polynomial with shared coefficients
+    pub fn share_poly_neg(&self, f_poly_share: &DensePolynomial<F>) -> DensePolynomial<F> {
+        -f_poly_share.clone()
+    }
+
+    /// Should multiply two polynomials with shared coefficients to get a larger degree polynomial with shared coefficients
+    /// multiplies two shared polynomials of degree < PERM_SIZE; the product
+    /// has degree up to 2*PERM_SIZE - 2, so it is evaluated over 2*PERM_SIZE points
     pub async fn share_poly_mult(&mut self, 
         f_poly_share: DensePolynomial<F>,
         g_poly_share: DensePolynomial<F>,
      ) -> DensePolynomial<F> {
-        let alpha = utils::multiplicative_subgroup_of_size(2*PERM_SIZE as u64);
-        let powers_of_alpha: Vec<F> = (0..2*PERM_SIZE)
-            .into_iter()
-            .map(|i| utils::compute_power(&alpha, i as u64))
-            .collect();
+        self.share_poly_mult_over_domain(f_poly_share, g_poly_share, 2*PERM_SIZE).await
+    }
+
+    /// multiplies two shared polynomials by multiplying their evaluations over
+    /// the subgroup of size domain_size. All parties must pass the same
+    /// domain_size, and it must exceed the degree of the product, or the
+    /// interpolated result aliases (wraps around) modulo X^domain_size - 1.
+    pub async fn share_poly_mult_over_domain(&mut self, 
+        f_poly_share: DensePolynomial<F>,
+        g_poly_share: DensePolynomial<F>,
+        domain_size: usize,
+     ) -> DensePolynomial<F> {
+        let product_degree = f_poly_share.degree() + g_poly_share.degree();
+        assert!(
+            domain_size > product_degree, 
+            "a domain of {} points cannot hold a product of degree {}", domain_size, product_degree
+        );
+
+        let alpha = utils::multiplicative_subgroup_of_size(domain_size as u64).unwrap();
+        let powers_of_alpha: Vec<F> = utils::power_table(&alpha, domain_size);
 
         let mut f_evals = Vec::new();
         let mut g_evals = Vec::new();
 
-        for i in 0..2*PERM_SIZE {
-            f_evals.push(self.share_poly_eval(f_poly_share.clone(), powers_of_alpha[i]));
-            g_evals.push(self.share_poly_eval(g_poly_share.clone(), powers_of_alpha[i]));
+        for alpha_i in &powers_of_alpha {
+            f_evals.push(self.share_poly_eval(f_poly_share.clone(), *alpha_i));
+            g_evals.push(self.share_poly_eval(g_poly_share.clone(), *alpha_i));
         }
 
         // Compute h_evals from f_evals and g_evals using Beaver mult
         let h_evals = self.batch_mult(&f_evals, &g_evals).await
             .into_iter()
-            .map(|x| self.get_wire(&x))
+            .map(|x| self.local_share(&x))
             .collect::<Vec<F>>();
 
         // Interpolate h_evals to get h_poly_share
@@ -473,7 +1387,320 @@ impl Evaluator {
         h_poly_share
     }
 
+    /// opens every coefficient of a shared polynomial, e.g. to compare the
+    /// product of share_poly_mult against the expected one; this reveals
+    /// the whole polynomial, so it is for debugging and tests only. The
+    /// parties first agree on the longest share, as a share whose leading
+    /// coefficients happen to be zero is shorter than the others
+    #[cfg(any(test, feature = "debug-open"))]
+    pub async fn output_share_poly(&mut self, poly: &DensePolynomial<F>) -> DensePolynomial<F> {
+        let label = self.compute_fresh_wire_label();
+
+        let len_handle = format!("{}/len", label);
+        let msg = EvalNetMsg::PublishValue {
+            sender: self.id.clone(),
+            handle: len_handle.clone(),
+            value: poly.coeffs.len().to_string(),
+        };
+        send_over_network!(msg, self);
+
+        let len = self.collect_messages_from_all_peers(&len_handle).await
+            .into_iter()
+            .map(|x| x.parse::<usize>().unwrap())
+            .fold(poly.coeffs.len(), usize::max);
+
+        let coeff_handles: Vec<String> = (0..len)
+            .map(|i| {
+                let handle = format!("{}/coeff/{}", label, i);
+                let share = poly.coeffs.get(i).copied().unwrap_or(F::zero());
+                self.wire_shares.insert(handle.clone(), share);
+                handle
+            })
+            .collect();
+
+        DensePolynomial::from_coefficients_vec(self.batch_output_wire(&coeff_handles).await)
+    }
+
+    /// checks that [h(X)] is [f(X)] * [g(X)], e.g. as computed by
+    /// share_poly_mult, at a single point z: every party KZG-commits to its
+    /// shares of f, g and h, z is the Fiat-Shamir hash of all commitments,
+    /// and every party opens its shares at z with proofs against its
+    /// commitments. A party cannot shift what it opens, as its shares were
+    /// fixed before z was known; the openings combine into f(z), g(z) and
+    /// h(z), which are revealed, and f(z) g(z) = h(z) for a correct product.
+    /// One that differs anywhere passes only if z is one of the at most
+    /// deg(h) roots of the difference. label must be unique per check
+    pub async fn check_share_poly_mult(&mut self,
+        pp: &UniversalParams<Curve>,
+        f_poly_share: &DensePolynomial<F>,
+        g_poly_share: &DensePolynomial<F>,
+        h_poly_share: &DensePolynomial<F>,
+        label: &str
+    ) -> Result<(), EvaluatorError> {
+        let names = ["f", "g", "h"];
+        let share_polys = [f_poly_share.clone(), g_poly_share.clone(), h_poly_share.clone()];
+        let my_id = self.node_id();
+
+        let my_commitments: Vec<G1> = share_polys.iter().map(|poly| self.commit_poly(pp, poly)).collect();
+        let commit_handles: Vec<String> = names.iter().map(|name| format!("{}/commit_{}", label, name)).collect();
+        let msg = EvalNetMsg::PublishBatchValue {
+            sender: self.id.clone(),
+            handles: commit_handles.clone(),
+            values: my_commitments.iter().map(encode_g1_as_bs58_str).collect(),
+        };
+        send_over_network!(msg, self);
+
+        // the commitments of f, g and h of every party, by node id
+        let mut commitments: BTreeMap<usize, Vec<G1>> = BTreeMap::new();
+        commitments.insert(my_id, my_commitments);
+        for handle in commit_handles.iter() {
+            for (sender, msg) in self.try_collect_messages_from_all_peers_with_senders(handle).await? {
+                commitments.entry(self.node_id_of(&sender)).or_default().push(decode_bs58_str_as_g1(&msg));
+            }
+        }
+
+        let mut bytes = Vec::new();
+        for (node_id, party_commitments) in commitments.iter() {
+            bytes.extend_from_slice(&(*node_id as u64).to_be_bytes());
+            party_commitments.serialize_compressed(&mut bytes).unwrap();
+        }
+        let z = utils::fs_hash(vec![label.as_bytes(), &bytes], 1)[0];
+        let zs = [z; 3];
+
+        let my_evals: Vec<F> = share_polys.iter().map(|poly| poly.evaluate(&z)).collect();
+        let my_proofs = eval_proof_shares(pp, &share_polys, &zs);
+        self.record_msm(share_polys.iter().map(|poly| poly.coeffs.len().saturating_sub(1)).sum());
+
+        let eval_handles: Vec<String> = names.iter().map(|name| format!("{}/eval_{}", label, name)).collect();
+        let proof_handles: Vec<String> = names.iter().map(|name| format!("{}/proof_{}", label, name)).collect();
+        let msg = EvalNetMsg::PublishBatchValue {
+            sender: self.id.clone(),
+            handles: [eval_handles.clone(), proof_handles.clone()].concat(),
+            values: my_evals.iter().map(encode_f_as_bs58_str)
+                .chain(my_proofs.iter().map(encode_g1_as_bs58_str))
+                .collect(),
+        };
+        send_over_network!(msg, self);
+
+        // the openings of f, g and h of every party, by node id
+        let mut evals: BTreeMap<usize, Vec<F>> = BTreeMap::new();
+        let mut proofs: BTreeMap<usize, Vec<G1>> = BTreeMap::new();
+        evals.insert(my_id, my_evals);
+        for handle in eval_handles.iter() {
+            for (sender, msg) in self.try_collect_messages_from_all_peers_with_senders(handle).await? {
+                evals.entry(self.node_id_of(&sender)).or_default().push(decode_bs58_str_as_f(&msg));
+            }
+        }
+        for handle in proof_handles.iter() {
+            for (sender, msg) in self.try_collect_messages_from_all_peers_with_senders(handle).await? {
+                proofs.entry(self.node_id_of(&sender)).or_default().push(decode_bs58_str_as_g1(&msg));
+            }
+        }
+
+        for (node_id, party_proofs) in proofs.iter() {
+            if !utils::kzg_check_batch(pp, &commitments[node_id], &zs, &evals[node_id], party_proofs) {
+                return Err(EvaluatorError::CommitmentMismatch { sender: self.peer_ids()[*node_id].clone() });
+            }
+        }
+
+        let node_ids: Vec<usize> = evals.keys().copied().collect();
+        let weights = self.reconstruction_weights(&node_ids);
+        let mut at_z = [F::zero(); 3];
+        for (weight, party_evals) in weights.iter().zip(evals.values()) {
+            for (sum, eval) in at_z.iter_mut().zip(party_evals) {
+                *sum += *weight * eval;
+            }
+        }
+
+        if at_z[0] * at_z[1] != at_z[2] {
+            return Err(EvaluatorError::ProductCheckFailed { label: label.to_string() });
+        }
+        Ok(())
+    }
+
+    /// a fresh triple ([a], [b], [c]) with c = a * b, from the triple source
     pub async fn beaver(&mut self) -> (String, String, String) {
+        self.beaver_batch(1).await.pop().unwrap()
+    }
+
+    /// len fresh triples, from the triple source; distributed, a batch
+    /// takes as many rounds as a single triple
+    pub async fn beaver_batch(&mut self, len: usize) -> Vec<(String, String, String)> {
+        match self.triple_source {
+            TripleSource::Dealer => (0..len).map(|_| self.dealer_triple()).collect(),
+            TripleSource::Distributed => self.distributed_triples(len).await,
+        }
+    }
+
+    /// the protocol of Gennaro, Rabin and Rabin: every party deals a Shamir
+    /// sharing of its own random a_i and b_i, and [a], [b] are their sums.
+    /// The local products of the shares of a and b lie on a polynomial of
+    /// degree 2 * threshold through a * b; every party reshares its product
+    /// with degree threshold, and the Lagrange combination of the resharings
+    /// is a sharing [c] of a * b of degree threshold.
+    async fn distributed_triples(&mut self, len: usize) -> Vec<(String, String, String)> {
+        // with_triple_source made sure the sharing is Shamir with 2 * threshold < n
+        let n = self.num_parties();
+        let threshold = match self.sharing {
+            SharingScheme::Shamir { threshold } => threshold,
+            SharingScheme::Additive => unreachable!("distributed triples need Shamir sharing"),
+        };
+
+        let triples: Vec<(String, String, String)> = (0..len)
+            .map(|_| (
+                self.compute_fresh_wire_label(),
+                self.compute_fresh_wire_label(),
+                self.compute_fresh_wire_label(),
+            ))
+            .collect();
+
+        // round 1: deal a_i and b_i
+        let handles: Vec<String> = triples
+            .iter()
+            .flat_map(|(h_a, h_b, _)| [h_a.clone(), h_b.clone()])
+            .collect();
+        self.deal_random_sharings(&handles).await;
+
+        // round 2: reshare the local products
+        let handles: Vec<String> = triples.iter().map(|(_, _, h_c)| h_c.clone()).collect();
+        let dealt: Vec<Vec<F>> = triples
+            .iter()
+            .map(|(h_a, h_b, _)| {
+                let product = self.local_share(h_a) * self.local_share(h_b);
+                utils::compute_shamir_shares(&product, threshold, n, &mut self.rng)
+            })
+            .collect();
+        let received = self.deal_private_shares(&handles, &dealt).await;
+        for (h, shares) in handles.iter().zip(received) {
+            let points: Vec<(F, F)> = shares
+                .into_iter()
+                .map(|(node_id, share)| (F::from(node_id as u64 + 1), share))
+                .collect();
+            self.wire_shares.insert(h.clone(), utils::lagrange_interpolate_at_zero(&points));
+        }
+
+        triples
+    }
+
+    /// one round in which every party deals a Shamir sharing of a random
+    /// value of its own for each of handles; the wire of a handle is the sum
+    /// of the sharings
+    async fn deal_random_sharings(&mut self, handles: &[String]) {
+        let n = self.num_parties();
+        let threshold = match self.sharing {
+            SharingScheme::Shamir { threshold } => threshold,
+            SharingScheme::Additive => unreachable!("only Shamir wires are dealt"),
+        };
+
+        let dealt: Vec<Vec<F>> = handles
+            .iter()
+            .map(|_| {
+                let contribution = F::rand(&mut self.rng);
+                utils::compute_shamir_shares(&contribution, threshold, n, &mut self.rng)
+            })
+            .collect();
+        let received = self.deal_private_shares(handles, &dealt).await;
+        for (h, shares) in handles.iter().zip(received) {
+            let sum = shares.iter().map(|(_, share)| *share).sum();
+            self.wire_shares.insert(h.clone(), sum);
+        }
+    }
+
+    /// sends party k the shares dealt[i][k] of every handles[i], and returns
+    /// for every handle the shares this party was dealt by all parties, itself
+    /// included, as (dealer node id, share) ordered by node id. The network
+    /// delivers every message to every peer, so each share travels padded
+    /// with a pad only its dealer and receiver can derive, see pair_keys
+    async fn deal_private_shares(&mut self, handles: &[String], dealt: &[Vec<F>]) -> Vec<Vec<(usize, F)>> {
+        // not the wire handles themselves, which may be opened later
+        let labels: Vec<String> = handles.iter().map(|h| format!("deal/{}", h)).collect();
+        let my_id = self.node_id();
+        let pair_keys = self.pair_keys().await;
+
+        let mut padded: Vec<(Pok3rPeerId, Vec<String>)> = vec![];
+        for (node_id, receiver) in self.peer_ids().into_iter().enumerate() {
+            if node_id == my_id { continue; }
+
+            let values: Vec<String> = labels
+                .iter()
+                .zip(dealt)
+                .map(|(label, shares)| {
+                    let pad = share_pad(&pair_keys[&node_id], label, my_id, node_id);
+                    encode_f_as_bs58_str(&(shares[node_id] + pad))
+                })
+                .collect();
+            padded.push((receiver, values));
+        }
+
+        // like publish_rounds_within_limit, with one SendBatchShare per
+        // receiver in every round
+        let num_rounds = (labels.len() + BATCH_ROUND_SIZE - 1) / BATCH_ROUND_SIZE;
+        let mut received = vec![];
+        let mut next_round = 0;
+        for round in 0..num_rounds {
+            while next_round < num_rounds
+                && self.inflight_rounds < self.max_inflight {
+                let bucket = next_round * BATCH_ROUND_SIZE..labels.len().min((next_round + 1) * BATCH_ROUND_SIZE);
+                for (receiver, values) in padded.iter() {
+                    let msg = EvalNetMsg::SendBatchShare {
+                        sender: self.id.clone(),
+                        receiver: receiver.clone(),
+                        handles: labels[bucket.clone()].to_vec(),
+                        values: values[bucket.clone()].to_vec(),
+                    };
+                    send_over_network!(msg, self);
+                }
+
+                self.inflight_rounds += 1;
+                self.peak_inflight_rounds = self.peak_inflight_rounds.max(self.inflight_rounds);
+                next_round += 1;
+            }
+
+            let bucket = round * BATCH_ROUND_SIZE..labels.len().min((round + 1) * BATCH_ROUND_SIZE);
+            for (label, shares) in labels[bucket.clone()].iter().zip(&dealt[bucket]) {
+                let mut from_dealers = vec![(my_id, shares[my_id])];
+                for (sender, msg) in self.collect_messages_from_all_peers_with_senders(label).await {
+                    let node_id = self.node_id_of(&sender);
+                    let pad = share_pad(&pair_keys[&node_id], label, node_id, my_id);
+                    from_dealers.push((node_id, decode_bs58_str_as_f(&msg) - pad));
+                }
+                from_dealers.sort_by_key(|(node_id, _)| *node_id);
+                received.push(from_dealers);
+            }
+            self.inflight_rounds -= 1;
+        }
+        received
+    }
+
+    /// a key shared with each other party, by node id: the first call runs a
+    /// Diffie-Hellman exchange in G1, where every party publishes g^a for a
+    /// secret a of its own, and the key with party j is a hash of g^(a a_j)
+    async fn pair_keys(&mut self) -> HashMap<usize, [u8; 32]> {
+        if let Some(keys) = &self.pair_keys {
+            return keys.clone();
+        }
+
+        let secret = F::rand(&mut self.rng);
+        let key = self.operation_key("pair_keys");
+        let msg = EvalNetMsg::PublishValue {
+            sender: self.id.clone(),
+            handle: key.clone(),
+            value: encode_g1_as_bs58_str(&G1::generator().mul(secret).into_affine()),
+        };
+        send_over_network!(msg, self);
+
+        let mut keys = HashMap::new();
+        for (sender, msg) in self.collect_messages_from_all_peers_with_senders(&key).await {
+            let mut shared_bytes = Vec::new();
+            decode_bs58_str_as_g1(&msg).mul(secret).into_affine().serialize_compressed(&mut shared_bytes).unwrap();
+            keys.insert(self.node_id_of(&sender), Sha256::digest(&shared_bytes).into());
+        }
+        self.pair_keys = Some(keys.clone());
+        keys
+    }
+
+    /// a triple from the (simulated) dealer, seeded identically for all parties
+    fn dealer_triple(&mut self) -> (String, String, String) {
         let n: usize = self.addr_book.len();
         let my_id = get_node_id_via_peer_id(&self.addr_book, &self.id).unwrap();
 
@@ -483,6 +1710,19 @@ impl Evaluator {
 
         let mut seeded_rng = StdRng::from_seed([42u8; 32]);
 
+        if let SharingScheme::Shamir { threshold } = self.sharing {
+            let a = F::rand(&mut seeded_rng);
+            let b = F::rand(&mut seeded_rng);
+            let share_a = self.deal_shamir_share(&a, threshold, &mut seeded_rng);
+            let share_b = self.deal_shamir_share(&b, threshold, &mut seeded_rng);
+            let share_c = self.deal_shamir_share(&(a * b), threshold, &mut seeded_rng);
+
+            self.wire_shares.insert(handle_a.clone(), share_a);
+            self.wire_shares.insert(handle_b.clone(), share_b);
+            self.wire_shares.insert(handle_c.clone(), share_c);
+            return (handle_a, handle_b, handle_c);
+        }
+
         let mut sum_a = F::from(0);
         let mut sum_b = F::from(0);
         let mut sum_c = F::from(0);
@@ -513,90 +1753,305 @@ impl Evaluator {
     }
 
     pub async fn output_wire(&mut self, wire_handle: &String) -> F {
-        let my_share = self.get_wire(wire_handle);
+        let my_share = self.local_share(wire_handle);
 
         let msg = EvalNetMsg::PublishValue {
             sender: self.id.clone(),
             handle: wire_handle.clone(),
             value: encode_f_as_bs58_str(&my_share),
         };
-        send_over_network!(msg, self.tx);
+        send_over_network!(msg, self);
+
+        self.reconstruct(wire_handle, my_share).await
+    }
+
+    /// combines my_share with the shares other parties published under
+    /// identifier: the sum of all n under additive sharing, and under
+    /// Shamir the interpolation of the first threshold + 1 to be available,
+    /// so the opening does not wait on parties that dropped out
+    async fn reconstruct(&mut self, identifier: &String, my_share: F) -> F {
+        match self.sharing {
+            SharingScheme::Additive => {
+                let incoming_msgs = self.collect_messages_from_all_peers(identifier).await;
+                let incoming_values: Vec<F> = incoming_msgs
+                    .into_iter()
+                    .map(|x| decode_bs58_str_as_f(&x))
+                    .collect();
+
+                let sum = incoming_values
+                    .iter()
+                    .fold(my_share, |acc, v| acc + v);
+                sum
+            },
+            SharingScheme::Shamir { threshold } => {
+                let incoming_msgs = self
+                    .collect_messages_from_any_peers_with_senders(identifier, threshold)
+                    .await;
+
+                let mut points = vec![(F::from(self.node_id() as u64 + 1), my_share)];
+                for (sender, msg) in incoming_msgs {
+                    let node_id = get_node_id_via_peer_id(&self.addr_book, &sender).unwrap();
+                    points.push((F::from(node_id + 1), decode_bs58_str_as_f(&msg)));
+                }
+                utils::lagrange_interpolate_at_zero(&points)
+            },
+        }
+    }
+
+    /// the node id of a peer of the address book
+    fn node_id_of(&self, peer_id: &Pok3rPeerId) -> usize {
+        get_node_id_via_peer_id(&self.addr_book, peer_id).unwrap() as usize
+    }
+
+    /// the weights that combine values the parties node_ids each computed
+    /// from their own share, linearly, into the value for the shared one:
+    /// 1 under additive sharing, where all parties contribute, and under
+    /// Shamir the Lagrange coefficients at zero of their evaluation points
+    fn reconstruction_weights(&self, node_ids: &[usize]) -> Vec<F> {
+        match self.sharing {
+            SharingScheme::Additive => vec![F::one(); node_ids.len()],
+            SharingScheme::Shamir { .. } => {
+                let xs: Vec<F> = node_ids.iter().map(|node_id| F::from(*node_id as u64 + 1)).collect();
+                utils::lagrange_coefficients_at_zero(&xs)
+            },
+        }
+    }
 
-        let incoming_msgs = self.collect_messages_from_all_peers(wire_handle).await;
-        let incoming_values: Vec<F> = incoming_msgs
+    /// combines (node id, g^[x]) of the parties into g^x, with the
+    /// weights of reconstruction_weights
+    fn combine_in_exponent<G: Group<ScalarField = F>>(&self, contributions: Vec<(usize, G)>) -> G {
+        let node_ids: Vec<usize> = contributions.iter().map(|(node_id, _)| *node_id).collect();
+        self.reconstruction_weights(&node_ids)
             .into_iter()
-            .map(|x| decode_bs58_str_as_f(&x))
-            .collect();
+            .zip(contributions)
+            .map(|(weight, (_, g))| if weight.is_one() { g } else { g * weight })
+            .sum()
+    }
 
-        let sum = incoming_values
-            .iter()
-            .fold(my_share, |acc, v| acc + v);
-        sum
+    /// committed-opening variant of output_wire, secure against a party
+    /// that changes its share after seeing everyone else's:
+    /// every party first broadcasts H(share || nonce), and only after all
+    /// commitments are in does it reveal (share, nonce). A reveal that does
+    /// not match its commitment aborts the opening, naming the sender.
+    pub async fn output_wire_committed(&mut self, wire_handle: &String) -> Result<F, EvaluatorError> {
+        let commitments = self.try_commit_wire(wire_handle).await?;
+        let openings = self.reveal_committed_wire(wire_handle, &commitments).await?;
+
+        let node_ids: Vec<usize> = openings.iter().map(|(sender, _, _)| self.node_id_of(sender)).collect();
+        Ok(self.reconstruction_weights(&node_ids)
+            .into_iter()
+            .zip(openings)
+            .map(|(weight, (_, share, _))| weight * share)
+            .sum())
     }
 
-    /*
-     * outputs the reconstructed value of all wires
-     */
-    pub async fn batch_output_wire(&mut self, wire_handles: &[String]) -> Vec<F> {
-        let mut outputs = Vec::new();
+    /// compares the kzg::srs_fingerprint of pp with every other party's,
+    /// to run before add_g1_elements_from_all_parties sums commitments made
+    /// under each party's own SRS; such a sum only means something if all
+    /// parties use the same SRS
+    pub async fn check_srs_agreement(&mut self, pp: &UniversalParams<Curve>) -> Result<(), EvaluatorError> {
+        let my_fingerprint = bs58::encode(crate::kzg::srs_fingerprint(pp)).into_string();
+
+        match self.first_disagreeing_party("srs_fingerprint", my_fingerprint).await? {
+            Some(sender) => Err(EvaluatorError::SrsMismatch { sender }),
+            None => Ok(()),
+        }
+    }
 
-        let mut handles = Vec::new();
-        let mut values = Vec::new();
+    /// compares a digest of the Fiat-Shamir challenges this party derived
+    /// under label with every other party's. Each party hashes its own
+    /// transcript, so one that serializes some element differently would
+    /// otherwise go on with other challenges, and the proof would only
+    /// fail verification at the end
+    pub async fn check_challenge_agreement(&mut self, label: &str, challenges: &[F]) -> Result<(), EvaluatorError> {
+        let mut bytes = Vec::new();
+        challenges.serialize_uncompressed(&mut bytes).unwrap();
+        let my_digest = bs58::encode(Sha256::digest(&bytes)).into_string();
+
+        match self.first_disagreeing_party(&format!("{}/challenge_digest", label), my_digest).await? {
+            Some(sender) => Err(EvaluatorError::ChallengeMismatch { label: label.to_string(), sender }),
+            None => Ok(()),
+        }
+    }
 
-        let len = wire_handles.len();
+    /// publishes my_value under the next operation key for label and
+    /// returns the first party, by peer id, that published another value
+    async fn first_disagreeing_party(&mut self, label: &str, my_value: String) -> Result<Option<Pok3rPeerId>, EvaluatorError> {
+        let handle = self.operation_key(label);
+        let msg = EvalNetMsg::PublishValue {
+            sender: self.id.clone(),
+            handle: handle.clone(),
+            value: my_value.clone(),
+        };
+        send_over_network!(msg, self);
 
-        for i in 0..len {
-            handles.push(wire_handles[i].clone());
-            values.push(encode_f_as_bs58_str(&self.get_wire(&wire_handles[i])));
+        let mut values = self.try_collect_messages_from_all_peers_with_senders(&handle).await?;
+        values.sort();
+        Ok(values.into_iter()
+            .find(|(_, value)| *value != my_value)
+            .map(|(sender, _)| sender))
+    }
+
+    /// a Checkpoint of the cards on handles, with the permutation argument
+    /// over them if it was computed already
+    pub fn checkpoint(&self, handles: &[String], perm_proof: Option<&PermutationProof>) -> Checkpoint {
+        Checkpoint {
+            handles: handles.to_vec(),
+            shares: handles.iter().map(|h| self.get_wire(h)).collect(),
+            perm_proof: perm_proof.cloned(),
+            gate_counter: self.gate_counter,
+            op_counter: self.op_counter,
+        }
+    }
+
+    /// continues a run from checkpoint on an evaluator that has not done
+    /// anything yet: checks that every other party resumed from the same
+    /// checkpoint, which is what keeps the wires of the parties consistent
+    /// with each other, and then restores the cards and the counters. The
+    /// check runs before the counters are restored, so it is under the
+    /// same operation key for parties resuming from different checkpoints
+    pub async fn resume_from(&mut self, checkpoint: &Checkpoint) -> Result<(), EvaluatorError> {
+        let my_fingerprint = bs58::encode(checkpoint.public_fingerprint()).into_string();
+        if let Some(sender) = self.first_disagreeing_party("checkpoint_fingerprint", my_fingerprint).await? {
+            return Err(EvaluatorError::CheckpointMismatch { sender });
         }
 
-        if len > 256 {
-            let mut processed_len = 0;
+        for (handle, share) in checkpoint.handles.iter().zip(Share::raw(&checkpoint.shares)) {
+            self.wire_shares.insert(handle.clone(), share);
+        }
+        self.gate_counter = checkpoint.gate_counter;
+        self.op_counter = checkpoint.op_counter;
+        Ok(())
+    }
 
-            while processed_len < len {
-                let this_iter_len = std::cmp::min(len - processed_len, 256);
-                let handles_bucket = &handles[processed_len..processed_len + this_iter_len].to_vec();
-                let values_bucket = &values[processed_len..processed_len + this_iter_len].to_vec();
+    /// first half of output_wire_committed: broadcasts H(share || nonce) for
+    /// wire_handle and returns every party's commitment, sorted by peer id.
+    /// The reveal may come much later, e.g. once a game is over.
+    pub async fn commit_wire(&mut self, wire_handle: &String) -> Vec<(Pok3rPeerId, String)> {
+        let commitments = self.try_commit_wire(wire_handle).await;
+        expect_running(self.cancel_token.clone(), commitments).await
+    }
 
-                let msg = EvalNetMsg::PublishBatchValue {
-                    sender: self.id.clone(),
-                    handles: handles_bucket.to_owned(),
-                    values: values_bucket.to_owned(),
-                };
-                send_over_network!(msg, self.tx);
+    async fn try_commit_wire(&mut self, wire_handle: &String) -> Result<Vec<(Pok3rPeerId, String)>, EvaluatorError> {
+        let my_share = self.local_share(wire_handle);
+        let mut nonce = [0u8; 32];
+        self.rng.fill_bytes(&mut nonce);
 
-                processed_len += this_iter_len;
+        let my_commitment = bs58::encode(commit_to_share(&my_share, &nonce)).into_string();
+        self.commit_nonces.insert(wire_handle.clone(), nonce);
+
+        let commit_handle = format!("{}/commit", wire_handle);
+        let msg = EvalNetMsg::PublishValue {
+            sender: self.id.clone(),
+            handle: commit_handle.clone(),
+            value: my_commitment.clone(),
+        };
+        send_over_network!(msg, self);
+
+        let mut commitments = self.try_collect_messages_from_all_peers_with_senders(&commit_handle).await?;
+        commitments.push((self.id.clone(), my_commitment));
+        commitments.sort();
+        Ok(commitments)
+    }
+
+    /// second half of output_wire_committed: reveals this party's
+    /// (share, nonce) for a wire committed with commit_wire and returns every
+    /// party's opening, sorted by peer id, after checking it against commitments
+    pub async fn reveal_committed_wire(
+        &mut self, 
+        wire_handle: &String, 
+        commitments: &[(Pok3rPeerId, String)]
+    ) -> Result<Vec<(Pok3rPeerId, F, [u8; 32])>, EvaluatorError> {
+        let my_share = self.local_share(wire_handle);
+        let nonce = self.commit_nonces
+            .remove(wire_handle)
+            .expect("wire was not committed with commit_wire");
+
+        let reveal_handle = format!("{}/reveal", wire_handle);
+        let msg = EvalNetMsg::PublishValue {
+            sender: self.id.clone(),
+            handle: reveal_handle.clone(),
+            value: encode_share_opening_as_bs58_str(&my_share, &nonce),
+        };
+        send_over_network!(msg, self);
+
+        let mut openings = self.try_collect_messages_from_all_peers_with_senders(&reveal_handle).await?
+            .into_iter()
+            .map(|(sender, reveal)| match decode_bs58_str_as_share_opening(&reveal) {
+                Some((share, nonce)) => Ok((sender, share, nonce)),
+                None => Err(EvaluatorError::CommitmentMismatch { sender }),
+            })
+            .collect::<Result<Vec<(Pok3rPeerId, F, [u8; 32])>, EvaluatorError>>()?;
+        openings.push((self.id.clone(), my_share, nonce));
+        openings.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (sender, share, nonce) in openings.iter() {
+            let commitment = commitments
+                .iter()
+                .find(|(peer_id, _)| peer_id.eq(sender))
+                .map(|(_, commitment)| commitment);
+            if !commitment.map_or(false, |c| share_opening_matches(c, share, nonce)) {
+                return Err(EvaluatorError::CommitmentMismatch { sender: sender.clone() });
             }
-        } else {
-            let msg = EvalNetMsg::PublishBatchValue {
-                sender: self.id.clone(),
-                handles: handles,
-                values: values,
-            };
-            send_over_network!(msg, self.tx);
         }
 
-        for i in 0..len {
-            let incoming_msgs = self.collect_messages_from_all_peers(&wire_handles[i]).await;
-            let incoming_values: Vec<F> = incoming_msgs
-                .into_iter()
-                .map(|x| decode_bs58_str_as_f(&x))
-                .collect();
+        Ok(openings)
+    }
 
-            let sum = incoming_values
-                .iter()
-                .fold(self.get_wire(&wire_handles[i]), |acc, v| acc + v);
+    /*
+     * outputs the reconstructed value of all wires
+     */
+    pub async fn batch_output_wire(&mut self, wire_handles: &[String]) -> Vec<F> {
+        let mut outputs = Vec::new();
+
+        let values: Vec<String> = wire_handles
+            .iter()
+            .map(|h| encode_f_as_bs58_str(&self.local_share(h)))
+            .collect();
+
+        let rounds = batch_rounds(wire_handles, &values, BATCH_ROUND_SIZE);
+        let mut next_round = 0;
+        for round in rounds.iter() {
+            next_round = self.publish_rounds_within_limit(&rounds, next_round).await;
 
-            outputs.push(sum);
+            for handle in round.0.iter() {
+                let my_share = self.local_share(handle);
+                outputs.push(self.reconstruct(handle, my_share).await);
+            }
+            self.inflight_rounds -= 1;
         }
 
         outputs
     }
 
+    /// publishes rounds[next_round..], one PublishBatchValue each, for as
+    /// long as max_inflight allows, and returns the first round not sent
+    async fn publish_rounds_within_limit(
+        &mut self,
+        rounds: &[(Vec<String>, Vec<String>)],
+        mut next_round: usize
+    ) -> usize {
+        while next_round < rounds.len()
+            && self.inflight_rounds < self.max_inflight {
+            let (handles, values) = &rounds[next_round];
+            let msg = EvalNetMsg::PublishBatchValue {
+                sender: self.id.clone(),
+                handles: handles.clone(),
+                values: values.clone(),
+            };
+            send_over_network!(msg, self);
+
+            self.inflight_rounds += 1;
+            self.peak_inflight_rounds = self.peak_inflight_rounds.max(self.inflight_rounds);
+            next_round += 1;
+        }
+        next_round
+    }
+
     // //on input wire [x], this outputs g^[x], and reconstructs and outputs g^x
     // we will use G1
     pub async fn output_wire_in_exponent(&mut self, wire_handle: &String) -> G1 {
-        let my_share = self.get_wire(wire_handle);
+        let my_share = self.local_share(wire_handle);
         let g = <Curve as Pairing>::G1Affine::generator();
         let my_share_exp = g.clone().mul(my_share).into_affine();
         
@@ -606,11 +2061,56 @@ impl Evaluator {
         ).await
     }
 
+    /// on input wires [x] and [r], reconstructs and outputs g^x * H^r for the
+    /// second generator H = utils::pedersen_generator(), a commitment that
+    /// hides x for as long as r stays shared; the blinding handle is returned
+    /// along with it, to open the commitment later by revealing x and r
+    pub async fn output_wire_in_exponent_pedersen(
+        &mut self, 
+        wire_handle: &String, 
+        blinding_handle: &String
+    ) -> (G1, String) {
+        let g = <Curve as Pairing>::G1Affine::generator();
+        let h = utils::pedersen_generator();
+        let my_share_exp = g.mul(self.local_share(wire_handle)) + h.mul(self.local_share(blinding_handle));
+
+        let commitment = self.add_g1_elements_from_all_parties(
+            &my_share_exp.into_affine(), 
+            &format!("{}/pedersen/{}", wire_handle, blinding_handle)
+        ).await;
+        (commitment, blinding_handle.clone())
+    }
+
+    /// on input wire [x], this outputs h^[x], and reconstructs and outputs h^x in G2
+    pub async fn output_wire_in_exponent_g2(&mut self, wire_handle: &String) -> G2 {
+        let my_share = self.local_share(wire_handle);
+        let h = <Curve as Pairing>::G2Affine::generator();
+        let my_share_exp = h.mul(my_share).into_affine();
+
+        // separate identifier, so the same wire can also be opened in G1
+        self.add_g2_elements_from_all_parties(
+            &my_share_exp, 
+            &format!("{}/g2", wire_handle)
+        ).await
+    }
+
+    /// reveals h^x for wire [x] together with a proof that it matches
+    /// com_g1 = g^x, e.g. an earlier output_wire_in_exponent of the same wire
+    pub async fn pairing_consistency_proof(
+        &mut self, 
+        wire_handle: &String, 
+        com_g1: &G1
+    ) -> (G2, PairingProof) {
+        let value_g2 = self.output_wire_in_exponent_g2(wire_handle).await;
+
+        (value_g2, PairingProof { com_g1: *com_g1, value_g2 })
+    }
+
     pub async fn batch_output_wire_in_exponent(&mut self, wire_handles: &[String]) -> Vec<G1> {
         let mut my_share_exps = Vec::new();
         let g = <Curve as Pairing>::G1Affine::generator();
         for i in 0..wire_handles.len() {
-            let my_share = self.get_wire(&wire_handles[i]);
+            let my_share = self.local_share(&wire_handles[i]);
             let my_share_exp = g.clone().mul(my_share).into_affine();
             my_share_exps.push(my_share_exp);
         }
@@ -622,28 +2122,53 @@ impl Evaluator {
     }
 
     // //on input wire [x], this outputs g^[x], and reconstructs and outputs g^x
+    /// a party that sends two different points for identifier is held to
+    /// the first one; try_add_g1_elements_from_all_parties fails instead
     pub async fn add_g1_elements_from_all_parties(
         &mut self, value: &G1, 
         identifier: &String
     ) -> G1 {
+        let sum = self.sum_g1_elements_from_all_parties(value, identifier).await;
+        expect_running(self.cancel_token.clone(), sum).await.0
+    }
 
-        let msg = EvalNetMsg::PublishValue {
-            sender: self.id.clone(),
-            handle: identifier.clone(),
-            value: encode_g1_as_bs58_str(value),
-        };
-        send_over_network!(msg, self.tx);
+    /// same as add_g1_elements_from_all_parties, but fails instead of
+    /// picking one when a party sent two different points for identifier
+    pub async fn try_add_g1_elements_from_all_parties(
+        &mut self, value: &G1, 
+        identifier: &str
+    ) -> Result<G1, EvaluatorError> {
+        match self.sum_g1_elements_from_all_parties(value, identifier).await? {
+            (sum, None) => Ok(sum),
+            (_, Some((name, sender))) => Err(EvaluatorError::DuplicateContribution { name, sender }),
+        }
+    }
 
-        let incoming_msgs = self.collect_messages_from_all_peers(identifier).await;
+    /// the sum of add_g1_elements_from_all_parties, and the mailbox key and
+    /// sender of a second, different point if some party sent one
+    async fn sum_g1_elements_from_all_parties(
+        &mut self, value: &G1, 
+        identifier: &str
+    ) -> Result<(G1, Option<(String, Pok3rPeerId)>), EvaluatorError> {
+        let key = self.operation_key(identifier);
+
+        let mut point_bytes = Vec::new();
+        value.serialize_compressed(&mut point_bytes).unwrap();
+        let msg = EvalNetMsg::PublicCommitment {
+            name: key.clone(),
+            node_id: self.node_id() as u64,
+            point_bytes,
+        };
+        send_over_network!(msg, self);
 
-        let incoming_values: Vec<G1> = incoming_msgs
-            .into_iter()
-            .map(|x| decode_bs58_str_as_g1(&x))
-            .collect();
+        let incoming_msgs = self.try_collect_messages_from_all_peers_with_senders(&key).await?;
+        let conflict = self.conflicts.remove(&key).map(|sender| (key, sender));
 
-        let mut sum: G1 = value.clone();
-        for v in incoming_values { sum = sum.add(v).into_affine(); }
-        sum
+        let mut contributions = vec![(self.node_id(), value.into_group())];
+        for (sender, msg) in incoming_msgs {
+            contributions.push((self.node_id_of(&sender), decode_bs58_str_as_g1(&msg).into_group()));
+        }
+        Ok((self.combine_in_exponent(contributions).into_affine(), conflict))
     }
 
     pub async fn batch_add_g1_elements_from_all_parties(
@@ -651,8 +2176,8 @@ impl Evaluator {
         inputs: &[G1],
         identifiers: &[String]
     ) -> Vec<G1> {
+        let identifiers: &[String] = &self.operation_keys(identifiers);
         assert_eq!(inputs.len(), identifiers.len());
-        let len = inputs.len();
 
         let mut outputs = Vec::new();
 
@@ -661,45 +2186,23 @@ impl Evaluator {
             .map(|e| encode_g1_as_bs58_str(e))
             .collect::<Vec<String>>();
 
-        if len > 256 {
-            let mut processed_len = 0;
-
-            while processed_len < len {
-                let this_iter_len = std::cmp::min(len - processed_len, 256);
-                let handles_bucket = &identifiers[processed_len..processed_len + this_iter_len].to_vec();
-                let values_bucket = &values[processed_len..processed_len + this_iter_len].to_vec();
-
-                let msg = EvalNetMsg::PublishBatchValue {
-                    sender: self.id.clone(),
-                    handles: handles_bucket.to_owned(),
-                    values: values_bucket.to_owned(),
-                };
-                send_over_network!(msg, self.tx);
+        let rounds = batch_rounds(identifiers, &values, BATCH_ROUND_SIZE);
+        let mut next_round = 0;
+        let mut i = 0;
+        for round in rounds.iter() {
+            next_round = self.publish_rounds_within_limit(&rounds, next_round).await;
+
+            for identifier in round.0.iter() {
+                let incoming_msgs = self.collect_messages_from_all_peers_with_senders(identifier).await;
+                let mut contributions = vec![(self.node_id(), inputs[i].into_group())];
+                for (sender, msg) in incoming_msgs {
+                    contributions.push((self.node_id_of(&sender), decode_bs58_str_as_g1(&msg).into_group()));
+                }
 
-                processed_len += this_iter_len;
+                outputs.push(self.combine_in_exponent(contributions).into_affine());
+                i += 1;
             }
-        }
-        else {
-            let msg = EvalNetMsg::PublishBatchValue {
-                sender: self.id.clone(),
-                handles: identifiers.into(),
-                values: values,
-            };
-            send_over_network!(msg, self.tx);
-        }
-
-        for i in 0..inputs.len() {
-            let incoming_msgs = self.collect_messages_from_all_peers(&identifiers[i]).await;
-            let incoming_values: Vec<G1> = incoming_msgs
-                .into_iter()
-                .map(|x| decode_bs58_str_as_g1(&x))
-                .collect();
-
-            let sum = incoming_values
-                .iter()
-                .fold(inputs[i], |acc, v| acc.add(v).into_affine());
-
-            outputs.push(sum);
+            self.inflight_rounds -= 1;
         }
 
         outputs
@@ -709,24 +2212,22 @@ impl Evaluator {
         &mut self, value: &G2, 
         identifier: &String
     ) -> G2 {
+        let key = self.operation_key(identifier);
 
         let msg = EvalNetMsg::PublishValue {
             sender: self.id.clone(),
-            handle: identifier.clone(),
+            handle: key.clone(),
             value: encode_g2_as_bs58_str(value),
         };
-        send_over_network!(msg, self.tx);
-
-        let incoming_msgs = self.collect_messages_from_all_peers(identifier).await;
+        send_over_network!(msg, self);
 
-        let incoming_values: Vec<G2> = incoming_msgs
-            .into_iter()
-            .map(|x| decode_bs58_str_as_g2(&x))
-            .collect();
+        let incoming_msgs = self.collect_messages_from_all_peers_with_senders(&key).await;
 
-        let mut sum: G2 = value.clone();
-        for v in incoming_values { sum = sum.add(v).into_affine(); }
-        sum
+        let mut contributions = vec![(self.node_id(), value.into_group())];
+        for (sender, msg) in incoming_msgs {
+            contributions.push((self.node_id_of(&sender), decode_bs58_str_as_g2(&msg).into_group()));
+        }
+        self.combine_in_exponent(contributions).into_affine()
     }
 
     pub async fn batch_add_g2_elements_from_all_parties(
@@ -734,10 +2235,9 @@ impl Evaluator {
         inputs: &[G2],
         identifiers: &[String]
     ) -> Vec<G2> {
+        let identifiers: &[String] = &self.operation_keys(identifiers);
         assert_eq!(inputs.len(), identifiers.len());
 
-        let len = inputs.len();
-
         let mut outputs = Vec::new();
 
         let values = inputs
@@ -745,45 +2245,23 @@ impl Evaluator {
             .map(|e| encode_g2_as_bs58_str(e))
             .collect::<Vec<String>>();
 
-        if len > 256 {
-            let mut processed_len = 0;
-
-            while processed_len < len {
-                let this_iter_len = std::cmp::min(len - processed_len, 256);
-                let handles_bucket = &identifiers[processed_len..processed_len + this_iter_len].to_vec();
-                let values_bucket = &values[processed_len..processed_len + this_iter_len].to_vec();
-
-                let msg = EvalNetMsg::PublishBatchValue {
-                    sender: self.id.clone(),
-                    handles: handles_bucket.to_owned(),
-                    values: values_bucket.to_owned(),
-                };
-                send_over_network!(msg, self.tx);
+        let rounds = batch_rounds(identifiers, &values, BATCH_ROUND_SIZE);
+        let mut next_round = 0;
+        let mut i = 0;
+        for round in rounds.iter() {
+            next_round = self.publish_rounds_within_limit(&rounds, next_round).await;
+
+            for identifier in round.0.iter() {
+                let incoming_msgs = self.collect_messages_from_all_peers_with_senders(identifier).await;
+                let mut contributions = vec![(self.node_id(), inputs[i].into_group())];
+                for (sender, msg) in incoming_msgs {
+                    contributions.push((self.node_id_of(&sender), decode_bs58_str_as_g2(&msg).into_group()));
+                }
 
-                processed_len += this_iter_len;
+                outputs.push(self.combine_in_exponent(contributions).into_affine());
+                i += 1;
             }
-        }
-        else {
-            let msg = EvalNetMsg::PublishBatchValue {
-                sender: self.id.clone(),
-                handles: identifiers.into(),
-                values: values,
-            };
-            send_over_network!(msg, self.tx);
-        }
-
-        for i in 0..inputs.len() {
-            let incoming_msgs = self.collect_messages_from_all_peers(&identifiers[i]).await;
-            let incoming_values: Vec<G2> = incoming_msgs
-                .into_iter()
-                .map(|x| decode_bs58_str_as_g2(&x))
-                .collect();
-
-            let sum = incoming_values
-                .iter()
-                .fold(inputs[i], |acc, v| acc.add(v).into_affine());
-
-            outputs.push(sum);
+            self.inflight_rounds -= 1;
         }
 
         outputs
@@ -794,24 +2272,22 @@ impl Evaluator {
         &mut self, value: &Gt, 
         identifier: &String
     ) -> Gt {
+        let key = self.operation_key(identifier);
 
         let msg = EvalNetMsg::PublishValue {
             sender: self.id.clone(),
-            handle: identifier.clone(),
+            handle: key.clone(),
             value: encode_gt_as_bs58_str(value),
         };
-        send_over_network!(msg, self.tx);
-
-        let incoming_msgs = self.collect_messages_from_all_peers(identifier).await;
+        send_over_network!(msg, self);
 
-        let incoming_values: Vec<Gt> = incoming_msgs
-            .into_iter()
-            .map(|x| decode_bs58_str_as_gt(&x))
-            .collect();
+        let incoming_msgs = self.collect_messages_from_all_peers_with_senders(&key).await;
 
-        let mut sum: Gt = value.clone();
-        for v in incoming_values { sum = sum.add(v); }
-        sum
+        let mut contributions = vec![(self.node_id(), *value)];
+        for (sender, msg) in incoming_msgs {
+            contributions.push((self.node_id_of(&sender), decode_bs58_str_as_gt(&msg)));
+        }
+        self.combine_in_exponent(contributions)
     }
 
     pub async fn batch_add_gt_elements_from_all_parties(
@@ -819,10 +2295,9 @@ impl Evaluator {
         inputs: &[Gt],
         identifiers: &[String]
     ) -> Vec<Gt> {
+        let identifiers: &[String] = &self.operation_keys(identifiers);
         assert_eq!(inputs.len(), identifiers.len());
 
-        let len = inputs.len();
-
         let mut outputs = Vec::new();
 
         let values = inputs
@@ -830,45 +2305,23 @@ impl Evaluator {
             .map(|e| encode_gt_as_bs58_str(e))
             .collect::<Vec<String>>();
 
-        if len > 64 {
-            let mut processed_len = 0;
-
-            while processed_len < len {
-                let this_iter_len = std::cmp::min(len - processed_len, 64);
-                let handles_bucket = &identifiers[processed_len..processed_len + this_iter_len].to_vec();
-                let values_bucket = &values[processed_len..processed_len + this_iter_len].to_vec();
+        let rounds = batch_rounds(identifiers, &values, GT_ROUND_SIZE);
+        let mut next_round = 0;
+        let mut i = 0;
+        for round in rounds.iter() {
+            next_round = self.publish_rounds_within_limit(&rounds, next_round).await;
+
+            for identifier in round.0.iter() {
+                let incoming_msgs = self.collect_messages_from_all_peers_with_senders(identifier).await;
+                let mut contributions = vec![(self.node_id(), inputs[i])];
+                for (sender, msg) in incoming_msgs {
+                    contributions.push((self.node_id_of(&sender), decode_bs58_str_as_gt(&msg)));
+                }
 
-                let msg = EvalNetMsg::PublishBatchValue {
-                    sender: self.id.clone(),
-                    handles: handles_bucket.to_owned(),
-                    values: values_bucket.to_owned(),
-                };
-                send_over_network!(msg, self.tx);
-
-                processed_len += this_iter_len;
+                outputs.push(self.combine_in_exponent(contributions));
+                i += 1;
             }
-        }
-        else {
-            let msg = EvalNetMsg::PublishBatchValue {
-                sender: self.id.clone(),
-                handles: identifiers.into(),
-                values: values,
-            };
-            send_over_network!(msg, self.tx);
-        }
-
-        for i in 0..inputs.len() {
-            let incoming_msgs = self.collect_messages_from_all_peers(&identifiers[i]).await;
-            let incoming_values: Vec<Gt> = incoming_msgs
-                .into_iter()
-                .map(|x| decode_bs58_str_as_gt(&x))
-                .collect();
-
-            let sum = incoming_values
-                .iter()
-                .fold(inputs[i], |acc, v| acc.add(v));
-
-            outputs.push(sum);
+            self.inflight_rounds -= 1;
         }
 
         outputs
@@ -883,9 +2336,11 @@ impl Evaluator {
     ) -> Gt {
         let mut sum = Gt::zero();
         
+        self.record_msm(bases.len());
+
         // Compute \sum_i g_i^[x_i]
         for (base, exponent_handle) in bases.iter().zip(exponent_handles.iter()) {
-            let my_share = self.get_wire(exponent_handle);
+            let my_share = self.local_share(exponent_handle);
             let exponentiated = base.clone().mul(my_share);
 
             sum = sum.add(exponentiated);
@@ -908,17 +2363,18 @@ impl Evaluator {
         let mut group_elements = vec![];
 
         for i in 0..len {
+            self.record_msm(bases[i].len());
             let msm_input = bases[i].iter().zip(exponent_handles[i].iter());
             let mut sum = Gt::zero();
 
             for (base, exponent_handle) in msm_input {
-                let exponent = self.get_wire(exponent_handle);
+                let exponent = self.local_share(exponent_handle);
 
                 if exponent == F::from(1) {
                     sum = sum.add(base);
                 }
                 else {
-                    sum = sum.add(base.mul(self.get_wire(exponent_handle)));
+                    sum = sum.add(base.mul(self.local_share(exponent_handle)));
                 }
             }
 
@@ -937,9 +2393,11 @@ impl Evaluator {
     ) -> G1 {
         let mut sum = G1::zero();
         
+        self.record_msm(bases.len());
+
         // Compute \sum_i g_i^[x_i]
         for (base, exponent_handle) in bases.iter().zip(exponent_handles.iter()) {
-            let my_share = self.get_wire(exponent_handle);
+            let my_share = self.local_share(exponent_handle);
             let exponentiated = base.clone().mul(my_share).into_affine();
 
             sum = sum.add(exponentiated).into_affine();
@@ -962,11 +2420,12 @@ impl Evaluator {
         let mut group_elements = vec![];
 
         for i in 0..len {
+            self.record_msm(bases[i].len());
             let msm_input = bases[i].iter().zip(exponent_handles[i].iter());
             let mut sum = G1::zero();
 
             for (base, exponent_handle) in msm_input {
-                let exponentiated = base.mul(self.get_wire(exponent_handle)).into_affine();
+                let exponentiated = base.mul(self.local_share(exponent_handle)).into_affine();
                 sum = sum.add(exponentiated).into_affine();
             }
 
@@ -976,6 +2435,21 @@ impl Evaluator {
         self.batch_add_g1_elements_from_all_parties(&group_elements, &identifiers).await
     }
 
+    /// exp_and_reveal_g1 of every (bases, exponent_handles) job, revealed
+    /// together in a single round; the i-th result is named name_prefix/i
+    pub async fn exp_and_reveal_g1_batch(
+        &mut self, 
+        jobs: &[(Vec<G1>, Vec<String>)], 
+        name_prefix: &str
+    ) -> Vec<G1> {
+        let (bases, exponent_handles): (Vec<Vec<G1>>, Vec<Vec<String>>) = jobs.iter().cloned().unzip();
+        let identifiers = (0..jobs.len())
+            .map(|i| format!("{}/{}", name_prefix, i))
+            .collect::<Vec<String>>();
+
+        self.batch_exp_and_reveal_g1(bases, exponent_handles, identifiers).await
+    }
+
     pub async fn exp_and_reveal_g2(
         &mut self, 
         bases: Vec<G2>, 
@@ -984,9 +2458,11 @@ impl Evaluator {
     ) -> G2 {
         let mut sum = G2::zero();
         
+        self.record_msm(bases.len());
+
         // Compute \sum_i g_i^[x_i]
         for (base, exponent_handle) in bases.iter().zip(exponent_handles.iter()) {
-            let my_share = self.get_wire(exponent_handle);
+            let my_share = self.local_share(exponent_handle);
             let exponentiated = base.clone().mul(my_share).into_affine();
 
             sum = sum.add(exponentiated).into_affine();
@@ -1009,11 +2485,12 @@ impl Evaluator {
         let mut group_elements = vec![];
 
         for i in 0..len {
+            self.record_msm(bases[i].len());
             let msm_input = bases[i].iter().zip(exponent_handles[i].iter());
             let mut sum = G2::zero();
 
             for (base, exponent_handle) in msm_input {
-                let exponentiated = base.mul(self.get_wire(exponent_handle)).into_affine();
+                let exponentiated = base.mul(self.local_share(exponent_handle)).into_affine();
                 sum = sum.add(exponentiated).into_affine();
             }
 
@@ -1034,7 +2511,7 @@ impl Evaluator {
         }
 
         let handle = self.compute_fresh_wire_label();
-        self.wire_shares.insert(handle.clone(), self.get_wire(&tmp));
+        self.wire_shares.insert(handle.clone(), self.local_share(&tmp));
         handle
     }
 
@@ -1050,14 +2527,21 @@ impl Evaluator {
         let mut output = Vec::new();
         for i in 0..input_labels.len() {
             let handle = self.compute_fresh_wire_label();
-            self.wire_shares.insert(handle.clone(), self.get_wire(&tmp[i]));
+            self.wire_shares.insert(handle.clone(), self.local_share(&tmp[i]));
             output.push(handle);
         }
 
         output
     }
 
-    pub fn get_wire(&self, handle: &String) -> F {
+    /// this party's share of the wire handle; only opening it, e.g. with
+    /// output_wire, gives the value on the wire
+    pub fn get_wire(&self, handle: &String) -> Share {
+        Share(self.local_share(handle))
+    }
+
+    /// get_wire, unwrapped for the share arithmetic of the evaluator
+    fn local_share(&self, handle: &String) -> F {
         self.wire_shares.get(handle).unwrap().clone()
     }
 
@@ -1065,7 +2549,7 @@ impl Evaluator {
         // get shares
         let f_shares = f_handles
             .iter()
-            .map(|h| self.get_wire(h))
+            .map(|h| self.local_share(h))
             .collect::<Vec<F>>();
 
         // Compute f_polynomial
@@ -1080,7 +2564,7 @@ impl Evaluator {
                 &(&divisor).into(),
             ).unwrap();
 
-        let pi_poly = utils::commit_poly(pp, &quotient);
+        let pi_poly = self.commit_poly(pp, &quotient);
         let pi = self.add_g1_elements_from_all_parties(&pi_poly, &f_name).await;
 
         pi
@@ -1099,7 +2583,7 @@ impl Evaluator {
                 &(&divisor).into(),
             ).unwrap();
 
-        let pi_poly = utils::commit_poly(pp, &quotient);
+        let pi_poly = self.commit_poly(pp, &quotient);
         let pi = self.add_g1_elements_from_all_parties(&pi_poly, &f_name).await;
 
         pi
@@ -1112,30 +2596,124 @@ impl Evaluator {
         z_s: &Vec<F>, 
         f_names: &Vec<String>
     ) -> Vec<G1> {
-        let len = share_polys.len();
-        assert_eq!(len, f_names.len());
+        assert_eq!(share_polys.len(), f_names.len());
 
-        let mut pi_share_vec = Vec::new();
-        for i in 0..len {
-            // Compute f_polynomial
-            let f_poly = share_polys[i].clone();
+        // the quotients have one coefficient less than the polynomials
+        for f_poly in share_polys.iter() {
+            self.record_msm(f_poly.coeffs.len().saturating_sub(1));
+        }
+        let pi_share_vec = eval_proof_shares(pp, share_polys, z_s);
 
-            let divisor = DensePolynomial::from_coefficients_vec(
-                vec![-z_s[i], F::from(1)]
-            );
+        self.batch_add_g1_elements_from_all_parties(&pi_share_vec, &f_names).await
+    }
 
-            // Divide by (X-z_i)
-            let (quotient, _remainder) = 
-                DenseOrSparsePolynomial::divide_with_q_and_r(
-                    &(&f_poly).into(),
-                    &(&divisor).into(),
-                ).unwrap();
+    /// the eval proofs of the polynomials whose values on the subgroup of
+    /// size n this party holds shares of, share_evals[i], at ω^{indices[i]};
+    /// opened in the Lagrange basis, lagrange = utils::lagrange_commitments
+    /// of size n, with no interpolation or division
+    pub async fn batch_eval_proof_at_roots(
+        &mut self, 
+        lagrange: &[G1], 
+        share_evals: &[Vec<F>], 
+        indices: &[usize], 
+        f_names: &[String]
+    ) -> Vec<G1> {
+        assert_eq!(share_evals.len(), indices.len());
+        assert_eq!(share_evals.len(), f_names.len());
 
-            let pi_poly = utils::commit_poly(pp, &quotient);
-            pi_share_vec.push(pi_poly);
+        for evals in share_evals.iter() {
+            self.record_msm(evals.len());
         }
+        let pi_share_vec: Vec<G1> = ark_std::cfg_iter!(share_evals)
+            .zip(ark_std::cfg_iter!(indices))
+            .map(|(evals, i)| utils::eval_proof_at_root(lagrange, evals, *i))
+            .collect();
 
-        self.batch_add_g1_elements_from_all_parties(&pi_share_vec, &f_names).await
+        self.batch_add_g1_elements_from_all_parties(&pi_share_vec, f_names).await
+    }
+
+    /// replaces the card at position (e.g. one exposed by a misdeal) with a
+    /// card drawn uniformly at random from the undealt positions after it,
+    /// and returns the handle of the new card. Only the first *dealable
+    /// cards of the deck are ever dealt, e.g. DECK_SIZE under
+    /// JokerPolicy::Excluded, which keeps the jokers out. The shuffled deck
+    /// already holds every value, so there is no fresh card to draw; instead,
+    /// the two cards swap places, and the burned card then swaps with the
+    /// last dealable one and *dealable shrinks by one, so it is never dealt
+    /// again. Swapping keeps the deck a permutation (and so provable with a
+    /// new permutation argument); the positions drawn are public, but as all
+    /// undealt cards are hidden they reveal nothing about any card.
+    pub async fn replace_card(
+        &mut self,
+        card_handles: &mut [String],
+        dealable: &mut usize,
+        position: usize
+    ) -> Result<String, EvaluatorError> {
+        assert!(*dealable <= card_handles.len(), "more dealable cards than cards in the deck");
+        let undealt = dealable.saturating_sub(position + 1);
+        assert!(undealt > 0, "no card left after position {} to replace it with", position);
+
+        let h_r = self.ran()?;
+        let r: BigUint = self.output_wire(&h_r).await.into();
+        let offset = (r % BigUint::from(undealt)).to_u64_digits().first().copied().unwrap_or(0);
+
+        let drawn = position + 1 + offset as usize;
+        card_handles.swap(position, drawn);
+        card_handles.swap(drawn, *dealable - 1);
+        *dealable -= 1;
+        Ok(card_handles[position].clone())
+    }
+
+    /// opens only the cards at the given positions of a committed deck
+    /// (e.g. the board), each with a KZG proof that f(ω^position) = card
+    /// against f_com; all other positions stay hidden behind the commitment.
+    /// f_poly_share is this party's share of the deck polynomial f(X).
+    /// Fails, before anything is opened, on a position outside the deck,
+    /// and with OpeningMismatch on an opening that does not match f_com
+    pub async fn reveal_positions(
+        &mut self,
+        pp: &UniversalParams<Curve>,
+        card_handles: &[String],
+        positions: &[usize],
+        f_poly_share: &DensePolynomial<F>,
+        f_com: &G1
+    ) -> Result<Vec<(usize, F, G1)>, EvaluatorError> {
+        let deck_size = card_handles.len();
+        if let Some(&position) = positions.iter().find(|&&i| i >= deck_size) {
+            return Err(EvaluatorError::PositionOutOfRange { position, deck_size });
+        }
+        let ω = utils::multiplicative_subgroup_of_size(deck_size as u64).unwrap();
+
+        let handles = positions
+            .iter()
+            .map(|&i| card_handles[i].clone())
+            .collect::<Vec<String>>();
+        let cards = self.batch_output_wire(&handles).await;
+
+        // the proofs are summed under operation keys, so opening the same
+        // position twice does not mix the shares of the two openings
+        let proofs = self.batch_eval_proof_with_share_poly(
+            pp,
+            &vec![f_poly_share.clone(); positions.len()],
+            &positions.iter().map(|&i| utils::compute_power(&ω, i as u64)).collect(),
+            &positions.iter().map(|i| format!("reveal_pi/{}", i)).collect()
+        ).await;
+
+        let openings = positions
+            .iter()
+            .zip(cards.into_iter().zip(proofs))
+            .map(|(&i, (card, pi))| (i, card, pi))
+            .collect::<Vec<(usize, F, G1)>>();
+
+        // every opening must be consistent with the shuffle commitment
+        for (i, card, pi) in openings.iter() {
+            let point = utils::compute_power(&ω, *i as u64);
+            if !utils::kzg_check(pp, f_com, &point, card, pi) {
+                return Err(EvaluatorError::OpeningMismatch { position: *i });
+            }
+        }
+
+        Ok(openings)
     }
 
     pub async fn dist_ibe_encrypt(
@@ -1144,12 +2722,12 @@ impl Evaluator {
         mask_share_handle: &String, // [r]
         pk: &G2, 
         id: BigUint
-    ) -> (G1, Gt) {
+    ) -> Result<(G1, Gt), utils::IdError> {
         // let msg_share = self.output_wire_in_exponent(msg_share_handle).await;
         // let mask_share = self.output_wire_in_exponent(mask_share_handle).await;
     
         // TODO: fix this. Need proper hash to curve
-        let x_f = F::from(id);
+        let x_f = utils::id_to_field_checked(&id)?;
         let hash_id = G1::generator().mul(x_f);
 
         let h = <Curve as Pairing>::pairing(hash_id, pk);
@@ -1166,7 +2744,7 @@ impl Evaluator {
             &String::from("ibe_c2".to_owned() + msg_share_handle + mask_share_handle)
         ).await;
     
-        (c1, c2)
+        Ok((c1, c2))
     }
 
     pub async fn batch_dist_ibe_encrypt(
@@ -1175,16 +2753,22 @@ impl Evaluator {
         mask_share_handles: &[String], // [r]
         pk: &G2, 
         ids: &[BigUint]
-    ) -> (Vec<G2>, Vec<Gt>) {
+    ) -> Result<(Vec<G2>, Vec<Gt>), utils::IdError> {
         assert_eq!(msg_share_handles.len(), mask_share_handles.len());
 
+        // every id is checked before any round, so no party opens anything
+        // for an encryption that cannot go through
+        let x_fs = ids
+            .iter()
+            .map(utils::id_to_field_checked)
+            .collect::<Result<Vec<F>, utils::IdError>>()?;
+
         // Compute e_i^r
-        let e_is = ids
+        let e_is = x_fs
             .iter()
             .zip(mask_share_handles.iter())
-            .map(|(id, mask)| {
-                let x_f = F::from(id.clone());
-                let hash_id_pow_r = G1::generator().mul(x_f).mul(self.get_wire(&mask));
+            .map(|(x_f, mask)| {
+                let hash_id_pow_r = G1::generator().mul(x_f).mul(self.local_share(mask));
 
                 <Curve as Pairing>::pairing(hash_id_pow_r, pk)
             })
@@ -1224,7 +2808,7 @@ impl Evaluator {
                 .collect::<Vec<String>>()
         ).await;
 
-        (c1s, c2s)
+        Ok((c1s, c2s))
     }
 
     //returns the handle which 
@@ -1270,34 +2854,90 @@ impl Evaluator {
             } => {
                 self.accept_handle_and_value_from_sender(sender, handle, value);
             },
+            EvalNetMsg::PublicCommitment {
+                name,
+                node_id,
+                point_bytes
+            } => {
+                // the network checked the point; the mailbox holds it as
+                // PublishValue would have, so collecting it is the same
+                if let Some(sender) = get_peer_id_via_node_id(&self.addr_book, *node_id) {
+                    let value = bs58::encode(point_bytes).into_string();
+                    self.accept_handle_and_value_from_sender(&sender, name, &value);
+                }
+            },
+            EvalNetMsg::SendBatchShare { 
+                sender,
+                receiver,
+                handles,
+                values
+            } => {
+                // like SendTriple, meant for the receiver only
+                if ! receiver.eq(&self.id) { return; }
+                // a malformed batch is dropped as a whole, as is one larger
+                // than a round of batch_rounds
+                if handles.len() != values.len() || handles.len() > BATCH_ROUND_SIZE { return; }
+
+                for (h,v) in handles.iter().zip(values.iter()) {
+                    self.accept_handle_and_value_from_sender(sender, h, v);
+                }
+            },
             EvalNetMsg::PublishBatchValue { 
                 sender,
                 handles,
                 values
             } => {
-                assert_eq!(handles.len(), values.len());
+                if handles.len() != values.len() || handles.len() > BATCH_ROUND_SIZE { return; }
 
                 for (h,v) in handles.iter().zip(values.iter()) {
                     self.accept_handle_and_value_from_sender(sender, h, v);
                 }
             },
+            EvalNetMsg::Ping { sender, nonce } => {
+                if sender != &self.id && self.addr_book.contains_key(sender) {
+                    self.answer_ping(sender, nonce);
+                }
+            },
             _ => return,
         }
     }
 
+    /// sends the Pong for a Ping right away, even if sends are buffered:
+    /// process_next_message cannot await, but the channel to networkd is
+    /// unbounded, so this never blocks
+    fn answer_ping(&mut self, pinger: &str, nonce: &str) {
+        let msg = EvalNetMsg::Pong { sender: self.id.clone(), receiver: pinger.to_string(), nonce: nonce.to_string() };
+        self.messages_sent += 1;
+        self.log_to_transcript(TranscriptDirection::Sent, &msg);
+        if let Err(err) = self.tx.unbounded_send(msg) {
+            eprint!("evaluator error {:?}", err);
+        }
+    }
+
     fn accept_handle_and_value_from_sender(&mut self, 
         sender: &String, 
         handle: &String, 
         value: &String
     ) {
-        // if already exists, then ignore
-        if self.mailbox.contains_key(handle) {
-            let sender_exists_for_handle = self.mailbox
-                .get(handle)
-                .unwrap()
-                .contains_key(sender);
-            if sender_exists_for_handle { return; } //ignore duplicate msg!
-        } else {
+        // only parties of the address book contribute to anything
+        if !self.addr_book.contains_key(sender) { return; }
+
+        // if already exists, then ignore, but remember if the value changed
+        if let Some(previous) = self.mailbox.get(handle).and_then(|s| s.get(sender)) {
+            if previous != value {
+                self.conflicts.insert(handle.clone(), sender.clone());
+            }
+            return; //ignore duplicate msg!
+        }
+
+        // a peer flooding us with handles nobody collects gets cut off;
+        // networkd checked that the message comes from sender
+        let max_pending = self.max_pending_per_peer();
+        let pending = self.pending_per_peer.entry(sender.clone()).or_insert(0);
+        if *pending >= max_pending { return; }
+        *pending += 1;
+
+        if !self.mailbox.contains_key(handle) {
             //mailbox never got a message by this handle so lets make room for it
             self.mailbox.insert(handle.clone(), HashMap::new());
         }
@@ -1308,6 +2948,25 @@ impl Evaluator {
             .insert(sender.clone(), value.clone());
     }
 
+    /// the next message from the network, or Err(Cancelled) once the run
+    /// was cancelled or the network is gone, as nothing will arrive then
+    async fn next_message(&mut self) -> Result<EvalNetMsg, EvaluatorError> {
+        // peers may be waiting on what we queued, as we are on them
+        self.flush().await;
+
+        let token = self.cancel_token.clone();
+        match cancellable(&token, self.rx.next()).await? {
+            Some(msg) => {
+                self.log_to_transcript(TranscriptDirection::Received, &msg);
+                Ok(msg)
+            },
+            None => {
+                self.cancel_token.cancel();
+                Err(EvaluatorError::Cancelled)
+            },
+        }
+    }
+
     fn exists_in_wire_shares(&self, handles: Vec<String>) -> bool {
         handles
             .iter()
@@ -1321,8 +2980,67 @@ impl Evaluator {
         &mut self, 
         identifier: &String
     ) -> Vec<String> {
+        self.collect_messages_from_all_peers_with_senders(identifier)
+            .await
+            .into_iter()
+            .map(|(_, msg)| msg)
+            .collect()
+    }
+
+    /// waits until count peers have sent a message for identifier, and
+    /// returns those (the lowest peer ids, if more are already in); messages
+    /// arriving after this from the remaining peers stay in the mailbox
+    async fn collect_messages_from_any_peers_with_senders(
+        &mut self, 
+        identifier: &String, 
+        count: usize
+    ) -> Vec<(Pok3rPeerId, String)> {
+        loop {
+            let received = self.mailbox
+                .get(identifier)
+                .map_or(0, |senders| senders.keys().filter(|s| !self.id.eq(*s)).count());
+            if received >= count { break; }
+
+            let msg = self.next_message().await;
+            let msg: EvalNetMsg = expect_running(self.cancel_token.clone(), msg).await;
+            self.process_next_message(&msg);
+        }
+
+        let senders = self.mailbox.remove(identifier).unwrap_or_default();
+        for sender in senders.keys() {
+            if let Some(pending) = self.pending_per_peer.get_mut(sender) {
+                *pending -= 1;
+            }
+        }
+
+        let mut messages: Vec<(Pok3rPeerId, String)> = senders
+            .into_iter()
+            .filter(|(sender, _)| !self.id.eq(sender))
+            .collect();
+        messages.sort();
+        messages.truncate(count);
+        messages
+    }
+
+    /// same as collect_messages_from_all_peers, but also returns who sent what;
+    /// peers are visited in a fixed order, so two calls line up index by index
+    async fn collect_messages_from_all_peers_with_senders(
+        &mut self, 
+        identifier: &String
+    ) -> Vec<(Pok3rPeerId, String)> {
+        let messages = self.try_collect_messages_from_all_peers_with_senders(identifier).await;
+        expect_running(self.cancel_token.clone(), messages).await
+    }
+
+    /// collect_messages_from_all_peers_with_senders for the operations that
+    /// can fail, which fail with Err(Cancelled) once the run is cancelled
+    async fn try_collect_messages_from_all_peers_with_senders(
+        &mut self, 
+        identifier: &String
+    ) -> Result<Vec<(Pok3rPeerId, String)>, EvaluatorError> {
         let mut messages = vec![];
-        let peers: Vec<Pok3rPeerId> = self.addr_book.keys().cloned().collect();
+        let mut peers: Vec<Pok3rPeerId> = self.addr_book.keys().cloned().collect();
+        peers.sort();
         for peer_id in peers {
             if self.id.eq(&peer_id) { continue; }
 
@@ -1336,7 +3054,7 @@ impl Evaluator {
                     if sender_exists_for_handle { break; }
                 }
 
-                let msg: EvalNetMsg = self.rx.select_next_some().await;
+                let msg: EvalNetMsg = self.next_message().await?;
                 self.process_next_message(&msg);
             }
 
@@ -1348,19 +3066,97 @@ impl Evaluator {
                 .unwrap()
                 .clone();
             
-            messages.push(msg);
+            messages.push((peer_id, msg));
         }
 
         //clear the mailbox because we might want to use identifier again
-        self.mailbox.remove(identifier);
+        if let Some(senders) = self.mailbox.remove(identifier) {
+            for sender in senders.keys() {
+                if let Some(pending) = self.pending_per_peer.get_mut(sender) {
+                    *pending -= 1;
+                }
+            }
+        }
 
-        messages
+        Ok(messages)
     }
 
 }
 
+impl Drop for Evaluator {
+    /// closes the channel towards the networking daemon, which makes it
+    /// return, and joins its thread; a daemon that does not stop within
+    /// NETWORK_SHUTDOWN_TIMEOUT is left running rather than hanging the drop
+    fn drop(&mut self) {
+        // what is still queued goes out before the channel closes
+        for msg in self.outbox.take().unwrap_or_default() {
+            let _ = self.tx.unbounded_send(msg);
+        }
+        self.tx.close_channel();
+
+        if let Some(handle) = self.network_thread.take() {
+            let deadline = Instant::now() + NETWORK_SHUTDOWN_TIMEOUT;
+            while !handle.is_finished() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            if handle.is_finished() {
+                let _ = handle.join();
+            } else {
+                eprintln!("networking daemon did not stop within {:?}", NETWORK_SHUTDOWN_TIMEOUT);
+            }
+        }
+    }
+}
+
+/// SharedEvaluator lets several threads use one party's evaluator: every
+/// field of Evaluator is Send (its channels, maps, the boxed rng and the
+/// transcript file), so it can move between threads, but it is not Sync
+/// (the rng is not), so it is shared behind an async mutex. Anything that
+/// talks to the network goes through lock and so is serialized, keeping
+/// the messages of a party in protocol order; purely local work, like
+/// commit_poly, runs without the lock and so runs concurrently
+#[derive(Clone)]
+pub struct SharedEvaluator {
+    inner: Arc<async_std::sync::Mutex<Evaluator>>,
+}
+
+impl SharedEvaluator {
+    pub fn new(evaluator: Evaluator) -> Self {
+        SharedEvaluator { inner: Arc::new(async_std::sync::Mutex::new(evaluator)) }
+    }
 
-fn encode_f_as_bs58_str(value: &F) -> String {
+    /// exclusive access to the evaluator, e.g. for a protocol step; other
+    /// users wait until the guard is dropped
+    pub async fn lock(&self) -> async_std::sync::MutexGuard<'_, Evaluator> {
+        self.inner.lock().await
+    }
+
+    /// Evaluator::commit_poly, computed without holding the lock
+    pub async fn commit_poly(&self, pp: &UniversalParams<Curve>, f: &DensePolynomial<F>) -> G1 {
+        let commitment = utils::commit_poly(pp, f);
+        self.lock().await.record_msm(f.coeffs.len());
+        commitment
+    }
+
+    /// the evaluator back, once no other clone of self is left
+    pub fn into_inner(self) -> Option<Evaluator> {
+        Arc::try_unwrap(self.inner).ok().map(|mutex| mutex.into_inner())
+    }
+}
+
+
+/// handles and values split into the batches of round_size that are
+/// published as one round each
+fn batch_rounds(handles: &[String], values: &[String], round_size: usize) -> Vec<(Vec<String>, Vec<String>)> {
+    handles
+        .chunks(round_size)
+        .zip(values.chunks(round_size))
+        .map(|(handles, values)| (handles.to_vec(), values.to_vec()))
+        .collect()
+}
+
+pub fn encode_f_as_bs58_str(value: &F) -> String {
     let mut buffer: Vec<u8> = Vec::new();
     value.serialize_compressed(&mut buffer).unwrap();
     bs58::encode(buffer).into_string()
@@ -1382,6 +3178,35 @@ fn decode_bs58_str_as_g1(msg: &String) -> G1 {
     G1::deserialize_compressed(&mut Cursor::new(decoded)).unwrap()
 }
 
+/// this party's shares of the eval proofs of share_polys[i] at z_s[i];
+/// purely local, so with the parallel feature the polynomials are
+/// divided and committed on the rayon thread pool
+fn eval_proof_shares(
+    pp: &UniversalParams<Curve>, 
+    share_polys: &[DensePolynomial<F>], 
+    z_s: &[F]
+) -> Vec<G1> {
+    assert_eq!(share_polys.len(), z_s.len());
+
+    ark_std::cfg_iter!(share_polys)
+        .zip(ark_std::cfg_iter!(z_s))
+        .map(|(f_poly, z)| {
+            let divisor = DensePolynomial::from_coefficients_vec(
+                vec![-*z, F::from(1)]
+            );
+
+            // Divide by (X-z_i)
+            let (quotient, _remainder) = 
+                DenseOrSparsePolynomial::divide_with_q_and_r(
+                    &f_poly.into(),
+                    &(&divisor).into(),
+                ).unwrap();
+
+            utils::commit_poly(pp, &quotient)
+        })
+        .collect()
+}
+
 fn encode_g2_as_bs58_str(value: &G2) -> String {
     let mut serialized_msg: Vec<u8> = Vec::new();
     value.serialize_compressed(&mut serialized_msg).unwrap();
@@ -1404,10 +3229,53 @@ fn decode_bs58_str_as_gt(msg: &String) -> Gt {
     Gt::deserialize_compressed(&mut Cursor::new(decoded)).unwrap()
 }
 
+/// hash commitment H(share || nonce) used by output_wire_committed
+fn commit_to_share(share: &F, nonce: &[u8; 32]) -> Vec<u8> {
+    let mut buffer: Vec<u8> = Vec::new();
+    share.serialize_compressed(&mut buffer).unwrap();
+    buffer.extend_from_slice(nonce);
+
+    let mut hasher = Sha256::new();
+    hasher.update(&buffer);
+    hasher.finalize().to_vec()
+}
 
+/// whether (share, nonce) opens commitment, as broadcast by commit_wire
+/// the pad of the share dealer deals to receiver under label, from the key
+/// the two of them share
+fn share_pad(pair_key: &[u8; 32], label: &str, dealer: usize, receiver: usize) -> F {
+    let dealer_bytes = (dealer as u64).to_le_bytes();
+    let receiver_bytes = (receiver as u64).to_le_bytes();
+    utils::fs_hash(vec![pair_key, label.as_bytes(), &dealer_bytes, &receiver_bytes], 1)[0]
+}
 
+pub fn share_opening_matches(commitment: &String, share: &F, nonce: &[u8; 32]) -> bool {
+    bs58::encode(commit_to_share(share, nonce)).into_string().eq(commitment)
+}
+
+fn encode_share_opening_as_bs58_str(share: &F, nonce: &[u8; 32]) -> String {
+    let mut buffer: Vec<u8> = Vec::new();
+    share.serialize_compressed(&mut buffer).unwrap();
+    buffer.extend_from_slice(nonce);
+    bs58::encode(buffer).into_string()
+}
+
+/// None for a reveal that is no (share, nonce) at all, which a party
+/// only sends when it cheats
+fn decode_bs58_str_as_share_opening(msg: &String) -> Option<(F, [u8; 32])> {
+    let buf: Vec<u8> = bs58::decode(msg).into_vec().ok()?;
+    let share_len = buf.len().checked_sub(32)?;
+    let (share_bytes, nonce_bytes) = buf.split_at(share_len);
+    let share = F::deserialize_compressed(share_bytes).ok()?;
+    Some((share, nonce_bytes.try_into().unwrap()))
+}
+
+
+
+/// runs against whatever party set the evaluator's address book holds
 pub async fn perform_sanity_testing(evaluator: &mut Evaluator) {
     println!("-------------- Running some sanity tests -----------------");
+    println!("parties: {}", evaluator.num_parties());
 
     println!("testing beaver triples...");
     let (h_a, h_b, h_c) = evaluator.beaver().await;
@@ -1417,8 +3285,8 @@ pub async fn perform_sanity_testing(evaluator: &mut Evaluator) {
     assert_eq!(c, a * b);
 
     println!("testing adder...");
-    let h_r1 = evaluator.ran();
-    let h_r2 = evaluator.ran();
+    let h_r1 = evaluator.ran().unwrap();
+    let h_r2 = evaluator.ran().unwrap();
     let r1 = evaluator.output_wire(&h_r1).await;
     let r2 = evaluator.output_wire(&h_r2).await;
     let h_sum_r1_r2 = evaluator.add(&h_r1, &h_r2);
@@ -1458,8 +3326,8 @@ pub async fn perform_sanity_testing(evaluator: &mut Evaluator) {
     let mut xs_handles = Vec::new();
     let mut ys_handles = Vec::new();
     for _i in 0..5 {
-        let h_r1 = evaluator.ran();
-        let h_r2 = evaluator.ran();
+        let h_r1 = evaluator.ran().unwrap();
+        let h_r2 = evaluator.ran().unwrap();
 
         xs_handles.push(h_r1);
         ys_handles.push(h_r2);
@@ -1480,18 +3348,18 @@ pub async fn perform_sanity_testing(evaluator: &mut Evaluator) {
 
 
     println!("testing inverter...");
-    let h_r3 = evaluator.ran();
+    let h_r3 = evaluator.ran().unwrap();
     let r3 = evaluator.output_wire(&h_r3).await;
-    let h_r3_inverted = evaluator.inv(&h_r3).await;
+    let h_r3_inverted = evaluator.inv(&h_r3).await.unwrap();
     let r3_inverted = evaluator.output_wire(&h_r3_inverted).await;
     assert_eq!(ark_bls12_377::Fr::from(1), r3 * r3_inverted);
 
     println!("testing batch inverter...");
     let xs_handles: Vec<String> = (0..5)
             .into_iter()
-            .map(|_| evaluator.ran())
+            .map(|_| evaluator.ran().unwrap())
             .collect();
-    let inv_xs_handles = evaluator.batch_inv(&xs_handles).await;
+    let inv_xs_handles = evaluator.batch_inv(&xs_handles).await.unwrap();
     for i in 0..5 {
         let x = evaluator.output_wire(&xs_handles[i]).await;
         let inv_x = evaluator.output_wire(&inv_xs_handles[i]).await;
@@ -1499,23 +3367,1299 @@ pub async fn perform_sanity_testing(evaluator: &mut Evaluator) {
     }
 
     println!("testing exponentiator...");
-    let h_r = evaluator.ran();
+    let h_r = evaluator.ran().unwrap();
     let r = evaluator.output_wire(&h_r).await;
     let h_r_exp_64 = evaluator.exp(&h_r).await;
     let r_exp_64 = evaluator.output_wire(&h_r_exp_64).await;
     assert_eq!(r.pow([64]), r_exp_64);
 
     println!("testing scale...");
-    let h_r = evaluator.ran();
+    let h_r = evaluator.ran().unwrap();
     let r = evaluator.output_wire(&h_r).await;
     let h_r_scaled = evaluator.scale(&h_r, F::from(42));
     let r_scaled = evaluator.output_wire(&h_r_scaled).await;
     assert_eq!(r * F::from(42), r_scaled);
 
     println!("testing output_wire and output_wire_in_exponent...");
-    let h_r = evaluator.ran();
+    let h_r = evaluator.ran().unwrap();
     let g_pow_r = evaluator.output_wire_in_exponent(&h_r).await;
     let r = evaluator.output_wire(&h_r).await;
     let g = <Curve as Pairing>::G1Affine::generator().clone();
     assert_eq!(g_pow_r, g.mul(&r));
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::network::in_process::*;
+
+    #[async_std::test]
+    async fn test_new_rejects_id_missing_from_addr_book() {
+        let addr_book = local_addr_book(3);
+        let (tx, _) = mpsc::unbounded::<EvalNetMsg>();
+        let (_, rx) = mpsc::unbounded::<EvalNetMsg>();
+
+        let result = Evaluator::new(&String::from("stranger"), addr_book, tx, rx).await;
+        match result {
+            Err(err) => assert_eq!(err, EvaluatorError::SelfNotInAddrBook {
+                id: String::from("stranger"),
+                known_ids: vec![
+                    String::from("party_0"), 
+                    String::from("party_1"), 
+                    String::from("party_2")
+                ],
+            }),
+            Ok(_) => panic!("evaluator accepted an id missing from the address book"),
+        }
+    }
+
+    #[async_std::test]
+    async fn test_sanity_suite_with_four_parties() {
+        run_parties(4, |mut evaluator| async move {
+            evaluator.test_networking().await.unwrap();
+            perform_sanity_testing(&mut evaluator).await;
+        }).await;
+    }
+
+    /// yields zero words for its first zeros_left draws, so that the next
+    /// F::rand (four words) comes out as zero
+    struct ZeroFirstRng {
+        zeros_left: usize,
+        inner: StdRng,
+    }
+
+    impl RngCore for ZeroFirstRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            if self.zeros_left > 0 {
+                self.zeros_left -= 1;
+                return 0;
+            }
+            self.inner.next_u64()
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                let word = self.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&word[..chunk.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[async_std::test]
+    async fn test_ran_nonzero_resamples_zero() {
+        let zero_first = |node_id: u64| ZeroFirstRng { zeros_left: 4, inner: StdRng::seed_from_u64(node_id) };
+
+        // every party's first share is zero, so ran() opens to zero
+        let outputs = run_parties(3, |evaluator| async move {
+            let node_id = evaluator.node_id() as u64;
+            let mut evaluator = evaluator.with_rng(zero_first(node_id));
+            let h_r = evaluator.ran().unwrap();
+            evaluator.output_wire(&h_r).await
+        }).await;
+        assert!(outputs.iter().all(|r| r.is_zero()));
+
+        // while ran_nonzero throws that draw away
+        let outputs = run_parties(3, |evaluator| async move {
+            let node_id = evaluator.node_id() as u64;
+            let mut evaluator = evaluator.with_rng(zero_first(node_id));
+            let h_r = evaluator.ran_nonzero().await.unwrap();
+            let r = evaluator.output_wire(&h_r).await;
+            let h_r_inv = evaluator.inv(&h_r).await.unwrap();
+            (r, evaluator.output_wire(&h_r_inv).await)
+        }).await;
+        for (r, r_inv) in outputs {
+            assert!(!r.is_zero());
+            assert_eq!(r * r_inv, F::one());
+        }
+
+        // and inv draws its mask again only when the opened r·s is zero
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let node_id = evaluator.node_id() as u64;
+            let h_x = evaluator.ran().unwrap();
+            let mut evaluator = evaluator.with_rng(zero_first(node_id));
+            let h_x_inv = evaluator.inv(&h_x).await.unwrap();
+            evaluator.batch_output_wire(&[h_x, h_x_inv]).await
+        }).await;
+        for output in outputs {
+            assert_eq!(output[0] * output[1], F::one());
+        }
+    }
+
+    #[async_std::test]
+    async fn test_ran_64_batch_matches_single_calls() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let h_as: Vec<String> = (0..8).map(|_| evaluator.ran().unwrap()).collect();
+
+            let sent_before = evaluator.messages_sent();
+            let h_cs = evaluator.ran_64_batch(&h_as).await;
+            let batch_sent = evaluator.messages_sent() - sent_before;
+            let batched = evaluator.batch_output_wire(&h_cs).await;
+
+            let sent_before = evaluator.messages_sent();
+            let h_c = evaluator.ran_64(&h_as[0]).await;
+            let single_sent = evaluator.messages_sent() - sent_before;
+
+            let mut single = vec![];
+            single.push(evaluator.output_wire(&h_c).await);
+            for h_a in &h_as[1..] {
+                let h_c = evaluator.ran_64(h_a).await;
+                single.push(evaluator.output_wire(&h_c).await);
+            }
+
+            (batched, single, batch_sent, single_sent)
+        }).await;
+
+        for (batched, single, batch_sent, single_sent) in outputs {
+            assert!(batched.iter().all(|c| c.pow([PERM_SIZE as u64]) == F::one()));
+            assert_eq!(batched, single);
+            assert_eq!(batch_sent, single_sent);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_unnamed_operations_get_distinct_keys() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let first = evaluator.operation_key("");
+            let second = evaluator.operation_key("");
+            assert_ne!(first, second);
+
+            // two reveals without names, which used to share a mailbox key
+            let x = G1::generator().mul(F::from(evaluator.node_id() as u64 + 1)).into_affine();
+            let y = G1::generator().mul(F::from(10 * (evaluator.node_id() as u64 + 1))).into_affine();
+            let sum_x = evaluator.add_g1_elements_from_all_parties(&x, &String::new()).await;
+            let sum_y = evaluator.add_g1_elements_from_all_parties(&y, &String::new()).await;
+            (sum_x, sum_y)
+        }).await;
+
+        for (sum_x, sum_y) in outputs {
+            assert_eq!(sum_x, G1::generator().mul(F::from(6u64)).into_affine());
+            assert_eq!(sum_y, G1::generator().mul(F::from(60u64)).into_affine());
+        }
+    }
+
+    #[async_std::test]
+    async fn test_challenge_mismatch_is_caught() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            // the same public transcript, but party 2 encodes it big-endian
+            let transcript = [F::from(7u64), F::from(11u64)];
+            let encoding = match evaluator.node_id() {
+                2 => utils::FieldEncoding::BigEndian,
+                _ => utils::FieldEncoding::LittleEndian,
+            };
+            let challenge = utils::fs_hash_fields_with(utils::HashConfig::Sha256, encoding, &transcript, 1);
+            let diverged = evaluator.check_challenge_agreement("test_y", &challenge).await;
+
+            // with one encoding, everyone agrees
+            let challenge = utils::fs_hash_fields_with(utils::HashConfig::Sha256, utils::FieldEncoding::LittleEndian, &transcript, 1);
+            (diverged, evaluator.check_challenge_agreement("test_y", &challenge).await)
+        }).await;
+
+        let mismatch = |sender: &str| Err(EvaluatorError::ChallengeMismatch {
+            label: String::from("test_y"),
+            sender: String::from(sender),
+        });
+        assert_eq!(outputs[0].0, mismatch("party_2"));
+        assert_eq!(outputs[1].0, mismatch("party_2"));
+        assert_eq!(outputs[2].0, mismatch("party_0"));
+        assert!(outputs.iter().all(|(_, agreed)| agreed.is_ok()));
+    }
+
+    #[async_std::test]
+    async fn test_srs_mismatch_is_caught_before_aggregation() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            // party 2 installed an SRS from another ceremony
+            let pp = match evaluator.node_id() {
+                2 => crate::kzg::ceremony_contribute(&utils::setup_kzg(64), b"another ceremony").0,
+                _ => utils::setup_kzg(64),
+            };
+
+            // each party commits to its additive share v(X) / 3 of the public
+            // v(X); the sum is the agreed commitment only under one SRS
+            let (v, v_com) = utils::canonical_deck_poly(&pp, 64).unwrap();
+            let v_share = &v * F::from(3u64).inverse().unwrap();
+            let v_share_com = utils::commit_poly(&pp, &v_share);
+            let aggregated = evaluator.add_g1_elements_from_all_parties(&v_share_com, &String::from("v_com")).await;
+
+            (evaluator.check_srs_agreement(&pp).await, aggregated == v_com)
+        }).await;
+
+        assert_eq!(outputs[0].0, Err(EvaluatorError::SrsMismatch { sender: String::from("party_2") }));
+        assert_eq!(outputs[1].0, Err(EvaluatorError::SrsMismatch { sender: String::from("party_2") }));
+        assert_eq!(outputs[2].0, Err(EvaluatorError::SrsMismatch { sender: String::from("party_0") }));
+        // without the check, nobody would notice but by the commitment
+        assert!(outputs.iter().all(|(_, aggregated_is_v_com)| !aggregated_is_v_com));
+
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let pp = utils::setup_kzg(64);
+            evaluator.check_srs_agreement(&pp).await
+        }).await;
+        assert!(outputs.iter().all(|result| result.is_ok()));
+    }
+
+    #[async_std::test]
+    async fn test_check_share_poly_mult_catches_corrupted_product() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let node_id = evaluator.node_id() as u64;
+            let f_share = DensePolynomial::<F>::rand(20, &mut StdRng::seed_from_u64(10 + node_id));
+            let g_share = DensePolynomial::<F>::rand(30, &mut StdRng::seed_from_u64(20 + node_id));
+            let h_share = evaluator.share_poly_mult_over_domain(f_share.clone(), g_share.clone(), 64).await;
+            let pp = utils::setup_kzg(64);
+
+            let honest = evaluator.check_share_poly_mult(&pp, &f_share, &g_share, &h_share, "honest").await;
+
+            // party 1 shifts its contribution to the product by X^3
+            let mut corrupted_share = h_share.clone();
+            if node_id == 1 {
+                corrupted_share = corrupted_share + DensePolynomial::from_coefficients_vec(vec![F::zero(), F::zero(), F::zero(), F::one()]);
+            }
+            let corrupted = evaluator.check_share_poly_mult(&pp, &f_share, &g_share, &corrupted_share, "corrupted").await;
+
+            (honest, corrupted)
+        }).await;
+
+        for (honest, corrupted) in outputs {
+            assert_eq!(honest, Ok(()));
+            assert_eq!(corrupted, Err(EvaluatorError::ProductCheckFailed { label: String::from("corrupted") }));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_clear_mul_agrees_with_scale() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let c = F::from(7u64);
+            let h_x = evaluator.ran().unwrap();
+            let x_share = evaluator.get_wire(&h_x);
+
+            let h_clear_mul = evaluator.clear_mul(&h_x, c);
+            let h_scale = evaluator.scale(&h_x, c);
+            // both leave [x] as it was, on a new wire
+            assert_ne!(h_clear_mul, h_x);
+            assert_ne!(h_scale, h_clear_mul);
+            assert_eq!(evaluator.get_wire(&h_x), x_share);
+
+            evaluator.batch_output_wire(&[h_x, h_clear_mul, h_scale]).await
+        }).await;
+
+        for opened in outputs {
+            assert_eq!(opened[1], F::from(7u64) * opened[0]);
+            assert_eq!(opened[2], opened[1]);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_sub_and_negate() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let h_x = evaluator.ran().unwrap();
+            let h_y = evaluator.ran().unwrap();
+            let h_x_minus_y = evaluator.sub(&h_x, &h_y);
+            let h_neg_x = evaluator.negate(&h_x);
+
+            evaluator.batch_output_wire(&[h_x, h_y, h_x_minus_y, h_neg_x]).await
+        }).await;
+
+        for opened in outputs {
+            assert_eq!(opened[2], opened[0] - opened[1]);
+            assert_eq!(opened[3], -opened[0]);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_share_poly_sub() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let node_id = evaluator.node_id() as u64;
+
+            // every party holds random coefficients, so f and g are random
+            let f_share = DensePolynomial::<F>::rand(20, &mut StdRng::seed_from_u64(10 + node_id));
+            let g_share = DensePolynomial::<F>::rand(30, &mut StdRng::seed_from_u64(20 + node_id));
+            let d_share = evaluator.share_poly_sub(&f_share, &g_share);
+            let sum_share = evaluator.share_poly_add(&d_share, &g_share);
+            let neg_share = evaluator.share_poly_neg(&f_share);
+
+            // the same random points at every party
+            let points: Vec<F> = (0..4u64).map(|i| F::rand(&mut StdRng::seed_from_u64(i))).collect();
+            let mut handles = vec![];
+            for x in points {
+                for share in [&f_share, &g_share, &d_share, &sum_share, &neg_share] {
+                    handles.push(evaluator.share_poly_eval(share.clone(), x));
+                }
+            }
+            evaluator.batch_output_wire(&handles).await
+        }).await;
+
+        for opened in outputs {
+            for evals in opened.chunks(5) {
+                let (f_x, g_x, d_x, sum_x, neg_x) = (evals[0], evals[1], evals[2], evals[3], evals[4]);
+                assert_eq!(d_x, f_x - g_x);
+                assert_eq!(sum_x, f_x);
+                assert_eq!(neg_x, -f_x);
+            }
+        }
+    }
+
+    #[async_std::test]
+    async fn test_exp_and_reveal_g1_batch_matches_single_calls() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let jobs = (0..32u64)
+                .map(|i| {
+                    let bases = vec![
+                        G1::generator().mul(F::from(i + 1)).into_affine(), 
+                        G1::generator().mul(F::from(i + 100)).into_affine()
+                    ];
+                    (bases, vec![evaluator.ran().unwrap(), evaluator.ran().unwrap()])
+                })
+                .collect::<Vec<(Vec<G1>, Vec<String>)>>();
+
+            let sent_before = evaluator.messages_sent();
+            let batched = evaluator.exp_and_reveal_g1_batch(&jobs, "batch_test").await;
+            let batched_sent = evaluator.messages_sent() - sent_before;
+
+            let sent_before = evaluator.messages_sent();
+            let mut single = vec![];
+            for (i, (bases, exponent_handles)) in jobs.iter().enumerate() {
+                single.push(evaluator.exp_and_reveal_g1(
+                    bases.clone(), 
+                    exponent_handles.clone(), 
+                    &format!("single_test/{}", i)
+                ).await);
+            }
+            let single_sent = evaluator.messages_sent() - sent_before;
+
+            (batched, single, batched_sent, single_sent)
+        }).await;
+
+        for (batched, single, batched_sent, single_sent) in outputs {
+            assert_eq!(batched, single);
+            assert_eq!(batched_sent, 1);
+            assert_eq!(single_sent, 32);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_output_wire_in_exponent_pedersen() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let h_x = evaluator.ran().unwrap();
+            let h_r1 = evaluator.ran().unwrap();
+            let h_r2 = evaluator.ran().unwrap();
+
+            let (c1, blinding1) = evaluator.output_wire_in_exponent_pedersen(&h_x, &h_r1).await;
+            let (c2, blinding2) = evaluator.output_wire_in_exponent_pedersen(&h_x, &h_r2).await;
+            assert_eq!((&blinding1, &blinding2), (&h_r1, &h_r2));
+
+            let x = evaluator.output_wire(&h_x).await;
+            let r1 = evaluator.output_wire(&blinding1).await;
+            let r2 = evaluator.output_wire(&blinding2).await;
+            (c1, c2, x, r1, r2)
+        }).await;
+
+        let g = G1::generator();
+        let h = utils::pedersen_generator();
+        assert_ne!(h, g);
+        for (c1, c2, x, r1, r2) in outputs {
+            // the same value behind different blindings looks different...
+            assert_ne!(c1, c2);
+            assert_ne!(c1, g.mul(x).into_affine());
+            // ...yet each opens to it
+            assert_eq!(c1, (g.mul(x) + h.mul(r1)).into_affine());
+            assert_eq!(c2, (g.mul(x) + h.mul(r2)).into_affine());
+        }
+    }
+
+    #[async_std::test]
+    async fn test_dropping_evaluators_joins_network_threads() {
+        // held by every daemon thread until it exits
+        let alive = Arc::new(());
+
+        for _ in 0..50 {
+            let (e2n_tx, mut e2n_rx) = mpsc::unbounded::<EvalNetMsg>();
+            let (n2e_tx, n2e_rx) = mpsc::unbounded::<EvalNetMsg>();
+
+            // like run_networking_daemon, returns once the evaluator's channel closes
+            let alive = alive.clone();
+            let handle = thread::spawn(move || {
+                let _alive = alive;
+                n2e_tx.unbounded_send(EvalNetMsg::ConnectionEstablished { success: true }).unwrap();
+                async_std::task::block_on(async { while e2n_rx.next().await.is_some() {} });
+            });
+
+            let evaluator = Evaluator::new(&String::from("party_0"), local_addr_book(1), e2n_tx, n2e_rx)
+                .await
+                .unwrap()
+                .with_network_thread(handle);
+            // a clone of the sender outliving the evaluator does not keep the daemon up
+            let _token = evaluator.cancellation_token();
+            drop(evaluator);
+        }
+
+        assert_eq!(Arc::strong_count(&alive), 1);
+    }
+
+    #[async_std::test]
+    async fn test_cancelled_run_fails_instead_of_waiting() {
+        let outputs = run_parties(2, |mut evaluator| async move {
+            evaluator.cancel();
+            let h_x = evaluator.ran().unwrap();
+            (
+                evaluator.wait_until_ready(Duration::from_secs(5)).await,
+                evaluator.output_wire_committed(&h_x).await,
+                evaluator.check_challenge_agreement("cancelled", &[F::one()]).await,
+            )
+        }).await;
+
+        for (ready, opened, agreed) in outputs {
+            assert_eq!(ready, Err(EvaluatorError::Cancelled));
+            assert_eq!(opened, Err(EvaluatorError::Cancelled));
+            assert_eq!(agreed, Err(EvaluatorError::Cancelled));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_mult_over_lossy_network() {
+        let lossy = |retransmit: bool| NetworkSim {
+            latency: Duration::from_millis(1),
+            jitter: Duration::from_millis(2),
+            drop_rate: 0.1,
+            retransmit,
+        };
+        // enough rounds that some message is lost along the way
+        let mults = |mut evaluator: Evaluator| async move {
+            let h_x = evaluator.ran().unwrap();
+            let mut h_y = evaluator.ran().unwrap();
+            for _ in 0..20 {
+                h_y = evaluator.mult(&h_x, &h_y).await;
+            }
+            evaluator.output_wire(&h_y).await
+        };
+
+        let outputs = run_parties_with_sim(3, lossy(true), |evaluator| async move {
+            async_std::future::timeout(Duration::from_secs(30), mults(evaluator)).await
+        }).await;
+        let opened: Vec<F> = outputs.into_iter().map(|output| output.expect("mult timed out")).collect();
+        assert!(opened.iter().all(|y| *y == opened[0]));
+
+        // the evaluator does not recover lost messages by itself
+        let outputs = run_parties_with_sim(3, lossy(false), |evaluator| async move {
+            async_std::future::timeout(Duration::from_secs(5), mults(evaluator)).await
+        }).await;
+        assert!(outputs.iter().any(|output| output.is_err()));
+    }
+
+    #[async_std::test]
+    async fn test_max_inflight_caps_outstanding_rounds() {
+        // 1000 values take 4 rounds of BATCH_ROUND_SIZE, the 600 mults 5
+        let values: Vec<F> = (0..1000u64).map(F::from).collect();
+        let values = &values;
+        let open = |max_inflight: Option<usize>| run_parties(3, move |evaluator| async move {
+            let mut evaluator = match max_inflight {
+                Some(limit) => evaluator.with_max_inflight(limit),
+                None => evaluator,
+            };
+            let handles: Vec<String> = values.iter()
+                .map(|v| evaluator.fixed_wire_handle(*v))
+                .collect();
+            let opened = evaluator.batch_output_wire(&handles).await;
+
+            let ones: Vec<String> = (0..600).map(|_| evaluator.fixed_wire_handle(F::one())).collect();
+            let products = evaluator.batch_mult(&handles[..600], &ones).await;
+            let products = evaluator.batch_output_wire(&products).await;
+
+            let gs = vec![G1::generator(); 300];
+            let labels: Vec<String> = (0..300).map(|i| format!("inflight_g1/{}", i)).collect();
+            let sums = evaluator.batch_add_g1_elements_from_all_parties(&gs, &labels).await;
+
+            (opened, products, sums, evaluator.peak_inflight_rounds())
+        });
+
+        for (opened, products, sums, peak) in open(Some(2)).await {
+            assert_eq!(&opened, values);
+            assert_eq!(products, values[..600]);
+            assert!(sums.iter().all(|s| *s == G1::generator().mul(F::from(3u64)).into_affine()));
+            assert_eq!(peak, 2);
+        }
+
+        // the default limit is above the 5 rounds of the largest batch
+        for (_, _, _, peak) in open(None).await {
+            assert_eq!(peak, 5);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_ping_all_reports_silent_peer() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            if evaluator.node_id() == 2 {
+                // connected, but never handles a message; it stays up well
+                // past the timeouts, as the others see the network gone once
+                // it returns
+                async_std::task::sleep(Duration::from_secs(3)).await;
+                return None;
+            }
+            let pings = evaluator.ping_all(Duration::from_millis(200)).await.unwrap();
+            let ready = evaluator.wait_until_ready(Duration::from_millis(300)).await;
+            Some((pings, ready))
+        }).await;
+
+        let mut outputs = outputs.into_iter();
+        let (pings, ready) = outputs.next().unwrap().unwrap();
+        assert_eq!(pings, vec![(1, true), (2, false)]);
+        assert_eq!(ready, Err(EvaluatorError::PeersUnreachable { node_ids: vec![2] }));
+
+        let (pings, ready) = outputs.next().unwrap().unwrap();
+        assert_eq!(pings, vec![(0, true), (2, false)]);
+        assert_eq!(ready, Err(EvaluatorError::PeersUnreachable { node_ids: vec![2] }));
+
+        // with everyone taking part, everyone is ready
+        let outputs = run_parties(3, |mut evaluator| async move {
+            evaluator.wait_until_ready(Duration::from_secs(5)).await
+        }).await;
+        assert!(outputs.iter().all(|ready| ready.is_ok()));
+    }
+
+    #[async_std::test]
+    async fn test_party_introspection() {
+        let outputs = run_parties(3, |evaluator| async move {
+            (evaluator.node_id(), evaluator.num_parties(), evaluator.peer_ids())
+        }).await;
+
+        let node_ids: Vec<usize> = outputs.iter().map(|(node_id, _, _)| *node_id).collect();
+        assert_eq!(node_ids, vec![0, 1, 2]);
+
+        for (_, num_parties, peer_ids) in outputs {
+            assert_eq!(num_parties, 3);
+            assert_eq!(peer_ids, vec!["party_0", "party_1", "party_2"]);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_with_rng_is_reproducible() {
+        let run = || run_parties(3, |evaluator| async move {
+            let node_id = get_node_id_via_peer_id(&evaluator.addr_book, &evaluator.id).unwrap();
+            let mut evaluator = evaluator.with_rng(StdRng::seed_from_u64(node_id));
+
+            let h_r1 = evaluator.ran().unwrap();
+            let h_r2 = evaluator.ran().unwrap();
+            let h_prod = evaluator.mult(&h_r1, &h_r2).await;
+            evaluator.batch_output_wire(&[h_r1, h_r2, h_prod]).await
+        });
+
+        let first = run().await;
+        let second = run().await;
+        assert_eq!(first, second);
+        assert_eq!(first[0][2], first[0][0] * first[0][1]);
+    }
+
+    #[async_std::test]
+    async fn test_shamir_output_wire_tolerates_dropout() {
+        let scheme = SharingScheme::Shamir { threshold: 1 };
+
+        // all 3 parties multiply; then party 2 drops out, and the
+        // other two still open a wire from their 2 = threshold + 1 shares
+        let outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = evaluator.with_sharing_scheme(scheme).unwrap();
+            evaluator.deal_random_shares(3).await;
+
+            let h_x = evaluator.ran().unwrap();
+            let h_y = evaluator.ran().unwrap();
+            let h_xy = evaluator.mult(&h_x, &h_y).await;
+            let h_x_plus_1 = evaluator.clear_add(&h_x, F::from(1));
+            let (x, y, xy, x_plus_1) = (
+                evaluator.output_wire(&h_x).await,
+                evaluator.output_wire(&h_y).await,
+                evaluator.output_wire(&h_xy).await,
+                evaluator.output_wire(&h_x_plus_1).await,
+            );
+            assert_eq!(xy, x * y);
+            assert_eq!(x_plus_1, x + F::from(1));
+
+            let h_z = evaluator.ran().unwrap();
+            let z = match evaluator.node_id() {
+                2 => None,
+                _ => Some(evaluator.output_wire(&h_z).await),
+            };
+            (x, z)
+        }).await;
+
+        assert!(outputs.iter().all(|(x, _)| *x == outputs[0].0));
+        assert!(outputs[0].1.is_some());
+        assert_eq!(outputs[0].1, outputs[1].1);
+        assert_eq!(outputs[2].1, None);
+    }
+
+    #[async_std::test]
+    async fn test_shamir_opens_in_exponent() {
+        let scheme = SharingScheme::Shamir { threshold: 1 };
+
+        let run = |seed: u64| run_parties(3, move |evaluator| async move {
+            let node_id = evaluator.node_id() as u64;
+            let mut evaluator = evaluator
+                .with_rng(StdRng::seed_from_u64(seed + node_id))
+                .with_sharing_scheme(scheme).unwrap();
+            evaluator.deal_random_shares(1).await;
+
+            let h_x = evaluator.ran().unwrap();
+            let x = evaluator.output_wire(&h_x).await;
+            assert_eq!(evaluator.output_wire_in_exponent(&h_x).await, G1::generator().mul(x).into_affine());
+            assert_eq!(evaluator.output_wire_in_exponent_g2(&h_x).await, G2::generator().mul(x).into_affine());
+            let gt = evaluator.exp_and_reveal_gt(vec![Gt::generator()], vec![h_x.clone()], &String::from("gt")).await;
+            assert_eq!(gt, Gt::generator().mul(x));
+            // the one dealt wire is used up
+            assert_eq!(evaluator.ran().err(), Some(EvaluatorError::RandomPoolEmpty));
+            x
+        });
+
+        // the wire is dealt from the parties' own randomness, so the same
+        // wire label holds another value in another run
+        let first = run(0).await;
+        let second = run(10).await;
+        assert!(first.iter().all(|x| *x == first[0]));
+        assert_ne!(first[0], second[0]);
+    }
+
+    #[async_std::test]
+    async fn test_mult_auto_refills_triple_pool() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let mut xs = vec![];
+            let mut ys = vec![];
+            let mut products = vec![];
+            for _ in 0..100 {
+                let h_x = evaluator.ran().unwrap();
+                let h_y = evaluator.ran().unwrap();
+                products.push(evaluator.mult_auto(&h_x, &h_y).await);
+                xs.push(h_x);
+                ys.push(h_y);
+            }
+
+            let xs = evaluator.batch_output_wire(&xs).await;
+            let ys = evaluator.batch_output_wire(&ys).await;
+            let products = evaluator.batch_output_wire(&products).await;
+            for i in 0..100 {
+                assert_eq!(products[i], xs[i] * ys[i]);
+            }
+
+            (evaluator.triple_pool_refills, evaluator.triple_pool.len())
+        }).await;
+
+        // 100 multiplications need a second batch of 64 triples
+        for (refills, left) in outputs {
+            assert_eq!(refills, 2);
+            assert_eq!(left, 2 * TRIPLE_POOL_SIZE - 100);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_triple_sources_produce_valid_triples() {
+        let scheme = SharingScheme::Shamir { threshold: 1 };
+
+        let mut messages = vec![];
+        for source in [TripleSource::Dealer, TripleSource::Distributed] {
+            let outputs = run_parties(3, |evaluator| async move {
+                let node_id = evaluator.node_id() as u64;
+                let mut evaluator = evaluator
+                    .with_rng(StdRng::seed_from_u64(node_id))
+                    .with_sharing_scheme(scheme).unwrap()
+                    .with_triple_source(source)
+                    .unwrap();
+
+                let sent_before = evaluator.messages_sent();
+                let mut triples = evaluator.beaver_batch(10).await;
+                triples.push(evaluator.beaver().await);
+                let sent_for_triples = evaluator.messages_sent() - sent_before;
+
+                for (h_a, h_b, h_c) in triples {
+                    let a = evaluator.output_wire(&h_a).await;
+                    let b = evaluator.output_wire(&h_b).await;
+                    let c = evaluator.output_wire(&h_c).await;
+                    assert_eq!(a * b, c);
+                }
+                sent_for_triples
+            }).await;
+            messages.push(outputs);
+        }
+
+        // the dealer is local; each distributed batch is two rounds of a
+        // message to each of the 2 peers, after the one key exchange
+        assert_eq!(messages[0], vec![0, 0, 0]);
+        assert_eq!(messages[1], vec![9, 9, 9]);
+    }
+
+    #[async_std::test]
+    async fn test_distributed_triples_need_shamir_and_drop_malformed_batches() {
+        run_parties(3, |mut evaluator| async move {
+            // a batch of two handles and one value is not half-accepted
+            evaluator.process_next_message(&EvalNetMsg::PublishBatchValue {
+                sender: String::from("party_1"),
+                handles: vec![String::from("a"), String::from("b")],
+                values: vec![encode_f_as_bs58_str(&F::one())],
+            });
+            assert!(evaluator.mailbox.is_empty());
+
+            let result = evaluator.with_triple_source(TripleSource::Distributed);
+            assert_eq!(
+                result.err(),
+                Some(EvaluatorError::UnsupportedTripleSource { sharing: SharingScheme::Additive, num_parties: 3 })
+            );
+        }).await;
+    }
+
+    #[async_std::test]
+    async fn test_sharing_scheme_needs_reachable_threshold_and_comes_first() {
+        let scheme = SharingScheme::Shamir { threshold: 3 };
+        run_parties(3, |evaluator| async move {
+            assert_eq!(
+                evaluator.with_sharing_scheme(scheme).err(),
+                Some(EvaluatorError::UnsupportedSharingScheme { sharing: scheme, num_parties: 3 })
+            );
+        }).await;
+
+        run_parties(3, |evaluator| async move {
+            let result = evaluator
+                .with_sharing_scheme(SharingScheme::Shamir { threshold: 1 }).unwrap()
+                .with_triple_source(TripleSource::Distributed).unwrap()
+                .with_sharing_scheme(SharingScheme::Additive);
+            assert_eq!(result.err(), Some(EvaluatorError::SharingSchemeAfterTripleSource));
+        }).await;
+    }
+
+    /// a few rounds of multiplications and openings
+    async fn transcript_steps(evaluator: &mut Evaluator) -> Vec<F> {
+        let h_x = evaluator.ran().unwrap();
+        let h_y = evaluator.ran().unwrap();
+        let h_xy = evaluator.mult(&h_x, &h_y).await;
+        let h_inv = evaluator.inv(&h_xy).await.unwrap();
+        evaluator.batch_output_wire(&[h_x, h_y, h_xy, h_inv]).await
+    }
+
+    #[async_std::test]
+    async fn test_transcript_replay_reproduces_run() {
+        let path = std::env::temp_dir().join(format!("pok3r_transcript_{}.jsonl", std::process::id()));
+        let path_ref = &path;
+
+        let mut outputs = run_parties(3, |evaluator| async move {
+            let seed = evaluator.node_id() as u64;
+            let mut evaluator = evaluator.with_rng(StdRng::seed_from_u64(seed));
+            if evaluator.node_id() == 0 {
+                evaluator.record_transcript(path_ref).unwrap();
+            }
+            transcript_steps(&mut evaluator).await
+        }).await;
+        let recorded_outputs = outputs.remove(0);
+        assert_eq!(recorded_outputs[2], recorded_outputs[0] * recorded_outputs[1]);
+
+        let transcript = read_transcript(&path).unwrap();
+        assert!(transcript.iter().any(|e| e.direction == TranscriptDirection::Sent));
+        assert!(transcript.iter().any(|e| e.direction == TranscriptDirection::Received));
+        assert!(transcript.windows(2).all(|w| w[0].elapsed_micros <= w[1].elapsed_micros));
+
+        // party 0 again, offline
+        let (tx, _) = mpsc::unbounded::<EvalNetMsg>();
+        let (n2e_tx, rx) = mpsc::unbounded::<EvalNetMsg>();
+        n2e_tx.unbounded_send(EvalNetMsg::ConnectionEstablished { success: true }).unwrap();
+        let mut evaluator = Evaluator::new(&String::from("party_0"), local_addr_book(3), tx, rx)
+            .await
+            .unwrap()
+            .with_rng(StdRng::seed_from_u64(0));
+        let mut sent = evaluator.replay_transcript(&path).unwrap();
+
+        assert_eq!(transcript_steps(&mut evaluator).await, recorded_outputs);
+
+        let recorded_sends = transcript
+            .iter()
+            .filter(|e| e.direction == TranscriptDirection::Sent)
+            .map(|e| serde_json::to_string(&e.msg).unwrap())
+            .collect::<Vec<String>>();
+        let replayed_sends = std::iter::from_fn(|| sent.try_recv().ok())
+            .map(|msg| serde_json::to_string(&msg).unwrap())
+            .collect::<Vec<String>>();
+        assert_eq!(replayed_sends, recorded_sends);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_msm_stats_count_commitments_and_proofs() {
+        let pp = utils::setup_kzg(1024);
+        let pp = &pp;
+
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let mut rng = ark_std::test_rng();
+            let polys: Vec<DensePolynomial<F>> = (0..4)
+                .map(|_| DensePolynomial::rand(PERM_SIZE - 1, &mut rng))
+                .collect();
+
+            let before = evaluator.msm_stats();
+            let coms: Vec<G1> = polys.iter().map(|poly| evaluator.commit_poly(pp, poly)).collect();
+            assert_eq!(coms[0], utils::commit_poly(pp, &polys[0]));
+            let after_commits = evaluator.msm_stats();
+
+            let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+            let names = (0..4).map(|i| format!("msm_stats_pi_{}", i)).collect();
+            evaluator.batch_eval_proof_with_share_poly(
+                pp, &polys, &utils::power_table(&ω, 4), &names
+            ).await;
+
+            let h_x = evaluator.ran().unwrap();
+            let _ = evaluator.exp_and_reveal_g1(
+                vec![G1::generator(); 3], vec![h_x; 3], &String::from("msm_stats_g1")
+            ).await;
+
+            (before, after_commits, evaluator.msm_stats())
+        }).await;
+
+        for (before, after_commits, after) in outputs {
+            assert_eq!(before, MsmStats::default());
+            assert_eq!(after_commits, MsmStats { count: 4, total_scalars: 4 * PERM_SIZE });
+            // 4 quotients of degree PERM_SIZE - 2, then one MSM over 3 bases
+            assert_eq!(after, MsmStats { count: 9, total_scalars: 8 * PERM_SIZE - 4 + 3 });
+        }
+    }
+
+    #[async_std::test]
+    async fn test_flush_sends_buffered_messages() {
+        let (tx, mut sent) = mpsc::unbounded::<EvalNetMsg>();
+        let (n2e_tx, rx) = mpsc::unbounded::<EvalNetMsg>();
+        n2e_tx.unbounded_send(EvalNetMsg::ConnectionEstablished { success: true }).unwrap();
+        let mut evaluator = Evaluator::new(&String::from("party_0"), local_addr_book(2), tx, rx)
+            .await
+            .unwrap()
+            .with_send_buffering(true);
+
+        let msg = EvalNetMsg::PublishValue {
+            sender: evaluator.id.clone(),
+            handle: String::from("h"),
+            value: String::from("v"),
+        };
+        send_over_network!(msg, evaluator);
+        // counted as sent, but still queued
+        assert_eq!(evaluator.messages_sent(), 1);
+        assert!(sent.try_recv().is_err());
+
+        evaluator.flush().await;
+        assert!(matches!(sent.try_recv(), Ok(EvalNetMsg::PublishValue { .. })));
+        assert!(sent.try_recv().is_err());
+    }
+
+    #[async_std::test]
+    async fn test_flush_before_mutual_output() {
+        let outputs = run_parties(2, |evaluator| async move {
+            let mut evaluator = evaluator.with_send_buffering(true);
+
+            // each party builds a batch of wires and queues its shares
+            let handles: Vec<String> = (0..10).map(|_| evaluator.ran().unwrap()).collect();
+            for h in handles.iter() {
+                let msg = EvalNetMsg::PublishValue {
+                    sender: evaluator.id.clone(),
+                    handle: h.clone(),
+                    value: encode_f_as_bs58_str(&evaluator.local_share(h)),
+                };
+                send_over_network!(msg, evaluator);
+            }
+            evaluator.flush().await;
+
+            // both wait on the other's shares
+            let mut opened = vec![];
+            for h in handles.iter() {
+                let my_share = evaluator.local_share(h);
+                opened.push(evaluator.reconstruct(h, my_share).await);
+            }
+
+            let h_sum = handles.iter().skip(1).fold(handles[0].clone(), |acc, h| evaluator.add(&acc, h));
+            let sum = evaluator.output_wire(&h_sum).await;
+            (opened, sum)
+        }).await;
+
+        for (opened, sum) in outputs.iter() {
+            assert_eq!(opened, &outputs[0].0);
+            assert_eq!(*sum, opened.iter().sum::<F>());
+        }
+    }
+
+    #[async_std::test]
+    async fn test_output_wire_committed() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let h = evaluator.ran().unwrap();
+            let committed = evaluator.output_wire_committed(&h).await.unwrap();
+            let plain = evaluator.output_wire(&h).await;
+            assert_eq!(committed, plain);
+            committed
+        }).await;
+
+        assert!(outputs.iter().all(|x| *x == outputs[0]));
+    }
+
+    #[async_std::test]
+    async fn test_reveal_positions() {
+        let pp = utils::setup_kzg(1024);
+        let pp = &pp;
+
+        run_parties(3, |mut evaluator| async move {
+            let card_handles = (0..PERM_SIZE)
+                .map(|_| evaluator.ran().unwrap())
+                .collect::<Vec<String>>();
+            let card_shares = card_handles
+                .iter()
+                .map(|h| evaluator.get_wire(h))
+                .collect::<Vec<Share>>();
+
+            let f_poly_share = utils::interpolate_poly_over_mult_subgroup(&Share::raw(&card_shares));
+            let f_com = evaluator.add_g1_elements_from_all_parties(
+                &utils::commit_poly(pp, &f_poly_share), 
+                &String::from("test_reveal_f")
+            ).await;
+
+            // out of the deck, so nothing is opened
+            assert_eq!(
+                evaluator.reveal_positions(pp, &card_handles, &[0, PERM_SIZE], &f_poly_share, &f_com).await,
+                Err(EvaluatorError::PositionOutOfRange { position: PERM_SIZE, deck_size: PERM_SIZE })
+            );
+
+            // not the deck that was committed to
+            let other_com = (f_com + G1::generator()).into_affine();
+            assert_eq!(
+                evaluator.reveal_positions(pp, &card_handles, &[3], &f_poly_share, &other_com).await,
+                Err(EvaluatorError::OpeningMismatch { position: 3 })
+            );
+
+            let openings = evaluator.reveal_positions(
+                pp, &card_handles, &[0, 1, 2], &f_poly_share, &f_com
+            ).await.unwrap();
+
+            // only the board was opened
+            assert_eq!(openings.iter().map(|o| o.0).collect::<Vec<usize>>(), vec![0, 1, 2]);
+
+            let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+            for (i, card, pi) in openings.iter() {
+                let point = utils::compute_power(&ω, *i as u64);
+                assert!(utils::kzg_check(pp, &f_com, &point, card, pi));
+                assert_eq!(*card, evaluator.output_wire(&card_handles[*i]).await);
+
+                // the proof does not open any other card value
+                assert!(!utils::kzg_check(pp, &f_com, &point, &(*card + F::one()), pi));
+            }
+        }).await;
+    }
+
+    #[async_std::test]
+    async fn test_mailbox_caps_pending_messages_per_peer() {
+        run_parties(2, |evaluator| async move {
+            let mut evaluator = evaluator.with_max_inflight(1);
+            let max_pending = evaluator.max_pending_per_peer();
+            assert_eq!(max_pending, 6 * BATCH_ROUND_SIZE);
+            let flooder = if evaluator.id == "party_0" { "party_1" } else { "party_0" };
+            let flooder = String::from(flooder);
+
+            // a batch larger than a round is dropped as a whole
+            let handles: Vec<String> = (0..BATCH_ROUND_SIZE + 1).map(|i| format!("big/{}", i)).collect();
+            evaluator.process_next_message(&EvalNetMsg::PublishBatchValue {
+                sender: flooder.clone(),
+                values: vec![String::from("v"); handles.len()],
+                handles,
+            });
+            assert!(evaluator.mailbox.is_empty());
+
+            for i in 0..max_pending + 10 {
+                evaluator.process_next_message(&EvalNetMsg::PublishValue {
+                    sender: flooder.clone(),
+                    handle: format!("bogus/{}", i),
+                    value: String::from("v"),
+                });
+            }
+            assert_eq!(evaluator.mailbox.len(), max_pending);
+            assert!(!evaluator.mailbox.contains_key(&format!("bogus/{}", max_pending)));
+
+            // collecting a value frees room for the next one
+            let collected = evaluator.collect_messages_from_all_peers(&String::from("bogus/0")).await;
+            assert_eq!(collected, vec![String::from("v")]);
+            evaluator.process_next_message(&EvalNetMsg::PublishValue {
+                sender: flooder.clone(),
+                handle: String::from("late"),
+                value: String::from("v"),
+            });
+            assert!(evaluator.mailbox.contains_key("late"));
+        }).await;
+    }
+
+    #[async_std::test]
+    async fn test_add_g1_ignores_strangers_and_rejects_duplicates() {
+        run_parties(3, |mut evaluator| async move {
+            let g = G1::generator();
+            let forged = encode_g1_as_bs58_str(&g.mul(F::from(100u64)).into_affine());
+
+            // a 4th party injects its own point into the sum
+            if evaluator.id == "party_0" {
+                evaluator.process_next_message(&EvalNetMsg::PublishValue {
+                    sender: String::from("party_3"),
+                    handle: String::from("op1/sum"),
+                    value: forged.clone(),
+                });
+                assert!(!evaluator.pending_per_peer.contains_key("party_3"));
+            }
+            let sum = evaluator.add_g1_elements_from_all_parties(&g, &String::from("sum")).await;
+            assert_eq!(sum, g.mul(F::from(3u64)).into_affine());
+
+            // party_1 appears to send two different points for the same sum;
+            // both are in before party_0 collects, however fast the real
+            // one of party_1 arrives
+            if evaluator.id == "party_0" {
+                for value in [forged.clone(), encode_g1_as_bs58_str(&g)] {
+                    evaluator.process_next_message(&EvalNetMsg::PublishValue {
+                        sender: String::from("party_1"),
+                        handle: String::from("op2/dup"),
+                        value,
+                    });
+                }
+            }
+            let result = evaluator.try_add_g1_elements_from_all_parties(&g, &String::from("dup")).await;
+            if evaluator.id == "party_0" {
+                assert_eq!(result, Err(EvaluatorError::DuplicateContribution {
+                    name: String::from("op2/dup"),
+                    sender: String::from("party_1"),
+                }));
+            } else {
+                assert_eq!(result, Ok(g.mul(F::from(3u64)).into_affine()));
+            }
+
+            // add_g1_elements_from_all_parties holds party_1 to its first point
+            if evaluator.id == "party_0" {
+                for value in [forged.clone(), encode_g1_as_bs58_str(&g)] {
+                    evaluator.process_next_message(&EvalNetMsg::PublishValue {
+                        sender: String::from("party_1"),
+                        handle: String::from("op3/first"),
+                        value,
+                    });
+                }
+            }
+            let sum = evaluator.add_g1_elements_from_all_parties(&g, &String::from("first")).await;
+            let expected = if evaluator.id == "party_0" { 102u64 } else { 3u64 };
+            assert_eq!(sum, g.mul(F::from(expected)).into_affine());
+        }).await;
+    }
+
+    #[async_std::test]
+    async fn test_pairing_consistency_proof() {
+        run_parties(3, |mut evaluator| async move {
+            let h = evaluator.ran().unwrap();
+            let com_g1 = evaluator.output_wire_in_exponent(&h).await;
+            let (value_g2, proof) = evaluator.pairing_consistency_proof(&h, &com_g1).await;
+
+            let x = evaluator.output_wire(&h).await;
+            assert_eq!(value_g2, G2::generator().mul(x).into_affine());
+            assert!(proof.verify());
+
+            // g2^x' for any other x' does not satisfy the pairing equation
+            let wrong = PairingProof { 
+                com_g1, 
+                value_g2: (value_g2 + G2::generator()).into_affine() 
+            };
+            assert!(!wrong.verify());
+        }).await;
+    }
+
+    #[async_std::test]
+    async fn test_share_poly_mult_over_domain() {
+        run_parties(3, |mut evaluator| async move {
+            let degree = 40;
+
+            let mut f_coeffs = vec![];
+            let mut g_coeffs = vec![];
+            let mut f_share_coeffs = vec![];
+            let mut g_share_coeffs = vec![];
+            for _ in 0..=degree {
+                let h = evaluator.ran().unwrap();
+                f_share_coeffs.push(evaluator.get_wire(&h));
+                f_coeffs.push(evaluator.output_wire(&h).await);
+
+                let h = evaluator.ran().unwrap();
+                g_share_coeffs.push(evaluator.get_wire(&h));
+                g_coeffs.push(evaluator.output_wire(&h).await);
+            }
+            let f = DensePolynomial::from_coefficients_vec(f_coeffs);
+            let g = DensePolynomial::from_coefficients_vec(g_coeffs);
+
+            // deg(f) + deg(g) + 1 = 81, so 128 points suffice
+            let product_share = evaluator.share_poly_mult_over_domain(
+                DensePolynomial::from_coefficients_vec(Share::raw(&f_share_coeffs)), 
+                DensePolynomial::from_coefficients_vec(Share::raw(&g_share_coeffs)), 
+                128
+            ).await;
+
+            for point in [F::from(420021312), F::from(7), -F::from(3)] {
+                let h = evaluator.share_poly_eval(product_share.clone(), point);
+                let product_at_point = evaluator.output_wire(&h).await;
+                assert_eq!(product_at_point, f.evaluate(&point) * g.evaluate(&point));
+            }
+        }).await;
+    }
+
+    #[async_std::test]
+    async fn test_output_share_poly_reconstructs_product() {
+        run_parties(3, |mut evaluator| async move {
+            let degree = 20;
+
+            let mut f_coeffs = vec![];
+            let mut g_coeffs = vec![];
+            let mut f_share_coeffs = vec![];
+            let mut g_share_coeffs = vec![];
+            for i in 0..=degree {
+                let h = evaluator.fixed_wire_handle(F::from(i as u64 + 1));
+                f_share_coeffs.push(evaluator.get_wire(&h));
+                f_coeffs.push(F::from(i as u64 + 1));
+
+                let h = evaluator.ran().unwrap();
+                g_share_coeffs.push(evaluator.get_wire(&h));
+                g_coeffs.push(evaluator.output_wire(&h).await);
+            }
+            let f_share = DensePolynomial::from_coefficients_vec(Share::raw(&f_share_coeffs));
+            let g_share = DensePolynomial::from_coefficients_vec(Share::raw(&g_share_coeffs));
+            let f = DensePolynomial::from_coefficients_vec(f_coeffs);
+            let g = DensePolynomial::from_coefficients_vec(g_coeffs);
+
+            assert_eq!(evaluator.output_share_poly(&f_share).await, f);
+
+            let product_share = evaluator.share_poly_mult_over_domain(f_share, g_share, 64).await;
+            assert_eq!(evaluator.output_share_poly(&product_share).await, &f * &g);
+        }).await;
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_eval_proof_shares_parallel_matches_single_thread() {
+        let pp = utils::setup_kzg(1024);
+        let mut rng = ark_std::test_rng();
+        let share_polys: Vec<DensePolynomial<F>> = (0..32)
+            .map(|_| DensePolynomial::rand(PERM_SIZE - 1, &mut rng))
+            .collect();
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let z_s = utils::power_table(&ω, share_polys.len());
+
+        let run_with_threads = |threads: usize| rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| eval_proof_shares(&pp, &share_polys, &z_s));
+
+        let single = run_with_threads(1);
+        assert_eq!(single, run_with_threads(4));
+        assert!(utils::kzg_check(
+            &pp, 
+            &utils::commit_poly(&pp, &share_polys[7]), 
+            &z_s[7], 
+            &share_polys[7].evaluate(&z_s[7]), 
+            &single[7]
+        ));
+    }
+
+    #[async_std::test]
+    async fn test_oprf_eval() {
+        run_parties(3, |mut evaluator| async move {
+            let sk = evaluator.ran().unwrap();
+            let y1 = evaluator.oprf_eval(&sk, F::from(5)).await.unwrap();
+            let y2 = evaluator.oprf_eval(&sk, F::from(5)).await.unwrap();
+            let y3 = evaluator.oprf_eval(&sk, F::from(6)).await.unwrap();
+
+            assert_eq!(y1, y2);
+            assert_ne!(y1, y3);
+
+            // the batched variants agree with the single-input one
+            let ys = evaluator.batch_oprf_eval(&sk, &[F::from(5), F::from(6)]).await.unwrap();
+            assert_eq!(ys, vec![y1, y3]);
+
+            let x = evaluator.fixed_wire_handle(F::from(6));
+            let ys = evaluator.batch_oprf_eval_shared(&sk, &[x]).await.unwrap();
+            assert_eq!(ys, vec![y3]);
+
+            // y = g^{1 / (sk + x)}
+            let sk = evaluator.output_wire(&sk).await;
+            let expected = G1::generator().mul((sk + F::from(5)).inverse().unwrap());
+            assert_eq!(y1, expected);
+        }).await;
+    }
+
+    #[async_std::test]
+    async fn test_output_wire_committed_names_cheater() {
+        // the cheater commits to its real share, but reveals another one,
+        // or something that is no opening at all
+        for malformed in [false, true] {
+            let outputs = run_parties(3, |mut evaluator| async move {
+                let h = evaluator.ran().unwrap();
+                if evaluator.id != "party_2" {
+                    return Some(evaluator.output_wire_committed(&h).await);
+                }
+
+                let share = Share::raw(&[evaluator.get_wire(&h)])[0];
+                let nonce = [7u8; 32];
+                let msg = EvalNetMsg::PublishValue {
+                    sender: evaluator.id.clone(),
+                    handle: format!("{}/commit", h),
+                    value: bs58::encode(commit_to_share(&share, &nonce)).into_string(),
+                };
+                send_over_network!(msg, evaluator);
+                let reveal = match malformed {
+                    false => encode_share_opening_as_bs58_str(&(share + F::one()), &nonce),
+                    true => bs58::encode([7u8; 5]).into_string(),
+                };
+                let msg = EvalNetMsg::PublishValue {
+                    sender: evaluator.id.clone(),
+                    handle: format!("{}/reveal", h),
+                    value: reveal,
+                };
+                send_over_network!(msg, evaluator);
+                None
+            }).await;
+
+            for output in outputs.into_iter().flatten() {
+                assert_eq!(output, Err(EvaluatorError::CommitmentMismatch {
+                    sender: String::from("party_2")
+                }));
+            }
+        }
+    }
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[async_std::test]
+    async fn test_shared_evaluator_commits_in_parallel() {
+        assert_send::<Evaluator>();
+        assert_send::<SharedEvaluator>();
+        assert_sync::<SharedEvaluator>();
+
+        let pp = utils::setup_kzg(64);
+        let pp = &pp;
+        let outputs = run_parties(2, |evaluator| async move {
+            let shared = SharedEvaluator::new(evaluator);
+            let polys: Vec<DensePolynomial<F>> = (0..8u64)
+                .map(|i| DensePolynomial::from_coefficients_vec(vec![F::from(i + 1); 32]))
+                .collect();
+
+            let commitments: Vec<G1> = std::thread::scope(|scope| {
+                let workers: Vec<_> = polys.iter().map(|poly| {
+                    let shared = shared.clone();
+                    scope.spawn(move || async_std::task::block_on(shared.commit_poly(pp, poly)))
+                }).collect();
+                workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+            });
+            for (poly, commitment) in polys.iter().zip(&commitments) {
+                assert_eq!(*commitment, utils::commit_poly(pp, poly));
+            }
+
+            // network access still goes through the one evaluator
+            let opened = {
+                let mut evaluator = shared.lock().await;
+                let h = evaluator.ran().unwrap();
+                evaluator.output_wire(&h).await
+            };
+
+            let evaluator = shared.into_inner().unwrap();
+            (evaluator.msm_stats().count, opened)
+        }).await;
+
+        assert!(outputs.iter().all(|(count, _)| *count == 8));
+        assert_eq!(outputs[0].1, outputs[1].1);
+    }
+}
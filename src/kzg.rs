@@ -6,11 +6,14 @@ use ark_ec::AffineRepr;
 //use ark_ec::AffineRepr;
 use ark_ec::{pairing::Pairing, CurveGroup};
 use ark_ec::{scalar_mul::fixed_base::FixedBase, VariableBaseMSM};
-use ark_ff::{One, PrimeField, UniformRand, Zero};
-use ark_poly::DenseUVPolynomial;
+use ark_ff::{Field, One, PrimeField, UniformRand, Zero};
+use ark_poly::{DenseUVPolynomial, EvaluationDomain, GeneralEvaluationDomain};
 use ark_std::{format, marker::PhantomData, ops::*, vec};
 
 use ark_std::rand::RngCore;
+use ark_serialize::CanonicalSerialize;
+use sha2::{Digest, Sha256};
+pub use pok3r_verify::kzg::{exact_domain_for, srs_fingerprint, DomainError, UniversalParams};
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
@@ -19,13 +22,6 @@ pub struct KZG10<E: Pairing, P: DenseUVPolynomial<E::ScalarField>> {
     _poly: PhantomData<P>,
 }
 
-pub struct UniversalParams<E: Pairing> {
-    /// Group elements of the form `{ \beta^i G }`, where `i` ranges from 0 to `degree`.
-    pub powers_of_g: Vec<E::G1Affine>,
-    /// Group elements of the form `{ \beta^i H }`, where `i` ranges from 0 to `degree`.
-    pub powers_of_h: Vec<E::G2Affine>,
-}
-
 #[derive(Debug)]
 pub enum Error {
     /// The degree provided in setup was too small; degree 0 polynomials
@@ -40,6 +36,10 @@ pub enum Error {
         /// The maximum number of powers provided in `Powers`.
         num_powers: usize,
     },
+
+    /// The scalar field has no multiplicative subgroup of exactly the
+    /// requested size.
+    Domain(DomainError),
 }
 
 impl<E, P> KZG10<E, P>
@@ -54,7 +54,7 @@ where
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```ignore
     /// use ark_poly_commit::kzg10::KZG10;
     /// use ark_bls12_381::Bls12_381;
     /// use ark_bls12_381::Fr;
@@ -114,7 +114,7 @@ where
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```ignore
     /// use ark_poly_commit::kzg10::{KZG10, Powers};
     /// use ark_bls12_381::Bls12_381;
     /// use ark_bls12_381::Fr;
@@ -195,6 +195,60 @@ where
         Self::commit_g1(params, &witness_polynomial)
     }
 
+    /// commitments [L_i(beta)] to the Lagrange basis of the subgroup of
+    /// the given size: L_i(X) = (1/n) sum_k ω^{-ik} X^k, so these are the
+    /// inverse FFT of the first `size` powers of g
+    pub fn lagrange_commitments(
+        params: &UniversalParams<E>,
+        size: usize,
+    ) -> Result<Vec<E::G1Affine>, Error> {
+        check_degree_is_too_large(size.saturating_sub(1), params.powers_of_g.len())?;
+        let domain = exact_domain_for::<E::ScalarField>(size).map_err(Error::Domain)?;
+
+        let powers: Vec<E::G1> = params.powers_of_g[..size].iter().map(|g| g.into_group()).collect();
+        Ok(E::G1::normalize_batch(&domain.ifft(&powers)))
+    }
+
+    /// the opening proof at ω^i of the polynomial of degree < n taking the
+    /// values evals on the subgroup of size n = evals.len(), without
+    /// dividing by (X - ω^i): the quotient q(X) takes the values
+    /// q(ω^j) = (f_j - f_i) / (ω^j - ω^i) for j != i and
+    /// q(ω^i) = -sum_{j != i} q(ω^j) ω^{j-i}, and is committed to in the
+    /// Lagrange basis
+    pub fn compute_opening_proof_at_root(
+        lagrange: &[E::G1Affine],
+        evals: &[E::ScalarField],
+        i: usize,
+    ) -> Result<E::G1Affine, Error> {
+        let n = evals.len();
+        check_degree_is_too_large(n.saturating_sub(1), lagrange.len())?;
+        let domain = exact_domain_for::<E::ScalarField>(n).map_err(Error::Domain)?;
+
+        // ω^{j-i} for every j, and 1 / (ω^j - ω^i) for every j != i
+        let ω = domain.group_gen();
+        let ω_i = ω.pow([i as u64]);
+        let ω_inv_i = domain.group_gen_inv().pow([i as u64]);
+        let mut shifts = Vec::with_capacity(n);
+        let mut cur = ω_inv_i;
+        for _ in 0..n {
+            shifts.push(cur);
+            cur *= ω;
+        }
+        let mut denominators: Vec<E::ScalarField> = shifts.iter().map(|s| *s * ω_i - ω_i).collect();
+        denominators[i] = E::ScalarField::one();
+        ark_ff::batch_inversion(&mut denominators);
+
+        let mut quotient = vec![E::ScalarField::zero(); n];
+        for j in (0..n).filter(|j| *j != i) {
+            let q_j = (evals[j] - evals[i]) * denominators[j];
+            quotient[j] = q_j;
+            quotient[i] -= q_j * shifts[j];
+        }
+
+        let quotient = convert_to_bigints(&quotient);
+        Ok(E::G1::msm_bigint(&lagrange[..n], &quotient).into_affine())
+    }
+
     pub fn check(
         params: &UniversalParams<E>,
         comm: &E::G1Affine,
@@ -217,6 +271,96 @@ where
 
 }
 
+/// ContributionProof lets anyone check one step of a powers-of-tau
+/// ceremony: the contributor replaced β by β·s for a secret s, and
+/// publishes s in G2 so the update can be checked with pairings
+pub struct ContributionProof<E: Pairing> {
+    /// `s H`, for the contributor's secret s
+    pub s_h: E::G2Affine,
+}
+
+/// updates prev with a secret derived from entropy, so the new β is
+/// unknown unless every contributor so far colludes; the proof lets
+/// the next contributor (or anyone) check the update with verify_contribution
+pub fn ceremony_contribute<E: Pairing>(
+    prev: &UniversalParams<E>, 
+    entropy: &[u8]
+) -> (UniversalParams<E>, ContributionProof<E>) {
+    // bind the secret to the SRS it updates, so reusing entropy across
+    // ceremonies (or steps) does not reuse the secret
+    let mut hasher = Sha256::new();
+    hasher.update(entropy);
+    let mut prev_bytes = Vec::new();
+    prev.powers_of_g[1].serialize_uncompressed(&mut prev_bytes).unwrap();
+    hasher.update(&prev_bytes);
+    let s = E::ScalarField::from_le_bytes_mod_order(&hasher.finalize());
+    assert!(!s.is_zero(), "the contribution secret must be non-zero");
+
+    let mut powers_of_s = vec![E::ScalarField::one()];
+    for i in 1..prev.powers_of_g.len().max(prev.powers_of_h.len()) {
+        powers_of_s.push(powers_of_s[i - 1] * s);
+    }
+
+    let powers_of_g: Vec<E::G1> = ark_std::cfg_iter!(prev.powers_of_g)
+        .zip(ark_std::cfg_iter!(powers_of_s))
+        .map(|(g, s_i)| g.mul(*s_i))
+        .collect();
+    let powers_of_h: Vec<E::G2> = ark_std::cfg_iter!(prev.powers_of_h)
+        .zip(ark_std::cfg_iter!(powers_of_s))
+        .map(|(h, s_i)| h.mul(*s_i))
+        .collect();
+
+    let next = UniversalParams {
+        powers_of_g: E::G1::normalize_batch(&powers_of_g),
+        powers_of_h: E::G2::normalize_batch(&powers_of_h),
+    };
+    let proof = ContributionProof { s_h: prev.powers_of_h[0].mul(s).into_affine() };
+
+    (next, proof)
+}
+
+/// checks that next is prev updated by the secret in proof, and that next
+/// is a well-formed SRS, i.e. it holds consecutive powers of one β in both groups
+pub fn verify_contribution<E: Pairing>(
+    prev: &UniversalParams<E>, 
+    next: &UniversalParams<E>, 
+    proof: &ContributionProof<E>
+) -> bool {
+    let n = next.powers_of_g.len();
+    if n < 2 || n != prev.powers_of_g.len() || next.powers_of_h.len() != prev.powers_of_h.len() {
+        return false;
+    }
+
+    let g = next.powers_of_g[0];
+    let h = next.powers_of_h[0];
+    if g != prev.powers_of_g[0] || h != prev.powers_of_h[0] || proof.s_h.is_zero() {
+        return false;
+    }
+
+    // β_next = β_prev · s
+    if E::pairing(next.powers_of_g[1], h) != E::pairing(prev.powers_of_g[1], proof.s_h) {
+        return false;
+    }
+
+    // every g_{i+1} = β g_i and every h_i = β^i h, batched with powers of a random r
+    let r = E::ScalarField::rand(&mut rand::thread_rng());
+    let mut powers_of_r = vec![E::ScalarField::one()];
+    for i in 1..n.max(next.powers_of_h.len()) {
+        powers_of_r.push(powers_of_r[i - 1] * r);
+    }
+
+    let shifted = E::G1::msm(&next.powers_of_g[1..], &powers_of_r[..n - 1]).unwrap();
+    let unshifted = E::G1::msm(&next.powers_of_g[..n - 1], &powers_of_r[..n - 1]).unwrap();
+    if E::pairing(shifted, h) != E::pairing(unshifted, next.powers_of_h[1]) {
+        return false;
+    }
+
+    let m = next.powers_of_h.len().min(n);
+    let g_batch = E::G1::msm(&next.powers_of_g[..m], &powers_of_r[..m]).unwrap();
+    let h_batch = E::G2::msm(&next.powers_of_h[..m], &powers_of_r[..m]).unwrap();
+    E::pairing(g_batch, h) == E::pairing(g, h_batch)
+}
+
 fn skip_leading_zeros_and_convert_to_bigints<F: PrimeField, P: DenseUVPolynomial<F>>(
     p: &P,
 ) -> (usize, Vec<F::BigInt>) {
@@ -0,0 +1,15 @@
+//! The networked evaluator of pok3r and what it is built from. The
+//! protocol and the binary running it live in main.rs; they are kept
+//! apart so that the types here, e.g. Share, can be checked from outside
+//! the crate.
+
+pub mod address_book;
+pub mod card;
+pub mod checkpoint;
+pub mod common;
+pub mod evaluator;
+pub mod kzg;
+pub mod message;
+pub mod network;
+pub mod poker;
+pub mod utils;
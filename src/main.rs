@@ -1,45 +1,112 @@
-use std::{thread, collections::{HashMap, HashSet}, time::{Duration, Instant}, vec, ops::*};
+use std::{thread, collections::{HashMap, HashSet, VecDeque}, sync::{Arc, Mutex}, time::{Duration, Instant}, vec, ops::*};
 use ark_ec::{CurveGroup, AffineRepr, pairing::Pairing, Group};
 use ark_ff::Field;
-use ark_poly::{ GeneralEvaluationDomain, EvaluationDomain, Polynomial, univariate::{DensePolynomial, DenseOrSparsePolynomial}, DenseUVPolynomial};
-use ark_serialize::CanonicalSerialize;
+use ark_poly::{ Polynomial, univariate::{DensePolynomial, DenseOrSparsePolynomial}, DenseUVPolynomial, EvaluationDomain};
 use ark_std::{Zero, One, UniformRand};
 use async_std::task;
-use futures::channel::*;
+use futures::{channel::*, stream::{self, Stream, StreamExt, TryStreamExt}};
 use clap::Parser;
 use kzg::UniversalParams;
 use num_bigint::BigUint;
+use once_cell::sync::OnceCell;
+use pok3r_verify::{
+    is_valid_public_key, verify_permutation_argument, verify_permutation_argument_in, verify_permutation_relation,
+    CombinedTranscript,
+};
 use rand::{rngs::StdRng, SeedableRng};
 use serde_json::json;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
-mod network;
-mod evaluator;
-mod address_book;
-mod common;
-mod utils;
-mod kzg;
+use pok3r::{address_book, card, checkpoint, common, evaluator, kzg, network, utils};
 
 use address_book::*;
 use evaluator::*;
 use common::*;
+use card::*;
+use checkpoint::Checkpoint;
 
 pub const PERFORM_TESTING: bool = false;
+/// open and print every card of the deck shuffle_deck returns; this reveals
+/// the deck to every party, so it is for debugging only
+pub const DEBUG_PRINT_DECK: bool = false;
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Name of the person to greet
-    #[arg(short, long)]
-    id: String,
+    #[arg(short, long, required_unless_present = "capabilities")]
+    id: Option<String>,
 
     /// Fixed value to generate deterministic peer id
-    #[clap(long)]
-    seed: u8,
+    #[clap(long, required_unless_present = "capabilities")]
+    seed: Option<u8>,
 
     /// number of parties doing the mpc
+    #[clap(long, required_unless_present = "capabilities")]
+    parties: Option<u64>,
+
+    /// print the supported protocol parameters as json and exit, for
+    /// comparing with the other parties before a game
+    #[clap(long)]
+    capabilities: bool,
+
+    /// threads for local proof work, one per core if not given (needs the parallel feature)
     #[clap(long)]
-    parties: u64,
+    threads: Option<usize>,
+
+    /// how to print the verification result: text, or a json object on
+    /// the last line of stdout
+    #[clap(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+
+    /// file this party checkpoints to after the shuffle and after the
+    /// permutation argument; if it exists, the run resumes from it
+    #[clap(long)]
+    checkpoint: Option<std::path::PathBuf>,
+
+    /// most batched rounds this party has in flight at once, 64 if not
+    /// given; all parties must pass the same, as it bounds the messages
+    /// buffered per peer
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    max_inflight: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// what main verified at the end of a run
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+struct RunResult {
+    permutation: bool,
+    encryption: bool,
+    /// candidate cards the shuffle sampled
+    shuffle_attempts: usize,
+    /// from the start of the shuffle to the end of verification
+    elapsed_ms: u128,
+}
+
+impl RunResult {
+    fn verified(&self) -> bool {
+        self.permutation && self.encryption
+    }
+
+    fn render(&self, format: OutputFormat) -> String {
+        let status = |verified: bool| if verified { "verified" } else { "verification failed" };
+        match format {
+            OutputFormat::Text => format!(
+                "Permutation argument {}\nEncryption proof {}", 
+                status(self.permutation), 
+                status(self.encryption)
+            ),
+            OutputFormat::Json => serde_json::to_string(self).unwrap(),
+        }
+    }
 }
 
 /*
@@ -108,7 +175,7 @@ Seed 62 peer id: 12D3KooWSK6f2ZJLRX8Q3LiuVnj9y3yXqJgFguJh7gdjtsSomnS8
 Seed 63 peer id: 12D3KooWHV2zfje5uXRV5nPsqArHdrVrh7GaAJVyhwr8ffZZ16om
 */
 
-fn parse_addr_book_from_json(num_parties: u64) -> Pok3rAddrBook {
+fn parse_addr_book_from_json(num_parties: u64) -> Result<Pok3rAddrBook, AddrBookError> {
     let config = json!({
         "addr_book": [ //addr_book is a list of ed25519 pubkeys
             "12D3KooWPjceQrSwdWXPyLLeABRXmuqt69Rg3sBYbU1Nft9HyQ6X",
@@ -152,24 +219,33 @@ fn parse_addr_book_from_json(num_parties: u64) -> Pok3rAddrBook {
         .map(|o| String::from(o.as_str().unwrap()))
         .collect();
 
-    let mut output: Pok3rAddrBook = HashMap::new();
-    let mut counter = 0;
-    for peer in &peers[0..num_parties as usize] {
-        let pok3rpeer = Pok3rPeer {
-            peer_id: peer.to_owned(),
-            node_id: counter,
-        };
-
-        output.insert(peer.to_owned(), pok3rpeer);
-        counter += 1;
-    }
-
-    output
+    build_addr_book(&peers[0..num_parties as usize])
 }
 
 #[async_std::main]
 async fn main() {
     let args = Args::parse();
+    if args.capabilities {
+        println!("{}", capabilities().to_json());
+        return;
+    }
+    // clap only lets these be missing with --capabilities
+    let (id, seed, parties) = (args.id.unwrap(), args.seed.unwrap(), args.parties.unwrap());
+
+    #[cfg(feature = "parallel")]
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads.unwrap_or(0))
+        .build_global()
+        .expect("thread pool is only built once");
+
+    let addr_book = match parse_addr_book_from_json(parties) {
+        Ok(addr_book) => addr_book,
+        Err(err) => {
+            eprintln!("Address book error {:?}", err);
+            std::process::exit(1);
+        }
+    };
+    let netd_addr_book = addr_book.clone();
 
     //these channels will connect the evaluator and the network daemons
     let (mut n2e_tx, n2e_rx) = mpsc::unbounded::<EvalNetMsg>();
@@ -178,8 +254,9 @@ async fn main() {
     let netd_handle = thread::spawn(move || {
         let result = task::block_on(
             network::run_networking_daemon(
-                args.seed, 
-                &parse_addr_book_from_json(args.parties), 
+                seed, 
+                &netd_addr_book, 
+                MAX_MESSAGE_SIZE,
                 &mut n2e_tx,
                 e2n_rx)
         );
@@ -188,17 +265,26 @@ async fn main() {
         }
     });
     
-    let addr_book = parse_addr_book_from_json(args.parties);
-    let mut mpc = Evaluator::new(&args.id, addr_book, e2n_tx, n2e_rx).await;
+    let mut mpc = match Evaluator::new(&id, addr_book, e2n_tx, n2e_rx).await {
+        Ok(mpc) => mpc.with_network_thread(netd_handle),
+        Err(err) => {
+            eprintln!("Evaluator error {:?}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(limit) = args.max_inflight {
+        mpc = mpc.with_max_inflight(limit as usize);
+    }
 
-    //this is a hack until we figure out
-    task::block_on(async {
-        task::sleep(Duration::from_secs(1)).await;
-        println!("After sleeping for 1 second.");
-    });
+    // connected is not yet ready: a peer may not receive our messages
+    // yet, so wait until every party answers a ping
+    if let Err(err) = mpc.wait_until_ready(Duration::from_secs(30)).await {
+        eprintln!("Evaluator error {:?}", err);
+        std::process::exit(1);
+    }
 
     if PERFORM_TESTING {
-        mpc.test_networking().await;
+        mpc.test_networking().await.expect("the parties cannot open a random wire");
         evaluator::perform_sanity_testing(&mut mpc).await;
         test_sigma(&mut mpc).await;
         test_local_kzg();
@@ -209,24 +295,59 @@ async fn main() {
     // KZG setup runs once
     let pp = utils::setup_kzg(1024);
 
+    // a checkpoint left by an earlier run, which all parties must resume from
+    let checkpoint = args.checkpoint.as_deref()
+        .filter(|path| path.exists())
+        .map(|path| Checkpoint::load(path).expect("cannot read the checkpoint"));
+    if let Some(checkpoint) = &checkpoint {
+        mpc.resume_from(checkpoint).await
+            .expect("the parties did not resume from the same checkpoint");
+        println!("resumed from checkpoint");
+    }
+    let save_checkpoint = |checkpoint: Checkpoint| {
+        if let Some(path) = &args.checkpoint {
+            checkpoint.save(path).expect("cannot write the checkpoint");
+        }
+    };
+
     // Actual protocol
     let s_total = Instant::now();
-    let s_shuffle = Instant::now();
-    let (card_share_handles, card_shares) = shuffle_deck(&mut mpc).await;
-    let t_shuffle = s_shuffle.elapsed();
-
-    println!("shuffle_deck: {:?}", t_shuffle);
+    let mut deck = match &checkpoint {
+        Some(checkpoint) => ShuffledDeck::from_parts(checkpoint.handles.clone(), checkpoint.shares.clone())
+            .expect("the checkpoint does not hold a full deck"),
+        None => {
+            let s_shuffle = Instant::now();
+            let deck = shuffle_deck_committed(&pp, &mut mpc).await
+                .expect("We don't have enough cards - try again");
+            let t_shuffle = s_shuffle.elapsed();
+
+            println!("shuffle_deck: {:?}", t_shuffle);
+            save_checkpoint(mpc.checkpoint(&deck.handles, None));
+            deck
+        },
+    };
     
-    let s_perm = Instant::now();
-    let perm_proof = compute_permutation_argument(
-        &pp, 
-        &mut mpc, 
-        card_share_handles.clone(), 
-        &card_shares
-    ).await;
-    let t_perm = s_perm.elapsed();
-
-    println!("compute_permutation_argument: {:?}", t_perm);
+    let perm_proof = match checkpoint.and_then(|checkpoint| checkpoint.perm_proof) {
+        Some(perm_proof) => perm_proof,
+        None => {
+            let s_perm = Instant::now();
+            let perm_proof = compute_committed_permutation_argument(
+                &pp, 
+                &mut mpc, 
+                &deck,
+                &card_mapping_commitment()
+            ).await.unwrap_or_else(|err| {
+                eprintln!("Evaluator error {:?}", err);
+                std::process::exit(1);
+            });
+            let t_perm = s_perm.elapsed();
+
+            println!("compute_permutation_argument: {:?}", t_perm);
+            save_checkpoint(mpc.checkpoint(&deck.handles, Some(&perm_proof)));
+            perm_proof
+        },
+    };
+    deck.f_com = Some(perm_proof.f_com);
 
     // Get a random public key pk in G2 - for testing (should be generated by DKG)
     let mut seeded_rng = StdRng::from_seed([42u8; 32]);
@@ -244,18 +365,19 @@ async fn main() {
     let encrypt_proof = encrypt_and_prove(
         &pp, 
         &mut mpc, 
-        card_share_handles.clone(), 
+        deck.handles.clone(), 
         perm_proof.f_com, 
         pk, 
         ids.clone()
-    ).await;
+    ).await
+        .expect("there is not one id per card");
     println!("total_MPC_time: {:?}", s_total.elapsed());
 
     // let t_encrypt = s_encrypt.elapsed();
 
     // println!("encrypt_and_prove: {:?}", t_encrypt);
 
-    let x_f = F::from(ids[15].clone());
+    let x_f = utils::id_to_field_checked(&ids[15]).unwrap();
     let hash_id_15 = G1::generator().mul(x_f);
     let dec_key = (hash_id_15 * msk).into_affine();
 
@@ -268,114 +390,390 @@ async fn main() {
     
     // println!("decrypt_one_card: {:?}", s_decryption.elapsed());
     
+    // verification needs only the SRS, not the network
+    let verifier = Verifier::new(pp);
+
     // let s_verify_perm = Instant::now();
-    let verified = verify_permutation_argument(&pp, &perm_proof).await;
+    let permutation = verifier.verify_permutation_argument(&perm_proof).await;
     // let t_verify_perm = s_verify_perm.elapsed();
 
     // println!("verify_permutation_argument: {:?}", t_verify_perm);
-    assert!(verified, "Permutation argument verification failed");
 
 
     // let s_verify_encrypt = Instant::now();
-    let verified = local_verify_encryption_proof(&pp, &encrypt_proof).await;
+    let encryption = verifier.verify_encryption_proof(&encrypt_proof).await;
     // let t_verify_encrypt = s_verify_encrypt.elapsed();
 
     // println!("local_verify_encryption_proof: {:?}", t_verify_encrypt);
-    assert!(verified, "Encryption proof verification failed");
 
     println!("verifier_time: {:?}", s_verifier.elapsed());
 
-    //eval_handle.join().unwrap();
-    netd_handle.join().unwrap();
+    let result = RunResult {
+        permutation,
+        encryption,
+        shuffle_attempts: deck.samples,
+        elapsed_ms: s_total.elapsed().as_millis(),
+    };
+    println!("{}", result.render(args.output));
+    if !result.verified() {
+        std::process::exit(1);
+    }
+
+    // stops the networking daemon and joins its thread
+    drop(mpc);
 }
 
-fn _map_roots_of_unity_to_cards() -> HashMap<F, String> {
-    let mut output: HashMap<F, String> = HashMap::new();
+pub fn map_roots_of_unity_to_cards() -> HashMap<F, Card> {
+    let mut output: HashMap<F, Card> = HashMap::new();
     
-    // get generator for the 64 powers of 64-th root of unity
-    let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64);
+    // get generator for the powers of the PERM_SIZE-th root of unity
+    let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
 
-    // map each power to a card
-    // map 64 cards
-    for i in 0..PERM_SIZE {
-        let ω_pow_i = utils::compute_power(&ω, i as u64);
-        let card_name = i.to_string();
-        output.insert(ω_pow_i, card_name);
+    // map the first 52 powers to cards, the rest are padding
+    for card in Card::deck() {
+        let ω_pow_i = utils::compute_power(&ω, card.to_index() as u64);
+        output.insert(ω_pow_i, card);
     }
 
     output
 }
 
-async fn shuffle_deck(evaluator: &mut Evaluator) -> (Vec<String>, Vec<F>) {
-    //step 1: parties invoke F_RAN to obtain [sk]
-    let sk = evaluator.ran();
+/// utils::mapping_commitment of map_roots_of_unity_to_cards, the mapping
+/// all parties and a Verifier assume unless told otherwise
+pub fn card_mapping_commitment() -> [u8; 32] {
+    utils::mapping_commitment(&map_roots_of_unity_to_cards())
+}
 
-    //stores (handle, wire value) pairs
-    let mut card_share_handles = Vec::new();
-    let mut card_share_values = Vec::new();
-    //stores set of card prfs encountered
-    let mut prfs = HashSet::new();
+/// names an opened card for debug output, without panicking on values
+/// card_mapping does not know: the other powers of ω are "padding", and
+/// anything else is an "unknown card", which means ran_64 or the prf
+/// produced a value outside the deck, so card derivation itself is broken
+pub fn describe_opened_card(card_mapping: &HashMap<F, Card>, opened_card: &F) -> String {
+    if let Some(card) = card_mapping.get(opened_card) {
+        return card.to_string();
+    }
 
-    // let t_52_64 = Instant::now();
-    // Compute prfs for cards 52 to 63 and add to prfs first
-    // So that the positions of these cards are fixed in the permutation
+    if opened_card.pow([PERM_SIZE as u64]) == F::one() {
+        return String::from("padding");
+    }
 
-    let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64);
-    let powers_of_ω = (0..PERM_SIZE)
-        .into_iter()
-        .map(|i| utils::compute_power(&ω, i as u64))
-        .collect::<Vec<F>>();
+    eprintln!("warning: opened card {} is not in the deck, card derivation is broken", opened_card);
+    String::from("unknown card")
+}
 
-    // y_i = g^{1 / (sk + w_i)}
-    let denoms = (DECK_SIZE..PERM_SIZE)
-        .into_iter()
-        .map(|i| evaluator.clear_add(&sk, powers_of_ω[i]))
-        .collect::<Vec<String>>();
+/// where a shuffle puts the jokers, i.e. the cards DECK_SIZE..PERM_SIZE
+/// that fill up the domain of the permutation argument
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum JokerPolicy {
+    /// at the first positions, the same ones in every shuffle
+    #[default]
+    Fixed,
+    /// at random positions, sampled like any other card
+    Shuffled,
+    /// at the last positions, in order: only the real cards are permuted,
+    /// over positions 0..DECK_SIZE. This only moves the jokers, it does not
+    /// remove them: the deck still holds PERM_SIZE cards, the jokers are
+    /// still dealt as wires, and the permutation argument still runs over
+    /// all PERM_SIZE of them
+    Excluded,
+}
+
+/// Progress steps of a shuffle: the fixed cards, then one per card found,
+/// reported after every batch of samples
+const SHUFFLE_STEPS: usize = PERM_SIZE + 1;
+
+/// how many more samples than expected a batch of the shuffle draws, in
+/// multiples of PERM_SIZE; a batch still misses a card with probability
+/// about e^-SHUFFLE_BATCH_MARGIN, and then the next batch only samples for
+/// the cards that are missing
+const SHUFFLE_BATCH_MARGIN: f64 = 2.0;
+
+/// batches after which a shuffle gives up and comes out short
+const MAX_SHUFFLE_BATCHES: usize = 8;
+
+/// the number of candidates a batch of the shuffle samples while missing
+/// of the PERM_SIZE card values have not come up yet: by the coupon
+/// collector, it takes PERM_SIZE * H(missing) uniform samples to expect
+/// all of them, plus SHUFFLE_BATCH_MARGIN
+pub fn shuffle_batch_size(missing: usize) -> usize {
+    if missing == 0 {
+        return 0;
+    }
+
+    let harmonic: f64 = (1..=missing).map(|k| 1.0 / k as f64).sum();
+    (PERM_SIZE as f64 * (harmonic + SHUFFLE_BATCH_MARGIN)).ceil() as usize
+}
+
+/// Progress steps of compute_permutation_argument: the commitments to
+/// f(X), g(X), t(X) and q(X), then the openings
+const PERMUTATION_STEPS: usize = 5;
+
+/// Progress steps of encrypt_and_prove: the ciphertexts, the masked
+/// commitments, the masked evaluations, their opening proofs, then the
+/// sigma proof
+const ENCRYPTION_STEPS: usize = 5;
+
+/// the rounds of shuffle_deck_stream, each yielding a batch of cards
+enum ShufflePhase {
+    Failed(EvaluatorError),
+    FixedCards { sk: String, joker_policy: JokerPolicy },
+    SampledCards { sk: String, prfs: HashSet<G1>, trailing: ShuffleRound, batches: usize },
+    Done,
+}
+
+/// the cards one round of the shuffle found, the prf it opened for each
+/// of them, and the candidates it sampled
+struct ShuffleRound {
+    cards: Vec<(String, Share)>,
+    card_prfs: Vec<G1>,
+    samples: usize,
+}
+
+/// shuffle_deck as a stream of (handle, share) pairs: the cards with fixed
+/// positions come out once their prfs are known, followed by every sampled
+/// card the prf loop finds to be distinct. Collecting the stream gives
+/// exactly the deck shuffle_deck returns; if the evaluator fails, e.g. as
+/// it runs out of random wires, the error is the last item.
+pub fn shuffle_deck_stream(
+    evaluator: &mut Evaluator, 
+    joker_policy: JokerPolicy
+) -> impl Stream<Item = Result<(String, Share), EvaluatorError>> + '_ {
+    shuffle_rounds(evaluator, joker_policy)
+        .flat_map(|round| stream::iter(match round {
+            Ok(round) => round.cards.into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err)],
+        }))
+}
+
+/// the rounds of a shuffle keyed by a fresh prf key [sk]
+fn shuffle_rounds(
+    evaluator: &mut Evaluator, 
+    joker_policy: JokerPolicy
+) -> impl Stream<Item = Result<ShuffleRound, EvaluatorError>> + '_ {
+    //step 1: parties invoke F_RAN to obtain [sk]
+    let phase = match evaluator.ran() {
+        Ok(sk) => ShufflePhase::FixedCards { sk, joker_policy },
+        Err(err) => ShufflePhase::Failed(err),
+    };
+    shuffle_rounds_from(evaluator, phase)
+}
+
+/// the rounds of a shuffle keyed by [sk]: the cards with fixed positions,
+/// then batches of samples, each sized by shuffle_batch_size for the cards
+/// still missing, until the deck is full or MAX_SHUFFLE_BATCHES ran. A
+/// round the evaluator fails in comes out as the error, and ends the shuffle
+fn shuffle_rounds_with_sk(
+    evaluator: &mut Evaluator, 
+    sk: String, 
+    joker_policy: JokerPolicy
+) -> impl Stream<Item = Result<ShuffleRound, EvaluatorError>> + '_ {
+    shuffle_rounds_from(evaluator, ShufflePhase::FixedCards { sk, joker_policy })
+}
+
+fn shuffle_rounds_from(
+    evaluator: &mut Evaluator, 
+    phase: ShufflePhase
+) -> impl Stream<Item = Result<ShuffleRound, EvaluatorError>> + '_ {
+    stream::unfold((evaluator, phase), |(evaluator, phase)| async move {
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let powers_of_ω = utils::power_table(&ω, PERM_SIZE);
+
+        match phase {
+            ShufflePhase::Failed(err) => Some((Err(err), (evaluator, ShufflePhase::Done))),
+            ShufflePhase::FixedCards { sk, joker_policy } => {
+                //stores set of card prfs encountered
+                let mut prfs = HashSet::new();
+                let mut jokers = ShuffleRound { cards: Vec::new(), card_prfs: Vec::new(), samples: 0 };
+
+                // Compute prfs for the jokers DECK_SIZE..PERM_SIZE and add to
+                // prfs first, so that no sampled card takes their place
+                // y_i = g^{1 / (sk + w_i)}
+                if joker_policy != JokerPolicy::Shuffled {
+                    let y_is = match evaluator.batch_oprf_eval(&sk, &powers_of_ω[DECK_SIZE..PERM_SIZE]).await {
+                        Ok(y_is) => y_is,
+                        Err(err) => return Some((Err(err), (evaluator, ShufflePhase::Done))),
+                    };
+
+                    for i in 0..(PERM_SIZE - DECK_SIZE) {
+                        prfs.insert(y_is[i]);
+                        let handle = evaluator.fixed_wire_handle(powers_of_ω[i + DECK_SIZE]);
+                        let share = evaluator.get_wire(&handle);
+                        jokers.cards.push((handle, share));
+                        jokers.card_prfs.push(y_is[i]);
+                    }
+                }
+
+                // jokers go before the sampled cards, or after them if excluded
+                let none = ShuffleRound { cards: Vec::new(), card_prfs: Vec::new(), samples: 0 };
+                let (round, trailing) = match joker_policy {
+                    JokerPolicy::Excluded => (none, jokers),
+                    _ => (jokers, none),
+                };
+                evaluator.report_progress(ProgressPhase::Shuffle, 1, SHUFFLE_STEPS);
+                Some((Ok(round), (evaluator, ShufflePhase::SampledCards { sk, prfs, trailing, batches: 0 })))
+            },
+            ShufflePhase::SampledCards { sk, mut prfs, trailing, batches } => {
+                // the prfs seen so far are exactly the card values that came up
+                let num_samples = shuffle_batch_size(PERM_SIZE - prfs.len());
+                let sampled = match evaluator.batch_ran_64(num_samples).await {
+                    Ok(c_is) => evaluator.batch_oprf_eval_shared(&sk, &c_is).await.map(|y_is| (c_is, y_is)),
+                    Err(err) => Err(err),
+                };
+                let (c_is, y_is) = match sampled {
+                    Ok(sampled) => sampled,
+                    Err(err) => return Some((Err(err), (evaluator, ShufflePhase::Done))),
+                };
+
+                let mut cards = Vec::new();
+                let mut card_prfs = Vec::new();
+                for i in 0..num_samples {
+                    //add card if it hasnt been seen before
+                    if ! prfs.contains(&y_is[i]) {
+                        prfs.insert(y_is[i]);
+                        cards.push((c_is[i].clone(), evaluator.get_wire(&c_is[i])));
+                        card_prfs.push(y_is[i]);
+                    }
+                }
+                evaluator.report_progress(ProgressPhase::Shuffle, prfs.len() + 1, SHUFFLE_STEPS);
+
+                // every party saw the same prfs, so all of them go on, or stop, together
+                let batches = batches + 1;
+                let next = if prfs.len() < PERM_SIZE && batches < MAX_SHUFFLE_BATCHES {
+                    ShufflePhase::SampledCards { sk, prfs, trailing, batches }
+                } else {
+                    cards.extend(trailing.cards);
+                    card_prfs.extend(trailing.card_prfs);
+                    ShufflePhase::Done
+                };
+
+                Some((Ok(ShuffleRound { cards, card_prfs, samples: num_samples }), (evaluator, next)))
+            },
+            ShufflePhase::Done => None,
+        }
+    })
+}
 
-    let t_is = evaluator.batch_inv(&denoms).await;
+/// a shuffled deck: the card at position i, i.e. f(ω^i) once the
+/// permutation argument interpolates the deck as f(X), is on wire handles[i]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShuffledDeck {
+    /// wire handles of the cards, by position
+    pub handles: Vec<String>,
+    /// this party's shares of the cards, by position
+    pub shares: Vec<Share>,
+    /// the root of unity of each position, positions[i] = ω^i
+    pub positions: Vec<F>,
+    /// the commitment to f(X), once shuffle_deck_committed or
+    /// compute_permutation_argument made it
+    pub f_com: Option<G1>,
+    /// candidate cards the shuffle sampled to find the deck; 0 for a deck
+    /// that was not shuffled here, e.g. one resumed from a Checkpoint
+    pub samples: usize,
+}
 
-    let y_is = evaluator.batch_output_wire_in_exponent(&t_is).await;
+#[derive(Debug, PartialEq)]
+pub enum ShuffleError {
+    /// The shuffle did not end up with one handle and one share for every
+    /// position of the deck, e.g. because the prf loop missed cards; the
+    /// permutation argument needs exactly the domain size.
+    WrongDeckSize {
+        /// The number of handles, or of shares if only those are off.
+        got: usize,
+        /// The deck size, PERM_SIZE.
+        expected: usize,
+    },
+    /// The evaluator failed during the shuffle, e.g. as it ran out of the
+    /// random wires dealt ahead of time.
+    Evaluator(EvaluatorError),
+}
 
-    for i in 0..(PERM_SIZE - DECK_SIZE) {
-        prfs.insert(y_is[i].clone());
-        let handle = evaluator.fixed_wire_handle(powers_of_ω[i + DECK_SIZE]);
-        card_share_handles.push(handle.clone());
-        card_share_values.push(evaluator.get_wire(&handle));
+impl ShuffledDeck {
+    fn new(cards: Vec<(String, Share)>) -> Result<Self, ShuffleError> {
+        let (handles, shares): (Vec<String>, Vec<Share>) = cards.into_iter().unzip();
+        Self::from_parts(handles, shares)
     }
 
-    // println!("PRFs for 52-64: {:?}", t_52_64.elapsed());
-    
-    // let t_ran64 = Instant::now();
-    let c_is = evaluator.batch_ran_64(NUM_SAMPLES).await;
-    // println!("RAN64: {:?}", t_ran64.elapsed());
+    fn from_rounds(rounds: Vec<ShuffleRound>) -> Result<Self, ShuffleError> {
+        let samples = rounds.iter().map(|round| round.samples).sum();
+        let cards = rounds.into_iter().flat_map(|round| round.cards).collect();
 
-    let t_is = (0..NUM_SAMPLES)
-        .into_iter()
-        .map(|i| evaluator.add(&c_is[i], &sk))
-        .collect::<Vec<String>>();    
-
-    // let t_inv = Instant::now();
-    let t_is = evaluator.batch_inv(&t_is).await;
-    // println!("INV: {:?}", t_inv.elapsed());
-
-    // let t_prfs = Instant::now();
-    let y_is = evaluator.batch_output_wire_in_exponent(&t_is).await;
-    // println!("PRFs: {:?}", t_prfs.elapsed());
-
-    for i in 0..NUM_SAMPLES {
-        //add card if it hasnt been seen before
-        if ! prfs.contains(&y_is[i]) {
-            prfs.insert(y_is[i].clone());
-            card_share_handles.push(c_is[i].clone());
-            card_share_values.push(evaluator.get_wire(&c_is[i]));
+        let mut deck = Self::new(cards)?;
+        deck.samples = samples;
+        Ok(deck)
+    }
+
+    /// a deck from its handles and shares, by position, provided there
+    /// are PERM_SIZE of each
+    fn from_parts(handles: Vec<String>, shares: Vec<Share>) -> Result<Self, ShuffleError> {
+        for got in [handles.len(), shares.len()] {
+            if got != PERM_SIZE {
+                return Err(ShuffleError::WrongDeckSize { got, expected: PERM_SIZE });
+            }
         }
+
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let positions = utils::power_table(&ω, PERM_SIZE);
+        Ok(ShuffledDeck { handles, shares, positions, f_com: None, samples: 0 })
+    }
+
+    /// the wire handle of the card at root of unity position, if position
+    /// is one of the positions
+    pub fn handle_at(&self, position: &F) -> Option<&String> {
+        self.positions.iter().position(|p| p == position).map(|i| &self.handles[i])
+    }
+}
+
+async fn shuffle_deck(evaluator: &mut Evaluator) -> Result<ShuffledDeck, ShuffleError> {
+    let deck = shuffle_deck_with_joker_policy(evaluator, JokerPolicy::Fixed).await?;
+    if DEBUG_PRINT_DECK {
+        print_opened_deck(evaluator, &deck).await;
     }
+    Ok(deck)
+}
+
+/// opens every card of deck and prints it by position; a card outside the
+/// deck is printed as what describe_opened_card names it, not a panic
+async fn print_opened_deck(evaluator: &mut Evaluator, deck: &ShuffledDeck) {
+    let card_mapping = map_roots_of_unity_to_cards();
+    let opened_cards = evaluator.batch_output_wire(&deck.handles).await;
+    for (i, opened_card) in opened_cards.iter().enumerate() {
+        println!("shuffle_deck: position {}: {}", i, describe_opened_card(&card_mapping, opened_card));
+    }
+}
+
+/// shuffle_deck, followed right away by the commitment f_com to the deck,
+/// before anything else is exchanged: the permutation argument must then
+/// come out with the same f_com, so no party can change its shares of
+/// the cards depending on what it sees later in the protocol
+pub async fn shuffle_deck_committed(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator
+) -> Result<ShuffledDeck, ShuffleError> {
+    let mut deck = shuffle_deck(evaluator).await?;
+    deck.f_com = Some(commit_deck(pp, evaluator, &deck).await);
+    Ok(deck)
+}
+
+/// the commitment f_com to the polynomial f(X) with f(ω^i) = the card at
+/// position i of deck
+async fn commit_deck(pp: &UniversalParams<Curve>, evaluator: &mut Evaluator, deck: &ShuffledDeck) -> G1 {
+    let f_share = utils::interpolate_poly_over_mult_subgroup(&Share::raw(&deck.shares));
+    let f_share_com = evaluator.commit_poly(pp, &f_share);
+    evaluator.add_g1_elements_from_all_parties(&f_share_com, &String::from("deck_f")).await
+}
 
-    // Assert that the length is 64
-    assert_eq!(card_share_handles.len(), PERM_SIZE, 
-        "We don't have enough cards - try again");
+/// shuffle_deck, placing the jokers according to joker_policy
+pub async fn shuffle_deck_with_joker_policy(
+    evaluator: &mut Evaluator, 
+    joker_policy: JokerPolicy
+) -> Result<ShuffledDeck, ShuffleError> {
+    let rounds = shuffle_rounds(evaluator, joker_policy)
+        .try_collect::<Vec<ShuffleRound>>()
+        .await
+        .map_err(ShuffleError::Evaluator)?;
 
-    return (card_share_handles.clone(), card_share_values);
+    // Fails unless there are PERM_SIZE cards
+    ShuffledDeck::from_rounds(rounds)
 
     // Pre-batched version
 
@@ -412,20 +810,127 @@ async fn shuffle_deck(evaluator: &mut Evaluator) -> (Vec<String>, Vec<F>) {
     // }
 }
 
+/// public record of a shuffle_deck_with_commitment run
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShuffleSeedCommitment {
+    /// handle of the prf key [sk], to reveal it with reveal_shuffle_seed
+    pub sk: String,
+    /// every party's H(sk share || nonce), sorted by peer id
+    pub commitments: Vec<(Pok3rPeerId, String)>,
+    /// y_i = g^{1 / (sk + card_i)} of the card at every position i, as the
+    /// shuffle opened it to tell the cards apart
+    pub card_prfs: Vec<G1>,
+    /// the commitment f_com to the deck, with f(ω^i) = card_i
+    pub f_com: G1,
+}
+
+/// shuffle_deck_committed, with every party committing to its share of
+/// the prf key [sk] before shuffling; after the game, reveal_shuffle_seed
+/// opens the commitments so that anyone can audit_shuffle_seed
+pub async fn shuffle_deck_with_commitment(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator
+) -> Result<(ShuffledDeck, ShuffleSeedCommitment), ShuffleError> {
+    let sk = evaluator.ran().map_err(ShuffleError::Evaluator)?;
+    let commitments = evaluator.commit_wire(&sk).await;
+
+    let rounds = shuffle_rounds_with_sk(evaluator, sk.clone(), JokerPolicy::Fixed)
+        .try_collect::<Vec<ShuffleRound>>()
+        .await
+        .map_err(ShuffleError::Evaluator)?;
+    // the prfs the shuffle opened, in the order of the deck
+    let card_prfs: Vec<G1> = rounds.iter().flat_map(|round| round.card_prfs.iter().copied()).collect();
+    let mut deck = ShuffledDeck::from_rounds(rounds)?;
+
+    let f_com = commit_deck(pp, evaluator, &deck).await;
+    deck.f_com = Some(f_com);
+
+    Ok((deck, ShuffleSeedCommitment { sk, commitments, card_prfs, f_com }))
+}
+
+/// opens every party's committed share of [sk], once the game is over
+pub async fn reveal_shuffle_seed(
+    evaluator: &mut Evaluator, 
+    commitment: &ShuffleSeedCommitment
+) -> Result<Vec<(Pok3rPeerId, F, [u8; 32])>, EvaluatorError> {
+    evaluator.reveal_committed_wire(&commitment.sk, &commitment.commitments).await
+}
+
+/// checks, without any network, that the openings match the commitments
+/// of every party, and that the (additively shared) sk they add up to is
+/// the key the shuffle used: every card opened against the deck
+/// commitment, e.g. with reveal_positions, must have the prf the shuffle
+/// opened for its position under sk
+pub fn audit_shuffle_seed(
+    pp: &UniversalParams<Curve>,
+    commitment: &ShuffleSeedCommitment, 
+    openings: &[(Pok3rPeerId, F, [u8; 32])],
+    cards: &[(usize, F, G1)]
+) -> bool {
+    if openings.len() != commitment.commitments.len() || commitment.card_prfs.len() != PERM_SIZE {
+        return false;
+    }
+
+    let mut sk = F::zero();
+    for ((peer_id, commitment), (sender, share, nonce)) in commitment.commitments.iter().zip(openings) {
+        if !peer_id.eq(sender) || !share_opening_matches(commitment, share, nonce) {
+            return false;
+        }
+        sk += share;
+    }
+
+    let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+    cards.iter().all(|(i, card, pi)| {
+        *i < PERM_SIZE
+            && utils::kzg_check(pp, &commitment.f_com, &utils::compute_power(&ω, *i as u64), card, pi)
+            && match (sk + card).inverse() {
+                Some(t_i) => G1::generator().mul(t_i).into_affine() == commitment.card_prfs[*i],
+                None => false,
+            }
+    })
+}
+
 async fn compute_permutation_argument(
     pp: &UniversalParams<Curve>,
     evaluator: &mut Evaluator,
     card_share_handles: Vec<String>,
-    card_share_values: &Vec<F>
-) -> PermutationProof {
+    card_share_values: &[Share],
+    mapping_commitment: &[u8; 32]
+) -> Result<PermutationProof, EvaluatorError> {
+    compute_permutation_argument_in(pp, evaluator, card_share_handles, card_share_values, mapping_commitment, None, None).await
+}
+
+/// compute_permutation_argument over deck, which fails with
+/// DeckCommitmentMismatch unless the argument is about deck.f_com, if the
+/// deck was committed to after the shuffle
+async fn compute_committed_permutation_argument(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator,
+    deck: &ShuffledDeck,
+    mapping_commitment: &[u8; 32]
+) -> Result<PermutationProof, EvaluatorError> {
+    compute_permutation_argument_in(pp, evaluator, deck.handles.clone(), &deck.shares, mapping_commitment, deck.f_com, None).await
+}
+
+/// compute_permutation_argument with the challenges drawn from transcript,
+/// as the first proof of a CombinedProof; None for a proof on its own
+async fn compute_permutation_argument_in(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator,
+    card_share_handles: Vec<String>,
+    card_share_values: &[Share],
+    mapping_commitment: &[u8; 32],
+    committed_f_com: Option<G1>,
+    mut transcript: Option<&mut CombinedTranscript>
+) -> Result<PermutationProof, EvaluatorError> {
+    // the deck may have any power of two size, not only PERM_SIZE
+    let n = card_share_values.len();
+    let domain = utils::exact_domain_for::<F>(n).map_err(EvaluatorError::Domain)?;
 
     // Compute r_i and r_i^-1
-    let r_is = (0..PERM_SIZE+1)
-        .into_iter()
-        .map(|_i| evaluator.ran())
-        .collect::<Vec<String>>();
+    let r_is = evaluator.batch_ran_nonzero(n+1).await?;
 
-    let r_inv_is = evaluator.batch_inv(&r_is).await;
+    let r_inv_is = evaluator.batch_inv(&r_is).await?;
 
     // for _i in 0..65 {
     //     let h_r_i = evaluator.ran();
@@ -437,8 +942,8 @@ async fn compute_permutation_argument(
 
     // Compute b_i from r_i and r_i^-1
     let b_is = evaluator.batch_mult(
-        &vec![r_inv_is[0].clone(); PERM_SIZE], 
-        &r_is[1..PERM_SIZE+1].to_vec()
+        &vec![r_inv_is[0].clone(); n], 
+        &r_is[1..n+1]
     ).await;
 
     // for i in 0..64 {
@@ -456,38 +961,39 @@ async fn compute_permutation_argument(
     // 8: Interpret the vector fi as evaluations of a polynomial f(X).
     let f_name = String::from("perm_f");
     let f_share = 
-        utils::interpolate_poly_over_mult_subgroup(card_share_values);
-    let f_share_com = utils::commit_poly(pp, &f_share);
+        utils::interpolate_poly_over_mult_subgroup(&Share::raw(card_share_values));
+    let f_share_com = evaluator.commit_poly(pp, &f_share);
 
-    // Commit to f(X)
+    // Commit to f(X); the round is needed for the argument anyway, so
+    // checking it against the deck's earlier commitment costs nothing
     let f_com = evaluator.add_g1_elements_from_all_parties(&f_share_com, &f_name).await;
+    if committed_f_com.map_or(false, |committed| committed != f_com) {
+        return Err(EvaluatorError::DeckCommitmentMismatch);
+    }
+    evaluator.report_progress(ProgressPhase::Permutation, 1, PERMUTATION_STEPS);
 
     // 9: Define the degree-64 polynomial v(X) such that the evaluation vector is (1, ω, . . . , ω63)
     // This polynomial is the unpermuted vector of cards 
-    let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64);
-    let v_evals: Vec<F> = (0..PERM_SIZE)
-        .into_iter()
-        .map(|i| utils::compute_power(&ω, i as u64))
-        .collect();
-    let v = utils::interpolate_poly_over_mult_subgroup(&v_evals);
-    
-    // Commit to v(X)
-    let v_com = utils::commit_poly(pp, &v);
-
-    // 12: Parties locally compute γ1 = FSHash(C,V )
-    // Hash v_com and f_com to obtain randomness for batching
-    let mut v_bytes = Vec::new();
-    let mut f_bytes = Vec::new();
+    let ω = domain.group_gen();
+    let v_evals: Vec<F> = utils::power_table(&ω, n);
 
-    v_com.serialize_uncompressed(&mut v_bytes).unwrap();
-    f_com.serialize_uncompressed(&mut f_bytes).unwrap();
+    // Commit to v(X), exactly as the verifier does
+    let (_, v_com) = utils::canonical_deck_poly(pp, n).map_err(EvaluatorError::Domain)?;
 
-    let y1 = utils::fs_hash(vec![&v_bytes, &f_bytes], 1)[0];
+    // 12: Parties locally compute γ1 = FSHash(C,V )
+    // Hash v_com and f_com to obtain randomness for batching, together with
+    // this party's own card mapping: if the parties disagree on it, they
+    // derive different challenges, and stop here
+    let y1 = match transcript.as_deref_mut() {
+        Some(transcript) => transcript.permutation_challenge_1(&v_com, &f_com, mapping_commitment),
+        None => permutation_challenge_1(&v_com, &f_com, mapping_commitment),
+    };
+    evaluator.check_challenge_agreement("perm_y1", &[y1]).await?;
 
     // 13: Locally compute g(X) shares from f(X) shares
     let mut g_eval_shares = vec![];
     let mut h_g_shares = vec![];
-    for i in 0..PERM_SIZE {
+    for i in 0..n {
         // let g_share_i = card_share_values[i] + y1;
         // g_eval_shares.push(g_i);
 
@@ -499,32 +1005,32 @@ async fn compute_permutation_argument(
     }
 
     let g_share_poly = 
-        utils::interpolate_poly_over_mult_subgroup(&g_eval_shares.clone());
+        utils::interpolate_poly_over_mult_subgroup(&Share::raw(&g_eval_shares));
 
     // Commit to g(X)
-    let g_share_com = utils::commit_poly(pp, &g_share_poly);
+    let g_share_com = evaluator.commit_poly(pp, &g_share_poly);
     let g_com = evaluator.add_g1_elements_from_all_parties(&g_share_com, &String::from("perm_g")).await;
-
-    // // Assert that g(X) is correctly computed in both prover and verifier
-    // // Commit to constant polynomial const(x) = y1
-    // let const_y1 = DensePolynomial::from_coefficients_vec(vec![y1]);
-    // let const_com_y1 = utils::commit_poly(pp, &const_y1);
-
-    // let g_com_verifier = (f_com.clone() + const_com_y1).into_affine();
-    // assert_eq!(g_com, g_com_verifier);
+    evaluator.report_progress(ProgressPhase::Permutation, 2, PERMUTATION_STEPS);
+
+    // The verifier does not receive g_com but derives it as f_com + const(y1),
+    // so ours must agree with that, or y2 and the opening of g(X) won't verify
+    if evaluator.sanity_checks() {
+        let const_y1 = DensePolynomial::from_coefficients_vec(vec![y1]);
+        let g_com_verifier = (f_com + utils::commit_poly(pp, &const_y1)).into_affine();
+        assert_eq!(g_com, g_com_verifier, "g_com is not f_com + const(y1)");
+    }
 
     // 14: Compute h(X) = v(X) + y1
     let mut h_evals = vec![];
-    for i in 0..PERM_SIZE {
-        let h_i = v_evals[i] + y1;
+    for v_i in v_evals.iter() {
+        let h_i = *v_i + y1;
         h_evals.push(h_i);
     }
     let h_poly = utils::interpolate_poly_over_mult_subgroup(&h_evals);
 
     // Compute s_i' and t_i'
 
-    let h_h_inv_g_is = (0..PERM_SIZE)
-        .into_iter()
+    let h_h_inv_g_is = (0..n)
         .map(|i| {
             let h_inv_i = h_evals[i].inverse().unwrap();
             let h_g_i = &h_g_shares[i];
@@ -533,11 +1039,11 @@ async fn compute_permutation_argument(
         .collect::<Vec<String>>();
 
     let h_s_prime_is = evaluator.batch_mult(
-        &r_is[0..PERM_SIZE].to_vec(), 
+        &r_is[0..n], 
         &h_h_inv_g_is
     ).await;
     let h_t_prime_is = evaluator.batch_mult(
-        &r_inv_is[1..PERM_SIZE+1].to_vec(), 
+        &r_inv_is[1..n+1], 
         &h_s_prime_is
     ).await;
 
@@ -587,7 +1093,7 @@ async fn compute_permutation_argument(
     // 21: Parties locally compute [ti]p ← [bi]p · ∏ij=0 t′j
     // 22: end for
     let mut t_is = vec![];
-    for i in 0..PERM_SIZE {
+    for (i, h_b_i) in b_is.iter().enumerate() {
         // let tmp = product of t'_i from 0 to i
         let mut tmp = F::one();
         for j in 0..(i+1) {
@@ -595,59 +1101,75 @@ async fn compute_permutation_argument(
         }
 
         // Multiply by b_i to remove random masks
-        let t_i = evaluator.scale(&b_is[i], tmp);       
+        let t_i = evaluator.scale(h_b_i, tmp);       
 
         t_is.push((t_i.clone(), evaluator.get_wire(&t_i)));
     }
 
     // Commit to t(X)
-    let t_shares : &Vec<F> = &t_is.clone()
+    let t_shares : &Vec<Share> = &t_is.clone()
         .into_iter()
         .map(|x| x.1)
         .collect();
-    let t_share_poly = utils::interpolate_poly_over_mult_subgroup(&t_shares);
-    let t_share_com = utils::commit_poly(pp, &t_share_poly);
+    let t_share_poly = utils::interpolate_poly_over_mult_subgroup(&Share::raw(t_shares));
+    let t_share_com = evaluator.commit_poly(pp, &t_share_poly);
     let t_com = evaluator.add_g1_elements_from_all_parties(&t_share_com, &String::from("t")).await;
+    evaluator.report_progress(ProgressPhase::Permutation, 3, PERMUTATION_STEPS);
 
     let tx_by_omega_share_poly = utils::poly_domain_div_ω(&t_share_poly, &ω);
 
     // Need to show that t(X) / t(X/ω) = g(X) / h(X)
     // 24: Compute [d(X)] as [d(X)] = h(X) * [t(X)] − [g(X) * t(X/ω)]
     let h_t_share_poly = h_poly.mul(&t_share_poly);
-    let g_tx_by_omega_share_poly = evaluator.share_poly_mult(
+    let g_tx_by_omega_share_poly = evaluator.share_poly_mult_over_domain(
         g_share_poly.clone(), 
-        tx_by_omega_share_poly.clone()
+        tx_by_omega_share_poly.clone(),
+        2 * n
     ).await;
+
+    // the multiplication is only semi-honest; a biased product would let
+    // a party forge the permutation relation
+    if evaluator.sanity_checks() {
+        evaluator.check_share_poly_mult(
+            pp,
+            &g_share_poly, 
+            &tx_by_omega_share_poly, 
+            &g_tx_by_omega_share_poly, 
+            "perm_g_tx_by_omega"
+        ).await?;
+    }
     
-    let d_share_poly = h_t_share_poly.sub(&g_tx_by_omega_share_poly);
+    let d_share_poly = evaluator.share_poly_sub(&h_t_share_poly, &g_tx_by_omega_share_poly);
 
     // Compute q(X) and r(X) as quotient and remainder of d(X) / (X^64 - 1)
-    // TOASSERT - Reconstructed r(X) should be 0
-    let domain = GeneralEvaluationDomain::<F>::new(PERM_SIZE).unwrap();
     let (q_share_poly, _) = d_share_poly.divide_by_vanishing_poly(domain).unwrap();
 
     // Commit to q(X)
-    let q_share_com = utils::commit_poly(pp, &q_share_poly);
+    let q_share_com = evaluator.commit_poly(pp, &q_share_poly);
     let q_com = evaluator.add_g1_elements_from_all_parties(&q_share_com, &String::from("perm_q")).await;
+    evaluator.report_progress(ProgressPhase::Permutation, 4, PERMUTATION_STEPS);
+
+    // Reconstructed r(X) should be 0: check d_com against q_com with one
+    // pairing equation instead of opening d(X) at every ω^i. This only
+    // re-checks our own work and costs a round, so it is a sanity check
+    if evaluator.sanity_checks() {
+        let d_share_com = evaluator.commit_poly(pp, &d_share_poly);
+        let d_com = evaluator.add_g1_elements_from_all_parties(&d_share_com, &String::from("perm_d")).await;
+        if !utils::kzg_check_vanishing(pp, &d_com, &q_com, n) {
+            return Err(EvaluatorError::VanishingCheckFailed { label: String::from("perm_d") });
+        }
+    }
 
     // Compute y2 = hash(v_com, f_com, q_com, t_com, g_com)
-    let mut v_bytes = Vec::new();
-    let mut f_bytes = Vec::new();
-    let mut q_bytes = Vec::new();
-    let mut t_bytes = Vec::new();
-    let mut g_bytes = Vec::new();
-
-    v_com.serialize_uncompressed(&mut v_bytes).unwrap();
-    f_com.serialize_uncompressed(&mut f_bytes).unwrap();
-    q_com.serialize_uncompressed(&mut q_bytes).unwrap();
-    t_com.serialize_uncompressed(&mut t_bytes).unwrap();
-    g_com.serialize_uncompressed(&mut g_bytes).unwrap();
-
-    let y2 = utils::fs_hash(vec![&v_bytes, &f_bytes, &q_bytes, &t_bytes, &g_bytes], 1)[0];
+    let y2 = match transcript {
+        Some(transcript) => transcript.permutation_challenge_2(&q_com, &t_com, &g_com),
+        None => permutation_challenge_2(&v_com, &f_com, &q_com, &t_com, &g_com),
+    };
+    evaluator.check_challenge_agreement("perm_y2", &[y2]).await?;
 
     // Compute polyevals and proofs
-    let w = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64);
-    let w63 = utils::compute_power(&w, PERM_SIZE as u64 - 1);
+    let w = utils::multiplicative_subgroup_of_size(n as u64).unwrap();
+    let w63 = utils::compute_power(&w, n as u64 - 1);
 
     // Evaluate t(x) at w^63
     let h_y1 = evaluator.share_poly_eval(t_share_poly.clone(), w63);
@@ -672,7 +1194,7 @@ async fn compute_permutation_argument(
         &vec![String::from("perm_pi_1"), String::from("perm_pi_2"), String::from("perm_pi_3"), String::from("perm_pi_4"), String::from("perm_pi_5")]
     ).await;
 
-    PermutationProof {
+    let proof = PermutationProof {
         y1: evaluator.output_wire(&h_y1).await,
         y2: evaluator.output_wire(&h_y2).await,
         y3: evaluator.output_wire(&h_y3).await,
@@ -685,237 +1207,300 @@ async fn compute_permutation_argument(
         pi_5: pi_s[4].clone(),
         f_com,
         q_com,
-        t_com
-    }
-}
-
-async fn verify_permutation_argument(
-    pp: &UniversalParams<Curve>,
-    perm_proof: &PermutationProof,
-) -> bool {
-    let mut b = true;
-
-    // Compute v(X) from powers of w
-    let w = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64);
-    let w63 = utils::compute_power(&w, PERM_SIZE as u64 - 1);
-
-    let v_evals: Vec<F> = (0..PERM_SIZE)
-        .into_iter()
-        .map(|i| utils::compute_power(&w, i as u64))
-        .collect();
-
-    let v = utils::interpolate_poly_over_mult_subgroup(&v_evals);
-    let v_com = utils::commit_poly(pp, &v);
-
-    // Compute hash1 and hash2
-    let mut v_bytes = Vec::new();
-    let mut f_bytes = Vec::new();
-    let mut q_bytes = Vec::new();
-    let mut t_bytes = Vec::new();
-    let mut g_bytes = Vec::new();
-
-    v_com.serialize_uncompressed(&mut v_bytes).unwrap();
-    perm_proof.f_com.serialize_uncompressed(&mut f_bytes).unwrap();
-
-    let hash1 = utils::fs_hash(vec![&v_bytes, &f_bytes], 1)[0];
-
-    // Compute g_com from f_com
-    let const_y1 = DensePolynomial::from_coefficients_vec(vec![hash1]);
-    let const_com_y1 = utils::commit_poly(pp, &const_y1);
-
-    let g_com = (perm_proof.f_com.clone() + const_com_y1).into_affine();
+        t_com,
+        deck_size: n,
+        mapping_commitment: *mapping_commitment,
+    };
+    evaluator.report_progress(ProgressPhase::Permutation, 5, PERMUTATION_STEPS);
 
-    perm_proof.q_com.serialize_uncompressed(&mut q_bytes).unwrap();
-    perm_proof.t_com.serialize_uncompressed(&mut t_bytes).unwrap();
-    g_com.serialize_uncompressed(&mut g_bytes).unwrap();
+    Ok(proof)
+}
 
-    let hash2 = utils::fs_hash(vec![&v_bytes, &f_bytes, &q_bytes, &t_bytes, &g_bytes], 1)[0];
-    
-    // Check all evaluation proofs
-    b = b & utils::kzg_check(
-        pp,
-        &perm_proof.t_com,
-        &w63,
-        &perm_proof.y1,
-        &perm_proof.pi_1
-    );
+/// proves knowledge of the shared witnesses, in order, that satisfy every
+/// relation of statement; label keeps the revealed commitments of
+/// different proofs apart
+pub async fn prove_sigma(
+    evaluator: &mut Evaluator,
+    statement: &SigmaStatement,
+    witness_handles: &[String],
+    label: &str
+) -> Result<SigmaStatementProof, EvaluatorError> {
+    prove_sigma_with_challenge(evaluator, statement, witness_handles, label, |commitments| {
+        SigmaStatementProof::challenge(statement, commitments)
+    }).await
+}
 
-    b = b & utils::kzg_check(
-        pp,
-        &perm_proof.t_com,
-        &hash2,
-        &perm_proof.y2,
-        &perm_proof.pi_2
-    );
+/// prove_sigma in the encryption proof format, whose challenge hashes only
+/// the commitments with hash (see SigmaStatementProof::legacy_challenge_with);
+/// only for the statement of encryption_sigma_statement
+async fn prove_encryption_sigma(
+    evaluator: &mut Evaluator,
+    statement: &SigmaStatement,
+    witness_handles: &[String],
+    label: &str,
+    hash: utils::HashConfig
+) -> Result<SigmaProof, EvaluatorError> {
+    let proof = prove_sigma_with_challenge(evaluator, statement, witness_handles, label, |commitments| {
+        SigmaStatementProof::legacy_challenge_with(hash, commitments)
+    }).await?;
+    Ok(SigmaProof::try_from(&proof).expect("not the encryption sigma statement"))
+}
 
-    b = b & utils::kzg_check(
-        pp,
-        &perm_proof.t_com,
-        &(hash2 / w),
-        &perm_proof.y3,
-        &perm_proof.pi_3
-    );
+async fn prove_sigma_with_challenge(
+    evaluator: &mut Evaluator,
+    statement: &SigmaStatement,
+    witness_handles: &[String],
+    label: &str,
+    challenge: impl Fn(&[SigmaElement]) -> F
+) -> Result<SigmaStatementProof, EvaluatorError> {
+    assert!(statement.is_well_formed(), "malformed sigma statement");
+    assert_eq!(witness_handles.len(), statement.num_witnesses);
 
-    b = b & utils::kzg_check(
-        pp,
-        &g_com,
-        &(hash2),
-        &perm_proof.y4,
-        &perm_proof.pi_4
-    );
+    // Message 1
+    // a_i = prod_j base_j^{b_{w_j}} for relation i, one fresh b_w per witness
+    let b: Vec<String> = (0..statement.num_witnesses)
+        .map(|_| evaluator.ran())
+        .collect::<Result<Vec<String>, EvaluatorError>>()?;
+
+    let mut commitments = vec![];
+    for (i, relation) in statement.relations.iter().enumerate() {
+        let id = format!("{}/a{}", label, i + 1);
+        let handles: Vec<String> = relation.terms.iter().map(|(_, w)| b[*w].clone()).collect();
+        let bases = relation.terms.iter().map(|(base, _)| base);
+
+        let commitment = match relation.target {
+            SigmaElement::G1(_) => SigmaElement::G1(evaluator.exp_and_reveal_g1(
+                bases.map(|base| base.as_g1().unwrap()).collect(), handles, &id
+            ).await),
+            SigmaElement::G2(_) => SigmaElement::G2(evaluator.exp_and_reveal_g2(
+                bases.map(|base| base.as_g2().unwrap()).collect(), handles, &id
+            ).await),
+            SigmaElement::Gt(_) => SigmaElement::Gt(Box::new(evaluator.exp_and_reveal_gt(
+                bases.map(|base| base.as_gt().unwrap()).collect(), handles, &id
+            ).await)),
+        };
+        commitments.push(commitment);
+    }
 
-    b = b & utils::kzg_check(
-        pp,
-        &perm_proof.q_com,
-        &hash2,
-        &perm_proof.y5,
-        &perm_proof.pi_5
-    );
+    // FS Hash of the statement and the commitments
+    let gamma = challenge(&commitments);
 
-    // y1 = t(w^63)
-    // y2 = t(hash2)
-    // y3 = t(hash2 / w)
-    // y4 = g(hash2)
-    // y5 = q(hash2)
-    // Check 1 : y2 * (v(hash2) + hash1) - y3 * y4 = y5 * (hash2^k - 1)
-    let tmp1 = perm_proof.y2 * (v.evaluate(&hash2) + hash1);
-    let tmp2 = perm_proof.y3 * perm_proof.y4;
-    let tmp3 = perm_proof.y5 * (hash2.pow([PERM_SIZE as u64]) - F::one());
+    // Message 3
+    // response_w = gamma * witness_w + b_w
+    let h_responses: Vec<String> = witness_handles.iter().zip(b.iter())
+        .map(|(h_w, h_b)| {
+            let h_scaled = evaluator.scale(h_w, gamma);
+            evaluator.add(&h_scaled, h_b)
+        })
+        .collect();
+    let responses = evaluator.batch_output_wire(&h_responses).await;
 
-    b = b & (tmp1 - tmp2 == tmp3);
+    Ok(SigmaStatementProof { commitments, responses })
+}
 
-    if tmp1 - tmp2 != tmp3 {
-        println!("VerifyPerm - Check 1 failed");
+/// (sum_i s_i c1_i) / (sum_i s_i): equal to c1 if every c1_i is, and for
+/// the Fiat-Shamir scalars s of the proof (which hash the ciphertexts) not
+/// equal to any g^r the prover could know otherwise. None if the s_i sum
+/// to zero, which happens with negligible probability
+fn batched_c1(ciphertexts: &[(G2, Gt)], s: &[F]) -> Option<G2> {
+    let mut c1_batch = G2::zero().into_group();
+    let mut s_sum = F::zero();
+    for ((c1_i, _), s_i) in ciphertexts.iter().zip(s) {
+        c1_batch += c1_i.mul(s_i);
+        s_sum += s_i;
     }
 
-    // Check 2 : y1 = 1
-    b = b & (perm_proof.y1 == F::one());
+    s_sum.inverse().map(|s_sum_inv| c1_batch.mul(s_sum_inv).into_affine())
+}
 
-    if perm_proof.y1 != F::one() {
-        println!("VerifyPerm - Check 2 failed");
-    }
-    
-    b
+#[derive(Debug, PartialEq)]
+pub enum EncryptError {
+    /// There is not one card handle and one id for every position of the
+    /// deck, so some card would be encrypted to no one, or some id get no card.
+    LengthMismatch {
+        /// The number of card handles.
+        cards: usize,
+        /// The number of ids.
+        ids: usize,
+        /// The deck size, PERM_SIZE.
+        expected: usize,
+    },
+    /// The public key is the identity or not in the G2 subgroup, e.g. after
+    /// a faulty key generation, so the ciphertexts would hide nothing.
+    InvalidPublicKey,
+    /// encrypt_to_many was given no id to encrypt the card to.
+    NoRecipients,
+    /// deal_hand was given positions and ids that are no deal: not one id
+    /// per position, a position outside the deck or dealt twice, or a card
+    /// dealt to undealt_card_id().
+    InvalidDeal,
+    /// An id is not smaller than the field modulus, so it would collide
+    /// with a smaller id.
+    InvalidId(utils::IdError),
+    /// prove_deal could not compute the permutation argument of the deck.
+    Permutation(EvaluatorError),
+    /// The evaluator failed while encrypting, e.g. as it ran out of the
+    /// random wires dealt ahead of time.
+    Evaluator(EvaluatorError),
 }
 
-// Proves the composite statement
-pub async fn dist_sigma_proof(
+/// the eval proofs of d_i = C^z_i at ω^i for every i in positions: for
+/// each, the polynomial taking the values z_i * card_j, opened at ω^i in
+/// the Lagrange basis of lagrange
+async fn masked_card_eval_proofs(
+    lagrange: &[G1],
     evaluator: &mut Evaluator,
-    base_1: &G1,
-    base_2: &G2,
-    base_3: &Gt,
-    wit_1_handles: Vec<String>,
-    wit_2_handle: String,
-    lin_comb_ran: Vec<F>
-) -> SigmaProof {
-    // Message 1
-    // a1 = base_1^b1
-    // a2 = base_2^b2
-    // a3 = gt^b1 * base_3^b2
-
-    let b1 = evaluator.ran();
-    let b2 = evaluator.ran();
-    let a1 = evaluator.exp_and_reveal_g1(
-        vec![base_1.clone()], 
-        vec![b1.clone()], 
-        &String::from("a1")
-    ).await;
-    let a2 = evaluator.exp_and_reveal_g2(
-        vec![base_2.clone()], 
-        vec![b2.clone()], 
-        &String::from("a2")
-    ).await;
-    let a3 = evaluator.exp_and_reveal_gt(
-        vec![Gt::generator(), base_3.clone()], 
-        vec![b1.clone(), b2.clone()], 
-        &String::from("a3")
-    ).await;
-
-    // FS Hash of a1,a2,a3
-    let (mut a1_bytes, mut a2_bytes, mut a3_bytes): (Vec<u8>, Vec<u8>, Vec<u8>) 
-        = (Vec::new(),Vec::new(),Vec::new());
+    card_handles: &[String],
+    z_is: &[String],
+    positions: &[usize]
+) -> Vec<G1> {
+    // Compute eval vector for z_i * card_shares
+    // each elem of vector - [batchmult(cards, z_i*64)]
+    // make it one vector - batchmult([cards * 64], [z1*64, z2*64, ])
 
-    a1.serialize_uncompressed(&mut a1_bytes).unwrap();
-    a2.serialize_uncompressed(&mut a2_bytes).unwrap();
-    a3.serialize_uncompressed(&mut a3_bytes).unwrap();
-    
-    let gamma = utils::fs_hash(vec![&a1_bytes, &a2_bytes, &a3_bytes], 1);
+    let card_handles_64 = positions
+        .iter()
+        .flat_map(|_| card_handles.to_vec())
+        .collect::<Vec<String>>();
 
-    // Message 3
-    let mut h_y = evaluator.scale(&wit_2_handle.clone(), gamma[0]);
-    h_y = evaluator.add(&h_y,&b2);
-    let y = evaluator.output_wire(&h_y).await;
+    let z_is_64 = positions
+        .iter()
+        .flat_map(|&i| vec![z_is[i].clone(); PERM_SIZE])
+        .collect::<Vec<String>>();
 
-    // x = gamma * sum_i (lin_comb_ran[i] * wit_1_handles[i]) + b1
-    let mut h_x = evaluator.scale(&wit_1_handles[0], lin_comb_ran[0]);
+    // let t_d_eval = Instant::now();
+    let d_eval_handles = evaluator.batch_mult(
+        &card_handles_64, 
+        &z_is_64
+    ).await;
+    // println!("Time taken for D_i evaluation : {:?}", t_d_eval.elapsed());
 
-    for i in 1..PERM_SIZE {
-        let tmp = evaluator.scale(&wit_1_handles[i], lin_comb_ran[i]);
-        h_x = evaluator.add(&tmp, &h_x);
+    let mut d_evals = vec![];
+    for d_eval_handle in d_eval_handles.iter() {
+        d_evals.push(evaluator.get_wire(d_eval_handle));
     }
-    h_x = evaluator.scale(&h_x, gamma[0]);
-    h_x = evaluator.add(&h_x, &b1);
 
-    let x = evaluator.output_wire(&h_x).await;
-    
-    SigmaProof{a1,a2,a3,x,y}
-}
+    // let t_pi = Instant::now();
+    let d_evals = d_evals.chunks(PERM_SIZE)
+        .map(Share::raw)
+        .collect::<Vec<Vec<F>>>();
 
-// Batch the bases before calling this
-// Verifies custom sigma proof generated by dist_sigma_proof
-pub fn local_verify_sigma_proof(
-    c: &G1, d_batch: &G1, 
-    g: &G2, c_1: &G2,
-    e_batch: &Gt, c2_batch: &Gt,
-    sigma: &SigmaProof
-) -> bool {
-    // Hash a1,a2,a3 to get gamma
-    let (mut a1_bytes, mut a2_bytes, mut a3_bytes): (Vec<u8>, Vec<u8>, Vec<u8>) 
-        = (Vec::new(),Vec::new(),Vec::new());
+    let pi_is = evaluator.batch_eval_proof_at_roots(
+        lagrange, 
+        &d_evals, 
+        positions, 
+        &positions.iter().map(|i| format!("{}/{}", "enc_prove_pi_", i)).collect::<Vec<String>>()
+    ).await;
+    // println!("Time taken for pi_i computation : {:?}", t_pi.elapsed());
+    pi_is
+}
 
-    sigma.a1.serialize_uncompressed(&mut a1_bytes).unwrap();
-    sigma.a2.serialize_uncompressed(&mut a2_bytes).unwrap();
-    sigma.a3.serialize_uncompressed(&mut a3_bytes).unwrap();
+/// when encrypt_and_prove computes the eval proofs of the masked cards:
+/// all PERM_SIZE of them up front, or each only once its card is revealed
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EvalProofMode {
+    #[default]
+    Eager,
+    /// eval_proofs of the EncryptProof stays empty; the proof of a card
+    /// comes from LazyEvalProofs when it is dealt or revealed
+    Lazy,
+}
 
-    let gamma = utils::fs_hash(vec![&a1_bytes, &a2_bytes, &a3_bytes], 1);
+/// what it takes to compute the eval proof of any one masked card after
+/// encrypt_and_prove: the handles of the cards and of their masks z_i.
+/// A proof costs PERM_SIZE mults and a reveal, instead of PERM_SIZE^2
+/// mults for all of them, and is computed once; the Lagrange bases it
+/// is opened in are cached by utils::lagrange_commitments
+pub struct LazyEvalProofs {
+    card_handles: Vec<String>,
+    z_is: Vec<String>,
+    lagrange: Arc<Vec<G1>>,
+    proofs: HashMap<usize, G1>,
+}
 
-    let mut b = true;
+impl LazyEvalProofs {
+    /// the eval proof of masked_commitments[i] at ω^i, the one eager mode
+    /// puts into eval_proofs[i]; all parties must ask for the same cards
+    pub async fn eval_proof(
+        &mut self,
+        evaluator: &mut Evaluator,
+        i: usize
+    ) -> G1 {
+        if let Some(pi) = self.proofs.get(&i) {
+            return *pi;
+        }
 
-    // Verify statement 1 : C^x = D_batch^gamma * a1
-    let lhs = c.mul(sigma.x);
-    let rhs = (d_batch.mul(gamma[0])).add(sigma.a1);
-    if ! lhs.eq(&rhs) {
-        println!("SigmaProof - Check 1 fail");
-        b = false;
+        let pi = masked_card_eval_proofs(&self.lagrange, evaluator, &self.card_handles, &self.z_is, &[i])
+            .await
+            .remove(0);
+        self.proofs.insert(i, pi);
+        pi
     }
+}
 
-    // Verify statement 2 : g^y = c_1^gamma * a2
-    let lhs = g.mul(sigma.y);
-    let rhs = c_1.mul(gamma[0]).add(sigma.a2);
-    if ! lhs.eq(&rhs) {
-        println!("SigmaProof - Check 2 fail");
-        b = false;
-    }
+async fn encrypt_and_prove(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator,
+    card_handles: Vec<String>,
+    card_commitment: G1,
+    pk: G2,
+    ids: Vec<BigUint>
+) -> Result<EncryptProof, EncryptError> {
+    encrypt_and_prove_with_mode(pp, evaluator, card_handles, card_commitment, pk, ids, EvalProofMode::Eager)
+        .await
+        .map(|(proof, _)| proof)
+}
 
-    // Verify statement 3 : g^x * e_batch^y = c2_batch^gamma * a3
-    let lhs = e_batch.mul(sigma.y).add(Gt::generator().mul(sigma.x));
-    let rhs = c2_batch.mul(gamma[0]).add(sigma.a3);
-    if ! lhs.eq(&rhs) {
-        println!("SigmaProof - Check 3 fail");
-        b = false;
-    }  
+/// encrypt_and_prove, with the eval proofs computed as mode says; the
+/// LazyEvalProofs computes the proof of any card later on, in either mode
+async fn encrypt_and_prove_with_mode(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator,
+    card_handles: Vec<String>,
+    card_commitment: G1,
+    pk: G2,
+    ids: Vec<BigUint>,
+    mode: EvalProofMode
+) -> Result<(EncryptProof, LazyEvalProofs), EncryptError> {
+    encrypt_and_prove_with_mode_in(pp, evaluator, card_handles, card_commitment, pk, ids, mode, None).await
+}
 
-    b
+/// encrypt_and_prove with the batching scalars drawn from transcript, as
+/// the second proof of a CombinedProof, after compute_permutation_argument_in
+/// over the same transcript made card_commitment
+async fn encrypt_and_prove_in(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator,
+    card_handles: Vec<String>,
+    card_commitment: G1,
+    pk: G2,
+    ids: Vec<BigUint>,
+    transcript: &mut CombinedTranscript
+) -> Result<EncryptProof, EncryptError> {
+    encrypt_and_prove_with_mode_in(pp, evaluator, card_handles, card_commitment, pk, ids, EvalProofMode::Eager, Some(transcript))
+        .await
+        .map(|(proof, _)| proof)
 }
 
-async fn encrypt_and_prove(
+#[allow(clippy::too_many_arguments)]
+async fn encrypt_and_prove_with_mode_in(
     pp: &UniversalParams<Curve>,
     evaluator: &mut Evaluator,
     card_handles: Vec<String>,
     card_commitment: G1,
     pk: G2,
-    ids: Vec<BigUint>
-) -> EncryptProof {
+    ids: Vec<BigUint>,
+    mode: EvalProofMode,
+    combined_transcript: Option<&mut CombinedTranscript>
+) -> Result<(EncryptProof, LazyEvalProofs), EncryptError> {
+    // everything below indexes the cards and ids by position, 0..PERM_SIZE
+    if card_handles.len() != PERM_SIZE || ids.len() != PERM_SIZE {
+        return Err(EncryptError::LengthMismatch { cards: card_handles.len(), ids: ids.len(), expected: PERM_SIZE });
+    }
+    if !is_valid_public_key(&pk) {
+        return Err(EncryptError::InvalidPublicKey);
+    }
+
     // Get all cards from card handles
     let mut cards = vec![];
     for h in card_handles.clone() {
@@ -923,14 +1508,14 @@ async fn encrypt_and_prove(
     }
 
     // Sample common randomness for encryption
-    let r = evaluator.ran();
-    let w = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64);
+    let r = evaluator.ran().map_err(EncryptError::Evaluator)?;
 
     // Sample masks to be encrypted
     let z_is = (0..PERM_SIZE)
         .into_iter()
         .map(|_i| evaluator.ran())
-        .collect::<Vec<String>>();
+        .collect::<Result<Vec<String>, EvaluatorError>>()
+        .map_err(EncryptError::Evaluator)?;
 
     let t_ibe = Instant::now();
     // Encrypt the masks to ids
@@ -939,8 +1524,9 @@ async fn encrypt_and_prove(
         &vec![r.clone(); PERM_SIZE], 
         &pk, 
         ids.as_slice()
-    ).await;
+    ).await.map_err(EncryptError::InvalidId)?;
     println!("IBE_enc: {:?}", t_ibe.elapsed());
+    evaluator.report_progress(ProgressPhase::Encryption, 1, ENCRYPTION_STEPS);
 
     let t_enc_prove = Instant::now();
     // Compute d_i = C^z_i
@@ -950,6 +1536,7 @@ async fn encrypt_and_prove(
         (0..PERM_SIZE).into_iter().map(|i| format!("{}/{}", "enc_prove_D_", i)).collect()
     ).await;
     // println!("Time taken for D_i computation : {:?}", t_d.elapsed());
+    evaluator.report_progress(ProgressPhase::Encryption, 2, ENCRYPTION_STEPS);
 
     // Compute v_i = z_i * card_i
     let v_is = evaluator.batch_mult(
@@ -958,49 +1545,22 @@ async fn encrypt_and_prove(
     ).await;
 
     let v_is_reconstructed = evaluator.batch_output_wire(&v_is).await;
-
-    // Compute eval vector for z_i * card_shares
-    // each elem of vector - [batchmult(cards, z_i*64)]
-    // make it one vector - batchmult([cards * 64], [z1*64, z2*64, ])
-
-    let card_handles_64 = (0..PERM_SIZE)
-        .into_iter()
-        .map(|_| card_handles.clone())
-        .flatten()
-        .collect::<Vec<String>>();
-
-    let z_is_64 = (0..PERM_SIZE)
-        .into_iter()
-        .map(|i| vec![z_is[i].clone(); PERM_SIZE])
-        .flatten()
-        .collect::<Vec<String>>();
-
-    // let t_d_eval = Instant::now();
-    let d_eval_handles = evaluator.batch_mult(
-        &card_handles_64, 
-        &z_is_64
-    ).await;
-    // println!("Time taken for D_i evaluation : {:?}", t_d_eval.elapsed());
-
-    let mut d_evals = vec![];
-    for i in 0..d_eval_handles.len() {
-        d_evals.push(evaluator.get_wire(&d_eval_handles[i]));
-    }
-
-    // let t_pi = Instant::now();
-    let d_evals = (0..PERM_SIZE)
-        .into_iter()
-        .map(|i| utils::interpolate_poly_over_mult_subgroup(&d_evals[i*PERM_SIZE..(i+1)*PERM_SIZE].to_vec()))
-        .collect::<Vec<DensePolynomial<F>>>();
-
-    // Compute eval_proof for d_is
-    let pi_is = evaluator.batch_eval_proof_with_share_poly(
-        pp, 
-        &d_evals, 
-        &(0..PERM_SIZE).into_iter().map(|i| utils::compute_power(&w, i as u64)).collect(), 
-        &(0..PERM_SIZE).into_iter().map(|i| format!("{}/{}", "enc_prove_pi_", i)).collect()
-    ).await;
-    // println!("Time taken for pi_i computation : {:?}", t_pi.elapsed());
+    evaluator.report_progress(ProgressPhase::Encryption, 3, ENCRYPTION_STEPS);
+
+    // Compute eval_proof for d_is, d_i opened at ω^i, unless they are
+    // left for LazyEvalProofs to compute once their card is revealed
+    let lagrange = utils::lagrange_commitments(pp, PERM_SIZE);
+    let pi_is = match mode {
+        EvalProofMode::Eager => masked_card_eval_proofs(
+            &lagrange,
+            evaluator,
+            &card_handles,
+            &z_is,
+            &(0..PERM_SIZE).collect::<Vec<usize>>()
+        ).await,
+        EvalProofMode::Lazy => vec![],
+    };
+    evaluator.report_progress(ProgressPhase::Encryption, 4, ENCRYPTION_STEPS);
 
 
     // for i in 0..64 {
@@ -1080,147 +1640,712 @@ async fn encrypt_and_prove(
 
     // Hash to obtain randomness for batching
 
-    let tmp_proof = EncryptProof{
-        pk: pk.clone(),
-        ids: ids.clone(),
-        card_commitment: card_commitment.clone(),
-        masked_commitments: d_is.clone(),
-        masked_evals: v_is_reconstructed.clone(),
-        eval_proofs: pi_is.clone(),
-        ciphertexts: c1s.clone().into_iter().zip(c2s.clone().into_iter()).collect(),
-        sigma_proof: None,
+    let ciphertexts: Vec<(G2, Gt)> = c1s.into_iter().zip(c2s).collect();
+    let transcript = EncryptTranscript {
+        pk: &pk,
+        ids: &ids,
+        card_commitment: &card_commitment,
+        masked_commitments: &d_is,
+        masked_evals: &v_is_reconstructed,
+        eval_proofs: &pi_is,
+        ciphertexts: &ciphertexts,
     };
 
-    let s1 = utils::fs_hash(vec![&tmp_proof.to_bytes(), b"0"], PERM_SIZE/2);
-    let s2 = utils::fs_hash(vec![&tmp_proof.to_bytes(), b"1"], PERM_SIZE/2);
-
-    let s = s1.into_iter().chain(s2.into_iter()).collect::<Vec<F>>();
+    let hash = combined_transcript.as_ref().map_or(utils::HashConfig::default(), |t| t.hash());
+    let s = match combined_transcript {
+        Some(combined_transcript) => combined_transcript.encryption_challenges(&transcript),
+        None => transcript.challenges(),
+    };
+    evaluator.check_challenge_agreement("enc_s", &s).await
+        .expect("the parties derived different challenges");
 
-    // let t_pairing = Instant::now();
-    // Compute batched pairing base for sigma proof
-    let mut batch_h = G1::zero();
-    for i in 0..PERM_SIZE {
-        // TODO: fix this. Need proper hash to curve
-        let x_f = F::from(ids[i].clone());
-        let hash_id = G1::generator().mul(x_f);
-        batch_h = batch_h.add(hash_id.mul(s[i])).into_affine();
-    }
-    let e_batch = <Curve as Pairing>::pairing(batch_h, pk);
-    // println!("Time taken for pairing computation : {:?}", t_pairing.elapsed());
+    // The sigma proof is about x = sum_i s_i z_i and y = r
+    let statement = batched_encryption_statement(&transcript, &s).unwrap();
 
-    let mut wit_1 = vec![];
-    
-    for i in 0..PERM_SIZE {
-        wit_1.push(z_is[i].clone());
+    let mut h_x = evaluator.scale(&z_is[0], s[0]);
+    for i in 1..PERM_SIZE {
+        let tmp = evaluator.scale(&z_is[i], s[i]);
+        h_x = evaluator.add(&tmp, &h_x);
     }
 
     // let t_sigma = Instant::now();
-    let proof = dist_sigma_proof(
-            evaluator,
-            &card_commitment,
-            &G2::generator(),
-            &e_batch,
-            wit_1,
-            r,
-            s.clone()
-        ).await;
+    let proof = prove_encryption_sigma(evaluator, &statement, &[h_x, r], "enc_sigma", hash).await
+        .map_err(EncryptError::Evaluator)?;
+    evaluator.report_progress(ProgressPhase::Encryption, 5, ENCRYPTION_STEPS);
     println!("encrypt_prove: {:?}", t_enc_prove.elapsed());
 
-    EncryptProof {
+    let proof = EncryptProof {
         pk: pk.clone(),
         ids: ids,
         card_commitment: card_commitment,
         masked_commitments: d_is,
         masked_evals: v_is_reconstructed,
         eval_proofs: pi_is,
-        ciphertexts: c1s.into_iter().zip(c2s.into_iter()).collect(),
+        ciphertexts,
         sigma_proof: Some(proof),
-    }
+    };
+    let lazy = LazyEvalProofs { card_handles, z_is, lagrange, proofs: HashMap::new() };
+    Ok((proof, lazy))
 }
 
-async fn local_verify_encryption_proof(
-    pp: &UniversalParams<Curve>,
-    proof: &EncryptProof,
-) -> bool {
-    // Check that all ciphertexts share the same randomness
-    let c1 = proof.ciphertexts[0].0.clone();
-    for i in 1..PERM_SIZE {
-        if proof.ciphertexts[i].0 != c1 {
-            return false;
+/// id that encrypts the cards deal_hand does not deal; no player is
+/// ever given its decryption key
+pub fn undealt_card_id() -> BigUint {
+    BigUint::from(0u8)
+}
+
+/// ids with which deal_hand encrypts the deck: recipient_ids[i] for the
+/// card at card_positions[i], undealt_card_id() for every other card.
+/// None unless there is one id per position, every position is in the
+/// deck and dealt once, and no card is dealt to undealt_card_id()
+fn deal_ids(card_positions: &[usize], recipient_ids: &[BigUint]) -> Option<Vec<BigUint>> {
+    if card_positions.len() != recipient_ids.len() {
+        return None;
+    }
+
+    let mut dealt = vec![false; PERM_SIZE];
+    let mut ids = vec![undealt_card_id(); PERM_SIZE];
+    for (&position, id) in card_positions.iter().zip(recipient_ids) {
+        if position >= PERM_SIZE || dealt[position] || *id == undealt_card_id() {
+            return None;
         }
+        dealt[position] = true;
+        ids[position] = id.clone();
     }
+    Some(ids)
+}
 
-    // // Check all the evaluation proofs
-    // for i in 0..PERM_SIZE {
-    //     if utils::kzg_check(
-    //         pp,
-    //         &proof.masked_commitments[i], 
-    //         &utils::compute_power(&utils::multiplicative_subgroup_of_size(PERM_SIZE as u64), i as u64), 
-    //         &proof.masked_evals[i], 
-    //         &proof.eval_proofs[i]
-    //     ) == false {
-    //         return false;
-    //     }
-    // }
+/// deal_hand together with the permutation argument of the deck, as a
+/// CombinedProof whose two proofs draw their challenges from one
+/// CombinedTranscript
+#[allow(clippy::too_many_arguments)]
+pub async fn prove_deal(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator,
+    card_handles: Vec<String>,
+    card_shares: &[Share],
+    mapping_commitment: &[u8; 32],
+    pk: G2,
+    card_positions: &[usize],
+    recipient_ids: &[BigUint]
+) -> Result<CombinedProof, EncryptError> {
+    prove_deal_in(
+        pp, evaluator, card_handles, card_shares, mapping_commitment, pk, card_positions, recipient_ids, CombinedTranscript::new()
+    ).await
+}
+
+/// prove_deal with the challenges drawn from transcript, e.g. one made
+/// with_hash and with_field_encoding for a verifier that recomputes them
+/// with another hash and byte order
+#[allow(clippy::too_many_arguments)]
+pub async fn prove_deal_in(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator,
+    card_handles: Vec<String>,
+    card_shares: &[Share],
+    mapping_commitment: &[u8; 32],
+    pk: G2,
+    card_positions: &[usize],
+    recipient_ids: &[BigUint],
+    mut transcript: CombinedTranscript
+) -> Result<CombinedProof, EncryptError> {
+    let ids = deal_ids(card_positions, recipient_ids).ok_or(EncryptError::InvalidDeal)?;
 
-    // UNSAFE - Check only one evaluation proof
-    if utils::kzg_check(
+    let perm = compute_permutation_argument_in(
         pp,
-        &proof.masked_commitments[15], 
-        &utils::compute_power(&utils::multiplicative_subgroup_of_size(PERM_SIZE as u64), 15 as u64), 
-        &proof.masked_evals[15], 
-        &proof.eval_proofs[15]
-    ) == false {
-        return false;
+        evaluator,
+        card_handles.clone(),
+        card_shares,
+        mapping_commitment,
+        None,
+        Some(&mut transcript)
+    ).await.map_err(EncryptError::Permutation)?;
+    let enc = encrypt_and_prove_in(pp, evaluator, card_handles, perm.f_com, pk, ids, &mut transcript).await?;
+
+    Ok(CombinedProof { perm, enc })
+}
+
+/// encrypts the card at card_positions[i] to player recipient_ids[i],
+/// all under the one group key pk and with a single proof; as a player
+/// only gets the decryption key of their own id, each player can open
+/// exactly the cards dealt to them
+pub async fn deal_hand(
+    pp: &UniversalParams<Curve>,
+    evaluator: &mut Evaluator,
+    card_handles: Vec<String>,
+    card_commitment: G1,
+    pk: G2,
+    card_positions: &[usize],
+    recipient_ids: &[BigUint]
+) -> Result<EncryptProof, EncryptError> {
+    let ids = deal_ids(card_positions, recipient_ids).ok_or(EncryptError::InvalidDeal)?;
+    encrypt_and_prove(pp, evaluator, card_handles, card_commitment, pk, ids).await
+}
+
+/// one card encrypted to several ids, e.g. a card a group of players all
+/// get to see, with a proof that every ciphertext holds the same card
+#[derive(Clone, Debug)]
+pub struct MultiRecipientEncryption {
+    pub pk: G2,
+    pub ids: Vec<BigUint>,
+    /// (c1, c2_k) for ids[k], with c1 = g^r the same for all of them and
+    /// c2_k = gt^card · e(H(id_k), pk)^r
+    pub ciphertexts: Vec<(G2, Gt)>,
+    pub sigma_proof: SigmaStatementProof,
+}
+
+/// encrypts the card on card_handle to every one of recipient_ids under
+/// pk, with the same randomness r for all of them; the sigma proof shows
+/// that c2_k / c2_0 = (e_k / e_0)^r for the r of c1, i.e. that the card
+/// is the same in every ciphertext
+pub async fn encrypt_to_many(
+    evaluator: &mut Evaluator,
+    card_handle: &str,
+    recipient_ids: &[BigUint],
+    pk: G2
+) -> Result<MultiRecipientEncryption, EncryptError> {
+    if recipient_ids.is_empty() {
+        return Err(EncryptError::NoRecipients);
+    }
+    if !is_valid_public_key(&pk) {
+        return Err(EncryptError::InvalidPublicKey);
     }
 
-    // Check the sigma proof
-    // Hash to obtain randomness for batching
-    let s1 = utils::fs_hash(vec![&proof.to_bytes(), b"0"], PERM_SIZE/2);
-    let s2 = utils::fs_hash(vec![&proof.to_bytes(), b"1"], PERM_SIZE/2);
+    let e_ks = recipient_ids
+        .iter()
+        .map(|id| {
+            let x_f = utils::id_to_field_checked(id).map_err(EncryptError::InvalidId)?;
+            Ok(<Curve as Pairing>::pairing(G1::generator().mul(x_f), pk))
+        })
+        .collect::<Result<Vec<Gt>, EncryptError>>()?;
+
+    let r = evaluator.ran().map_err(EncryptError::Evaluator)?;
+    let c1 = evaluator.exp_and_reveal_g2(vec![G2::generator()], vec![r.clone()], &String::from("multi_c1")).await;
+
+    let c2s = evaluator.batch_exp_and_reveal_gt(
+        e_ks.iter().map(|e_k| vec![Gt::generator(), *e_k]).collect(),
+        vec![vec![card_handle.to_string(), r.clone()]; recipient_ids.len()],
+        (0..recipient_ids.len()).map(|k| format!("multi_c2/{}", k)).collect()
+    ).await;
 
-    let s = s1.into_iter().chain(s2.into_iter()).collect::<Vec<F>>();
+    let ciphertexts: Vec<(G2, Gt)> = c2s.into_iter().map(|c2| (c1, c2)).collect();
+    let statement = multi_recipient_statement(&pk, recipient_ids, &ciphertexts).unwrap();
+    let sigma_proof = prove_sigma(evaluator, &statement, &[r], "multi_sigma").await
+        .map_err(EncryptError::Evaluator)?;
 
+    Ok(MultiRecipientEncryption { pk, ids: recipient_ids.to_vec(), ciphertexts, sigma_proof })
+}
+
+/// the statement of the sigma proof of encrypt_to_many, over the witness r:
+///   g^r = c1 and E^r = C, for E = sum_k s_k (e_k - e_0) and
+///   C = sum_k s_k (c2_k - c2_0), k >= 1
+/// with scalars s hashing pk, the ids and the ciphertexts. None if there
+/// is no ciphertext, an id is not a valid field element, or the
+/// ciphertexts do not share their c1
+fn multi_recipient_statement(pk: &G2, ids: &[BigUint], ciphertexts: &[(G2, Gt)]) -> Option<SigmaStatement> {
+    use ark_serialize::CanonicalSerialize;
+
+    if ciphertexts.is_empty() || ids.len() != ciphertexts.len() {
+        return None;
+    }
+    let c1 = ciphertexts[0].0;
+    if ciphertexts.iter().any(|(c1_k, _)| *c1_k != c1) {
+        return None;
+    }
+
+    let mut bytes = Vec::new();
+    pk.serialize_compressed(&mut bytes).unwrap();
+    for (id, (c1_k, c2_k)) in ids.iter().zip(ciphertexts) {
+        bytes.extend_from_slice(&id.to_bytes_be());
+        c1_k.serialize_compressed(&mut bytes).unwrap();
+        c2_k.serialize_compressed(&mut bytes).unwrap();
+    }
+    let s = utils::fs_hash(vec![&bytes], ids.len());
+
+    let mut e_ks = vec![];
+    for id in ids {
+        let x_f = utils::id_to_field_checked(id).ok()?;
+        e_ks.push(<Curve as Pairing>::pairing(G1::generator().mul(x_f), pk));
+    }
+
+    let mut e_batch = Gt::zero();
+    let mut c2_batch = Gt::zero();
+    for k in 1..ids.len() {
+        e_batch += (e_ks[k] - e_ks[0]).mul(s[k]);
+        c2_batch += (ciphertexts[k].1 - ciphertexts[0].1).mul(s[k]);
+    }
+
+    Some(SigmaStatement::new(1)
+        .relation(vec![(SigmaElement::G2(G2::generator()), 0)], SigmaElement::G2(c1))
+        .relation(vec![(SigmaElement::Gt(Box::new(e_batch)), 0)], SigmaElement::Gt(Box::new(c2_batch))))
+}
+
+/// checks that the ciphertexts of encryption all hold the same card
+pub fn verify_multi_recipient_encryption(encryption: &MultiRecipientEncryption) -> bool {
+    if !is_valid_public_key(&encryption.pk) {
+        return false;
+    }
+
+    match multi_recipient_statement(&encryption.pk, &encryption.ids, &encryption.ciphertexts) {
+        Some(statement) => verify_sigma(&statement, &encryption.sigma_proof),
+        None => false,
+    }
+}
+
+/// the encryption sigma statement of proof, batched with the scalars s;
+/// None if an id is not a valid field element or the batching fails
+fn batched_encryption_statement(proof: &EncryptTranscript, s: &[F]) -> Option<SigmaStatement> {
     // Compute e_batch
     let mut accumulator = G1::zero();
 
     for i in 0..PERM_SIZE {
         //TODO: do a real hash to curve
-        let x_f = F::from(proof.ids[i].clone());
+        let x_f = utils::id_to_field_checked(&proof.ids[i]).ok()?;
         let hash_id = G1::generator().mul(x_f);
 
         accumulator = accumulator.add(hash_id.mul(s[i])).into_affine();
     }
     
-    let e_batch = <Curve as Pairing>::pairing(accumulator, &proof.pk);
+    let e_batch = <Curve as Pairing>::pairing(accumulator, proof.pk);
 
     // Compute d_batch
-    let mut d_batch = G1::zero();
-
-    for i in 0..PERM_SIZE {
-        d_batch = d_batch.add(proof.masked_commitments[i].mul(s[i])).into_affine();
-    }
+    let d_batch = proof.batched_masked_commitment(s);
 
     // Compute c2_batch
-    let mut c2_batch = Gt::zero();
+    let c2_is: Vec<Gt> = proof.ciphertexts.iter().map(|(_, c2_i)| *c2_i).collect();
+    let c2_batch = batch_gt(&c2_is, s);
 
-    for i in 0..PERM_SIZE {
-        c2_batch = c2_batch.add(proof.ciphertexts[i].1.mul(s[i]));
-    }    
+    // All ciphertexts must share the randomness r the sigma proof is about:
+    // its statement 2 is checked against the batched c1 instead of any one
+    // of the c1_i, and that is g^r only if every c1_i is, but for a
+    // negligible chance over s
+    let c1 = batched_c1(proof.ciphertexts, s)?;
 
-    // Verify sigma proof
-    if local_verify_sigma_proof(
-        &proof.card_commitment, 
+    Some(encryption_sigma_statement(
+        proof.card_commitment, 
         &d_batch, 
         &G2::generator(), 
         &c1, 
         &e_batch, 
-        &c2_batch, 
-        proof.sigma_proof.as_ref().unwrap()) == false {
+        &c2_batch
+    ))
+}
+
+/// checks an encryption proof made in the given EvalProofMode; the mode
+/// is the verifier's choice, as a Lazy proof leaves every card to be
+/// checked with its eval proof once it is revealed
+async fn local_verify_encryption_proof(
+    pp: &UniversalParams<Curve>,
+    proof: &EncryptProof,
+    mode: EvalProofMode,
+) -> bool {
+    local_verify_encryption_proof_in(pp, proof, mode, None).await
+}
+
+/// local_verify_encryption_proof with the batching scalars drawn from
+/// transcript, for the encryption proof of a CombinedProof
+async fn local_verify_encryption_proof_in(
+    pp: &UniversalParams<Curve>,
+    proof: &EncryptProof,
+    mode: EvalProofMode,
+    transcript: Option<&mut CombinedTranscript>,
+) -> bool {
+    // no card is hidden from anyone under a degenerate key
+    if !is_valid_public_key(&proof.pk) {
+        return false;
+    }
+
+    if proof.ids.len() != PERM_SIZE
+        || proof.masked_commitments.len() != PERM_SIZE
+        || proof.masked_evals.len() != PERM_SIZE
+        || proof.ciphertexts.len() != PERM_SIZE {
         return false;
     }
 
-    true
+    // Check that every eval_proofs[i] opens masked_commitments[i] to
+    // masked_evals[i] at ω^i; without this the masked evaluations are
+    // not bound to the masked commitments
+    if mode == EvalProofMode::Eager {
+        if proof.eval_proofs.len() != PERM_SIZE {
+            return false;
+        }
+
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        if !utils::kzg_check_batch(
+            pp,
+            &proof.masked_commitments,
+            &utils::power_table(&ω, PERM_SIZE),
+            &proof.masked_evals,
+            &proof.eval_proofs
+        ) {
+            return false;
+        }
+    }
+
+    // Check the sigma proof
+    // Hash to obtain randomness for batching
+    let hash = transcript.as_ref().map_or(utils::HashConfig::default(), |t| t.hash());
+    let s = match transcript {
+        Some(transcript) => transcript.encryption_challenges(&proof.transcript()),
+        None => proof.recompute_challenges(),
+    };
+
+    let statement = match batched_encryption_statement(&proof.transcript(), &s) {
+        Some(statement) => statement,
+        None => return false,
+    };
+
+    // Verify sigma proof
+    match &proof.sigma_proof {
+        Some(sigma_proof) => verify_encryption_sigma_with(hash, &statement, sigma_proof),
+        None => false,
+    }
+}
+
+/// AggregateProof verifies the permutation proofs of many shuffles, e.g.
+/// all hands of a tournament, together: the KZG openings of all of them are
+/// checked with one kzg_check_batch, i.e. two pairings, instead of two
+/// pairings per opening. It accepts exactly if every proof would pass
+/// verify_permutation_argument, except with negligible probability
+#[derive(Clone, Debug)]
+pub struct AggregateProof {
+    pub proofs: Vec<PermutationProof>,
+    /// most proofs whose openings go into one combined check
+    batch_size: usize,
+}
+
+impl AggregateProof {
+    pub fn new(proofs: Vec<PermutationProof>) -> Self {
+        AggregateProof { proofs, batch_size: usize::MAX }
+    }
+
+    /// combines the openings of at most batch_size proofs per check rather
+    /// than of all of them, bounding what is held in memory at once; each
+    /// check costs two pairings
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "a batch holds at least one proof");
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn push(&mut self, proof: PermutationProof) {
+        self.proofs.push(proof);
+    }
+
+    pub fn verify(&self, pp: &UniversalParams<Curve>) -> bool {
+        for batch in self.proofs.chunks(self.batch_size) {
+            let mut comms = Vec::new();
+            let mut xs = Vec::new();
+            let mut evals = Vec::new();
+            let mut pis = Vec::new();
+
+            for proof in batch {
+                if !verify_permutation_relation(pp, proof) {
+                    return false;
+                }
+                let openings = match proof.eval_proofs(pp) {
+                    Some(openings) => openings,
+                    None => return false,
+                };
+                for (comm, x, eval, pi) in openings {
+                    comms.push(comm);
+                    xs.push(x);
+                    evals.push(eval);
+                    pis.push(pi);
+                }
+            }
+
+            if !utils::kzg_check_batch(pp, &comms, &xs, &evals, &pis) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// number of verification results a Verifier remembers
+pub const VERIFICATION_CACHE_SIZE: usize = 64;
+
+/// hits and misses of a Verifier's verification cache
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VerificationCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// which check a verification cache entry remembers
+#[derive(Clone, Copy, PartialEq)]
+enum ProofKind {
+    Permutation,
+    Encryption,
+    LazyEncryption,
+}
+
+/// (kind, SRS fingerprint, proof fingerprint); as the SRS is part of the
+/// key, results obtained under one SRS are never reused under another
+type VerificationCacheKey = (ProofKind, [u8; 16], [u8; 16]);
+
+/// the VERIFICATION_CACHE_SIZE most recently used verification results
+#[derive(Default)]
+struct VerificationCache {
+    entries: VecDeque<(VerificationCacheKey, bool)>,
+    stats: VerificationCacheStats,
+}
+
+impl VerificationCache {
+    fn get(&mut self, key: &VerificationCacheKey) -> Option<bool> {
+        match self.entries.iter().position(|(k, _)| k == key) {
+            Some(i) => {
+                // move it to the back, as the most recently used
+                let entry = self.entries.remove(i).unwrap();
+                self.entries.push_back(entry);
+                self.stats.hits += 1;
+                Some(entry.1)
+            },
+            None => {
+                self.stats.misses += 1;
+                None
+            },
+        }
+    }
+
+    fn insert(&mut self, key: VerificationCacheKey, verified: bool) {
+        if self.entries.len() == VERIFICATION_CACHE_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, verified));
+    }
+}
+
+/// Verifier checks published proofs using only the SRS; unlike Evaluator,
+/// it needs no address book, channels or networking daemon, so it can be
+/// embedded in applications that only observe a game
+pub struct Verifier {
+    /// set once the SRS is available
+    pp: OnceCell<UniversalParams<Curve>>,
+    /// fetches pp on first use, if it was not given up front
+    provider: Option<Box<dyn utils::SrsProvider>>,
+    /// results of proofs verified before, so resubmitted proofs are not rechecked
+    cache: Mutex<VerificationCache>,
+    /// number of cards a permutation proof must be about
+    deck_size: usize,
+    /// utils::mapping_commitment of the card mapping a permutation proof must use
+    mapping_commitment: [u8; 32],
+    /// the Fiat-Shamir hash of the CombinedTranscript of combined proofs
+    hash: utils::HashConfig,
+    /// byte order of the field elements in that CombinedTranscript
+    field_encoding: utils::FieldEncoding,
+}
+
+impl Verifier {
+    pub fn new(pp: UniversalParams<Curve>) -> Self {
+        Verifier {
+            pp: OnceCell::from(pp),
+            provider: None,
+            cache: Mutex::default(),
+            deck_size: PERM_SIZE,
+            mapping_commitment: card_mapping_commitment(),
+            hash: utils::HashConfig::default(),
+            field_encoding: utils::FieldEncoding::default(),
+        }
+    }
+
+    /// a Verifier that awaits provider for the SRS when it first verifies
+    pub fn with_srs_provider(provider: Box<dyn utils::SrsProvider>) -> Self {
+        Verifier {
+            pp: OnceCell::new(),
+            provider: Some(provider),
+            cache: Mutex::default(),
+            deck_size: PERM_SIZE,
+            mapping_commitment: card_mapping_commitment(),
+            hash: utils::HashConfig::default(),
+            field_encoding: utils::FieldEncoding::default(),
+        }
+    }
+
+    /// expects permutation proofs over deck_size cards instead of PERM_SIZE;
+    /// encryption proofs are still over PERM_SIZE cards
+    pub fn with_deck_size(mut self, deck_size: usize) -> Self {
+        self.deck_size = deck_size;
+        self
+    }
+
+    /// expects permutation proofs made with card_mapping instead of
+    /// map_roots_of_unity_to_cards
+    pub fn with_card_mapping(mut self, card_mapping: &HashMap<F, Card>) -> Self {
+        self.mapping_commitment = utils::mapping_commitment(card_mapping);
+        self
+    }
+
+    /// expects combined proofs whose challenges were drawn with hash, see
+    /// prove_deal_in; standalone proofs always use SHA-256
+    pub fn with_hash(mut self, hash: utils::HashConfig) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    /// expects combined proofs whose transcript absorbed field elements in
+    /// encoding, see CombinedTranscript::with_field_encoding
+    pub fn with_field_encoding(mut self, encoding: utils::FieldEncoding) -> Self {
+        self.field_encoding = encoding;
+        self
+    }
+
+    /// the SRS; only the first call of a Verifier made with_srs_provider
+    /// actually waits, every other call returns right away
+    pub async fn srs(&self) -> &UniversalParams<Curve> {
+        if let Some(pp) = self.pp.get() {
+            return pp;
+        }
+
+        let provider = self.provider.as_ref().expect("Verifier has neither an SRS nor a provider");
+        let pp = provider.srs().await;
+        // a concurrent call may have fetched it first; either SRS is the same
+        let _ = self.pp.set(pp);
+        self.pp.get().unwrap()
+    }
+
+    pub async fn kzg_check(&self, comm: &G1, x: &F, eval: &F, proof: &G1) -> bool {
+        utils::kzg_check(self.srs().await, comm, x, eval, proof)
+    }
+
+    /// a proof over some other number of cards, or under another card
+    /// mapping, than expected is rejected outright, even if it is valid
+    /// for them: it shuffles a different deck
+    pub async fn verify_permutation_argument(&self, perm_proof: &PermutationProof) -> bool {
+        if perm_proof.deck_size != self.deck_size || perm_proof.mapping_commitment != self.mapping_commitment {
+            return false;
+        }
+
+        let pp = self.srs().await;
+        let key = (ProofKind::Permutation, srs_fingerprint(pp), perm_proof.fingerprint());
+        if let Some(verified) = self.cache.lock().unwrap().get(&key) {
+            return verified;
+        }
+
+        let verified = verify_permutation_argument(pp, perm_proof);
+        self.cache.lock().unwrap().insert(key, verified);
+        verified
+    }
+
+    /// verify_permutation_argument for every proof of aggregate, with their
+    /// openings checked together; results are not cached
+    pub async fn verify_aggregate(&self, aggregate: &AggregateProof) -> bool {
+        let expected = |proof: &PermutationProof| 
+            proof.deck_size == self.deck_size && proof.mapping_commitment == self.mapping_commitment;
+        if !aggregate.proofs.iter().all(expected) {
+            return false;
+        }
+
+        aggregate.verify(self.srs().await)
+    }
+
+    /// how often proofs were found in (or missing from) the verification cache
+    pub fn cache_stats(&self) -> VerificationCacheStats {
+        self.cache.lock().unwrap().stats
+    }
+
+    /// checks that the deck committed to in perm_proof.f_com has no repeated
+    /// card. shuffle_deck only drops repeated prfs locally, so a verifier cannot
+    /// rely on it; instead, the permutation argument shows that the evaluations
+    /// of f(X) are a permutation of (1, ω, ..., ω^{PERM_SIZE-1}), which are all
+    /// distinct, so any deck passing it has every card exactly once
+    pub async fn verify_no_duplicates(&self, perm_proof: &PermutationProof) -> bool {
+        self.verify_permutation_argument(perm_proof).await
+    }
+
+    pub async fn verify_encryption_proof(&self, proof: &EncryptProof) -> bool {
+        self.verify_encryption_proof_in_mode(proof, EvalProofMode::Eager).await
+    }
+
+    /// verify_encryption_proof for a proof of EvalProofMode::Lazy: the
+    /// masked evaluations are not checked against the masked commitments
+    /// here, so each card must be checked with
+    /// verify_decrypted_card_with_eval_proof once it is revealed
+    pub async fn verify_encryption_proof_lazy(&self, proof: &EncryptProof) -> bool {
+        self.verify_encryption_proof_in_mode(proof, EvalProofMode::Lazy).await
+    }
+
+    async fn verify_encryption_proof_in_mode(&self, proof: &EncryptProof, mode: EvalProofMode) -> bool {
+        let pp = self.srs().await;
+        let kind = match mode {
+            EvalProofMode::Eager => ProofKind::Encryption,
+            EvalProofMode::Lazy => ProofKind::LazyEncryption,
+        };
+        let key = (kind, srs_fingerprint(pp), proof.fingerprint());
+        if let Some(verified) = self.cache.lock().unwrap().get(&key) {
+            return verified;
+        }
+
+        let verified = local_verify_encryption_proof(pp, proof, mode).await;
+        self.cache.lock().unwrap().insert(key, verified);
+        verified
+    }
+
+    /// checks a deal_hand proof, and that it deals exactly the card at
+    /// card_positions[i] to recipient_ids[i]
+    pub async fn verify_deal(
+        &self, 
+        proof: &EncryptProof, 
+        card_positions: &[usize], 
+        recipient_ids: &[BigUint]
+    ) -> bool {
+        deal_ids(card_positions, recipient_ids).map_or(false, |ids| proof.ids == ids)
+            && self.verify_encryption_proof(proof).await
+    }
+
+    /// checks a prove_deal proof, as verify_deal checks a deal_hand one
+    pub async fn verify_combined_deal(
+        &self, 
+        proof: &CombinedProof, 
+        card_positions: &[usize], 
+        recipient_ids: &[BigUint]
+    ) -> bool {
+        deal_ids(card_positions, recipient_ids).map_or(false, |ids| proof.enc.ids == ids)
+            && self.verify_combined(proof).await
+    }
+
+    /// checks both proofs of a CombinedProof, with their challenges drawn
+    /// from one CombinedTranscript as the prover drew them; rejects proofs
+    /// about two different decks before running either check. Results are
+    /// not cached
+    pub async fn verify_combined(&self, proof: &CombinedProof) -> bool {
+        if !proof.is_consistent()
+            || proof.perm.deck_size != self.deck_size
+            || proof.perm.mapping_commitment != self.mapping_commitment {
+            return false;
+        }
+
+        let pp = self.srs().await;
+        let mut transcript = CombinedTranscript::new()
+            .with_hash(self.hash)
+            .with_field_encoding(self.field_encoding);
+        verify_permutation_argument_in(pp, &proof.perm, Some(&mut transcript))
+            && local_verify_encryption_proof_in(pp, &proof.enc, EvalProofMode::Eager, Some(&mut transcript)).await
+    }
+
+    /// checks both proofs of an archived game, and that the bundle's f_com,
+    /// pk and ids are the ones they were made for
+    pub async fn verify_bundle(&self, bundle: &GameBundle) -> bool {
+        if !bundle.is_consistent() {
+            return false;
+        }
+
+        self.verify_permutation_argument(&bundle.perm_proof).await
+            && self.verify_encryption_proof(&bundle.enc_proof).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_sigma_proof(
+        &self,
+        c: &G1, d_batch: &G1, 
+        g: &G2, c_1: &G2,
+        e_batch: &Gt, c2_batch: &Gt,
+        sigma: &SigmaProof
+    ) -> bool {
+        local_verify_sigma_proof(c, d_batch, g, c_1, e_batch, c2_batch, sigma)
+    }
 }
 
 /// Estimating time to decrypt one card at game time
@@ -1239,11 +2364,8 @@ pub fn decrypt_one_card(
     let exp_mask = c2.sub(div);
 
     // Raise exp_mask to all elements in {1, w, w^2, ... w^63}
-    let w = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64);
-    let w_powers = (0..64)
-        .into_iter()
-        .map(|i| utils::compute_power(&w, i as u64))
-        .collect::<Vec<F>>();
+    let w = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+    let w_powers = utils::power_table(&w, 64);
 
     let mut i = 0;
     for _ in 0..64 {
@@ -1255,28 +2377,98 @@ pub fn decrypt_one_card(
     return i;
 }
 
+/// checks that plaintext_card is the card proof encrypts at position i,
+/// as decrypted with decryption_key = msk * H(ids[i]): the key must be
+/// the one of ids[i] under pk, the mask it recovers from ciphertexts[i]
+/// must take plaintext_card to masked_evals[i], and eval_proofs[i] must
+/// open masked_commitments[i] to masked_evals[i] at ω^i. The key opens
+/// every card encrypted to ids[i], so it is only to be shown once all of
+/// those cards are public anyway
+pub fn verify_decrypted_card(
+    pp: &UniversalParams<Curve>,
+    proof: &EncryptProof,
+    i: usize,
+    plaintext_card: F,
+    decryption_key: &G1
+) -> bool {
+    match proof.eval_proofs.get(i) {
+        Some(eval_proof) => verify_decrypted_card_with_eval_proof(
+            pp, proof, i, plaintext_card, decryption_key, eval_proof
+        ),
+        None => false,
+    }
+}
+
+/// verify_decrypted_card for a proof of EvalProofMode::Lazy, with the
+/// eval proof of card i that LazyEvalProofs computed when it was revealed
+pub fn verify_decrypted_card_with_eval_proof(
+    pp: &UniversalParams<Curve>,
+    proof: &EncryptProof,
+    i: usize,
+    plaintext_card: F,
+    decryption_key: &G1,
+    eval_proof: &G1
+) -> bool {
+    if i >= PERM_SIZE || i >= proof.ids.len() || i >= proof.ciphertexts.len()
+        || i >= proof.masked_evals.len() || i >= proof.masked_commitments.len() {
+        return false;
+    }
+
+    // only the powers of ω are cards, or padding
+    if plaintext_card.pow([PERM_SIZE as u64]) != F::one() {
+        return false;
+    }
+
+    // e(sk * H(id), g) = e(H(id), pk)
+    //TODO: do a real hash to curve
+    let x_f = match utils::id_to_field_checked(&proof.ids[i]) {
+        Ok(x_f) => x_f,
+        Err(_) => return false,
+    };
+    let hash_id = G1::generator().mul(x_f);
+    if <Curve>::pairing(decryption_key, G2::generator()) != <Curve>::pairing(hash_id, proof.pk) {
+        return false;
+    }
+
+    let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+    if !utils::kzg_check(
+        pp,
+        &proof.masked_commitments[i],
+        &utils::compute_power(&ω, i as u64),
+        &proof.masked_evals[i],
+        eval_proof
+    ) {
+        return false;
+    }
+
+    // IBE decryption to get g^mask, then masked_eval = mask * card
+    let (c1, c2) = proof.ciphertexts[i];
+    let exp_mask = c2.sub(<Curve>::pairing(decryption_key, c1));
+
+    exp_mask.mul(plaintext_card) == Gt::generator().mul(proof.masked_evals[i])
+}
+
 /// Verify that sigma proof is correctly verified by local_verify_sigma_proof
 pub async fn test_sigma(evaluator: &mut Evaluator) {
     println!("testing sigma protocol...");
 
     let mut wit_1_handles = vec![];
     let mut lin_comb_ran = vec![];
-    let wit_2_handle = evaluator.ran();
+    let wit_2_handle = evaluator.ran().unwrap();
 
     for _ in 0..PERM_SIZE {
-        wit_1_handles.push(evaluator.ran());
+        wit_1_handles.push(evaluator.ran().unwrap());
         lin_comb_ran.push(F::rand(&mut ark_std::test_rng()));
     }
 
-    let mut d_i = vec![];
-    let mut d_batch = G1::zero();
+    let d_jobs = wit_1_handles
+        .iter()
+        .map(|h| (vec![G1::generator().mul(F::from(20)).into_affine()], vec![h.clone()]))
+        .collect::<Vec<(Vec<G1>, Vec<String>)>>();
+    let d_i = evaluator.exp_and_reveal_g1_batch(&d_jobs, "test_D_").await;
 
+    let mut d_batch = G1::zero();
     for i in 0..PERM_SIZE {
-        d_i.push(evaluator.exp_and_reveal_g1(
-            vec![G1::generator().mul(F::from(20)).into_affine()], 
-            vec![wit_1_handles[i].clone()], 
-            &format!("{}/{}", "test_D_", i)
-        ).await);
         d_batch = d_batch.add(d_i[i].mul(lin_comb_ran[i].clone())).into_affine();
     }
 
@@ -1303,24 +2495,23 @@ pub async fn test_sigma(evaluator: &mut Evaluator) {
         c2_batch = c2_batch.add(c2_i.mul(lin_comb_ran[i].clone()));
     }
 
-    let pi = dist_sigma_proof(
-            evaluator,
-            &G1::generator().mul(F::from(20)).into_affine(), 
-            &G2::generator(), 
-            &e_batch, 
-            wit_1_handles.clone(), 
-            wit_2_handle.clone(), 
-            lin_comb_ran.clone()).await;
+    let mut h_x = evaluator.scale(&wit_1_handles[0], lin_comb_ran[0]);
+    for i in 1..PERM_SIZE {
+        let tmp = evaluator.scale(&wit_1_handles[i], lin_comb_ran[i]);
+        h_x = evaluator.add(&tmp, &h_x);
+    }
 
-    let check = local_verify_sigma_proof(
+    let statement = encryption_sigma_statement(
         &G1::generator().mul(F::from(20)).into_affine(), 
         &d_batch, 
         &G2::generator(), 
         &c_1, 
         &e_batch, 
-        &c2_batch, 
-        &pi
+        &c2_batch
     );
+    let pi = prove_sigma(evaluator, &statement, &[h_x, wit_2_handle.clone()], "test_sigma").await.unwrap();
+
+    let check = verify_sigma(&statement, &pi);
         
     assert!(check == true, "Verification failed");
 }
@@ -1370,7 +2561,7 @@ pub async fn test_dist_kzg(evaluator: &mut Evaluator) {
     // let mut actual_evals = vec![];
 
     for _ in 0..PERM_SIZE {
-        let tmp = evaluator.ran();
+        let tmp = evaluator.ran().unwrap();
         evals.push(evaluator.get_wire(&tmp));
         // actual_evals.push(evaluator.output_wire(&tmp).await);
     }
@@ -1378,11 +2569,11 @@ pub async fn test_dist_kzg(evaluator: &mut Evaluator) {
     // let actual_poly = utils::interpolate_poly_over_mult_subgroup(&actual_evals);
     // let actual_evaluation_at_w = evaluator.share_poly_eval(actual_poly.clone(), utils::multiplicative_subgroup_of_size(64)).await;
 
-    let poly = utils::interpolate_poly_over_mult_subgroup(&evals);
+    let poly = utils::interpolate_poly_over_mult_subgroup(&Share::raw(&evals));
     let com_share = utils::commit_poly(&pp, &poly);
     let com = evaluator.add_g1_elements_from_all_parties(&com_share, &String::from("kzg_test_com")).await;
 
-    let w = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64);
+    let w = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
     let pi = evaluator.eval_proof_with_share_poly(&pp, poly.clone(), w, String::from("kzg_test_pi")).await;
 
     let evaluation_at_w = evaluator.share_poly_eval(poly.clone(), w);
@@ -1399,14 +2590,14 @@ async fn test_share_poly_mult(evaluator: &mut Evaluator) {
     let mut share_evals_2 = vec![];
 
     for _ in 0..PERM_SIZE {
-        let tmp = evaluator.ran();
+        let tmp = evaluator.ran().unwrap();
         share_evals_1.push(evaluator.get_wire(&tmp));
-        let tmp = evaluator.ran();
+        let tmp = evaluator.ran().unwrap();
         share_evals_2.push(evaluator.get_wire(&tmp));
     }
 
-    let share_poly_1 = utils::interpolate_poly_over_mult_subgroup(&share_evals_1);
-    let share_poly_2 = utils::interpolate_poly_over_mult_subgroup(&share_evals_2);
+    let share_poly_1 = utils::interpolate_poly_over_mult_subgroup(&Share::raw(&share_evals_1));
+    let share_poly_2 = utils::interpolate_poly_over_mult_subgroup(&Share::raw(&share_evals_2));
 
     let random_point = F::from(420021312);
 
@@ -1425,4 +2616,1739 @@ async fn test_share_poly_mult(evaluator: &mut Evaluator) {
     let v_3 = evaluator.output_wire(&poly_3_val).await;
 
     assert_eq!(v_1 * v_2, v_3, "Share poly mult failed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_serialize::CanonicalSerialize;
+    use pok3r_verify::format::PROOF_MAGIC;
+    use pok3r_verify::{verify_masked_commitments, PERMUTATION_EVAL_PROOFS};
+    use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    use network::in_process::run_parties;
+
+    /// master secret key of the IBE setup used by the test runs
+    const TEST_MSK: u64 = 42;
+
+    /// public outputs of one complete run of the protocol
+    struct ProtocolRun {
+        pp: UniversalParams<Curve>,
+        perm_proof: PermutationProof,
+        encrypt_proof: EncryptProof,
+        /// eval proofs LazyEvalProofs computed after the eager run, by position
+        lazy_eval_proofs: Vec<(usize, G1)>,
+    }
+
+    /// the evaluator with its randomness seeded by its node id, so runs
+    /// are reproducible and a shuffle is known to find every card
+    fn seeded_evaluator(evaluator: Evaluator) -> Evaluator {
+        let seed = evaluator.node_id() as u64;
+        evaluator.with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn test_ids() -> Vec<BigUint> {
+        (0..PERM_SIZE)
+            .map(|i| BigUint::from(i as u8))
+            .collect()
+    }
+
+    /// runs shuffle, permutation argument and encryption once among
+    /// 3 in-process parties; the run is shared by all tests that need real proofs
+    fn protocol_run() -> &'static ProtocolRun {
+        static RUN: OnceCell<ProtocolRun> = OnceCell::new();
+
+        RUN.get_or_init(|| task::block_on(async {
+            let pp = utils::setup_kzg(1024);
+            let pp_ref = &pp;
+
+            let mut outputs = run_parties(3, |evaluator| async move {
+                let mut evaluator = seeded_evaluator(evaluator);
+                let ShuffledDeck { handles: card_share_handles, shares: card_shares, .. } = shuffle_deck(&mut evaluator).await.unwrap();
+                let perm_proof = compute_permutation_argument(
+                    pp_ref, 
+                    &mut evaluator, 
+                    card_share_handles.clone(), 
+                    &card_shares,
+                    &card_mapping_commitment()
+                ).await.unwrap();
+
+                let pk = G2::generator().mul(F::from(TEST_MSK)).into_affine();
+                let (encrypt_proof, mut lazy) = encrypt_and_prove_with_mode(
+                    pp_ref, 
+                    &mut evaluator, 
+                    card_share_handles, 
+                    perm_proof.f_com, 
+                    pk, 
+                    test_ids(),
+                    EvalProofMode::Eager
+                ).await.unwrap();
+
+                let mut lazy_eval_proofs = vec![];
+                for i in [0, 15, PERM_SIZE - 1] {
+                    lazy_eval_proofs.push((i, lazy.eval_proof(&mut evaluator, i).await));
+                }
+
+                (perm_proof, encrypt_proof, lazy_eval_proofs)
+            }).await;
+
+            let (perm_proof, encrypt_proof, lazy_eval_proofs) = outputs.remove(0);
+            ProtocolRun { pp, perm_proof, encrypt_proof, lazy_eval_proofs }
+        }))
+    }
+
+    /// (d_batch, e_batch, c2_batch) that the sigma proof of proof is about
+    fn batched_sigma_statement(proof: &EncryptProof) -> (G1, Gt, Gt) {
+        let s = proof.recompute_challenges();
+
+        let mut h_batch = G1::zero();
+        let mut d_batch = G1::zero();
+        let mut c2_batch = Gt::zero();
+        for (i, s_i) in s.iter().enumerate().take(PERM_SIZE) {
+            let hash_id = G1::generator().mul(F::from(proof.ids[i].clone()));
+            h_batch = (h_batch + hash_id.mul(s_i)).into_affine();
+            d_batch = (d_batch + proof.masked_commitments[i].mul(s_i)).into_affine();
+            c2_batch += proof.ciphertexts[i].1.mul(s_i);
+        }
+        let e_batch = <Curve as Pairing>::pairing(h_batch, proof.pk);
+
+        (d_batch, e_batch, c2_batch)
+    }
+
+    #[test]
+    fn test_recompute_challenges() {
+        let run = protocol_run();
+        let proof = &run.perm_proof;
+
+        // the prover opened t(X) and g(X) = f(X) + y1 at exactly y2
+        let (y1, y2) = proof.recompute_challenges(&run.pp).unwrap();
+        let const_y1 = DensePolynomial::from_coefficients_vec(vec![y1]);
+        let g_com = (proof.f_com + utils::commit_poly(&run.pp, &const_y1)).into_affine();
+
+        assert!(utils::kzg_check(&run.pp, &proof.t_com, &y2, &proof.y2, &proof.pi_2));
+        assert!(utils::kzg_check(&run.pp, &g_com, &y2, &proof.y4, &proof.pi_4));
+
+        // the sigma proof was produced over the batch implied by s
+        let proof = &run.encrypt_proof;
+        assert_eq!(proof.recompute_challenges().len(), PERM_SIZE);
+
+        let (d_batch, e_batch, c2_batch) = batched_sigma_statement(proof);
+        assert!(local_verify_sigma_proof(
+            &proof.card_commitment, 
+            &d_batch, 
+            &G2::generator(), 
+            &proof.ciphertexts[0].0, 
+            &e_batch, 
+            &c2_batch, 
+            proof.sigma_proof.as_ref().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_unbatched_sigma_verification_matches_batched() {
+        let proof = &protocol_run().encrypt_proof;
+        let s = proof.recompute_challenges();
+        let sigma = proof.sigma_proof.as_ref().unwrap();
+        let (d_batch, e_batch, c2_batch) = batched_sigma_statement(proof);
+        let c_1 = proof.ciphertexts[0].0;
+
+        let e_per_id: Vec<Gt> = proof.ids
+            .iter()
+            .map(|id| <Curve as Pairing>::pairing(G1::generator().mul(F::from(id.clone())), proof.pk))
+            .collect();
+        let c2_is: Vec<Gt> = proof.ciphertexts.iter().map(|(_, c2_i)| *c2_i).collect();
+
+        let batched = |sigma: &SigmaProof| local_verify_sigma_proof(
+            &proof.card_commitment, &d_batch, &G2::generator(), &c_1, &e_batch, &c2_batch, sigma
+        );
+        let unbatched = |sigma: &SigmaProof, c2_is: &[Gt]| verify_sigma_proof_unbatched(
+            &proof.card_commitment, &proof.masked_commitments, &G2::generator(), &c_1, &e_per_id, c2_is, &s, sigma
+        );
+
+        let mut tampered = sigma.clone();
+        tampered.y += F::one();
+        for candidate in [sigma, &tampered] {
+            assert_eq!(unbatched(candidate, &c2_is), batched(candidate));
+        }
+        assert!(unbatched(sigma, &c2_is));
+
+        // a ciphertext moved to another card, or left out, changes the batch
+        let mut swapped = c2_is.clone();
+        swapped.swap(0, 1);
+        assert!(!unbatched(sigma, &swapped));
+        assert!(!unbatched(sigma, &c2_is[1..]));
+    }
+
+    #[test]
+    fn test_encrypt_proof_write_to_matches_to_bytes() {
+        let run = protocol_run();
+        let proof = &run.encrypt_proof;
+
+        let mut written = Vec::new();
+        proof.write_to(&mut written).unwrap();
+        assert_eq!(written, proof.to_bytes());
+
+        // the dump is the transcript, which to_bytes follows with the sigma proof
+        let dumped: Vec<u8> = proof.transcript_dump().into_iter().flat_map(|(_, bytes)| bytes).collect();
+        assert!(written.starts_with(&dumped));
+        assert_eq!(written[dumped.len()], 1);
+
+        // the challenges hash the same bytes they did over the chunks
+        let s1 = utils::fs_hash(vec![&dumped, b"0"], PERM_SIZE/2);
+        let s2 = utils::fs_hash(vec![&dumped, b"1"], PERM_SIZE/2);
+        assert_eq!(proof.recompute_challenges(), [s1, s2].concat());
+    }
+
+    /// writes the seed corpus of the fuzz targets under fuzz/corpus; run
+    /// with `cargo test write_fuzz_corpus -- --ignored` after a layout change
+    #[test]
+    #[ignore]
+    fn write_fuzz_corpus() {
+        let run = protocol_run();
+        let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+        let write = |target: &str, name: &str, bytes: &[u8]| {
+            let dir = corpus.join(target);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(name), bytes).unwrap();
+        };
+
+        write("permutation_proof", "valid", &run.perm_proof.to_bytes());
+        write("encrypt_proof", "valid", &run.encrypt_proof.to_bytes());
+
+        let sender = String::from("party_0");
+        let msgs = [
+            EvalNetMsg::ConnectionEstablished { success: true },
+            EvalNetMsg::Greeting { sender: sender.clone(), message: String::from("hello") },
+            EvalNetMsg::PublishValue {
+                sender: sender.clone(),
+                handle: String::from("h"),
+                value: encode_f_as_bs58_str(&F::one()),
+            },
+            EvalNetMsg::PublishBatchValue {
+                sender: sender.clone(),
+                handles: vec![String::from("h1"), String::from("h2")],
+                values: vec![encode_f_as_bs58_str(&F::one()), encode_f_as_bs58_str(&F::zero())],
+            },
+            EvalNetMsg::SendTriple {
+                sender: sender.clone(),
+                receiver: String::from("party_1"),
+                handle_a: String::from("a"),
+                share_a: encode_f_as_bs58_str(&F::one()),
+                handle_b: String::from("b"),
+                share_b: encode_f_as_bs58_str(&F::one()),
+                handle_c: String::from("c"),
+                share_c: encode_f_as_bs58_str(&F::one()),
+            },
+            EvalNetMsg::SendBatchShare {
+                sender,
+                receiver: String::from("party_1"),
+                handles: vec![String::from("h")],
+                values: vec![encode_f_as_bs58_str(&F::one())],
+            },
+            EvalNetMsg::PublicCommitment {
+                name: String::from("h"),
+                node_id: 0,
+                point_bytes: {
+                    let mut point_bytes = Vec::new();
+                    G1::generator().serialize_compressed(&mut point_bytes).unwrap();
+                    point_bytes
+                },
+            },
+            EvalNetMsg::Ping { sender: String::from("party_0"), nonce: String::from("n") },
+            EvalNetMsg::Pong {
+                sender: String::from("party_1"),
+                receiver: String::from("party_0"),
+                nonce: String::from("n"),
+            },
+        ];
+        for (i, msg) in msgs.iter().enumerate() {
+            write("eval_net_msg", &format!("valid_{}", i), &serde_json::to_vec(msg).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_verify_masked_commitments() {
+        let run = protocol_run();
+        assert!(verify_masked_commitments(&run.encrypt_proof));
+
+        // a d_0 that is no longer C^{z_0}
+        let mut malformed = run.encrypt_proof.clone();
+        malformed.masked_commitments[0] = (malformed.masked_commitments[0] + G1::generator()).into_affine();
+        assert!(!verify_masked_commitments(&malformed));
+
+        let mut unproven = run.encrypt_proof.clone();
+        unproven.sigma_proof = None;
+        assert!(!verify_masked_commitments(&unproven));
+    }
+
+    #[test]
+    fn test_encrypt_transcript_matches_proof_bytes() {
+        let run = protocol_run();
+        let proof = &run.encrypt_proof;
+
+        // the prover absorbs its values before any EncryptProof exists
+        let ids = proof.ids.clone();
+        let masked_commitments = proof.masked_commitments.clone();
+        let masked_evals = proof.masked_evals.clone();
+        let eval_proofs = proof.eval_proofs.clone();
+        let ciphertexts = proof.ciphertexts.clone();
+        let transcript = EncryptTranscript {
+            pk: &proof.pk,
+            ids: &ids,
+            card_commitment: &proof.card_commitment,
+            masked_commitments: &masked_commitments,
+            masked_evals: &masked_evals,
+            eval_proofs: &eval_proofs,
+            ciphertexts: &ciphertexts,
+        };
+
+        let mut written = Vec::new();
+        transcript.write_to(&mut written).unwrap();
+        let mut unproven = proof.clone();
+        unproven.sigma_proof = None;
+        assert_eq!([&written[..], &[0]].concat(), unproven.to_bytes());
+
+        // the old derivation hashed the bytes of a throwaway EncryptProof
+        let s1 = utils::fs_hash(vec![&written, b"0"], PERM_SIZE/2);
+        let s2 = utils::fs_hash(vec![&written, b"1"], PERM_SIZE/2);
+        let s = transcript.challenges();
+        assert_eq!(s, [s1, s2].concat());
+        assert_eq!(s, proof.recompute_challenges());
+    }
+
+    #[test]
+    fn test_verify_permutation_rejects_g_other_than_f_plus_y1() {
+        let run = protocol_run();
+        let proof = &run.perm_proof;
+        assert!(verify_permutation_argument(&run.pp, proof));
+
+        // a prover opening g'(X) = f(X) + y1 + 1 at y2 instead of g(X):
+        // the quotient, and so pi_4, is the same as for g(X), and the
+        // opening is valid for the commitment to g'(X) ...
+        let (y1, y2) = proof.recompute_challenges(&run.pp).unwrap();
+        let g_com = proof.g_com(&run.pp, &y1);
+        let g_prime_com = (g_com + run.pp.powers_of_g[0]).into_affine();
+        let mut cheat = proof.clone();
+        cheat.y4 += F::one();
+        assert!(utils::kzg_check(&run.pp, &g_prime_com, &y2, &cheat.y4, &cheat.pi_4));
+
+        // ... but the verifier checks it against f_com + const(y1) it derives
+        assert!(!utils::kzg_check(&run.pp, &g_com, &y2, &cheat.y4, &cheat.pi_4));
+        assert!(!verify_permutation_argument(&run.pp, &cheat));
+    }
+
+    #[test]
+    fn test_permutation_eval_proofs_verify_one_by_one() {
+        let run = protocol_run();
+        let proof = &run.perm_proof;
+        let (_, y2) = proof.recompute_challenges(&run.pp).unwrap();
+
+        let openings: Vec<(G1, F, F, G1)> = (0..PERMUTATION_EVAL_PROOFS)
+            .map(|i| proof.eval_proof(&run.pp, i).unwrap())
+            .collect();
+        assert!(proof.eval_proof(&run.pp, PERMUTATION_EVAL_PROOFS).is_none());
+        assert_eq!(openings, proof.eval_proofs(&run.pp).unwrap().to_vec());
+
+        // the points and commitments each opening is checked against
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let points: Vec<F> = openings.iter().map(|(_, point, _, _)| *point).collect();
+        assert_eq!(points, vec![ω.pow([PERM_SIZE as u64 - 1]), y2, y2 / ω, y2, y2]);
+        assert_eq!(openings[0].0, proof.t_com);
+        assert_eq!(openings[4].0, proof.q_com);
+
+        for (i, (commitment, point, value, pi)) in openings.iter().enumerate() {
+            assert!(utils::kzg_check(&run.pp, commitment, point, value, pi), "opening {}", i);
+        }
+
+        // a wrong value breaks its own opening and no other
+        let mut cheat = proof.clone();
+        cheat.y3 += F::one();
+        let checks: Vec<bool> = (0..PERMUTATION_EVAL_PROOFS)
+            .map(|i| {
+                let (commitment, point, value, pi) = cheat.eval_proof(&run.pp, i).unwrap();
+                utils::kzg_check(&run.pp, &commitment, &point, &value, &pi)
+            })
+            .collect();
+        assert_eq!(checks, vec![true, true, false, true, true]);
+    }
+
+    #[async_std::test]
+    async fn test_verify_permutation_of_other_deck_size() {
+        let pp = utils::setup_kzg(64);
+        let pp_ref = &pp;
+
+        let mut proofs = run_parties(3, |mut evaluator| async move {
+            // a fixed permutation of the 8th roots of unity
+            let ω = utils::multiplicative_subgroup_of_size(8).unwrap();
+            let handles: Vec<String> = [5, 2, 7, 0, 3, 6, 1, 4]
+                .iter()
+                .map(|&i| evaluator.fixed_wire_handle(utils::compute_power(&ω, i)))
+                .collect();
+            let shares: Vec<Share> = handles.iter().map(|h| evaluator.get_wire(h)).collect();
+            compute_permutation_argument(pp_ref, &mut evaluator, handles, &shares, &card_mapping_commitment()).await.unwrap()
+        }).await;
+        let proof = proofs.remove(0);
+        assert_eq!(proof.deck_size, 8);
+        assert!(verify_permutation_argument(&pp, &proof));
+
+        // the size travels with the proof
+        let reloaded = PermutationProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(reloaded.deck_size, 8);
+        assert!(Verifier::new(utils::setup_kzg(64)).with_deck_size(8).verify_permutation_argument(&reloaded).await);
+
+        // valid for 8 cards, but not the deck the verifier asked about
+        assert!(!Verifier::new(utils::setup_kzg(64)).with_deck_size(64).verify_permutation_argument(&reloaded).await);
+        assert!(!Verifier::new(utils::setup_kzg(64)).verify_permutation_argument(&reloaded).await);
+
+        // nor does claiming another size make it pass
+        let mut relabeled = reloaded.clone();
+        relabeled.deck_size = 16;
+        assert!(!verify_permutation_argument(&pp, &relabeled));
+        relabeled.deck_size = 6;
+        assert!(!verify_permutation_argument(&pp, &relabeled));
+    }
+
+    #[async_std::test]
+    async fn test_permutation_sanity_check_rejects_non_permutation() {
+        let pp = utils::setup_kzg(64);
+        let pp_ref = &pp;
+
+        let outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = evaluator.with_sanity_checks(true);
+            // card 5 twice and card 4 never: d(X) cannot vanish
+            let ω = utils::multiplicative_subgroup_of_size(8).unwrap();
+            let handles: Vec<String> = [5, 2, 7, 0, 3, 6, 1, 5]
+                .iter()
+                .map(|&i| evaluator.fixed_wire_handle(utils::compute_power(&ω, i)))
+                .collect();
+            let shares: Vec<Share> = handles.iter().map(|h| evaluator.get_wire(h)).collect();
+            compute_permutation_argument(pp_ref, &mut evaluator, handles, &shares, &card_mapping_commitment()).await.err()
+        }).await;
+
+        for output in outputs {
+            assert_eq!(output, Some(EvaluatorError::VanishingCheckFailed { label: String::from("perm_d") }));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_aggregate_proof_of_many_shuffles() {
+        let pp = utils::setup_kzg(64);
+        let pp_ref = &pp;
+
+        // real proofs of ten different permutations of the 8th roots of unity
+        let mut proofs = Vec::new();
+        for shift in 0..10u64 {
+            let mut outputs = run_parties(3, |mut evaluator| async move {
+                let ω = utils::multiplicative_subgroup_of_size(8).unwrap();
+                let handles: Vec<String> = [5, 2, 7, 0, 3, 6, 1, 4]
+                    .iter()
+                    .map(|&i| evaluator.fixed_wire_handle(utils::compute_power(&ω, (i + shift) % 8)))
+                    .collect();
+                let shares: Vec<Share> = handles.iter().map(|h| evaluator.get_wire(h)).collect();
+                compute_permutation_argument(pp_ref, &mut evaluator, handles, &shares, &card_mapping_commitment()).await.unwrap()
+            }).await;
+            proofs.push(outputs.remove(0));
+        }
+
+        let aggregate = AggregateProof::new(proofs.clone());
+        assert!(aggregate.verify(&pp));
+        assert!(aggregate.clone().with_batch_size(3).verify(&pp));
+        assert!(Verifier::new(utils::setup_kzg(64)).with_deck_size(8).verify_aggregate(&aggregate).await);
+
+        // one bad opening among them fails the whole aggregate
+        let mut corrupted = AggregateProof::new(proofs[..6].to_vec());
+        let mut cheat = proofs[6].clone();
+        cheat.pi_2 = (cheat.pi_2 + G1::generator()).into_affine();
+        assert!(!verify_permutation_argument(&pp, &cheat));
+        corrupted.push(cheat);
+        corrupted.proofs.extend_from_slice(&proofs[7..]);
+        assert!(!corrupted.verify(&pp));
+        assert!(!corrupted.with_batch_size(3).verify(&pp));
+    }
+
+    #[test]
+    fn test_transcript_dump_matches_hashed_bytes() {
+        let run = protocol_run();
+
+        let concat = |chunks: &[(String, Vec<u8>)]| -> Vec<u8> {
+            chunks.iter().flat_map(|(_, bytes)| bytes.clone()).collect()
+        };
+
+        // permutation proof: one transcript per challenge, in hashing order
+        let proof = &run.perm_proof;
+        let dump = proof.transcript_dump(&run.pp).unwrap();
+        let (y1_chunks, y2_chunks): (Vec<_>, Vec<_>) = dump
+            .into_iter()
+            .partition(|(label, _)| label.starts_with("y1/"));
+        assert_eq!(
+            y2_chunks.iter().map(|(label, _)| label.as_str()).collect::<Vec<&str>>(),
+            vec!["y2/v_com", "y2/f_com", "y2/q_com", "y2/t_com", "y2/g_com"]
+        );
+
+        let (y1, y2) = proof.recompute_challenges(&run.pp).unwrap();
+        assert_eq!(utils::fs_hash(vec![&concat(&y1_chunks)], 1)[0], y1);
+        assert_eq!(utils::fs_hash(vec![&concat(&y2_chunks)], 1)[0], y2);
+
+        // encryption proof: the dump is exactly the hashed transcript
+        let proof = &run.encrypt_proof;
+        let dump = proof.transcript_dump();
+        let mut transcript = Vec::new();
+        proof.transcript().write_to(&mut transcript).unwrap();
+        assert_eq!(concat(&dump), transcript);
+
+        let s = proof.recompute_challenges();
+        let s1 = utils::fs_hash(vec![&concat(&dump), b"0"], PERM_SIZE/2);
+        assert_eq!(s[..PERM_SIZE/2], s1[..]);
+    }
+
+    #[async_std::test]
+    async fn test_mapping_commitment_binds_the_card_mapping() {
+        let run = protocol_run();
+        let agreed = map_roots_of_unity_to_cards();
+        assert_eq!(run.perm_proof.mapping_commitment, utils::mapping_commitment(&agreed));
+
+        // the same pairs in a map of its own, i.e. seeded and so iterated
+        // differently, commit the same, but two cards trading roots of unity do not
+        let reordered: HashMap<F, Card> = agreed.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(utils::mapping_commitment(&reordered), utils::mapping_commitment(&agreed));
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let mut swapped = agreed.clone();
+        swapped.insert(F::one(), agreed[&ω]);
+        swapped.insert(ω, agreed[&F::one()]);
+        assert_ne!(utils::mapping_commitment(&swapped), utils::mapping_commitment(&agreed));
+
+        // a verifier expecting the other mapping rejects the proof
+        assert!(Verifier::new(utils::setup_kzg(1024)).verify_permutation_argument(&run.perm_proof).await);
+        assert!(!Verifier::new(utils::setup_kzg(1024)).with_card_mapping(&swapped).verify_permutation_argument(&run.perm_proof).await);
+
+        // and relabeling the proof breaks its challenges
+        let mut relabeled = run.perm_proof.clone();
+        relabeled.mapping_commitment = utils::mapping_commitment(&swapped);
+        assert!(!verify_permutation_argument(&run.pp, &relabeled));
+    }
+
+    #[async_std::test]
+    async fn test_verifier_without_network() {
+        let run = protocol_run();
+
+        // an independent verifier that only knows the public SRS
+        let verifier = Verifier::new(utils::setup_kzg(1024));
+        assert!(verifier.verify_permutation_argument(&run.perm_proof).await);
+        assert!(verifier.verify_encryption_proof(&run.encrypt_proof).await);
+
+        let proof = &run.perm_proof;
+        let w = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let w63 = utils::compute_power(&w, PERM_SIZE as u64 - 1);
+        assert!(verifier.kzg_check(&proof.t_com, &w63, &proof.y1, &proof.pi_1).await);
+    }
+
+    /// hands out the test SRS only after a delay, counting the fetches
+    struct DelayedSrs {
+        delay: Duration,
+        fetches: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl utils::SrsProvider for DelayedSrs {
+        async fn srs(&self) -> UniversalParams<Curve> {
+            task::sleep(self.delay).await;
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            utils::setup_kzg(1024)
+        }
+    }
+
+    #[async_std::test]
+    async fn test_verifier_awaits_srs_provider() {
+        let run = protocol_run();
+
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let provider = DelayedSrs { delay: Duration::from_millis(100), fetches: fetches.clone() };
+        let verifier = Verifier::with_srs_provider(Box::new(provider));
+
+        let start = Instant::now();
+        assert!(verifier.verify_permutation_argument(&run.perm_proof).await);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // the SRS is now installed, so later checks do not fetch it again
+        assert!(verifier.verify_encryption_proof(&run.encrypt_proof).await);
+        assert!(verifier.verify_no_duplicates(&run.perm_proof).await);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[async_std::test]
+    async fn test_verifier_caches_results() {
+        let run = protocol_run();
+        let verifier = Verifier::new(utils::setup_kzg(1024));
+
+        assert!(verifier.verify_permutation_argument(&run.perm_proof).await);
+        assert_eq!(verifier.cache_stats(), VerificationCacheStats { hits: 0, misses: 1 });
+        assert!(verifier.verify_permutation_argument(&run.perm_proof).await);
+        assert_eq!(verifier.cache_stats(), VerificationCacheStats { hits: 1, misses: 1 });
+
+        // a rejected proof is remembered as rejected
+        let mut tampered = run.encrypt_proof.clone();
+        tampered.masked_evals[0] += F::one();
+        assert!(!verifier.verify_encryption_proof(&tampered).await);
+        assert!(!verifier.verify_encryption_proof(&tampered).await);
+        assert_eq!(verifier.cache_stats(), VerificationCacheStats { hits: 2, misses: 2 });
+
+        // the same proof under another SRS is not a hit
+        let other_srs = [7u8; 16];
+        let key = (ProofKind::Permutation, other_srs, run.perm_proof.fingerprint());
+        assert_eq!(verifier.cache.lock().unwrap().get(&key), None);
+    }
+
+    #[async_std::test]
+    async fn test_verify_combined_rejects_separate_proofs_and_swapped_deck() {
+        let run = protocol_run();
+        let verifier = Verifier::new(utils::setup_kzg(1024));
+
+        // two proofs about the same deck, but each with its own challenges
+        let combined = CombinedProof {
+            perm: run.perm_proof.clone(),
+            enc: run.encrypt_proof.clone(),
+        };
+        assert!(combined.is_consistent());
+        assert!(!verifier.verify_combined(&combined).await);
+
+        // an encryption proof claiming to encrypt some other committed deck
+        let mut swapped = combined.clone();
+        swapped.enc.card_commitment = (swapped.enc.card_commitment + G1::generator()).into_affine();
+        assert!(!swapped.is_consistent());
+        assert!(!verifier.verify_combined(&swapped).await);
+    }
+
+    #[async_std::test]
+    async fn test_verify_no_duplicates_rejects_repeated_card() {
+        let run = protocol_run();
+        let verifier = Verifier::new(utils::setup_kzg(1024));
+        assert!(verifier.verify_no_duplicates(&run.perm_proof).await);
+
+        // a deck in which card ω^1 replaces card ω^0, so ω^1 appears twice
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let mut deck = utils::power_table(&ω, PERM_SIZE);
+        deck[0] = deck[1];
+        let f = utils::interpolate_poly_over_mult_subgroup(&deck);
+
+        let mut duplicated = run.perm_proof.clone();
+        duplicated.f_com = utils::commit_poly(&run.pp, &f);
+        assert!(!verifier.verify_no_duplicates(&duplicated).await);
+    }
+
+    #[async_std::test]
+    async fn test_sanity_checks_off_skips_openings() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let ShuffledDeck { handles: card_share_handles, shares: card_shares, .. } = shuffle_deck(&mut evaluator).await.unwrap();
+
+            let mut runs = vec![];
+            for enabled in [true, false] {
+                // same randomness for both proofs
+                evaluator = seeded_evaluator(evaluator).with_sanity_checks(enabled);
+
+                let sent_before = evaluator.messages_sent();
+                let proof = compute_permutation_argument(
+                    pp_ref, 
+                    &mut evaluator, 
+                    card_share_handles.clone(), 
+                    &card_shares,
+                    &card_mapping_commitment()
+                ).await.unwrap();
+                runs.push((proof.to_bytes(), evaluator.messages_sent() - sent_before));
+            }
+            runs
+        }).await;
+
+        for runs in outputs {
+            let (checked_proof, checked_sent) = &runs[0];
+            let (unchecked_proof, unchecked_sent) = &runs[1];
+            assert_eq!(checked_proof, unchecked_proof);
+            // the extra messages are this party's share of the d(X) commitment,
+            // and for the check of g(X) * t(X/ω) the commitments to its shares
+            // and their openings
+            assert_eq!(checked_sent - unchecked_sent, 3);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_replace_card_keeps_a_valid_permutation() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let mut outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let mut card_share_handles = shuffle_deck(&mut evaluator).await.unwrap().handles;
+            let before = card_share_handles.clone();
+
+            let mut dealable = DECK_SIZE;
+            let replacement = evaluator.replace_card(&mut card_share_handles, &mut dealable, 5).await.unwrap();
+            assert_eq!(card_share_handles[5], replacement);
+            assert_ne!(replacement, before[5]);
+            assert_eq!(card_share_handles[..5], before[..5]);
+            // burn the card dealt next a few more times
+            for position in 6..16 {
+                evaluator.replace_card(&mut card_share_handles, &mut dealable, position).await.unwrap();
+            }
+
+            // the burned cards sit behind the dealable ones, and so do the
+            // cards that were never dealable
+            assert_eq!(dealable, DECK_SIZE - 11);
+            let burned: HashSet<&String> = card_share_handles[dealable..DECK_SIZE].iter().collect();
+            assert!(burned.contains(&before[5]));
+            assert_eq!(burned.len(), 11);
+            assert_eq!(card_share_handles[DECK_SIZE..], before[DECK_SIZE..]);
+
+            let card_shares = card_share_handles
+                .iter()
+                .map(|h| evaluator.get_wire(h))
+                .collect::<Vec<Share>>();
+            let perm_proof = compute_permutation_argument(
+                pp_ref, 
+                &mut evaluator, 
+                card_share_handles.clone(), 
+                &card_shares,
+                &card_mapping_commitment()
+            ).await.unwrap();
+
+            let old_card = evaluator.output_wire(&before[5]).await;
+            let new_card = evaluator.output_wire(&replacement).await;
+            let dealt = evaluator.batch_output_wire(&card_share_handles[..dealable]).await;
+            (perm_proof, old_card, new_card, dealt)
+        }).await;
+        let (perm_proof, old_card, new_card, dealt) = outputs.remove(0);
+
+        assert_ne!(old_card, new_card);
+        // the burned card is never dealt again
+        assert!(!dealt.contains(&old_card));
+        assert!(Verifier::new(pp).verify_permutation_argument(&perm_proof).await);
+    }
+
+    #[async_std::test]
+    async fn test_deal_hand_to_three_players() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+        let players = [BigUint::from(101u8), BigUint::from(102u8), BigUint::from(103u8)];
+        let positions = [0, 1, 2, 3, 4, 5];
+        let recipients = [0, 1, 2, 0, 1, 2]
+            .iter()
+            .map(|&p| players[p].clone())
+            .collect::<Vec<BigUint>>();
+        let recipients_ref = &recipients;
+
+        let mut outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let ShuffledDeck { handles: card_share_handles, shares: card_shares, .. } = shuffle_deck(&mut evaluator).await.unwrap();
+
+            let pk = G2::generator().mul(F::from(TEST_MSK)).into_affine();
+            prove_deal(
+                pp_ref, 
+                &mut evaluator, 
+                card_share_handles, 
+                &card_shares,
+                &card_mapping_commitment(),
+                pk, 
+                &positions, 
+                recipients_ref
+            ).await.unwrap()
+        }).await;
+        let combined = outputs.remove(0);
+
+        let verifier = Verifier::new(pp);
+        assert!(verifier.verify_combined(&combined).await);
+        // neither proof has the challenges of a proof on its own
+        assert!(!verifier.verify_permutation_argument(&combined.perm).await);
+        assert!(!verifier.verify_encryption_proof(&combined.enc).await);
+        assert!(verifier.verify_combined_deal(&combined, &positions, &recipients).await);
+        assert_eq!(combined.enc.ids[6], undealt_card_id());
+
+        // the same proof does not deal player 0's first card to player 1
+        let mut swapped = recipients.clone();
+        swapped[0] = players[1].clone();
+        assert!(!verifier.verify_combined_deal(&combined, &positions, &swapped).await);
+
+        // a position dealt twice, or outside the deck, is no deal
+        let mut twice = positions;
+        twice[1] = twice[0];
+        assert!(!verifier.verify_combined_deal(&combined, &twice, &recipients).await);
+        let mut outside = positions;
+        outside[0] = PERM_SIZE;
+        assert!(!verifier.verify_combined_deal(&combined, &outside, &recipients).await);
+
+        // nor is a card dealt to the id no one has the key of
+        let mut to_no_one = recipients.clone();
+        to_no_one[0] = undealt_card_id();
+        assert!(!verifier.verify_combined_deal(&combined, &positions, &to_no_one).await);
+        assert_eq!(deal_ids(&positions, &to_no_one), None);
+    }
+
+    #[async_std::test]
+    async fn test_combined_proof_with_keccak_big_endian_challenges() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+        let positions = [0, 1];
+        let recipients = [BigUint::from(101u8), BigUint::from(102u8)];
+        let recipients_ref = &recipients;
+
+        let mut outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let ShuffledDeck { handles: card_share_handles, shares: card_shares, .. } = shuffle_deck(&mut evaluator).await.unwrap();
+
+            let pk = G2::generator().mul(F::from(TEST_MSK)).into_affine();
+            prove_deal_in(
+                pp_ref,
+                &mut evaluator,
+                card_share_handles,
+                &card_shares,
+                &card_mapping_commitment(),
+                pk,
+                &positions,
+                recipients_ref,
+                CombinedTranscript::new()
+                    .with_hash(utils::HashConfig::Keccak256)
+                    .with_field_encoding(utils::FieldEncoding::BigEndian)
+            ).await.unwrap()
+        }).await;
+        let combined = outputs.remove(0);
+
+        // the verifier recomputes every challenge with Keccak over big-endian
+        // field elements too; with another hash or byte order it draws other ones
+        let keccak = || Verifier::new(utils::setup_kzg(1024)).with_hash(utils::HashConfig::Keccak256);
+        assert!(keccak().with_field_encoding(utils::FieldEncoding::BigEndian).verify_combined(&combined).await);
+        assert!(!keccak().verify_combined(&combined).await);
+        assert!(!Verifier::new(utils::setup_kzg(1024)).with_field_encoding(utils::FieldEncoding::BigEndian).verify_combined(&combined).await);
+        assert!(!Verifier::new(pp).verify_combined(&combined).await);
+    }
+
+    #[async_std::test]
+    async fn test_encryption_proof_rejects_divergent_c1() {
+        let run = protocol_run();
+        let proof = &run.encrypt_proof;
+        let sigma = proof.sigma_proof.as_ref().unwrap();
+        let verifier = Verifier::new(utils::setup_kzg(1024));
+
+        let s = proof.recompute_challenges();
+        assert_eq!(batched_c1(&proof.ciphertexts, &s), Some(proof.ciphertexts[0].0));
+        assert!(verifier.verify_encryption_proof(proof).await);
+
+        // one card encrypted under other randomness than the rest
+        let mut divergent = proof.clone();
+        divergent.ciphertexts[7].0 = (divergent.ciphertexts[7].0 + G2::generator()).into_affine();
+        let s = divergent.recompute_challenges();
+        let c1 = batched_c1(&divergent.ciphertexts, &s).unwrap();
+        assert_ne!(c1, divergent.ciphertexts[0].0);
+        assert_ne!(c1, divergent.ciphertexts[7].0);
+
+        // statement 2 of the sigma proof proves knowledge of r only for the
+        // honest c1, so it fails on the batched one
+        let mut bytes = [Vec::new(), Vec::new(), Vec::new()];
+        sigma.a1.serialize_uncompressed(&mut bytes[0]).unwrap();
+        sigma.a2.serialize_uncompressed(&mut bytes[1]).unwrap();
+        sigma.a3.serialize_uncompressed(&mut bytes[2]).unwrap();
+        let gamma = utils::fs_hash(vec![&bytes[0], &bytes[1], &bytes[2]], 1)[0];
+        assert_eq!(G2::generator().mul(sigma.y), proof.ciphertexts[0].0.mul(gamma) + sigma.a2);
+        assert_ne!(G2::generator().mul(sigma.y), c1.mul(gamma) + sigma.a2);
+
+        assert!(!verifier.verify_encryption_proof(&divergent).await);
+    }
+
+    /// the three checks of the encryption sigma proof, written out
+    fn encryption_sigma_checks(
+        c: &G1, d_batch: &G1, 
+        g: &G2, c_1: &G2,
+        e_batch: &Gt, c2_batch: &Gt,
+        sigma: &SigmaProof
+    ) -> bool {
+        let mut bytes = [Vec::new(), Vec::new(), Vec::new()];
+        sigma.a1.serialize_uncompressed(&mut bytes[0]).unwrap();
+        sigma.a2.serialize_uncompressed(&mut bytes[1]).unwrap();
+        sigma.a3.serialize_uncompressed(&mut bytes[2]).unwrap();
+        let gamma = utils::fs_hash(vec![&bytes[0], &bytes[1], &bytes[2]], 1)[0];
+
+        c.mul(sigma.x) == d_batch.mul(gamma) + sigma.a1
+            && g.mul(sigma.y) == c_1.mul(gamma) + sigma.a2
+            && Gt::generator().mul(sigma.x) + e_batch.mul(sigma.y) == c2_batch.mul(gamma) + sigma.a3
+    }
+
+    #[test]
+    fn test_encryption_statement_via_dsl_verifies_identically() {
+        let proof = &protocol_run().encrypt_proof;
+        let (d_batch, e_batch, c2_batch) = batched_sigma_statement(proof);
+        let c_1 = proof.ciphertexts[0].0;
+        let sigma = proof.sigma_proof.as_ref().unwrap();
+
+        let statement = SigmaStatement::new(2)
+            .relation(vec![(SigmaElement::G1(proof.card_commitment), 0)], SigmaElement::G1(d_batch))
+            .relation(vec![(SigmaElement::G2(G2::generator()), 1)], SigmaElement::G2(c_1))
+            .relation(
+                vec![(SigmaElement::Gt(Box::new(Gt::generator())), 0), (SigmaElement::Gt(Box::new(e_batch)), 1)],
+                SigmaElement::Gt(Box::new(c2_batch))
+            );
+        assert!(statement.is_well_formed());
+
+        let mut tampered = sigma.clone();
+        tampered.x += F::one();
+        let mut swapped = sigma.clone();
+        std::mem::swap(&mut swapped.x, &mut swapped.y);
+
+        for candidate in [sigma, &tampered, &swapped] {
+            let expected = encryption_sigma_checks(
+                &proof.card_commitment, &d_batch, &G2::generator(), &c_1, &e_batch, &c2_batch, candidate
+            );
+            assert_eq!(verify_encryption_sigma(&statement, candidate), expected);
+            let generic = SigmaStatementProof::from(candidate);
+            assert_eq!(SigmaProof::try_from(&generic).unwrap().to_bytes(), candidate.to_bytes());
+        }
+        assert!(verify_encryption_sigma(&statement, sigma));
+        // the generic verifier binds the statement into its challenge, so
+        // it does not accept a proof in the encryption format
+        assert!(!verify_sigma(&statement, &SigmaStatementProof::from(sigma)));
+
+        // a statement whose bases are in the wrong group is rejected outright
+        let mixed = SigmaStatement::new(1)
+            .relation(vec![(SigmaElement::G2(G2::generator()), 0)], SigmaElement::G1(d_batch));
+        assert!(!mixed.is_well_formed());
+    }
+
+    #[test]
+    fn test_sigma_proof_rejects_substituted_commitment() {
+        let proof = &protocol_run().encrypt_proof;
+        let (d_batch, e_batch, c2_batch) = batched_sigma_statement(proof);
+        let sigma = proof.sigma_proof.as_ref().unwrap();
+
+        let verify = |sigma: &SigmaProof| local_verify_sigma_proof(
+            &proof.card_commitment, 
+            &d_batch, 
+            &G2::generator(), 
+            &proof.ciphertexts[0].0, 
+            &e_batch, 
+            &c2_batch, 
+            sigma
+        );
+        assert!(verify(sigma));
+
+        // gamma hashes a1, a2 and a3, and each of them appears in exactly one
+        // check, so replacing any one commitment (keeping the other two and
+        // the responses) must be caught
+        let mut forged = sigma.clone();
+        forged.a1 = (forged.a1 + G1::generator()).into_affine();
+        assert!(!verify(&forged));
+
+        let mut forged = sigma.clone();
+        forged.a2 = (forged.a2 + G2::generator()).into_affine();
+        assert!(!verify(&forged));
+
+        let mut forged = sigma.clone();
+        forged.a3 += Gt::generator();
+        assert!(!verify(&forged));
+    }
+
+    #[test]
+    fn test_describe_opened_card_out_of_domain() {
+        let card_mapping = map_roots_of_unity_to_cards();
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+
+        assert_eq!(describe_opened_card(&card_mapping, &F::one()), Card::from_index(0).unwrap().to_string());
+        assert_eq!(describe_opened_card(&card_mapping, &utils::compute_power(&ω, 100)), "padding");
+        // not a PERM_SIZE-th root of unity, so no card can ever open to it
+        assert_eq!(describe_opened_card(&card_mapping, &F::from(3u64)), "unknown card");
+    }
+
+    #[async_std::test]
+    async fn test_print_opened_deck_out_of_domain() {
+        // a deck holding a value outside the mapping prints instead of panicking
+        run_parties(2, |mut evaluator| async move {
+            let handles = vec![
+                evaluator.fixed_wire_handle(F::one()),
+                evaluator.fixed_wire_handle(F::from(3u64)),
+            ];
+            let shares = handles.iter().map(|handle| evaluator.get_wire(handle)).collect();
+            let deck = ShuffledDeck { handles, shares, positions: vec![], f_com: None, samples: 0 };
+            print_opened_deck(&mut evaluator, &deck).await;
+        }).await;
+    }
+
+    #[test]
+    fn test_parse_addr_book_assigns_contiguous_node_ids() {
+        let addr_book = parse_addr_book_from_json(4).unwrap();
+        assert_eq!(addr_book.len(), 4);
+
+        let mut node_ids: Vec<u64> = addr_book.values().map(|peer| peer.node_id).collect();
+        node_ids.sort();
+        assert_eq!(node_ids, vec![0, 1, 2, 3]);
+
+        // ids follow the order of the configured list, not of the map
+        assert_eq!(
+            get_node_id_via_peer_id(&addr_book, &String::from("12D3KooWPjceQrSwdWXPyLLeABRXmuqt69Rg3sBYbU1Nft9HyQ6X")), 
+            Some(0)
+        );
+        assert_eq!(parse_addr_book_from_json(0).err(), Some(AddrBookError::Empty));
+    }
+
+    #[async_std::test]
+    async fn test_cancel_mid_shuffle_deck() {
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let token = evaluator.cancellation_token();
+            let canceller = async {
+                task::sleep(Duration::from_millis(50)).await;
+                token.cancel();
+                Instant::now()
+            };
+
+            let (result, cancelled_at) = futures::join!(
+                cancellable(&token, shuffle_deck(&mut evaluator)), 
+                canceller
+            );
+            (result.map(|_| ()), cancelled_at.elapsed(), token.is_cancelled())
+        }).await;
+
+        for (result, since_cancel, is_cancelled) in outputs {
+            assert_eq!(result, Err(EvaluatorError::Cancelled));
+            assert!(since_cancel < Duration::from_millis(500), "took {:?} to cancel", since_cancel);
+            assert!(is_cancelled);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_shuffle_seed_reveal_matches_commitment() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let (deck, commitment) = shuffle_deck_with_commitment(pp_ref, &mut evaluator).await.unwrap();
+            // ... the game is played, and the board is opened ...
+            let f_share = utils::interpolate_poly_over_mult_subgroup(&Share::raw(&deck.shares));
+            let board = evaluator.reveal_positions(
+                pp_ref, &deck.handles, &[0, 20, 21, 22, 100], &f_share, &commitment.f_com
+            ).await.unwrap();
+            let openings = reveal_shuffle_seed(&mut evaluator, &commitment).await.unwrap();
+            (commitment, openings, board)
+        }).await;
+
+        for (commitment, openings, board) in outputs.iter() {
+            assert_eq!(commitment.commitments.len(), 3);
+            assert_eq!(commitment, &outputs[0].0);
+            assert!(audit_shuffle_seed(&pp, commitment, openings, board));
+
+            // a party claiming another share of sk is caught
+            let mut forged = openings.clone();
+            forged[1].1 += F::one();
+            assert!(!audit_shuffle_seed(&pp, commitment, &forged, board));
+
+            // a shuffle that was keyed by some other sk
+            let mut other_shuffle = commitment.clone();
+            other_shuffle.card_prfs[20] = G1::generator();
+            assert!(!audit_shuffle_seed(&pp, &other_shuffle, openings, board));
+
+            // a card that is not the one committed at its position
+            let mut swapped = board.clone();
+            swapped[1].1 = board[2].1;
+            assert!(!audit_shuffle_seed(&pp, commitment, openings, &swapped));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_shuffle_matches_reference_shuffle() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let (deck, commitment) = shuffle_deck_with_commitment(pp_ref, &mut evaluator).await.unwrap();
+            let cards = evaluator.batch_output_wire(&deck.handles).await;
+            let sk = evaluator.output_wire(&commitment.sk).await;
+            (cards, sk, commitment.card_prfs)
+        }).await;
+        let (cards, sk, card_prfs) = &outputs[0];
+
+        let reference = utils::reference_shuffle(*sk, PERM_SIZE);
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let canonical = utils::power_table(&ω, PERM_SIZE);
+
+        // the prf opened for each position is the reference prf of the card
+        // that position holds, and every card is held once
+        let mut held = vec![false; PERM_SIZE];
+        for (card, y_p) in cards.iter().zip(card_prfs) {
+            let index = canonical.iter().position(|ω_pow_i| ω_pow_i == card).unwrap();
+            assert!(!held[index]);
+            held[index] = true;
+            assert_eq!(*y_p, G1::generator().mul(reference[index]).into_affine());
+        }
+    }
+
+    #[async_std::test]
+    async fn test_shuffle_deck_stream_matches_shuffle_deck() {
+        // same seeds, so both runs sample the same cards
+        let decks = run_parties(3, |evaluator| async move {
+            shuffle_deck(&mut seeded_evaluator(evaluator)).await.unwrap()
+        }).await;
+        let streamed = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            shuffle_deck_stream(&mut evaluator, JokerPolicy::Fixed).try_collect::<Vec<(String, Share)>>().await.unwrap()
+        }).await;
+
+        for (deck, cards) in decks.into_iter().zip(streamed) {
+            assert_eq!(cards.len(), PERM_SIZE);
+            let (handles, shares): (Vec<String>, Vec<Share>) = cards.into_iter().unzip();
+            assert_eq!(handles, deck.handles);
+            assert_eq!(shares, deck.shares);
+        }
+    }
+
+    /// shuffles run by test_shuffle_position_is_uniform
+    const UNIFORMITY_RUNS: u64 = 160;
+    /// position buckets of the chi-squared test, ~20 runs expected in each
+    const UNIFORMITY_BUCKETS: usize = 8;
+    /// 99.9% quantile of the chi-squared distribution with
+    /// UNIFORMITY_BUCKETS - 1 = 7 degrees of freedom
+    const CHI_SQUARED_7_999: f64 = 24.32;
+
+    /// slow (a few seconds per shuffle), so run it with --ignored. For an
+    /// unbiased shuffle the statistic has mean 7 and variance 14, and exceeds
+    /// CHI_SQUARED_7_999 in one of 1000 runs of this test; a bias of a few
+    /// percent of the runs towards some buckets shows up far above it
+    #[async_std::test]
+    #[ignore]
+    async fn test_shuffle_position_is_uniform() {
+        let jokers = PERM_SIZE - DECK_SIZE;
+        // card ω^0 is a real card, so it lands on any position but the jokers'
+        let positions = DECK_SIZE;
+        let bucket_of = |position: usize| (position - jokers) * UNIFORMITY_BUCKETS / positions;
+
+        let mut counts = [0usize; UNIFORMITY_BUCKETS];
+        for run in 0..UNIFORMITY_RUNS {
+            let decks = run_parties(2, |evaluator| async move {
+                let seed = run * 1000 + evaluator.node_id() as u64;
+                let mut evaluator = evaluator.with_rng(StdRng::seed_from_u64(seed));
+                // a shuffle that misses cards is dropped rather than retried;
+                // whether one does is independent of where ω^0 lands
+                let cards = shuffle_deck_stream(&mut evaluator, JokerPolicy::Fixed)
+                    .try_collect::<Vec<(String, Share)>>()
+                    .await
+                    .unwrap();
+                if cards.len() < PERM_SIZE {
+                    return None;
+                }
+
+                let handles: Vec<String> = cards.into_iter().map(|(handle, _)| handle).collect();
+                Some(evaluator.batch_output_wire(&handles).await)
+            }).await;
+
+            if let Some(deck) = &decks[0] {
+                let position = deck.iter().position(|card| *card == F::one()).unwrap();
+                counts[bucket_of(position)] += 1;
+            }
+        }
+
+        let runs: usize = counts.iter().sum();
+        let mut chi_squared = 0.0;
+        for (bucket, count) in counts.iter().enumerate() {
+            let size = (jokers..PERM_SIZE).filter(|p| bucket_of(*p) == bucket).count();
+            let expected = (runs * size) as f64 / positions as f64;
+            chi_squared += (*count as f64 - expected).powi(2) / expected;
+        }
+        assert!(chi_squared < CHI_SQUARED_7_999, 
+            "positions of ω^0 {:?} over {} shuffles are not uniform: chi-squared {}", counts, runs, chi_squared);
+    }
+
+    #[test]
+    fn test_verify_decrypted_card() {
+        let run = protocol_run();
+        let proof = &run.encrypt_proof;
+        let i = 15;
+
+        let key_of = |id: &BigUint| G1::generator().mul(F::from(id.clone()) * F::from(TEST_MSK)).into_affine();
+        let decryption_key = key_of(&proof.ids[i]);
+
+        // decrypt card i the way a player does: find the power of ω that the
+        // recovered mask takes to masked_evals[i]
+        let (c1, c2) = proof.ciphertexts[i];
+        let exp_mask = c2 - <Curve>::pairing(decryption_key, c1);
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let card = utils::power_table(&ω, PERM_SIZE)
+            .into_iter()
+            .find(|card| exp_mask.mul(card) == Gt::generator().mul(proof.masked_evals[i]))
+            .unwrap();
+
+        assert!(verify_decrypted_card(&run.pp, proof, i, card, &decryption_key));
+
+        // any other card, or the right card claimed for another position, fails
+        assert!(!verify_decrypted_card(&run.pp, proof, i, card * ω, &decryption_key));
+        assert!(!verify_decrypted_card(&run.pp, proof, i + 1, card, &key_of(&proof.ids[i + 1])));
+        // as does a key that is not the one of ids[i]
+        assert!(!verify_decrypted_card(&run.pp, proof, i, card, &key_of(&proof.ids[i + 1])));
+        assert!(!verify_decrypted_card(&run.pp, proof, PERM_SIZE, card, &decryption_key));
+    }
+
+    #[async_std::test]
+    async fn test_run_result_json() {
+        let run = protocol_run();
+        let verifier = Verifier::new(utils::setup_kzg(1024));
+        let result = RunResult {
+            permutation: verifier.verify_permutation_argument(&run.perm_proof).await,
+            encryption: verifier.verify_encryption_proof(&run.encrypt_proof).await,
+            shuffle_attempts: 2079,
+            elapsed_ms: 1234,
+        };
+        assert!(result.verified());
+
+        let parsed: serde_json::Value = serde_json::from_str(&result.render(OutputFormat::Json)).unwrap();
+        assert_eq!(parsed, json!({
+            "permutation": true,
+            "encryption": true,
+            "shuffle_attempts": 2079,
+            "elapsed_ms": 1234
+        }));
+
+        // the default stays human-readable
+        assert_eq!(
+            result.render(OutputFormat::default()),
+            "Permutation argument verified\nEncryption proof verified"
+        );
+    }
+
+    #[test]
+    fn test_capabilities_json() {
+        let parsed: serde_json::Value = serde_json::from_str(&capabilities().to_json()).unwrap();
+        assert_eq!(parsed["curve"], "bls12-377");
+        assert_eq!(parsed["proof_format_version"], PROOF_FORMAT_VERSION);
+        assert_eq!(parsed["deck_sizes"], json!([DECK_SIZE]));
+
+        // the flag stands on its own, without the arguments a game needs
+        let args = Args::try_parse_from(["pok3r", "--capabilities"]).unwrap();
+        assert!(args.capabilities && args.id.is_none());
+        assert!(Args::try_parse_from(["pok3r"]).is_err());
+    }
+
+    #[async_std::test]
+    async fn test_shuffle_reports_increasing_progress() {
+        let outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let progress = evaluator.subscribe_progress();
+
+            // a UI reads along while the shuffle runs
+            let watcher = progress
+                .take_while(|p| futures::future::ready(p.step < p.total))
+                .collect::<Vec<Progress>>();
+            let (deck, events) = futures::join!(shuffle_deck(&mut evaluator), watcher);
+            assert!(deck.is_ok());
+            events
+        }).await;
+
+        for events in outputs {
+            // the fixed cards, then every batch but the last, which the
+            // watcher saw come in and stopped at
+            assert_eq!(events[0].step, 1);
+            assert!(events.len() <= MAX_SHUFFLE_BATCHES);
+            assert!(events.iter().all(|p| p.phase == ProgressPhase::Shuffle && p.total == PERM_SIZE + 1));
+            assert!(events.windows(2).all(|w| w[0].step < w[1].step));
+        }
+    }
+
+    #[test]
+    fn test_shuffle_batch_size() {
+        assert_eq!(shuffle_batch_size(0), 0);
+        // a single missing card takes PERM_SIZE samples to expect it
+        assert_eq!(shuffle_batch_size(1), 3 * PERM_SIZE);
+        // H(255) is about 6.12, so the first batch expects a full deck
+        // after ~1567 samples and draws ~512 more
+        assert!((2075..2085).contains(&shuffle_batch_size(DECK_SIZE)));
+        assert!((1..=PERM_SIZE).all(|k| shuffle_batch_size(k - 1) < shuffle_batch_size(k)));
+    }
+
+    #[async_std::test]
+    async fn test_shuffle_completes_in_few_batches() {
+        let outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let sk = evaluator.ran().unwrap();
+            let sent_before = evaluator.messages_sent();
+            let rounds = shuffle_rounds_with_sk(&mut evaluator, sk, JokerPolicy::Fixed)
+                .try_collect::<Vec<ShuffleRound>>()
+                .await
+                .unwrap();
+            let sent = evaluator.messages_sent() - sent_before;
+            (rounds, sent)
+        }).await;
+
+        for (rounds, sent) in outputs {
+            // the fixed cards, then one batch, or a second one for a card or two
+            let batches = rounds.len() - 1;
+            assert!((1..=2).contains(&batches), "{} batches", batches);
+            assert_eq!(rounds.iter().map(|round| round.cards.len()).sum::<usize>(), PERM_SIZE);
+            assert_eq!(rounds[1].samples, shuffle_batch_size(DECK_SIZE));
+
+            // a batch takes a fixed number of rounds, with up to 256 values
+            // in a message, where sampling one by one took rounds per sample
+            let samples: usize = rounds.iter().map(|round| round.samples).sum();
+            assert!(sent < samples / 4, "{} messages for {} samples", sent, samples);
+        }
+    }
+
+    #[test]
+    fn test_short_deck_is_wrong_deck_size() {
+        let card = |i: usize| (format!("card_{}", i), Share::default());
+
+        let short: Vec<(String, Share)> = (0..PERM_SIZE - 1).map(card).collect();
+        assert_eq!(
+            ShuffledDeck::new(short),
+            Err(ShuffleError::WrongDeckSize { got: PERM_SIZE - 1, expected: PERM_SIZE })
+        );
+
+        // handles and shares that disagree in length are caught as well
+        let (handles, mut shares): (Vec<String>, Vec<Share>) = (0..PERM_SIZE).map(card).unzip();
+        shares.pop();
+        assert_eq!(
+            ShuffledDeck::from_parts(handles, shares),
+            Err(ShuffleError::WrongDeckSize { got: PERM_SIZE - 1, expected: PERM_SIZE })
+        );
+
+        let full: Vec<(String, Share)> = (0..PERM_SIZE).map(card).collect();
+        assert!(ShuffledDeck::new(full).is_ok());
+    }
+
+    #[async_std::test]
+    async fn test_shuffled_deck_positions() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let mut deck = shuffle_deck(&mut evaluator).await.unwrap();
+            assert_eq!(deck.f_com, None);
+
+            let perm_proof = compute_permutation_argument(
+                pp_ref, 
+                &mut evaluator, 
+                deck.handles.clone(), 
+                &deck.shares,
+                &card_mapping_commitment()
+            ).await.unwrap();
+            deck.f_com = Some(perm_proof.f_com);
+
+            let cards = evaluator.batch_output_wire(&deck.handles).await;
+            (deck, cards)
+        }).await;
+
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        for (deck, cards) in outputs {
+            for i in 0..PERM_SIZE {
+                assert_eq!(deck.positions[i], utils::compute_power(&ω, i as u64));
+                assert_eq!(deck.handle_at(&deck.positions[i]), Some(&deck.handles[i]));
+            }
+
+            // f(X) takes the card on handles[i] at positions[i]
+            let f = utils::interpolate_poly_over_mult_subgroup(&cards);
+            assert_eq!(deck.f_com, Some(utils::commit_poly(&pp, &f)));
+            assert_eq!(deck.handle_at(&F::from(2u64)), None);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_shuffle_deck_committed_matches_permutation_argument() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let outputs = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let mut deck = shuffle_deck_committed(pp_ref, &mut evaluator).await.unwrap();
+
+            let perm_proof = compute_committed_permutation_argument(
+                pp_ref, 
+                &mut evaluator, 
+                &deck,
+                &card_mapping_commitment()
+            ).await.unwrap();
+
+            // an argument about another deck than the committed one fails
+            deck.f_com = Some(G1::generator());
+            let mismatch = compute_committed_permutation_argument(
+                pp_ref, 
+                &mut evaluator, 
+                &deck,
+                &card_mapping_commitment()
+            ).await.err();
+            (perm_proof.f_com, mismatch)
+        }).await;
+
+        for (f_com, mismatch) in outputs.iter() {
+            assert_eq!(*f_com, outputs[0].0);
+            assert_eq!(*mismatch, Some(EvaluatorError::DeckCommitmentMismatch));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_resume_from_checkpoint_after_permutation() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let checkpoints = run_parties(3, |evaluator| async move {
+            let mut evaluator = seeded_evaluator(evaluator);
+            let deck = shuffle_deck(&mut evaluator).await.unwrap();
+            let perm_proof = compute_permutation_argument(
+                pp_ref,
+                &mut evaluator,
+                deck.handles.clone(),
+                &deck.shares,
+                &card_mapping_commitment()
+            ).await.unwrap();
+
+            evaluator.checkpoint(&deck.handles, Some(&perm_proof)).to_bytes()
+        }).await;
+        let checkpoints = &checkpoints;
+
+        // the run continues with new evaluators, as after a restart
+        let proofs = run_parties(3, |mut evaluator| async move {
+            let checkpoint = Checkpoint::from_bytes(&checkpoints[evaluator.node_id()]).unwrap();
+            evaluator.resume_from(&checkpoint).await.unwrap();
+
+            let perm_proof = checkpoint.perm_proof.unwrap();
+            let pk = G2::generator().mul(F::from(TEST_MSK)).into_affine();
+            let encrypt_proof = encrypt_and_prove(
+                pp_ref,
+                &mut evaluator,
+                checkpoint.handles,
+                perm_proof.f_com,
+                pk,
+                test_ids()
+            ).await.unwrap();
+            (perm_proof, encrypt_proof)
+        }).await;
+
+        let verifier = Verifier::new(utils::setup_kzg(1024));
+        let (perm_proof, encrypt_proof) = &proofs[0];
+        assert!(verifier.verify_permutation_argument(perm_proof).await);
+        assert!(verifier.verify_encryption_proof(encrypt_proof).await);
+        assert_eq!(encrypt_proof.card_commitment, perm_proof.f_com);
+
+        // a party resuming from an earlier point is caught before it computes anything
+        let results = run_parties(3, |mut evaluator| async move {
+            let mut checkpoint = Checkpoint::from_bytes(&checkpoints[evaluator.node_id()]).unwrap();
+            if evaluator.node_id() == 2 {
+                checkpoint.perm_proof = None;
+            }
+            evaluator.resume_from(&checkpoint).await
+        }).await;
+        assert_eq!(results[0], Err(EvaluatorError::CheckpointMismatch { sender: String::from("party_2") }));
+        assert_eq!(results[2], Err(EvaluatorError::CheckpointMismatch { sender: String::from("party_0") }));
+    }
+
+    #[async_std::test]
+    async fn test_encrypt_rejects_mismatched_lengths() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let results = run_parties(3, |mut evaluator| async move {
+            let handles: Vec<String> = (0..PERM_SIZE)
+                .map(|i| evaluator.fixed_wire_handle(F::from(i as u64)))
+                .collect();
+            let pk = G2::generator().mul(F::from(TEST_MSK)).into_affine();
+
+            let short_ids = test_ids()[..32].to_vec();
+            let too_few_ids = encrypt_and_prove(
+                pp_ref, &mut evaluator, handles.clone(), G1::generator(), pk, short_ids
+            ).await;
+            let too_few_cards = encrypt_and_prove(
+                pp_ref, &mut evaluator, handles[..PERM_SIZE - 1].to_vec(), G1::generator(), pk, test_ids()
+            ).await;
+            (too_few_ids.err(), too_few_cards.err())
+        }).await;
+
+        for (too_few_ids, too_few_cards) in results {
+            assert_eq!(too_few_ids, Some(EncryptError::LengthMismatch { cards: PERM_SIZE, ids: 32, expected: PERM_SIZE }));
+            assert_eq!(too_few_cards, Some(EncryptError::LengthMismatch { cards: PERM_SIZE - 1, ids: PERM_SIZE, expected: PERM_SIZE }));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_encrypt_rejects_identity_public_key() {
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let results = run_parties(3, |mut evaluator| async move {
+            let handles: Vec<String> = (0..PERM_SIZE)
+                .map(|i| evaluator.fixed_wire_handle(F::from(i as u64)))
+                .collect();
+            encrypt_and_prove(pp_ref, &mut evaluator, handles, G1::generator(), G2::zero(), test_ids()).await.err()
+        }).await;
+        assert!(results.iter().all(|err| *err == Some(EncryptError::InvalidPublicKey)));
+
+        // nor does a verifier accept a proof under the identity
+        let run = protocol_run();
+        let mut proof = run.encrypt_proof.clone();
+        assert!(local_verify_encryption_proof(&run.pp, &proof, EvalProofMode::Eager).await);
+        proof.pk = G2::zero();
+        assert!(!local_verify_encryption_proof(&run.pp, &proof, EvalProofMode::Eager).await);
+    }
+
+    #[async_std::test]
+    async fn test_encrypt_rejects_id_outside_field() {
+        use ark_ff::PrimeField;
+
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+
+        let results = run_parties(3, |mut evaluator| async move {
+            let modulus: BigUint = F::MODULUS.into();
+            let mut ids = test_ids();
+            ids[3] = modulus.clone();
+            let handles: Vec<String> = (0..PERM_SIZE)
+                .map(|i| evaluator.fixed_wire_handle(F::from(i as u64)))
+                .collect();
+            let pk = G2::generator().mul(F::from(TEST_MSK)).into_affine();
+
+            let to_all = encrypt_and_prove(pp_ref, &mut evaluator, handles.clone(), G1::generator(), pk, ids).await.err();
+            let to_many = encrypt_to_many(&mut evaluator, &handles[0], &[BigUint::from(1u8), modulus], pk).await.err();
+            (to_all, to_many)
+        }).await;
+        for (to_all, to_many) in results {
+            assert_eq!(to_all, Some(EncryptError::InvalidId(utils::IdError::IdTooLarge)));
+            assert_eq!(to_many, Some(EncryptError::InvalidId(utils::IdError::IdTooLarge)));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_encryption_proof_verifies_after_round_trip() {
+        let run = protocol_run();
+        let reloaded = EncryptProof::from_bytes(&run.encrypt_proof.to_bytes()).unwrap();
+        assert!(local_verify_encryption_proof(&run.pp, &reloaded, EvalProofMode::Eager).await);
+
+        // a proof without its sigma proof is rejected, not a panic
+        let mut unproven = reloaded.clone();
+        unproven.sigma_proof = None;
+        let reloaded = EncryptProof::from_bytes(&unproven.to_bytes()).unwrap();
+        assert!(reloaded.sigma_proof.is_none());
+        assert!(!local_verify_encryption_proof(&run.pp, &reloaded, EvalProofMode::Eager).await);
+    }
+
+    #[async_std::test]
+    async fn test_lazy_eval_proofs_match_eager_mode() {
+        // for the same masks, a proof computed on reveal is the eager one
+        let run = protocol_run();
+        for (i, pi) in run.lazy_eval_proofs.iter() {
+            assert_eq!(*pi, run.encrypt_proof.eval_proofs[*i]);
+        }
+
+        let pp = utils::setup_kzg(1024);
+        let pp_ref = &pp;
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let cards = utils::power_table(&ω, PERM_SIZE);
+        let card_commitment = utils::commit_poly(&pp, &utils::interpolate_poly_over_mult_subgroup(&cards));
+        let cards = &cards;
+
+        let outputs = run_parties(3, |mut evaluator| async move {
+            let handles: Vec<String> = cards.iter()
+                .map(|card| evaluator.fixed_wire_handle(*card))
+                .collect();
+            let pk = G2::generator().mul(F::from(TEST_MSK)).into_affine();
+            let (proof, mut lazy) = encrypt_and_prove_with_mode(
+                pp_ref, &mut evaluator, handles, card_commitment, pk, test_ids(), EvalProofMode::Lazy
+            ).await.unwrap();
+
+            let pi = lazy.eval_proof(&mut evaluator, 15).await;
+            // asking again for the same card costs nothing
+            assert_eq!(lazy.eval_proof(&mut evaluator, 15).await, pi);
+            (proof, pi)
+        }).await;
+
+        let (proof, pi) = &outputs[0];
+        assert!(proof.eval_proofs.is_empty());
+        assert!(local_verify_encryption_proof(&pp, proof, EvalProofMode::Lazy).await);
+        // a proof without eval proofs does not pass as an eager one
+        assert!(!local_verify_encryption_proof(&pp, proof, EvalProofMode::Eager).await);
+        // nor does leaving them out skip the length checks
+        let mut short = proof.clone();
+        short.ids.truncate(32);
+        assert!(!local_verify_encryption_proof(&pp, &short, EvalProofMode::Lazy).await);
+
+        let x_f = utils::id_to_field_checked(&proof.ids[15]).unwrap();
+        let decryption_key = G1::generator().mul(x_f * F::from(TEST_MSK)).into_affine();
+        assert!(verify_decrypted_card_with_eval_proof(&pp, proof, 15, cards[15], &decryption_key, pi));
+        assert!(!verify_decrypted_card_with_eval_proof(&pp, proof, 16, cards[16], &decryption_key, pi));
+        assert!(!verify_decrypted_card(&pp, proof, 15, cards[15], &decryption_key));
+    }
+
+    #[async_std::test]
+    async fn test_encrypt_to_many_recipients() {
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let card = ω.pow([5u64]);
+        let ids = vec![BigUint::from(101u8), BigUint::from(102u8), BigUint::from(103u8)];
+        let ids_ref = &ids;
+
+        let mut outputs = run_parties(3, |mut evaluator| async move {
+            let card_handle = evaluator.fixed_wire_handle(card);
+            let pk = G2::generator().mul(F::from(TEST_MSK)).into_affine();
+            let encryption = encrypt_to_many(&mut evaluator, &card_handle, ids_ref, pk).await.unwrap();
+            let no_one = encrypt_to_many(&mut evaluator, &card_handle, &[], pk).await.err();
+            (encryption, no_one)
+        }).await;
+        let (encryption, no_one) = outputs.remove(0);
+        assert_eq!(no_one, Some(EncryptError::NoRecipients));
+        assert!(verify_multi_recipient_encryption(&encryption));
+
+        // every recipient decrypts the same card
+        for (id, (c1, c2)) in ids.iter().zip(&encryption.ciphertexts) {
+            let x_f = utils::id_to_field_checked(id).unwrap();
+            let decryption_key = G1::generator().mul(x_f * F::from(TEST_MSK)).into_affine();
+            let mask = <Curve as Pairing>::pairing(decryption_key, *c1);
+            assert_eq!(*c2 - mask, Gt::generator().mul(card));
+        }
+
+        // nor does the proof cover one recipient getting another card
+        let mut tampered = encryption.clone();
+        tampered.ciphertexts[1].1 += Gt::generator();
+        assert!(!verify_multi_recipient_encryption(&tampered));
+    }
+
+    #[async_std::test]
+    async fn test_joker_policies() {
+        // the jokers pad the real cards up to the permutation's domain
+        let (domain, padding) = utils::domain_for::<F>(DECK_SIZE).unwrap();
+        assert_eq!((domain.size(), padding), (PERM_SIZE, PERM_SIZE - DECK_SIZE));
+
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let powers_of_ω = utils::power_table(&ω, PERM_SIZE);
+        let jokers = &powers_of_ω[DECK_SIZE..PERM_SIZE];
+        let num_jokers = PERM_SIZE - DECK_SIZE;
+
+        for joker_policy in [JokerPolicy::Fixed, JokerPolicy::Shuffled, JokerPolicy::Excluded] {
+            let mut decks = run_parties(3, |evaluator| async move {
+                let mut evaluator = seeded_evaluator(evaluator);
+                let handles = shuffle_deck_with_joker_policy(&mut evaluator, joker_policy).await.unwrap().handles;
+                evaluator.batch_output_wire(&handles).await
+            }).await;
+            let deck = decks.remove(0);
+
+            // every policy deals every card exactly once
+            let mut sorted = deck.clone();
+            sorted.sort();
+            let mut expected = powers_of_ω.clone();
+            expected.sort();
+            assert_eq!(sorted, expected, "{:?}", joker_policy);
+
+            match joker_policy {
+                JokerPolicy::Fixed => assert_eq!(&deck[..num_jokers], jokers),
+                JokerPolicy::Excluded => {
+                    assert_eq!(&deck[DECK_SIZE..], jokers);
+                    assert!(deck[..DECK_SIZE].iter().all(|card| !jokers.contains(card)));
+                },
+                // not where Fixed puts them, at least for these seeds
+                JokerPolicy::Shuffled => assert_ne!(&deck[..num_jokers], jokers),
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_fingerprints() {
+        let run = protocol_run();
+        let sigma = run.encrypt_proof.sigma_proof.as_ref().unwrap();
+
+        let mut other_perm = run.perm_proof.clone();
+        other_perm.y2 += F::one();
+        assert_ne!(run.perm_proof.fingerprint(), other_perm.fingerprint());
+        assert_eq!(run.perm_proof.fingerprint(), run.perm_proof.clone().fingerprint());
+
+        let mut other_enc = run.encrypt_proof.clone();
+        other_enc.masked_evals[7] += F::one();
+        assert_ne!(run.encrypt_proof.fingerprint(), other_enc.fingerprint());
+
+        let mut other_sigma = sigma.clone();
+        other_sigma.x += F::one();
+        assert_ne!(sigma.fingerprint(), other_sigma.fingerprint());
+        // the sigma proof is part of the encryption proof's fingerprint
+        let mut resigned = run.encrypt_proof.clone();
+        resigned.sigma_proof = Some(other_sigma);
+        assert_ne!(run.encrypt_proof.fingerprint(), resigned.fingerprint());
+
+        // logs show fingerprints and sizes, not thousands of group elements
+        let logged = format!("{:?}", run.encrypt_proof);
+        assert!(logged.len() < 300, "{}", logged);
+        assert!(logged.contains(&format!("cards: {}", PERM_SIZE)));
+        assert!(format!("{:?}", run.perm_proof).starts_with("PermutationProof { fingerprint: "));
+    }
+
+    #[test]
+    fn test_proof_bytes_are_versioned() {
+        let run = protocol_run();
+        let sigma = run.encrypt_proof.sigma_proof.as_ref().unwrap();
+
+        let perm_bytes = run.perm_proof.to_bytes();
+        let enc_bytes = run.encrypt_proof.to_bytes();
+        let sigma_bytes = sigma.to_bytes();
+
+        for bytes in [&perm_bytes, &enc_bytes, &sigma_bytes] {
+            assert_eq!(bytes[..2], PROOF_MAGIC);
+            assert_eq!(bytes[2], PROOF_FORMAT_VERSION);
+        }
+
+        // round trips, the encryption proof with its sigma proof
+        assert_eq!(PermutationProof::from_bytes(&perm_bytes).unwrap().to_bytes(), perm_bytes);
+        let reloaded = EncryptProof::from_bytes(&enc_bytes).unwrap();
+        assert_eq!(reloaded.to_bytes(), enc_bytes);
+        assert_eq!(reloaded.sigma_proof.as_ref().map(|sigma| sigma.to_bytes()), Some(sigma_bytes.clone()));
+        assert_eq!(SigmaProof::from_bytes(&sigma_bytes).unwrap().to_bytes(), sigma_bytes);
+
+        // a proof written by some future layout is rejected before parsing
+        let mut future = enc_bytes.clone();
+        future[2] = PROOF_FORMAT_VERSION + 1;
+        assert!(matches!(
+            EncryptProof::from_bytes(&future), 
+            Err(ProofFormatError::VersionMismatch { found, expected }) 
+                if found == PROOF_FORMAT_VERSION + 1 && expected == PROOF_FORMAT_VERSION
+        ));
+
+        let mut future = perm_bytes.clone();
+        future[2] = PROOF_FORMAT_VERSION + 1;
+        assert!(PermutationProof::from_bytes(&future).is_err());
+
+        assert!(matches!(SigmaProof::from_bytes(&sigma_bytes[3..]), Err(ProofFormatError::BadMagic)));
+        assert!(matches!(
+            SigmaProof::from_bytes(&sigma_bytes[..sigma_bytes.len() - 1]), 
+            Err(ProofFormatError::Malformed)
+        ));
+    }
+
+    #[async_std::test]
+    async fn test_game_bundle_round_trip_verifies() {
+        let run = protocol_run();
+        let verifier = Verifier::new(utils::setup_kzg(1024));
+
+        let bundle = GameBundle::new(run.perm_proof.clone(), run.encrypt_proof.clone());
+        let bytes = bundle.to_bytes();
+        let reloaded = GameBundle::from_bytes(&bytes).unwrap();
+        assert_eq!(reloaded.to_bytes(), bytes);
+        assert!(reloaded.enc_proof.sigma_proof.is_some());
+        assert!(verifier.verify_bundle(&reloaded).await);
+
+        // fields that disagree with the proofs are rejected
+        let mut tampered = reloaded.clone();
+        tampered.f_com = (tampered.f_com + G1::generator()).into_affine();
+        assert!(!verifier.verify_bundle(&tampered).await);
+        let mut tampered = reloaded.clone();
+        tampered.ids.swap(0, 1);
+        assert!(!verifier.verify_bundle(&tampered).await);
+
+        assert!(matches!(GameBundle::from_bytes(&bytes[..bytes.len() - 1]), Err(ProofFormatError::Malformed)));
+    }
+
+    #[async_std::test]
+    async fn test_verifier_rejects_tampered_masked_eval() {
+        let run = protocol_run();
+        let proof = &run.encrypt_proof;
+        let ω = utils::multiplicative_subgroup_of_size(PERM_SIZE as u64).unwrap();
+        let points = utils::power_table(&ω, PERM_SIZE);
+
+        assert!(utils::kzg_check_batch(
+            &run.pp, &proof.masked_commitments, &points, &proof.masked_evals, &proof.eval_proofs
+        ));
+
+        // any card, not only the one the old spot check looked at
+        let mut tampered = proof.clone();
+        tampered.masked_evals[3] += F::one();
+        assert!(!utils::kzg_check_batch(
+            &run.pp, &tampered.masked_commitments, &points, &tampered.masked_evals, &tampered.eval_proofs
+        ));
+        assert!(!Verifier::new(utils::setup_kzg(1024)).verify_encryption_proof(&tampered).await);
+    }
 }
\ No newline at end of file
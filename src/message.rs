@@ -0,0 +1,117 @@
+//! The messages the evaluator and networkd exchange, and the decoding of
+//! a peer's frame into one. Only serde and pok3r-verify are needed here,
+//! so the fuzz targets can build this file on its own.
+
+use serde::{Serialize, Deserialize};
+use ark_serialize::CanonicalDeserialize;
+use pok3r_verify::G1;
+
+/// largest EvalNetMsg frame (in bytes) the networkd accepts from a peer
+pub const MAX_MESSAGE_SIZE: usize = 65536;
+
+/// EvalNetMsg represents the types of messages that
+/// we expect to flow between the evaluator and networkd
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EvalNetMsg {
+    ConnectionEstablished { success: bool },
+    Greeting { sender: String, message: String },
+    PublishValue { 
+        sender: String,
+        handle: String,
+        value: String
+    },
+    PublishBatchValue { 
+        sender: String,
+        handles: Vec<String>,
+        values: Vec<String>
+    },
+    SendTriple { 
+        sender: String, 
+        receiver: String, 
+        handle_a: String, 
+        share_a: String,
+        handle_b: String,
+        share_b: String,
+        handle_c: String,
+        share_c: String,
+    },
+    /// shares meant for receiver only, e.g. of a value sender deals
+    SendBatchShare {
+        sender: String,
+        receiver: String,
+        handles: Vec<String>,
+        values: Vec<String>
+    },
+    /// sender's contribution to a public group element, e.g. its share of a
+    /// KZG commitment, as a compressed G1 point; decode_eval_net_msg checks
+    /// that it is on the curve and in the prime-order subgroup
+    PublicCommitment {
+        name: String,
+        node_id: u64,
+        point_bytes: Vec<u8>
+    },
+    /// asks every party to answer with a Pong, which tells a responsive
+    /// peer from one behind a half-open connection
+    Ping {
+        sender: String,
+        nonce: String
+    },
+    /// sender's answer to the Ping of receiver with nonce
+    Pong {
+        sender: String,
+        receiver: String,
+        nonce: String
+    },
+}
+
+#[derive(Debug)]
+pub enum NetworkError {
+    /// A peer sent a frame larger than the configured maximum.
+    MessageTooLarge {
+        /// The size of the received frame in bytes.
+        size: usize,
+        /// The maximum accepted frame size in bytes.
+        max_size: usize,
+    },
+    /// A peer sent a frame that does not parse as an EvalNetMsg.
+    MalformedMessage(serde_json::Error),
+    /// A peer sent a PublicCommitment whose point is not a valid
+    /// element of the G1 subgroup.
+    InvalidCommitment {
+        /// The name the commitment was sent under.
+        name: String,
+    },
+}
+
+impl std::fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkError::MessageTooLarge { size, max_size } => 
+                write!(f, "message of {} bytes exceeds the maximum of {} bytes", size, max_size),
+            NetworkError::MalformedMessage(err) => 
+                write!(f, "malformed message: {}", err),
+            NetworkError::InvalidCommitment { name } => 
+                write!(f, "commitment {} is not a point of the G1 subgroup", name),
+        }
+    }
+}
+
+/// parses a frame received from a peer, checking its size before
+/// handing it to the json parser, and the point of a PublicCommitment
+/// before handing it to the evaluator
+pub fn decode_eval_net_msg(data: &[u8], max_message_size: usize) -> Result<EvalNetMsg, NetworkError> {
+    if data.len() > max_message_size {
+        return Err(NetworkError::MessageTooLarge { size: data.len(), max_size: max_message_size });
+    }
+
+    let msg: EvalNetMsg = serde_json::from_slice(data).map_err(NetworkError::MalformedMessage)?;
+    if let EvalNetMsg::PublicCommitment { name, point_bytes, .. } = &msg {
+        // deserialize_compressed validates, i.e. also does the subgroup check
+        if G1::deserialize_compressed(&point_bytes[..]).is_err() {
+            return Err(NetworkError::InvalidCommitment { name: name.clone() });
+        }
+    }
+
+    Ok(msg)
+}
@@ -13,7 +13,7 @@ use std::hash::{Hash, Hasher};
 use std::time::Duration;
 
 use crate::address_book::*;
-use crate::common::*;
+use crate::message::*;
 
 // We create a custom network behaviour that combines Gossipsub and Mdns.
 #[derive(NetworkBehaviour)]
@@ -29,9 +29,30 @@ fn generate_ed25519(secret_key_seed: u8) -> identity::Keypair {
     identity::Keypair::ed25519_from_bytes(bytes).expect("only errors on wrong length")
 }
 
+/// whether msg was sent by peer_id, the authenticated source of the
+/// message, rather than by whoever it names as its sender. The evaluator
+/// keys its mailbox and its per-peer limits on that sender, so a peer
+/// must not be able to speak for another one
+pub fn is_sent_by(msg: &EvalNetMsg, peer_id: &str, addr_book: &Pok3rAddrBook) -> bool {
+    match msg {
+        //only networkd itself tells the evaluator this
+        EvalNetMsg::ConnectionEstablished { .. } => false,
+        EvalNetMsg::PublicCommitment { node_id, .. } =>
+            get_peer_id_via_node_id(addr_book, *node_id).map_or(false, |id| id == peer_id),
+        EvalNetMsg::Greeting { sender, .. }
+        | EvalNetMsg::PublishValue { sender, .. }
+        | EvalNetMsg::PublishBatchValue { sender, .. }
+        | EvalNetMsg::SendTriple { sender, .. }
+        | EvalNetMsg::SendBatchShare { sender, .. }
+        | EvalNetMsg::Ping { sender, .. }
+        | EvalNetMsg::Pong { sender, .. } => sender == peer_id,
+    }
+}
+
 pub async fn run_networking_daemon(
     secret_key_seed: u8,
     addr_book: &Pok3rAddrBook,
+    max_message_size: usize,
     tx: &mut mpsc::UnboundedSender<EvalNetMsg>, 
     mut rx: mpsc::UnboundedReceiver<EvalNetMsg>) -> Result<(), Box<dyn Error>> {
     // Create a random PeerId
@@ -67,6 +88,7 @@ pub async fn run_networking_daemon(
         .heartbeat_interval(Duration::from_secs(10)) // This is set to aid debugging by not cluttering the log space
         .validation_mode(gossipsub::ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
         .message_id_fn(message_id_fn) // content-address messages. No two messages of the same content will be propagated.
+        .max_transmit_size(max_message_size) // frames above this are dropped by the codec before being buffered
         .build()
         .expect("Valid config");
 
@@ -101,13 +123,17 @@ pub async fn run_networking_daemon(
     loop {
         select! {
             //receives requests for publishing messages from the evaluator
-            msg_to_send = rx.select_next_some() => {
-                let s = serde_json::to_string(&msg_to_send).unwrap();
-                if let Err(e) = swarm
-                    .behaviour_mut().gossipsub
-                    .publish(topic.clone(), <String as AsRef<[u8]>>::as_ref(&s)) {
-                    println!("Publish error: {e:?}");
-                }
+            msg_to_send = rx.next() => match msg_to_send {
+                Some(msg_to_send) => {
+                    let s = serde_json::to_string(&msg_to_send).unwrap();
+                    if let Err(e) = swarm
+                        .behaviour_mut().gossipsub
+                        .publish(topic.clone(), <String as AsRef<[u8]>>::as_ref(&s)) {
+                        println!("Publish error: {e:?}");
+                    }
+                },
+                //the evaluator is gone or was cancelled, so we are done
+                None => return Ok(()),
             },
             //discovers peers, and notifies evaluator when all peers in addr_book are connected
             event = swarm.select_next_some() => match event {
@@ -142,15 +168,32 @@ pub async fn run_networking_daemon(
                 },
                 //all received messages over gossip channel are pushed to the evaluator
                 SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message {
-                    propagation_source: _peer_id,
+                    propagation_source: peer_id,
                     message_id: _id,
                     message,
                 })) => { 
-                    let msg_as_str = String::from_utf8_lossy(&message.data);
-                    let deserialized_struct = serde_json::from_str(&msg_as_str).unwrap();
-                    let r = tx.send(deserialized_struct).await;
-                    if let Err(err) = r {
-                        eprint!("network error {:?}", err);
+                    //gossipsub signs messages, so source is the author
+                    let source = message.source.map(|source| source.to_base58());
+                    match decode_eval_net_msg(&message.data, max_message_size) {
+                        Ok(deserialized_struct) if !source.as_ref()
+                            .map_or(false, |source| is_sent_by(&deserialized_struct, source, addr_book)) => {
+                            eprintln!("dropping message from {peer_id} sent on behalf of another peer");
+                            if let Some(source) = message.source {
+                                swarm.behaviour_mut().gossipsub.blacklist_peer(&source);
+                            }
+                        },
+                        Ok(deserialized_struct) => {
+                            let r = tx.send(deserialized_struct).await;
+                            if let Err(err) = r {
+                                eprint!("network error {:?}", err);
+                            }
+                        },
+                        //a misbehaving peer is cut off rather than crashing us
+                        Err(err) => {
+                            eprintln!("dropping message from {peer_id}: {err}");
+                            swarm.behaviour_mut().gossipsub.blacklist_peer(&peer_id);
+                            let _ = swarm.disconnect_peer_id(peer_id);
+                        }
                     }
                 },
                 //prints out the address this program is listening on for new connections
@@ -161,4 +204,266 @@ pub async fn run_networking_daemon(
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// in-process replacement for the networking daemon, used by tests
+pub mod in_process {
+    use futures::{prelude::*, channel::*};
+    use futures::future::join_all;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::address_book::*;
+    use crate::common::*;
+    use crate::evaluator::Evaluator;
+
+    /// conditions on the links between the parties of an in-process network,
+    /// see run_in_process_network; the default is a perfect network
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct NetworkSim {
+        /// delay of every delivery
+        pub latency: Duration,
+        /// extra delay of up to this much, drawn per delivery
+        pub jitter: Duration,
+        /// probability that an attempt to deliver a message is lost
+        pub drop_rate: f64,
+        /// whether a lost delivery is attempted again after another delay,
+        /// like a reliable transport beneath the evaluator; without it the
+        /// message is gone, and the evaluator waits for it forever
+        pub retransmit: bool,
+    }
+
+    impl NetworkSim {
+        fn is_perfect(&self) -> bool {
+            self.latency.is_zero() && self.jitter.is_zero() && self.drop_rate == 0.0
+        }
+
+        /// delivers msg to tx as the link allows, giving up on a lost
+        /// delivery unless retransmit is set
+        async fn deliver(self, msg: EvalNetMsg, tx: mpsc::UnboundedSender<EvalNetMsg>, mut rng: StdRng) {
+            loop {
+                let jitter = self.jitter.mul_f64(rng.gen::<f64>());
+                async_std::task::sleep(self.latency + jitter).await;
+
+                if rng.gen::<f64>() >= self.drop_rate {
+                    //the receiving evaluator may have finished already
+                    let _ = tx.unbounded_send(msg);
+                    return;
+                }
+                if !self.retransmit { return; }
+            }
+        }
+    }
+
+    /// Endpoints handed to an evaluator: a sender towards the network
+    /// and a receiver for messages coming from the network
+    pub type EvaluatorEndpoints = (
+        mpsc::UnboundedSender<EvalNetMsg>,
+        mpsc::UnboundedReceiver<EvalNetMsg>
+    );
+
+    /// address book of num_parties local parties named party_0, party_1, ...
+    pub fn local_addr_book(num_parties: u64) -> Pok3rAddrBook {
+        let mut output: Pok3rAddrBook = HashMap::new();
+        for node_id in 0..num_parties {
+            let peer_id = format!("party_{}", node_id);
+            output.insert(peer_id.clone(), Pok3rPeer { peer_id, node_id });
+        }
+        output
+    }
+
+    /// runs all parties of addr_book inside this process, connected by channels
+    /// instead of libp2p. Like gossipsub, every message published by a party is
+    /// delivered to all other parties (but not back to the sender). Each party's
+    /// receiver starts with a ConnectionEstablished message, so Evaluator::new
+    /// can be used on the returned endpoints as-is.
+    ///
+    /// The links have the latency and losses of sim. Which deliveries get
+    /// lost depends only on the sender's node id and its sequence of
+    /// messages, so a test sees the same losses every run; with latency or
+    /// jitter, messages may arrive out of order
+    pub fn run_in_process_network(
+        addr_book: &Pok3rAddrBook,
+        sim: NetworkSim
+    ) -> HashMap<Pok3rPeerId, EvaluatorEndpoints> {
+        let mut e2n_rxs = vec![];
+        let mut n2e_txs = vec![];
+        let mut endpoints = HashMap::new();
+
+        for peer_id in addr_book.keys() {
+            let (e2n_tx, e2n_rx) = mpsc::unbounded::<EvalNetMsg>();
+            let (n2e_tx, n2e_rx) = mpsc::unbounded::<EvalNetMsg>();
+
+            n2e_tx
+                .unbounded_send(EvalNetMsg::ConnectionEstablished { success: true })
+                .expect("receiver is alive");
+
+            e2n_rxs.push((peer_id.clone(), e2n_rx));
+            n2e_txs.push((peer_id.clone(), n2e_tx));
+            endpoints.insert(peer_id.clone(), (e2n_tx, n2e_rx));
+        }
+
+        //one forwarding task per party, fanning out its messages to everyone else
+        for (sender_id, mut e2n_rx) in e2n_rxs {
+            let receivers: Vec<mpsc::UnboundedSender<EvalNetMsg>> = n2e_txs
+                .iter()
+                .filter(|(peer_id, _)| !peer_id.eq(&sender_id))
+                .map(|(_, tx)| tx.clone())
+                .collect();
+
+            let node_id = get_node_id_via_peer_id(addr_book, &sender_id).unwrap();
+            let addr_book = addr_book.clone();
+            async_std::task::spawn(async move {
+                let mut rng = StdRng::seed_from_u64(node_id);
+                while let Some(msg) = e2n_rx.next().await {
+                    //like networkd, drop what a party sends on behalf of another
+                    if !super::is_sent_by(&msg, &sender_id, &addr_book) { continue; }
+                    for tx in receivers.iter() {
+                        if sim.is_perfect() {
+                            //the receiving evaluator may have finished already
+                            let _ = tx.unbounded_send(msg.clone());
+                        } else {
+                            let link_rng = StdRng::seed_from_u64(rng.gen());
+                            async_std::task::spawn(sim.deliver(msg.clone(), tx.clone(), link_rng));
+                        }
+                    }
+                }
+            });
+        }
+
+        endpoints
+    }
+
+    /// runs f for every party of a fresh in-process network of num_parties,
+    /// concurrently on the current task, and returns the results by node id
+    pub async fn run_parties<T, Fut>(
+        num_parties: u64,
+        f: impl Fn(Evaluator) -> Fut
+    ) -> Vec<T>
+    where Fut: Future<Output = T> {
+        run_parties_with_sim(num_parties, NetworkSim::default(), f).await
+    }
+
+    /// run_parties over links with the latency and losses of sim
+    pub async fn run_parties_with_sim<T, Fut>(
+        num_parties: u64,
+        sim: NetworkSim,
+        f: impl Fn(Evaluator) -> Fut
+    ) -> Vec<T>
+    where Fut: Future<Output = T> {
+        let addr_book = local_addr_book(num_parties);
+        let mut endpoints = run_in_process_network(&addr_book, sim);
+
+        let mut peers: Vec<Pok3rPeer> = addr_book.values().cloned().collect();
+        peers.sort_by_key(|p| p.node_id);
+
+        let f = &f;
+        let parties = peers.into_iter().map(|peer| {
+            let (tx, rx) = endpoints.remove(&peer.peer_id).unwrap();
+            let addr_book = addr_book.clone();
+            async move {
+                let evaluator = Evaluator::new(&peer.peer_id, addr_book, tx, rx).await.unwrap();
+                f(evaluator).await
+            }
+        });
+
+        join_all(parties).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_rejects_oversized_frame() {
+        let msg = EvalNetMsg::Greeting { sender: String::from("party_0"), message: String::from("hello") };
+        let frame = serde_json::to_vec(&msg).unwrap();
+        assert!(decode_eval_net_msg(&frame, MAX_MESSAGE_SIZE).is_ok());
+
+        // a well-formed frame is still rejected once over the limit
+        match decode_eval_net_msg(&frame, frame.len() - 1) {
+            Err(NetworkError::MessageTooLarge { size, max_size }) => {
+                assert_eq!(size, frame.len());
+                assert_eq!(max_size, frame.len() - 1);
+            },
+            _ => panic!("oversized frame was accepted"),
+        }
+
+        // the size check comes before parsing, so junk is never handed to serde
+        let junk = vec![b'['; MAX_MESSAGE_SIZE + 1];
+        assert!(matches!(
+            decode_eval_net_msg(&junk, MAX_MESSAGE_SIZE), 
+            Err(NetworkError::MessageTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_commitment_off_subgroup() {
+        use ark_ec::AffineRepr;
+        use ark_ff::One;
+        use ark_serialize::CanonicalSerialize;
+
+        type G1 = ark_bls12_377::G1Affine;
+
+        let frame = |point: &G1| {
+            let mut point_bytes = Vec::new();
+            point.serialize_compressed(&mut point_bytes).unwrap();
+            let msg = EvalNetMsg::PublicCommitment { name: String::from("op0/f_com"), node_id: 1, point_bytes };
+            serde_json::to_vec(&msg).unwrap()
+        };
+
+        assert!(decode_eval_net_msg(&frame(&G1::generator()), MAX_MESSAGE_SIZE).is_ok());
+
+        // a point on the curve, but outside the prime-order subgroup
+        let mut x = ark_bls12_377::Fq::one();
+        let off_subgroup = loop {
+            if let Some(point) = G1::get_point_from_x_unchecked(x, false) {
+                if !point.is_in_correct_subgroup_assuming_on_curve() { break point; }
+            }
+            x += ark_bls12_377::Fq::one();
+        };
+        assert!(off_subgroup.is_on_curve());
+
+        match decode_eval_net_msg(&frame(&off_subgroup), MAX_MESSAGE_SIZE) {
+            Err(NetworkError::InvalidCommitment { name }) => assert_eq!(name, "op0/f_com"),
+            _ => panic!("commitment off the subgroup was accepted"),
+        }
+
+        // as are bytes that are no point at all
+        let junk = EvalNetMsg::PublicCommitment { name: String::from("junk"), node_id: 1, point_bytes: vec![7; 3] };
+        assert!(matches!(
+            decode_eval_net_msg(&serde_json::to_vec(&junk).unwrap(), MAX_MESSAGE_SIZE),
+            Err(NetworkError::InvalidCommitment { .. })
+        ));
+    }
+
+    #[test]
+    fn test_is_sent_by_rejects_spoofed_sender() {
+        let addr_book = in_process::local_addr_book(3);
+
+        let value = |sender: &str| EvalNetMsg::PublishValue {
+            sender: String::from(sender),
+            handle: String::from("op0/x"),
+            value: String::from("v"),
+        };
+        assert!(is_sent_by(&value("party_1"), "party_1", &addr_book));
+        assert!(!is_sent_by(&value("party_2"), "party_1", &addr_book));
+
+        let commitment = EvalNetMsg::PublicCommitment { name: String::from("op0/f_com"), node_id: 1, point_bytes: vec![] };
+        assert!(is_sent_by(&commitment, "party_1", &addr_book));
+        assert!(!is_sent_by(&commitment, "party_2", &addr_book));
+
+        // a peer never gets to tell the evaluator the network is up
+        assert!(!is_sent_by(&EvalNetMsg::ConnectionEstablished { success: true }, "party_1", &addr_book));
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_frame() {
+        assert!(matches!(
+            decode_eval_net_msg(b"{\"type\":\"Greeting\"", MAX_MESSAGE_SIZE), 
+            Err(NetworkError::MalformedMessage(_))
+        ));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::card::*;
+
+/// Category of a poker hand, from weakest to strongest
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourOfAKind,
+    StraightFlush,
+}
+
+/// HandRank is the value of a hand; comparing two HandRanks compares the
+/// hands, first by category and then by the ranks breaking ties in it
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HandRank {
+    pub category: Category,
+    /// ranks in the order they are compared: the groups (quads, trips,
+    /// pairs) from largest to smallest, then the kickers, highest first.
+    /// A straight is represented by its top card only, Five for the wheel.
+    pub tiebreak: Vec<Rank>,
+}
+
+/// value of the best 5-card hand among cards (e.g. 2 hole cards and
+/// 5 community cards)
+pub fn evaluate_hand(cards: &[Card]) -> HandRank {
+    let n = cards.len();
+    assert!(n >= 5, "a poker hand needs at least 5 cards, got {}", n);
+
+    (0u32..(1 << n))
+        .filter(|mask| mask.count_ones() == 5)
+        .map(|mask| {
+            let mut five = [cards[0]; 5];
+            let mut k = 0;
+            for (i, card) in cards.iter().enumerate() {
+                if mask & (1 << i) != 0 {
+                    five[k] = *card;
+                    k += 1;
+                }
+            }
+            evaluate_five(&five)
+        })
+        .max()
+        .unwrap()
+}
+
+fn evaluate_five(cards: &[Card; 5]) -> HandRank {
+    let mut ranks: Vec<Rank> = cards.iter().map(|c| c.rank).collect();
+    ranks.sort_by(|a, b| b.cmp(a));
+
+    let is_flush = cards.iter().all(|c| c.suit == cards[0].suit);
+
+    // (count, rank) for every distinct rank, largest groups first
+    let mut groups: Vec<(usize, Rank)> = Vec::new();
+    for rank in ranks.iter() {
+        match groups.iter_mut().find(|(_, r)| r == rank) {
+            Some((count, _)) => *count += 1,
+            None => groups.push((1, *rank)),
+        }
+    }
+    groups.sort_by(|a, b| b.cmp(a));
+
+    let straight_high = if groups.len() == 5 {
+        if ranks[0].value() - ranks[4].value() == 4 {
+            Some(ranks[0])
+        } else if ranks == [Rank::Ace, Rank::Five, Rank::Four, Rank::Three, Rank::Two] {
+            // the wheel, where the Ace plays low
+            Some(Rank::Five)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    let group_ranks: Vec<Rank> = groups.iter().map(|(_, r)| *r).collect();
+    let counts: Vec<usize> = groups.iter().map(|(c, _)| *c).collect();
+
+    let (category, tiebreak) = match (straight_high, is_flush, counts.as_slice()) {
+        (Some(high), true, _) => (Category::StraightFlush, vec![high]),
+        (_, _, [4, 1]) => (Category::FourOfAKind, group_ranks),
+        (_, _, [3, 2]) => (Category::FullHouse, group_ranks),
+        (_, true, _) => (Category::Flush, ranks),
+        (Some(high), false, _) => (Category::Straight, vec![high]),
+        (_, _, [3, 1, 1]) => (Category::ThreeOfAKind, group_ranks),
+        (_, _, [2, 2, 1]) => (Category::TwoPair, group_ranks),
+        (_, _, [2, 1, 1, 1]) => (Category::OnePair, group_ranks),
+        _ => (Category::HighCard, ranks),
+    };
+
+    HandRank { category, tiebreak }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn hand(cards: &str) -> HandRank {
+        let cards: Vec<Card> = cards
+            .split_whitespace()
+            .map(|c| c.parse().unwrap())
+            .collect();
+        evaluate_hand(&cards)
+    }
+
+    #[test]
+    fn test_categories() {
+        assert_eq!(hand("As Ks Qs Js Ts").category, Category::StraightFlush);
+        assert_eq!(hand("9c 9d 9h 9s 2c").category, Category::FourOfAKind);
+        assert_eq!(hand("9c 9d 9h 2s 2c").category, Category::FullHouse);
+        assert_eq!(hand("Ah 9h 7h 4h 2h").category, Category::Flush);
+        assert_eq!(hand("9c Td Jh Qs Kc").category, Category::Straight);
+        assert_eq!(hand("9c 9d 9h Ks 2c").category, Category::ThreeOfAKind);
+        assert_eq!(hand("9c 9d Kh Ks 2c").category, Category::TwoPair);
+        assert_eq!(hand("9c 9d Kh 3s 2c").category, Category::OnePair);
+        assert_eq!(hand("Ac 9d Kh 3s 2c").category, Category::HighCard);
+    }
+
+    #[test]
+    fn test_category_order() {
+        let ladder = [
+            "Ac Qd 9h 5s 3c",
+            "2c 2d 3h 4s 6c",
+            "2c 2d 3h 3s 4c",
+            "2c 2d 2h 3s 4c",
+            "Ac 2d 3h 4s 5c",
+            "2h 3h 4h 5h 7h",
+            "2c 2d 2h 3s 3c",
+            "2c 2d 2h 2s 3c",
+            "Ah 2h 3h 4h 5h",
+        ];
+        for pair in ladder.windows(2) {
+            assert!(hand(pair[0]) < hand(pair[1]), "{} should lose to {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_wheel_straights() {
+        let wheel = hand("Ac 2d 3h 4s 5c");
+        assert_eq!(wheel, HandRank { category: Category::Straight, tiebreak: vec![Rank::Five] });
+        assert!(wheel < hand("2c 3d 4h 5s 6c"));
+        assert!(hand("Tc Jd Qh Ks Ac") > hand("9c Td Jh Qs Kc"));
+
+        // the Ace does not wrap around
+        assert_eq!(hand("Qc Kd Ah 2s 3c").category, Category::HighCard);
+
+        let steel_wheel = hand("Ah 2h 3h 4h 5h");
+        assert_eq!(steel_wheel.category, Category::StraightFlush);
+        assert!(steel_wheel < hand("2h 3h 4h 5h 6h"));
+    }
+
+    #[test]
+    fn test_kicker_tie_breaks() {
+        assert!(hand("Ac Ad Kh 7s 2c") > hand("Ah As Qh Js Tc"));
+        assert!(hand("Ac Ad Kh 7s 3c") > hand("Ah As Kc 7d 2c"));
+        assert!(hand("Kc Kd 2h 2s 3c") > hand("Qc Qd Jh Js Ac"));
+        assert!(hand("Kc Kd 2h 2s 4c") > hand("Kh Ks 2d 2c 3c"));
+        assert!(hand("3c 3d 3h 2s 2c") > hand("2h 2s 2d Ac Ad"));
+        assert!(hand("Ah Jh 9h 4h 2h") > hand("Ad Jd 9d 3d 2d"));
+        assert!(hand("Ac Kd 9h 4s 3c") > hand("Ad Kc 9s 4h 2c"));
+
+        // suits never break ties
+        assert_eq!(hand("Ac Kd 9h 4s 3c"), hand("Ad Kc 9s 4h 3h"));
+    }
+
+    #[test]
+    fn test_best_five_of_seven() {
+        // the board has a straight, the hole cards complete a flush
+        let best = hand("2h 3h 7h 8c 9h Td Jh");
+        assert_eq!(best, HandRank {
+            category: Category::Flush,
+            tiebreak: vec![Rank::Jack, Rank::Nine, Rank::Seven, Rank::Three, Rank::Two]
+        });
+
+        // only the best five cards count, the sixth and seventh do not kick
+        assert_eq!(hand("Ac Ad Kh Qs Jc 3d 2h"), hand("Ac Ad Kh Qs Jc 4d 3h"));
+    }
+
+    #[test]
+    fn test_all_five_card_hands() {
+        let deck = Card::deck();
+        let mut frequencies: HashMap<Category, usize> = HashMap::new();
+
+        for a in 0..52 {
+            for b in a + 1..52 {
+                for c in b + 1..52 {
+                    for d in c + 1..52 {
+                        for e in d + 1..52 {
+                            let five = [deck[a], deck[b], deck[c], deck[d], deck[e]];
+                            *frequencies.entry(evaluate_five(&five).category).or_insert(0) += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        assert_eq!(frequencies[&Category::StraightFlush], 40);
+        assert_eq!(frequencies[&Category::FourOfAKind], 624);
+        assert_eq!(frequencies[&Category::FullHouse], 3744);
+        assert_eq!(frequencies[&Category::Flush], 5108);
+        assert_eq!(frequencies[&Category::Straight], 10200);
+        assert_eq!(frequencies[&Category::ThreeOfAKind], 54912);
+        assert_eq!(frequencies[&Category::TwoPair], 123552);
+        assert_eq!(frequencies[&Category::OnePair], 1098240);
+        assert_eq!(frequencies[&Category::HighCard], 1302540);
+    }
+}
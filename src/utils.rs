@@ -7,12 +7,13 @@ use ark_ff::{Field, FftField, PrimeField};
 use ark_std::{UniformRand, test_rng, ops::*};
 use ark_poly::{
     Polynomial,
+    DenseUVPolynomial,
     univariate::DensePolynomial, 
     EvaluationDomain, 
     Radix2EvaluationDomain,
     Evaluations, GeneralEvaluationDomain, domain
 };
-use ark_ec::{pairing::Pairing, CurveGroup};
+use ark_ec::{pairing::Pairing, AffineRepr, CurveGroup};
 use ark_serialize::*;
 use ark_ec::{
     hashing::{
@@ -25,7 +26,11 @@ use ark_ff::{
     One, Zero,
 };
 use num_bigint::{BigInt, BigUint, Sign};
+use std::{collections::{BTreeMap, HashMap}, sync::{Arc, Mutex}};
+use once_cell::sync::Lazy;
+use async_trait::async_trait;
 
+use crate::card::Card;
 use crate::kzg::UniversalParams;
 
 type Curve = ark_bls12_377::Bls12_377;
@@ -35,32 +40,40 @@ type G1 = <Curve as Pairing>::G1Affine;
 type G2 = <Curve as Pairing>::G2Affine;
 type G1Config = ark_ec::short_weierstrass::Affine<ark_bls12_377::g1::Config>;
 
-macro_rules! requires_power_of_2 {
-    ($x:expr) => {
-        assert!($x > 0 && ($x & ($x - 1)) == 0, "{} is not a power of 2", $x);
-    };
+pub use pok3r_verify::kzg::{domain_for, exact_domain_for, DomainError};
+
+#[derive(Debug, PartialEq)]
+pub enum SubgroupError {
+    /// The field has no multiplicative subgroup of the requested size
+    /// that this crate can use as an evaluation domain.
+    NoSuchSubgroup,
 }
 
-/// returns a generator of the multiplicative subgroup of input size n
-pub fn multiplicative_subgroup_of_size(n: u64) -> F {
-    requires_power_of_2!(n);
-    let domain = Radix2EvaluationDomain::<F>::new(n as usize).unwrap();
-    domain.group_gen
+/// returns a generator of the multiplicative subgroup of input size n.
+/// The scalar field of bls12-377 has 2-adicity 47, so the supported sizes
+/// are the powers of 2 from 1 up to 2^47; any other size, e.g. a deck of
+/// 52 cards, has no such subgroup here and must be padded to a power of 2
+pub fn multiplicative_subgroup_of_size(n: u64) -> Result<F, SubgroupError> {
+    if n == 0 || (n & (n - 1)) != 0 {
+        return Err(SubgroupError::NoSuchSubgroup);
+    }
+    let domain = exact_domain_for::<F>(n as usize).map_err(|_| SubgroupError::NoSuchSubgroup)?;
+    Ok(domain.group_gen())
 }
 
 /// interpolate polynomial which evaluates to points in v
 /// the domain is the powers of n-th root of unity, where n is size of v
-/// assumes n is a power of 2
+/// panics unless n is a power of 2, see try_interpolate_poly_over_mult_subgroup
 pub fn interpolate_poly_over_mult_subgroup(v: &Vec<F>) -> DensePolynomial<F> {
-    let n = v.len();
-    let mut evals = vec![];
-    for i in 0..n {
-        evals.push(v[i]);
-    }
+    try_interpolate_poly_over_mult_subgroup(v)
+        .unwrap_or_else(|err| panic!("cannot interpolate {} points: {:?}", v.len(), err))
+}
 
-    let domain = GeneralEvaluationDomain::<F>::new(n).unwrap();
-    let eval_form = Evaluations::from_vec_and_domain(evals, domain);
-    eval_form.interpolate()
+/// interpolate_poly_over_mult_subgroup, or the DomainError of a number of
+/// points with no subgroup of exactly that size, e.g. the padding they need
+pub fn try_interpolate_poly_over_mult_subgroup(v: &[F]) -> Result<DensePolynomial<F>, DomainError> {
+    let domain = exact_domain_for::<F>(v.len())?;
+    Ok(Evaluations::from_vec_and_domain(v.to_vec(), domain).interpolate())
 }
 
 // Generate setup with fixed seed to make sure all parties use the same KZG params
@@ -70,13 +83,254 @@ pub fn setup_kzg(n: usize) -> UniversalParams<Curve> {
     params
 }
 
+// set at most once and never freed, so handing out &'static is sound;
+// kept with its fingerprint
+static GLOBAL_SRS: Mutex<Option<(&'static UniversalParams<Curve>, [u8; 32])>> = Mutex::new(None);
+
+#[derive(Debug, PartialEq)]
+pub enum SrsError {
+    /// A global SRS was already installed; it cannot be replaced, so that
+    /// all proofs of a process are made under the same SRS.
+    AlreadySet,
+
+    /// There is no canonical v(X) of the size, as the field has no
+    /// subgroup of exactly that size.
+    Domain(DomainError),
+
+    /// Committing the canonical v(X) under the SRS does not give the
+    /// commitment the parties agreed on, so it is not the agreed SRS.
+    DeckCommitmentMismatch {
+        /// The size of the v(X) that was committed.
+        size: usize,
+    },
+}
+
+/// installs srs, e.g. the final SRS of a ceremony (see kzg::ceremony_contribute),
+/// as the SRS of this process
+pub fn set_global_srs(srs: UniversalParams<Curve>) -> Result<(), SrsError> {
+    let mut global = GLOBAL_SRS.lock().unwrap();
+    if global.is_some() {
+        return Err(SrsError::AlreadySet);
+    }
+
+    let fingerprint = crate::kzg::srs_fingerprint(&srs);
+    println!("installed SRS with fingerprint {}", hex_string(&fingerprint));
+    *global = Some((Box::leak(Box::new(srs)), fingerprint));
+    Ok(())
+}
+
+/// the SRS installed with set_global_srs, if any
+pub fn global_srs() -> Option<&'static UniversalParams<Curve>> {
+    GLOBAL_SRS.lock().unwrap().map(|(srs, _)| srs)
+}
+
+/// kzg::srs_fingerprint of the SRS installed with set_global_srs, if any
+pub fn global_srs_fingerprint() -> Option<[u8; 32]> {
+    GLOBAL_SRS.lock().unwrap().map(|(_, fingerprint)| fingerprint)
+}
+
+/// checks srs against the commitment to the canonical v(X) of the given
+/// size that the parties agreed on, before trusting commitments under it
+pub fn check_srs_deck_commitment(srs: &UniversalParams<Curve>, size: usize, expected: &G1) -> Result<(), SrsError> {
+    if size > srs.powers_of_g.len() || canonical_deck_poly(srs, size).map_err(SrsError::Domain)?.1 != *expected {
+        return Err(SrsError::DeckCommitmentMismatch { size });
+    }
+    Ok(())
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// v(X) with v(ω^i) = ω^i on the subgroup of the given size, i.e. the
+/// unpermuted deck, and its commitment under pp. The permutation prover
+/// and verifier both take v from here, so they cannot disagree on it;
+/// it is only computed once per size and SRS
+pub fn canonical_deck_poly(pp: &UniversalParams<Curve>, size: usize) -> Result<(DensePolynomial<F>, G1), DomainError> {
+    pok3r_verify::kzg::deck_poly(pp, size)
+}
+
+/// SHA-256 of the (ω^i, card index) pairs of mapping, in deck order, so
+/// two mappings commit to the same bytes only if every card stands for
+/// the same root of unity in both
+pub fn mapping_commitment(mapping: &HashMap<F, Card>) -> [u8; 32] {
+    use sha2::Digest;
+
+    let mut pairs: Vec<(usize, &F)> = mapping
+        .iter()
+        .map(|(ω_pow_i, card)| (card.to_index(), ω_pow_i))
+        .collect();
+    pairs.sort_by_key(|(index, _)| *index);
+
+    let mut bytes = Vec::new();
+    for (index, ω_pow_i) in pairs {
+        ω_pow_i.serialize_uncompressed(&mut bytes).unwrap();
+        bytes.extend_from_slice(&(index as u64).to_le_bytes());
+    }
+    sha2::Sha256::digest(&bytes).into()
+}
+
+// [L_i(β)] for the subgroup of a size, by size and kzg::srs_fingerprint
+type LagrangeCommitments = BTreeMap<(usize, [u8; 32]), Arc<Vec<G1>>>;
+
+static LAGRANGE_COMMITMENTS: Lazy<Mutex<LagrangeCommitments>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// commitments to the Lagrange basis polynomials of the subgroup of the
+/// given size under pp; computed once per size and SRS
+pub fn lagrange_commitments(pp: &UniversalParams<Curve>, size: usize) -> Arc<Vec<G1>> {
+    let key = (size, crate::kzg::srs_fingerprint(pp));
+
+    if let Some(lagrange) = LAGRANGE_COMMITMENTS.lock().unwrap().get(&key) {
+        return lagrange.clone();
+    }
+
+    let lagrange = Arc::new(KZG::lagrange_commitments(pp, size).unwrap());
+
+    LAGRANGE_COMMITMENTS.lock().unwrap().insert(key, lagrange.clone());
+    lagrange
+}
+
+/// the KZG opening proof at ω^i of the polynomial taking the values evals
+/// on the subgroup of size evals.len(), given the lagrange_commitments of
+/// that size; the same proof as opening its interpolation by division,
+/// but without interpolating or dividing
+pub fn eval_proof_at_root(lagrange: &[G1], evals: &[F], i: usize) -> G1 {
+    KZG::compute_opening_proof_at_root(lagrange, evals, i).unwrap()
+}
+
+/// SrsProvider hands out an SRS that may only become available later,
+/// e.g. one still being downloaded or read from disk
+#[async_trait]
+pub trait SrsProvider: Send + Sync {
+    async fn srs(&self) -> UniversalParams<Curve>;
+}
+
 pub fn commit_poly(pp: &UniversalParams<Curve>, f: &DensePolynomial<F>) -> G1 {
     KZG::commit_g1(pp, f).unwrap()
 }
 
-pub fn kzg_check(pp: &UniversalParams<Curve>, comm: &G1, x: &F, eval: &F, proof: &G1) -> bool {
-    let b = KZG::check(pp, &comm, *x, *eval, &proof);
-    b
+pub use pok3r_verify::kzg_check;
+
+/// checks many KZG openings at once: for r_i = r^i with random r it tests
+/// e(sum r_i (comm_i - eval_i g + x_i proof_i), h) == e(sum r_i proof_i, beta h),
+/// which costs two pairings instead of two per opening; false unless
+/// there are as many points, evaluations and proofs as commitments
+pub fn kzg_check_batch(
+    pp: &UniversalParams<Curve>, 
+    comms: &[G1], 
+    xs: &[F], 
+    evals: &[F], 
+    proofs: &[G1]
+) -> bool {
+    let n = comms.len();
+    if xs.len() != n || evals.len() != n || proofs.len() != n {
+        return false;
+    }
+    if n == 0 { return true; }
+
+    // the batching scalars depend on every opening being checked
+    let mut bytes = Vec::new();
+    for i in 0..n {
+        comms[i].serialize_uncompressed(&mut bytes).unwrap();
+        xs[i].serialize_uncompressed(&mut bytes).unwrap();
+        evals[i].serialize_uncompressed(&mut bytes).unwrap();
+        proofs[i].serialize_uncompressed(&mut bytes).unwrap();
+    }
+    // powers of one challenge, as fs_hash cannot output hundreds of elements
+    let r = power_table(&fs_hash(vec![&bytes, b"kzg_batch"], 1)[0], n);
+
+    let g = pp.powers_of_g[0];
+    let h = pp.powers_of_h[0];
+    let beta_h = pp.powers_of_h[1];
+
+    let mut lhs_acc = <Curve as Pairing>::G1::zero();
+    let mut rhs_acc = <Curve as Pairing>::G1::zero();
+    for i in 0..n {
+        let term = comms[i].into_group() - g.mul(evals[i]) + proofs[i].mul(xs[i]);
+        lhs_acc += term.mul(r[i]);
+        rhs_acc += proofs[i].mul(r[i]);
+    }
+
+    <Curve as Pairing>::pairing(lhs_acc, h) == <Curve as Pairing>::pairing(rhs_acc, beta_h)
+}
+
+/// checks d(X) = q(X) * (X^n - 1) given only the commitments to d and q,
+/// via e(d_com, h) == e(q_com, beta^n h - h); i.e. d vanishes on the
+/// subgroup of size n, without opening d anywhere
+pub fn kzg_check_vanishing(pp: &UniversalParams<Curve>, d_com: &G1, q_com: &G1, n: usize) -> bool {
+    let h = pp.powers_of_h[0];
+    let vanishing_at_beta_h = pp.powers_of_h[n].into_group() - h;
+
+    <Curve as Pairing>::pairing(d_com, h) == <Curve as Pairing>::pairing(q_com, vanishing_at_beta_h)
+}
+
+/// opens poly at X = 0, i.e. its constant term f_0, with a KZG proof.
+/// Unlike the openings on the subgroup, 0 is not a root of unity, and
+/// the witness (f(X) - f_0) / X is just f with its coefficients shifted
+pub fn prove_constant_term(pp: &UniversalParams<Curve>, poly: &DensePolynomial<F>) -> (F, G1) {
+    let zero = F::zero();
+    let constant_term = poly.evaluate(&zero);
+    let pi = KZG::compute_opening_proof(pp, poly, &zero).unwrap();
+
+    (constant_term, pi)
+}
+
+/// checks an opening of prove_constant_term against the commitment comm
+pub fn verify_constant_term(pp: &UniversalParams<Curve>, comm: &G1, constant_term: &F, pi: &G1) -> bool {
+    kzg_check(pp, comm, &F::zero(), constant_term, pi)
+}
+
+/// the point-wise counterpart of kzg_check_vanishing: d(ω^i) == 0 for all i < n
+pub fn vanishes_on_subgroup(d: &DensePolynomial<F>, n: usize) -> bool {
+    let ω = multiplicative_subgroup_of_size(n as u64).unwrap();
+    power_table(&ω, n).iter().all(|x| d.evaluate(x).is_zero())
+}
+
+/// EqualityProof is a structure for the proof that two KZG commitments
+/// hide the same polynomial: one opening, at a point derived from both
+/// commitments, that verifies against each of them. It reveals the
+/// polynomial's evaluation at that point and nothing else.
+pub struct EqualityProof {
+    pub point: F,
+    pub eval: F,
+    pub pi: G1,
+}
+
+/// z = FSHash(com1, com2), the point at which both commitments are opened
+fn commitment_equality_challenge(com1: &G1, com2: &G1) -> F {
+    let mut com1_bytes = Vec::new();
+    let mut com2_bytes = Vec::new();
+
+    com1.serialize_uncompressed(&mut com1_bytes).unwrap();
+    com2.serialize_uncompressed(&mut com2_bytes).unwrap();
+
+    fs_hash(vec![&com1_bytes, &com2_bytes, b"equality"], 1)[0]
+}
+
+pub fn prove_commitment_equality(
+    pp: &UniversalParams<Curve>, 
+    poly: &DensePolynomial<F>, 
+    com1: &G1, 
+    com2: &G1
+) -> EqualityProof {
+    let point = commitment_equality_challenge(com1, com2);
+    let eval = poly.evaluate(&point);
+    let pi = KZG::compute_opening_proof(pp, poly, &point).unwrap();
+
+    EqualityProof { point, eval, pi }
+}
+
+pub fn verify_commitment_equality(
+    pp: &UniversalParams<Curve>, 
+    com1: &G1, 
+    com2: &G1, 
+    proof: &EqualityProof
+) -> bool {
+    // a single witness opening both commitments to the same value at
+    // the same point forces com1 - com2 to be the identity
+    proof.point == commitment_equality_challenge(com1, com2) &&
+        kzg_check(pp, com1, &proof.point, &proof.eval, &proof.pi) &&
+        kzg_check(pp, com2, &proof.point, &proof.eval, &proof.pi)
 }
 
 pub fn compute_additive_shares(value: &F, num_shares: usize) -> Vec<F> {
@@ -93,21 +347,126 @@ pub fn compute_additive_shares(value: &F, num_shares: usize) -> Vec<F> {
     shares
 }
 
+/// Shamir sharing of value with the given threshold: a random polynomial
+/// of degree threshold with constant term value, evaluated at 1..=num_shares;
+/// any threshold + 1 shares determine value, threshold shares reveal nothing
+pub fn compute_shamir_shares<R: rand::Rng>(
+    value: &F, 
+    threshold: usize, 
+    num_shares: usize, 
+    rng: &mut R
+) -> Vec<F> {
+    assert!(threshold < num_shares, "threshold {} needs more than {} shares", threshold, num_shares);
+
+    let mut coeffs = vec![*value];
+    coeffs.extend((0..threshold).map(|_| F::rand(rng)));
+    let poly = DensePolynomial::from_coefficients_vec(coeffs);
+
+    (1..=num_shares)
+        .map(|x| poly.evaluate(&F::from(x as u64)))
+        .collect()
+}
+
+/// the Lagrange coefficients at 0 for the evaluation points xs: the value
+/// at 0 of the polynomial through (x_i, y_i) is sum_i coeffs[i] * y_i, so
+/// they also combine Shamir shares held in the exponent
+pub fn lagrange_coefficients_at_zero(xs: &[F]) -> Vec<F> {
+    xs.iter()
+        .enumerate()
+        .map(|(i, x_i)| {
+            let mut num = F::one();
+            let mut den = F::one();
+            for (j, x_j) in xs.iter().enumerate() {
+                if i != j {
+                    num *= x_j;
+                    den *= *x_j - x_i;
+                }
+            }
+            num / den
+        })
+        .collect()
+}
+
+/// evaluates at 0 the polynomial through points (x_i, y_i), i.e.
+/// reconstructs a Shamir-shared value from (evaluation point, share) pairs
+pub fn lagrange_interpolate_at_zero(points: &[(F, F)]) -> F {
+    let xs: Vec<F> = points.iter().map(|(x, _)| *x).collect();
+    lagrange_coefficients_at_zero(&xs)
+        .into_iter()
+        .zip(points)
+        .map(|(coeff, (_, y))| coeff * y)
+        .sum()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IdError {
+    /// The id is not smaller than the scalar field modulus; reducing it
+    /// would make it collide with a smaller id (and its identity point).
+    IdTooLarge,
+}
+
+/// converts an identity to a field element, rejecting ids that
+/// F::from would silently reduce modulo the field order
+pub fn id_to_field_checked(id: &BigUint) -> Result<F, IdError> {
+    let modulus: BigUint = F::MODULUS.into();
+    if id >= &modulus {
+        return Err(IdError::IdTooLarge);
+    }
+
+    Ok(F::from(id.clone()))
+}
+
 pub fn compute_root(x: &F) -> F {
     x.sqrt().unwrap()
 }
 
+/// domain separator of pedersen_generator, so that no other use of
+/// hash-to-curve in G1 lands on the same point
+const PEDERSEN_GENERATOR_DST: &[u8] = b"pok3r-pedersen-generator";
+
+/// the second generator H of G1 for Pedersen commitments g^x * H^r; it is
+/// hashed to the curve, so no one knows its discrete log to the base g
+pub fn pedersen_generator() -> G1 {
+    let hasher = MapToCurveBasedHasher::<
+        Projective<ark_bls12_377::g1::Config>,
+        DefaultFieldHasher<blake2::Blake2b512>,
+        WBMap<ark_bls12_377::g1::Config>,
+    >::new(PEDERSEN_GENERATOR_DST).unwrap();
+    hasher.hash(b"H").unwrap()
+}
+
+/// the shuffle in the clear, as an oracle for tests: the prf 1/(sk + ω^i)
+/// of every card ω^i of a deck of deck_size cards, by card index. Where
+/// the cards land is up to the random samples, not sk, so a correct
+/// shuffle keyed by sk opens, for the card ω^i at any position, the prf
+/// at index i of this
+pub fn reference_shuffle(sk: F, deck_size: usize) -> Vec<F> {
+    let ω = multiplicative_subgroup_of_size(deck_size as u64).unwrap();
+    power_table(&ω, deck_size)
+        .iter()
+        .map(|ω_pow_i| (sk + ω_pow_i).inverse().expect("sk is -ω^i for some card"))
+        .collect()
+}
+
 pub fn compute_power(x: &F, n: u64) -> F {
     x.pow([n])
 }
 
-pub fn fs_hash(x: Vec<&[u8]>, num_output: usize) -> Vec<F> {
-    let hasher = <DefaultFieldHasher<Sha256> as HashToField<F>>::new(b"pok3r");
-    let field_elements = hasher.hash_to_field(&x.concat(), num_output);
-
-    field_elements
+/// computes (1, x, x^2, ..., x^(n-1)) with one multiplication per entry
+pub fn power_table(x: &F, n: usize) -> Vec<F> {
+    let mut powers = Vec::with_capacity(n);
+    let mut cur = F::one();
+    for _ in 0..n {
+        powers.push(cur);
+        cur *= x;
+    }
+    powers
 }
 
+pub use pok3r_verify::format::{fs_hash, fs_hash_with, HashConfig};
+
+pub use pok3r_verify::format::{fs_hash_fields_with, FieldEncoding};
+
 //computes f(x/ω)
 pub fn poly_domain_div_ω(f: &DensePolynomial<F>, ω: &F) -> DensePolynomial<F> {
     let mut new_poly = f.clone();
@@ -122,10 +481,18 @@ pub fn poly_domain_div_ω(f: &DensePolynomial<F>, ω: &F) -> DensePolynomial<F>
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_multiplicative_subgroup_of_unsupported_size() {
+        for n in [0, 52, 255, 1 << 48] {
+            assert_eq!(multiplicative_subgroup_of_size(n), Err(SubgroupError::NoSuchSubgroup));
+        }
+        assert!(multiplicative_subgroup_of_size(1 << 47).is_ok());
+    }
+
     #[test]
     fn test_multiplicative_subgroup_of_size() {
         let n: u64 = 64;
-        let ω = multiplicative_subgroup_of_size(n);
+        let ω = multiplicative_subgroup_of_size(n).unwrap();
         
         //check if ω^n = 1
         let ω_pow_n_minus_1 = ω.pow([n-1]);
@@ -141,4 +508,359 @@ mod tests {
             assert_ne!(ω_pow_i, one);
         }
     }
+
+    #[test]
+    fn test_id_to_field_checked() {
+        assert_eq!(id_to_field_checked(&BigUint::from(15u8)), Ok(F::from(15)));
+
+        let modulus: BigUint = F::MODULUS.into();
+        let largest = &modulus - BigUint::from(1u8);
+        assert_eq!(id_to_field_checked(&largest), Ok(-F::from(1)));
+
+        // F::from would have wrapped these around to 0 and 1
+        assert_eq!(id_to_field_checked(&modulus), Err(IdError::IdTooLarge));
+        assert_eq!(id_to_field_checked(&(&modulus + BigUint::from(1u8))), Err(IdError::IdTooLarge));
+    }
+
+    #[test]
+    fn test_shamir_reconstructs_from_any_subset() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let secret = F::rand(&mut rng);
+        let shares = compute_shamir_shares(&secret, 2, 5, &mut rng);
+
+        let points = |subset: &[usize]| -> Vec<(F, F)> {
+            subset.iter().map(|&i| (F::from((i + 1) as u64), shares[i])).collect()
+        };
+
+        // any threshold + 1 = 3 shares, or more, give back the secret
+        for subset in [[0, 1, 2], [0, 2, 4], [1, 3, 4], [2, 3, 4]] {
+            assert_eq!(lagrange_interpolate_at_zero(&points(&subset)), secret);
+        }
+        assert_eq!(lagrange_interpolate_at_zero(&points(&[0, 1, 2, 3, 4])), secret);
+
+        // threshold shares are not enough
+        assert_ne!(lagrange_interpolate_at_zero(&points(&[1, 3])), secret);
+    }
+
+    #[test]
+    fn test_srs_ceremony() {
+        use crate::kzg::{ceremony_contribute, verify_contribution};
+
+        let mut srs = setup_kzg(64);
+        let mut proofs = vec![];
+        for entropy in [b"alice", b"bobby", b"carol"] {
+            let (next, proof) = ceremony_contribute(&srs, entropy);
+            assert!(verify_contribution(&srs, &next, &proof));
+            proofs.push(proof);
+            srs = next;
+        }
+
+        // a step's proof does not vouch for a different update
+        let (next, _) = ceremony_contribute(&srs, b"dave");
+        assert!(!verify_contribution(&srs, &next, &proofs[0]));
+
+        // nor does it vouch for an SRS that is not powers of a single β
+        let (mut broken, proof) = ceremony_contribute(&srs, b"dave");
+        broken.powers_of_g[7] = broken.powers_of_g[8];
+        assert!(!verify_contribution(&srs, &broken, &proof));
+
+        // the final SRS commits to and opens degree-64 polynomials
+        let mut rng = StdRng::seed_from_u64(64);
+        let f = DensePolynomial::<F>::rand(64, &mut rng);
+        let z = F::rand(&mut rng);
+        let com = commit_poly(&srs, &f);
+        let pi = KZG::compute_opening_proof(&srs, &f, &z).unwrap();
+        assert!(kzg_check(&srs, &com, &z, &f.evaluate(&z), &pi));
+
+        // and is told apart from the SRS the ceremony started from
+        assert_ne!(crate::kzg::srs_fingerprint(&srs), crate::kzg::srs_fingerprint(&setup_kzg(64)));
+
+        // it becomes the SRS of the process, once
+        let fingerprint = crate::kzg::srs_fingerprint(&srs);
+        assert_eq!(set_global_srs(srs), Ok(()));
+        assert!(global_srs().is_some());
+        assert_eq!(global_srs_fingerprint(), Some(fingerprint));
+        assert_eq!(set_global_srs(setup_kzg(64)), Err(SrsError::AlreadySet));
+    }
+
+    #[test]
+    fn test_power_table() {
+        let ω = multiplicative_subgroup_of_size(64).unwrap();
+        let table = power_table(&ω, 64);
+
+        assert_eq!(table.len(), 64);
+        for (i, power) in table.iter().enumerate() {
+            assert_eq!(*power, compute_power(&ω, i as u64));
+        }
+        assert!(power_table(&ω, 0).is_empty());
+    }
+
+    #[test]
+    fn test_commitment_equality() {
+        use ark_poly::DenseUVPolynomial;
+
+        let pp = setup_kzg(64);
+        let mut rng = test_rng();
+
+        let deck = DensePolynomial::<F>::rand(40, &mut rng);
+        let f_com = commit_poly(&pp, &deck);
+        let f_com_again = commit_poly(&pp, &deck);
+
+        let proof = prove_commitment_equality(&pp, &deck, &f_com, &f_com_again);
+        assert!(verify_commitment_equality(&pp, &f_com, &f_com_again, &proof));
+
+        // a different deck cannot be passed off as the committed one
+        let other_deck = DensePolynomial::<F>::rand(40, &mut rng);
+        let other_com = commit_poly(&pp, &other_deck);
+        let proof = prove_commitment_equality(&pp, &deck, &f_com, &other_com);
+        assert!(!verify_commitment_equality(&pp, &f_com, &other_com, &proof));
+        let proof = prove_commitment_equality(&pp, &other_deck, &f_com, &other_com);
+        assert!(!verify_commitment_equality(&pp, &f_com, &other_com, &proof));
+    }
+
+    #[test]
+    fn test_fs_hash_with() {
+        let configs = [HashConfig::Sha256, HashConfig::Keccak256, HashConfig::Blake2b];
+        let outputs: Vec<Vec<F>> = configs
+            .iter()
+            .map(|config| fs_hash_with(*config, vec![b"deck", b"commitment"], 2))
+            .collect();
+
+        for (config, output) in configs.iter().zip(outputs.iter()) {
+            assert_eq!(&fs_hash_with(*config, vec![b"deck", b"commitment"], 2), output);
+        }
+        assert_ne!(outputs[0], outputs[1]);
+        assert_ne!(outputs[0], outputs[2]);
+        assert_ne!(outputs[1], outputs[2]);
+
+        // the default is unchanged
+        assert_eq!(fs_hash(vec![b"deck", b"commitment"], 2), outputs[0]);
+
+        // Ethereum's keccak256(""), not NIST SHA3-256
+        assert_eq!(
+            HashConfig::Keccak256.digest(b""),
+            vec![
+                0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+                0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_srs_fingerprint_and_deck_commitment() {
+        let srs = setup_kzg(64);
+        let (other, _) = crate::kzg::ceremony_contribute(&srs, b"another ceremony");
+
+        assert_eq!(crate::kzg::srs_fingerprint(&srs), crate::kzg::srs_fingerprint(&setup_kzg(64)));
+        assert_ne!(crate::kzg::srs_fingerprint(&srs), crate::kzg::srs_fingerprint(&other));
+
+        let (_, agreed) = canonical_deck_poly(&srs, 64).unwrap();
+        assert_eq!(check_srs_deck_commitment(&srs, 64, &agreed), Ok(()));
+        assert_eq!(check_srs_deck_commitment(&other, 64, &agreed), Err(SrsError::DeckCommitmentMismatch { size: 64 }));
+        assert_eq!(check_srs_deck_commitment(&srs, 128, &agreed), Err(SrsError::DeckCommitmentMismatch { size: 128 }));
+    }
+
+    #[test]
+    fn test_field_encodings() {
+        let x = F::from(0x0102u64);
+        let le = FieldEncoding::LittleEndian.encode(&x);
+        let be = FieldEncoding::BigEndian.encode(&x);
+
+        // 32 bytes each, the low byte first or last
+        assert_eq!(le.len(), 32);
+        assert_eq!(&le[..3], &[0x02, 0x01, 0x00]);
+        assert!(le[2..].iter().all(|b| *b == 0));
+        assert_eq!(be.iter().rev().cloned().collect::<Vec<u8>>(), le);
+        assert_eq!(&be[29..], &[0x00, 0x01, 0x02]);
+
+        // the default is ark's canonical serialization
+        let mut canonical = Vec::new();
+        x.serialize_uncompressed(&mut canonical).unwrap();
+        assert_eq!(FieldEncoding::default().encode(&x), canonical);
+
+        let mut rng = test_rng();
+        for encoding in [FieldEncoding::LittleEndian, FieldEncoding::BigEndian] {
+            let y = F::rand(&mut rng);
+            assert_eq!(encoding.decode(&encoding.encode(&y)), Some(y));
+
+            // a value at or above the modulus, or the wrong length, is not an element
+            let modulus = BigUint::from(F::MODULUS);
+            let mut too_big = modulus.to_bytes_le();
+            too_big.resize(32, 0);
+            if encoding == FieldEncoding::BigEndian {
+                too_big.reverse();
+            }
+            assert_eq!(encoding.decode(&too_big), None);
+            assert_eq!(encoding.decode(&encoding.encode(&y)[1..]), None);
+        }
+
+        // fields hashed little-endian are the default transcript bytes
+        let xs = [x, F::from(7u64)];
+        assert_eq!(
+            fs_hash_fields_with(HashConfig::default(), FieldEncoding::LittleEndian, &xs, 2),
+            fs_hash(vec![&le, &FieldEncoding::LittleEndian.encode(&xs[1])], 2)
+        );
+        assert_ne!(
+            fs_hash_fields_with(HashConfig::default(), FieldEncoding::BigEndian, &xs, 2),
+            fs_hash_fields_with(HashConfig::default(), FieldEncoding::LittleEndian, &xs, 2)
+        );
+    }
+
+    #[test]
+    fn test_kzg_check_batch() {
+        use ark_poly::DenseUVPolynomial;
+
+        let pp = setup_kzg(64);
+        let mut rng = test_rng();
+
+        let mut comms = vec![];
+        let mut xs = vec![];
+        let mut evals = vec![];
+        let mut proofs = vec![];
+        for i in 0..8 {
+            let poly = DensePolynomial::<F>::rand(20, &mut rng);
+            let x = F::from(i as u64 + 3);
+            comms.push(commit_poly(&pp, &poly));
+            xs.push(x);
+            evals.push(poly.evaluate(&x));
+            proofs.push(KZG::compute_opening_proof(&pp, &poly, &x).unwrap());
+        }
+        assert!(kzg_check_batch(&pp, &comms, &xs, &evals, &proofs));
+
+        let mut wrong_evals = evals.clone();
+        wrong_evals[5] += F::one();
+        assert!(!kzg_check(&pp, &comms[5], &xs[5], &wrong_evals[5], &proofs[5]));
+        assert!(!kzg_check_batch(&pp, &comms, &xs, &wrong_evals, &proofs));
+
+        // a proof left out is no opening, not a panic
+        assert!(!kzg_check_batch(&pp, &comms, &xs, &evals, &proofs[1..]));
+    }
+
+    #[test]
+    fn test_kzg_check_vanishing_agrees_with_pointwise() {
+        use ark_poly::DenseUVPolynomial;
+
+        let n = 64;
+        let pp = setup_kzg(256);
+        let mut rng = test_rng();
+        let domain = exact_domain_for::<F>(n).unwrap();
+
+        let q = DensePolynomial::<F>::rand(100, &mut rng);
+        let d = q.mul_by_vanishing_poly(domain);
+        assert!(vanishes_on_subgroup(&d, n));
+        assert!(kzg_check_vanishing(&pp, &commit_poly(&pp, &d), &commit_poly(&pp, &q), n));
+
+        // a non-zero remainder shows up in both checks
+        let remainder = DensePolynomial::from_coefficients_vec(vec![F::from(5), F::from(1)]);
+        let d = &d + &remainder;
+        let (q, _) = d.divide_by_vanishing_poly(domain).unwrap();
+        assert!(!vanishes_on_subgroup(&d, n));
+        assert!(!kzg_check_vanishing(&pp, &commit_poly(&pp, &d), &commit_poly(&pp, &q), n));
+    }
+
+    #[test]
+    fn test_eval_proof_at_root_matches_division() {
+        use ark_poly::DenseUVPolynomial;
+
+        let pp = setup_kzg(256);
+        let mut rng = test_rng();
+
+        for n in [16, 256] {
+            let lagrange = lagrange_commitments(&pp, n);
+            let f = DensePolynomial::<F>::rand(n - 1, &mut rng);
+            let ω = multiplicative_subgroup_of_size(n as u64).unwrap();
+            let points = power_table(&ω, n);
+            let evals: Vec<F> = points.iter().map(|x| f.evaluate(x)).collect();
+
+            for i in [0, 1, n / 2, n - 1] {
+                let fast = eval_proof_at_root(&lagrange, &evals, i);
+                let slow = KZG::compute_opening_proof(&pp, &f, &points[i]).unwrap();
+                assert_eq!(fast, slow);
+                assert!(kzg_check(&pp, &commit_poly(&pp, &f), &points[i], &evals[i], &fast));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lagrange_commitments_are_cached() {
+        let pp = setup_kzg(64);
+        let lagrange = lagrange_commitments(&pp, 16);
+
+        // the same size and SRS give back the bases computed before
+        assert!(Arc::ptr_eq(&lagrange, &lagrange_commitments(&pp, 16)));
+        assert!(!Arc::ptr_eq(&lagrange, &lagrange_commitments(&pp, 32)));
+        assert!(!Arc::ptr_eq(&lagrange, &lagrange_commitments(&KZG::setup(64, &mut test_rng()).unwrap(), 16)));
+    }
+
+    #[test]
+    fn test_constant_term_opening() {
+        use ark_poly::DenseUVPolynomial;
+
+        let pp = setup_kzg(64);
+        let mut rng = test_rng();
+
+        let poly = DensePolynomial::<F>::rand(40, &mut rng);
+        let comm = commit_poly(&pp, &poly);
+
+        let (constant_term, pi) = prove_constant_term(&pp, &poly);
+        assert_eq!(constant_term, poly.coeffs[0]);
+        assert!(verify_constant_term(&pp, &comm, &constant_term, &pi));
+
+        // neither another value nor another polynomial passes
+        assert!(!verify_constant_term(&pp, &comm, &(constant_term + F::one()), &pi));
+        let other_comm = commit_poly(&pp, &DensePolynomial::<F>::rand(40, &mut rng));
+        assert!(!verify_constant_term(&pp, &other_comm, &constant_term, &pi));
+    }
+
+    #[test]
+    fn test_canonical_deck_poly_matches_inline_construction() {
+        let pp = setup_kzg(256);
+
+        for size in [64, 256] {
+            let ω = multiplicative_subgroup_of_size(size as u64).unwrap();
+            let v_evals: Vec<F> = power_table(&ω, size);
+            let v = interpolate_poly_over_mult_subgroup(&v_evals);
+            let v_com = commit_poly(&pp, &v);
+
+            assert_eq!(canonical_deck_poly(&pp, size).unwrap(), (v, v_com));
+        }
+
+        // another SRS gets its own commitment
+        let other_pp = KZG::setup(256, &mut test_rng()).unwrap();
+        let (v, v_com) = canonical_deck_poly(&other_pp, 64).unwrap();
+        assert_eq!(v_com, commit_poly(&other_pp, &v));
+        assert_ne!(v_com, canonical_deck_poly(&pp, 64).unwrap().1);
+    }
+
+    #[test]
+    fn test_domain_for() {
+        let (domain, padding) = domain_for::<F>(64).unwrap();
+        assert_eq!((domain.size(), padding), (64, 0));
+
+        // non-power-of-two sizes are rounded up, and the padding is reported
+        let (domain, padding) = domain_for::<F>(52).unwrap();
+        assert_eq!((domain.size(), padding), (64, 12));
+        assert_eq!(exact_domain_for::<F>(52).unwrap_err(), DomainError::NeedsPadding { size: 52, padded_size: 64 });
+        assert_eq!(exact_domain_for::<F>(64).unwrap().size(), 64);
+
+        assert_eq!(domain_for::<F>(0).unwrap_err(), DomainError::EmptyDomain);
+        assert_eq!(domain_for::<F>(1 << 48).unwrap_err(), DomainError::DomainTooLarge { size: 1 << 48 });
+    }
+
+    #[test]
+    fn test_try_interpolate_reports_padding() {
+        let evals = vec![F::from(1); 52];
+        assert_eq!(
+            try_interpolate_poly_over_mult_subgroup(&evals).unwrap_err(),
+            DomainError::NeedsPadding { size: 52, padded_size: 64 }
+        );
+        assert!(try_interpolate_poly_over_mult_subgroup(&evals[..32]).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot interpolate 52 points")]
+    fn test_interpolate_rejects_non_power_of_two() {
+        let evals = vec![F::from(1); 52];
+        interpolate_poly_over_mult_subgroup(&evals);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,6 @@
+/// a Share cannot be passed where a plaintext F is expected
+#[test]
+fn test_share_is_not_plaintext() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/share_is_not_plaintext.rs");
+}
@@ -0,0 +1,89 @@
+//! One Fiat-Shamir transcript for a permutation proof and an encryption
+//! proof about the same committed deck.
+
+use alloc::{vec, vec::Vec};
+
+use crate::encryption::EncryptTranscript;
+use crate::format::*;
+use crate::{F, G1, PERM_SIZE};
+
+/// the transcript both proofs of a combined proof derive their challenges
+/// from, in the order they are proven: the permutation argument absorbs
+/// the deck commitment f_com, as its first message and only there, then
+/// its own commitments; the encryption proof goes on from there with its
+/// transcript less card_commitment, which must be that same f_com. Every
+/// challenge thus depends on both proofs' deck, and none is the challenge
+/// of either proof on its own
+#[derive(Clone, Debug)]
+pub struct CombinedTranscript {
+    bytes: Vec<u8>,
+    /// the Fiat-Shamir hash every challenge is drawn with
+    hash: HashConfig,
+    /// byte order of the field elements absorbed
+    encoding: FieldEncoding,
+}
+
+impl Default for CombinedTranscript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CombinedTranscript {
+    pub fn new() -> Self {
+        CombinedTranscript {
+            bytes: b"pok3r/combined".to_vec(),
+            hash: HashConfig::default(),
+            encoding: FieldEncoding::default(),
+        }
+    }
+
+    /// draws every challenge, including the sigma proof's, with hash
+    /// instead of SHA-256; prover and verifier must pick the same one
+    pub fn with_hash(mut self, hash: HashConfig) -> Self {
+        self.hash = hash;
+        self
+    }
+
+    pub fn hash(&self) -> HashConfig {
+        self.hash
+    }
+
+    /// absorbs field elements, i.e. the masked evaluations of the
+    /// encryption proof, in encoding instead of ark's little-endian
+    pub fn with_field_encoding(mut self, encoding: FieldEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    fn challenges(&self, label: &[u8], num_output: usize) -> Vec<F> {
+        fs_hash_with(self.hash, vec![&self.bytes, label], num_output)
+    }
+
+    /// γ1 of the permutation argument, after absorbing f_com, the
+    /// canonical deck v_com and the card mapping
+    pub fn permutation_challenge_1(&mut self, v_com: &G1, f_com: &G1, mapping_commitment: &[u8; 32]) -> F {
+        write_element(&mut self.bytes, f_com);
+        write_element(&mut self.bytes, v_com);
+        self.bytes.extend_from_slice(mapping_commitment);
+        self.challenges(b"y1", 1)[0]
+    }
+
+    /// γ2 of the permutation argument, after absorbing q_com, t_com and
+    /// g_com; v_com and f_com are in the transcript already
+    pub fn permutation_challenge_2(&mut self, q_com: &G1, t_com: &G1, g_com: &G1) -> F {
+        write_element(&mut self.bytes, q_com);
+        write_element(&mut self.bytes, t_com);
+        write_element(&mut self.bytes, g_com);
+        self.challenges(b"y2", 1)[0]
+    }
+
+    /// the PERM_SIZE batching scalars s of the encryption proof, after
+    /// absorbing its transcript less card_commitment
+    pub fn encryption_challenges(&mut self, transcript: &EncryptTranscript) -> Vec<F> {
+        transcript.write_without_card_commitment(&mut self.bytes, self.encoding).unwrap();
+        let s1 = self.challenges(b"s0", PERM_SIZE / 2);
+        let s2 = self.challenges(b"s1", PERM_SIZE / 2);
+        s1.into_iter().chain(s2).collect()
+    }
+}
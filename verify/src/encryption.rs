@@ -0,0 +1,319 @@
+//! The encryptions of the dealt cards under their recipients' ids, and
+//! the public values their sigma proof is batched over.
+
+use alloc::{format, string::{String, ToString}, vec, vec::Vec};
+use ark_ec::AffineRepr;
+use ark_serialize::CanonicalSerialize;
+use ark_std::io::{self, Write};
+use core::fmt;
+use num_bigint::BigUint;
+
+use crate::format::*;
+use crate::sigma::{batch_g1, SigmaElement, SigmaProof, SigmaRelation, SigmaStatementProof};
+use crate::{F, G1, G2, Gt, PERM_SIZE};
+
+fn write_element_to<W: Write, T: CanonicalSerialize>(w: &mut W, element: &T) -> io::Result<()> {
+    element.serialize_uncompressed(w).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+fn write_len_to<W: Write>(w: &mut W, len: usize) -> io::Result<()> {
+    w.write_all(&(len as u32).to_le_bytes())
+}
+
+/// whether pk can be encrypted to: a point of the G2 subgroup other than
+/// the identity, which would make every ciphertext degenerate
+pub fn is_valid_public_key(pk: &G2) -> bool {
+    !pk.is_zero() && pk.is_on_curve() && pk.is_in_correct_subgroup_assuming_on_curve()
+}
+
+/// EncryptProof is a structure for the encryptions and attached proofs
+/// produced by encrypt_and_prove and verified by local_verify_encryption_proof
+#[derive(Clone)]
+pub struct EncryptProof {
+    pub pk: G2,
+    pub ids: Vec<BigUint>,
+    pub card_commitment: G1,
+    pub masked_commitments: Vec<G1>,
+    pub masked_evals: Vec<F>,
+    pub eval_proofs: Vec<G1>,
+    pub ciphertexts: Vec<(G2,Gt)>,
+    pub sigma_proof: Option<SigmaProof>,
+}
+
+impl EncryptProof {
+    /// the transcript the batching scalars hash, followed by a byte telling
+    /// whether a sigma proof is attached and, if so, the sigma proof; it is
+    /// computed over the transcript, so it stays out of what is hashed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// writes the bytes of to_bytes straight into w, without building the
+    /// intermediate buffer or the labeled chunks of transcript_dump
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.transcript().write_to(w)?;
+
+        match &self.sigma_proof {
+            Some(sigma_proof) => {
+                w.write_all(&[1])?;
+                write_element_to(w, &sigma_proof.a1)?;
+                write_element_to(w, &sigma_proof.a2)?;
+                write_element_to(w, &sigma_proof.a3)?;
+                write_element_to(w, &sigma_proof.x)?;
+                write_element_to(w, &sigma_proof.y)
+            },
+            None => w.write_all(&[0]),
+        }
+    }
+
+    /// the public values of the proof that its batching scalars hash
+    pub fn transcript(&self) -> EncryptTranscript<'_> {
+        EncryptTranscript {
+            pk: &self.pk,
+            ids: &self.ids,
+            card_commitment: &self.card_commitment,
+            masked_commitments: &self.masked_commitments,
+            masked_evals: &self.masked_evals,
+            eval_proofs: &self.eval_proofs,
+            ciphertexts: &self.ciphertexts,
+        }
+    }
+
+    /// the transcript part of to_bytes as labeled chunks, for diffing
+    /// against another implementation's transcript; the batching scalars s
+    /// hash these chunks followed by b"0" (first half) and b"1" (second half)
+    pub fn transcript_dump(&self) -> Vec<(String, Vec<u8>)> {
+        let mut header = Vec::new();
+        write_header(&mut header);
+        let mut chunks = vec![("header".to_string(), header), labeled("pk", &self.pk)];
+
+        chunks.push(labeled_len("ids.len", self.ids.len()));
+        for (i, id) in self.ids.iter().enumerate() {
+            let id_bytes = id.to_bytes_be();
+            chunks.push(labeled_len(&format!("ids[{}].len", i), id_bytes.len()));
+            chunks.push((format!("ids[{}]", i), id_bytes));
+        }
+
+        chunks.push(labeled("card_commitment", &self.card_commitment));
+
+        chunks.push(labeled_len("masked_commitments.len", self.masked_commitments.len()));
+        for (i, masked_commitment) in self.masked_commitments.iter().enumerate() {
+            chunks.push(labeled(&format!("masked_commitments[{}]", i), masked_commitment));
+        }
+
+        chunks.push(labeled_len("masked_evals.len", self.masked_evals.len()));
+        for (i, masked_eval) in self.masked_evals.iter().enumerate() {
+            chunks.push(labeled(&format!("masked_evals[{}]", i), masked_eval));
+        }
+
+        chunks.push(labeled_len("eval_proofs.len", self.eval_proofs.len()));
+        for (i, eval_proof) in self.eval_proofs.iter().enumerate() {
+            chunks.push(labeled(&format!("eval_proofs[{}]", i), eval_proof));
+        }
+
+        chunks.push(labeled_len("ciphertexts.len", self.ciphertexts.len()));
+        for (i, (ciphertext1, ciphertext2)) in self.ciphertexts.iter().enumerate() {
+            chunks.push(labeled(&format!("ciphertexts[{}].0", i), ciphertext1));
+            chunks.push(labeled(&format!("ciphertexts[{}].1", i), ciphertext2));
+        }
+
+        chunks
+    }
+
+    /// parses the output of to_bytes, sigma proof included if there is one
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofFormatError> {
+        let reader = &mut &bytes[..];
+        read_header(reader)?;
+
+        let pk = read_element(reader)?;
+
+        let mut ids = Vec::new();
+        for _ in 0..read_len(reader)? {
+            let len = read_len(reader)?;
+            if reader.len() < len {
+                return Err(ProofFormatError::Malformed);
+            }
+            ids.push(BigUint::from_bytes_be(&reader[..len]));
+            *reader = &reader[len..];
+        }
+
+        let card_commitment = read_element(reader)?;
+        let masked_commitments = read_vec(reader)?;
+        let masked_evals = read_vec(reader)?;
+        let eval_proofs = read_vec(reader)?;
+
+        let mut ciphertexts = Vec::new();
+        for _ in 0..read_len(reader)? {
+            ciphertexts.push((read_element(reader)?, read_element(reader)?));
+        }
+
+        let (has_sigma_proof, rest) = reader.split_first().ok_or(ProofFormatError::Malformed)?;
+        *reader = rest;
+        let sigma_proof = match has_sigma_proof {
+            0 => None,
+            1 => Some(SigmaProof {
+                a1: read_element(reader)?,
+                a2: read_element(reader)?,
+                a3: read_element(reader)?,
+                x: read_element(reader)?,
+                y: read_element(reader)?,
+            }),
+            _ => return Err(ProofFormatError::Malformed),
+        };
+
+        let proof = EncryptProof {
+            pk,
+            ids,
+            card_commitment,
+            masked_commitments,
+            masked_evals,
+            eval_proofs,
+            ciphertexts,
+            sigma_proof,
+        };
+        finish(reader, proof)
+    }
+
+    /// covers the sigma proof too, if one is attached
+    pub fn fingerprint(&self) -> [u8; 16] {
+        fingerprint_of(&self.to_bytes())
+    }
+
+    /// re-derives the PERM_SIZE batching scalars s used by the sigma proof,
+    /// exactly as local_verify_encryption_proof does; they hash only the
+    /// transcript, so prover and verifier hash the same bytes
+    pub fn recompute_challenges(&self) -> Vec<F> {
+        self.transcript().challenges()
+    }
+}
+
+/// the public values of an encryption proof that the batching scalars s
+/// of its sigma proof are derived from, borrowed from wherever they live:
+/// the prover derives s before there is an EncryptProof to hold them
+#[derive(Clone, Copy)]
+pub struct EncryptTranscript<'a> {
+    pub pk: &'a G2,
+    pub ids: &'a [BigUint],
+    pub card_commitment: &'a G1,
+    pub masked_commitments: &'a [G1],
+    pub masked_evals: &'a [F],
+    pub eval_proofs: &'a [G1],
+    pub ciphertexts: &'a [(G2, Gt)],
+}
+
+impl EncryptTranscript<'_> {
+    /// the bytes of EncryptProof::to_bytes for these values, up to the
+    /// sigma proof
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_parts(w, true, FieldEncoding::LittleEndian)
+    }
+
+    /// write_to without card_commitment, and with the masked evaluations
+    /// in encoding, for a transcript that absorbed the deck commitment
+    /// already, see CombinedTranscript
+    pub fn write_without_card_commitment<W: Write>(&self, w: &mut W, encoding: FieldEncoding) -> io::Result<()> {
+        self.write_parts(w, false, encoding)
+    }
+
+    fn write_parts<W: Write>(&self, w: &mut W, with_card_commitment: bool, encoding: FieldEncoding) -> io::Result<()> {
+        let mut header = Vec::new();
+        write_header(&mut header);
+        w.write_all(&header)?;
+        write_element_to(w, self.pk)?;
+
+        write_len_to(w, self.ids.len())?;
+        for id in self.ids {
+            let id_bytes = id.to_bytes_be();
+            write_len_to(w, id_bytes.len())?;
+            w.write_all(&id_bytes)?;
+        }
+
+        if with_card_commitment {
+            write_element_to(w, self.card_commitment)?;
+        }
+
+        write_len_to(w, self.masked_commitments.len())?;
+        for masked_commitment in self.masked_commitments {
+            write_element_to(w, masked_commitment)?;
+        }
+
+        write_len_to(w, self.masked_evals.len())?;
+        for masked_eval in self.masked_evals {
+            w.write_all(&encoding.encode(masked_eval))?;
+        }
+
+        write_len_to(w, self.eval_proofs.len())?;
+        for eval_proof in self.eval_proofs {
+            write_element_to(w, eval_proof)?;
+        }
+
+        write_len_to(w, self.ciphertexts.len())?;
+        for (ciphertext1, ciphertext2) in self.ciphertexts {
+            write_element_to(w, ciphertext1)?;
+            write_element_to(w, ciphertext2)?;
+        }
+
+        Ok(())
+    }
+
+    /// D_batch = sum_i s_i d_i, the masked commitments batched with s
+    pub fn batched_masked_commitment(&self, s: &[F]) -> G1 {
+        batch_g1(self.masked_commitments, s)
+    }
+
+    /// the PERM_SIZE batching scalars s: the first half hashes the
+    /// transcript followed by b"0", the second half followed by b"1"
+    pub fn challenges(&self) -> Vec<F> {
+        // hash_to_field wants the whole message at once, so the transcript
+        // is written a single time and the suffix swapped in place
+        let mut input = Vec::new();
+        self.write_to(&mut input).unwrap();
+        input.push(b'0');
+        let s1 = fs_hash(vec![&input], PERM_SIZE/2);
+        *input.last_mut().unwrap() = b'1';
+        let s2 = fs_hash(vec![&input], PERM_SIZE/2);
+
+        s1.into_iter().chain(s2).collect()
+    }
+}
+
+/// prints only a fingerprint and the sizes, not the ciphertexts
+impl fmt::Debug for EncryptProof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncryptProof")
+            .field("fingerprint", &fingerprint_hex(&self.fingerprint()))
+            .field("cards", &self.ciphertexts.len())
+            .field("bytes", &self.to_bytes().len())
+            .field("sigma_proof", &self.sigma_proof)
+            .finish()
+    }
+}
+
+/// checks that the masked commitments d_i = C^{z_i} are rerandomizations
+/// of card_commitment C, as far as the proof can show it: for D_batch =
+/// sum_i s_i d_i, the sigma proof's response x must satisfy
+/// C^x = a1 * D_batch^gamma. This is statement 1 of the encryption sigma
+/// proof on its own, so local_verify_encryption_proof implies it; a
+/// failure here pins a rejection on the d_i rather than the ciphertexts
+pub fn verify_masked_commitments(proof: &EncryptProof) -> bool {
+    let sigma = match &proof.sigma_proof {
+        Some(sigma) => SigmaStatementProof::from(sigma),
+        None => return false,
+    };
+    if proof.masked_commitments.len() != PERM_SIZE {
+        return false;
+    }
+
+    let s = proof.recompute_challenges();
+    let d_batch = proof.transcript().batched_masked_commitment(&s);
+    let relation = SigmaRelation {
+        terms: vec![(SigmaElement::G1(proof.card_commitment), 0)],
+        target: SigmaElement::G1(d_batch),
+    };
+
+    let gamma = SigmaStatementProof::legacy_challenge(&sigma.commitments);
+    let lhs = relation.evaluate(&sigma.responses);
+    lhs.is_some() && lhs == relation.target.scale(&gamma).combine(&sigma.commitments[0])
+}
@@ -0,0 +1,208 @@
+//! The byte layout shared by all serialized proofs, and the Fiat-Shamir
+//! hash over it.
+
+use alloc::{format, string::{String, ToString}, vec::Vec};
+use ark_ff::field_hashers::{DefaultFieldHasher, HashToField};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use sha2::{Digest, Sha256};
+
+use crate::F;
+
+/// every serialized proof starts with PROOF_MAGIC followed by the version
+/// of its layout; bump PROOF_FORMAT_VERSION whenever a layout changes
+pub const PROOF_MAGIC: [u8; 2] = *b"p3";
+pub const PROOF_FORMAT_VERSION: u8 = 4;
+
+#[derive(Debug, PartialEq)]
+pub enum ProofFormatError {
+    /// The bytes do not start with PROOF_MAGIC, so they are not a proof.
+    BadMagic,
+    /// The proof was serialized with a layout this build cannot parse.
+    VersionMismatch {
+        /// The version found in the serialized proof.
+        found: u8,
+        /// The version this build reads and writes.
+        expected: u8,
+    },
+    /// The bytes after the header do not parse as the expected proof.
+    Malformed,
+}
+
+pub fn write_header(bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&PROOF_MAGIC);
+    bytes.push(PROOF_FORMAT_VERSION);
+}
+
+pub fn read_header(reader: &mut &[u8]) -> Result<(), ProofFormatError> {
+    if reader.len() < PROOF_MAGIC.len() + 1 || reader[..PROOF_MAGIC.len()] != PROOF_MAGIC {
+        return Err(ProofFormatError::BadMagic);
+    }
+
+    let found = reader[PROOF_MAGIC.len()];
+    if found != PROOF_FORMAT_VERSION {
+        return Err(ProofFormatError::VersionMismatch { found, expected: PROOF_FORMAT_VERSION });
+    }
+
+    *reader = &reader[PROOF_MAGIC.len() + 1..];
+    Ok(())
+}
+
+pub fn write_element<T: CanonicalSerialize>(bytes: &mut Vec<u8>, element: &T) {
+    element.serialize_uncompressed(bytes).unwrap();
+}
+
+pub fn read_element<T: CanonicalDeserialize>(reader: &mut &[u8]) -> Result<T, ProofFormatError> {
+    T::deserialize_uncompressed(reader).map_err(|_| ProofFormatError::Malformed)
+}
+
+pub fn write_len(bytes: &mut Vec<u8>, len: usize) {
+    bytes.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+pub fn read_len(reader: &mut &[u8]) -> Result<usize, ProofFormatError> {
+    if reader.len() < 4 {
+        return Err(ProofFormatError::Malformed);
+    }
+
+    let len = u32::from_le_bytes(reader[..4].try_into().unwrap()) as usize;
+    *reader = &reader[4..];
+    Ok(len)
+}
+
+pub fn read_vec<T: CanonicalDeserialize>(reader: &mut &[u8]) -> Result<Vec<T>, ProofFormatError> {
+    // do not trust the length for preallocation, every element must actually be there
+    (0..read_len(reader)?).map(|_| read_element(reader)).collect()
+}
+
+pub fn finish<T>(reader: &[u8], proof: T) -> Result<T, ProofFormatError> {
+    if !reader.is_empty() {
+        return Err(ProofFormatError::Malformed);
+    }
+    Ok(proof)
+}
+
+pub fn write_bytes(bytes: &mut Vec<u8>, blob: &[u8]) {
+    write_len(bytes, blob.len());
+    bytes.extend_from_slice(blob);
+}
+
+pub fn read_bytes<'a>(reader: &mut &'a [u8]) -> Result<&'a [u8], ProofFormatError> {
+    let len = read_len(reader)?;
+    if reader.len() < len {
+        return Err(ProofFormatError::Malformed);
+    }
+
+    let (blob, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(blob)
+}
+
+pub fn labeled<T: CanonicalSerialize>(label: &str, element: &T) -> (String, Vec<u8>) {
+    let mut bytes = Vec::new();
+    write_element(&mut bytes, element);
+    (label.to_string(), bytes)
+}
+
+pub fn labeled_len(label: &str, len: usize) -> (String, Vec<u8>) {
+    let mut bytes = Vec::new();
+    write_len(&mut bytes, len);
+    (label.to_string(), bytes)
+}
+
+/// first 16 bytes of SHA-256 over a proof's bytes, for telling proofs
+/// apart in logs without printing them
+pub fn fingerprint_of(bytes: &[u8]) -> [u8; 16] {
+    Sha256::digest(bytes)[..16].try_into().unwrap()
+}
+
+pub fn fingerprint_hex(fingerprint: &[u8; 16]) -> String {
+    fingerprint.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// hash function behind the Fiat-Shamir challenges; a verifier must use
+/// the same one as the prover, e.g. Keccak256 for an on-chain verifier
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum HashConfig {
+    #[default]
+    Sha256,
+    Keccak256,
+    Blake2b,
+}
+
+impl HashConfig {
+    /// plain digest of data, as an external verifier would compute it
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashConfig::Sha256 => Sha256::digest(data).to_vec(),
+            HashConfig::Keccak256 => sha3::Keccak256::digest(data).to_vec(),
+            HashConfig::Blake2b => blake2::Blake2b512::digest(data).to_vec(),
+        }
+    }
+}
+
+/// the Fiat-Shamir hash of the proofs: SHA-256 based hash to field over
+/// the concatenation of x
+pub fn fs_hash(x: Vec<&[u8]>, num_output: usize) -> Vec<F> {
+    fs_hash_with(HashConfig::Sha256, x, num_output)
+}
+
+/// fs_hash with the hash to field built on config
+pub fn fs_hash_with(config: HashConfig, x: Vec<&[u8]>, num_output: usize) -> Vec<F> {
+    let input = x.concat();
+    match config {
+        HashConfig::Sha256 => <DefaultFieldHasher<Sha256> as HashToField<F>>::new(b"pok3r")
+            .hash_to_field(&input, num_output),
+        HashConfig::Keccak256 => <DefaultFieldHasher<sha3::Keccak256> as HashToField<F>>::new(b"pok3r")
+            .hash_to_field(&input, num_output),
+        HashConfig::Blake2b => <DefaultFieldHasher<blake2::Blake2b512> as HashToField<F>>::new(b"pok3r")
+            .hash_to_field(&input, num_output),
+    }
+}
+
+/// byte order in which field elements enter a transcript: ark's canonical
+/// serialization is little-endian, while an EVM verifier reads 32-byte
+/// big-endian words
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FieldEncoding {
+    #[default]
+    LittleEndian,
+    BigEndian,
+}
+
+impl FieldEncoding {
+    /// the 32 bytes of x, least (LittleEndian) or most (BigEndian)
+    /// significant byte first
+    pub fn encode(&self, x: &F) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        x.serialize_uncompressed(&mut bytes).unwrap();
+        if *self == FieldEncoding::BigEndian {
+            bytes.reverse();
+        }
+        bytes
+    }
+
+    /// the inverse of encode; None unless bytes is the encoding of an
+    /// element, i.e. 32 bytes of a value below the modulus
+    pub fn decode(&self, bytes: &[u8]) -> Option<F> {
+        let mut bytes = bytes.to_vec();
+        if *self == FieldEncoding::BigEndian {
+            bytes.reverse();
+        }
+        let mut reader = bytes.as_slice();
+        let x = F::deserialize_uncompressed(&mut reader).ok()?;
+        reader.is_empty().then_some(x)
+    }
+}
+
+/// fs_hash_with over the field elements x, each encoded with encoding
+pub fn fs_hash_fields_with(config: HashConfig, encoding: FieldEncoding, x: &[F], num_output: usize) -> Vec<F> {
+    let encoded: Vec<Vec<u8>> = x.iter().map(|x_i| encoding.encode(x_i)).collect();
+    fs_hash_with(config, encoded.iter().map(|bytes| bytes.as_slice()).collect(), num_output)
+}
+
+/// fs_hash over the chunks of a transcript, in order
+pub fn hash_transcript(chunks: &[(String, Vec<u8>)], suffix: &[u8], num_output: usize) -> Vec<F> {
+    let mut x: Vec<&[u8]> = chunks.iter().map(|(_, bytes)| bytes.as_slice()).collect();
+    x.push(suffix);
+    fs_hash(x, num_output)
+}
@@ -0,0 +1,150 @@
+//! The KZG verifier: the public parameters and the pairing check of an
+//! opening, and the commitment to the canonical deck both sides derive.
+
+use alloc::vec::Vec;
+use ark_ec::{pairing::Pairing, AffineRepr, CurveGroup, VariableBaseMSM};
+use ark_ff::{FftField, One, PrimeField};
+use ark_poly::{
+    univariate::DensePolynomial, DenseUVPolynomial, EvaluationDomain, Evaluations,
+    GeneralEvaluationDomain,
+};
+use ark_serialize::CanonicalSerialize;
+use ark_std::ops::*;
+use sha2::{Digest, Sha256};
+
+use crate::{Curve, F, G1};
+
+pub struct UniversalParams<E: Pairing> {
+    /// Group elements of the form `{ \beta^i G }`, where `i` ranges from 0 to `degree`.
+    pub powers_of_g: Vec<E::G1Affine>,
+    /// Group elements of the form `{ \beta^i H }`, where `i` ranges from 0 to `degree`.
+    pub powers_of_h: Vec<E::G2Affine>,
+}
+
+/// SHA-256 over both power vectors of srs; parties compare fingerprints
+/// before trusting each other's commitments under their SRS
+pub fn srs_fingerprint<E: Pairing>(srs: &UniversalParams<E>) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    srs.powers_of_g.serialize_uncompressed(&mut bytes).unwrap();
+    srs.powers_of_h.serialize_uncompressed(&mut bytes).unwrap();
+    Sha256::digest(&bytes).into()
+}
+
+/// checks that proof opens comm to eval at x, i.e.
+/// e(comm - eval g, h) == e(proof, beta h - x h)
+pub fn kzg_check(pp: &UniversalParams<Curve>, comm: &G1, x: &F, eval: &F, proof: &G1) -> bool {
+    let g = pp.powers_of_g[0];
+    let h = pp.powers_of_h[0];
+    let beta_h = pp.powers_of_h[1];
+
+    let inner = comm.into_group() - g.mul(eval);
+    let lhs = <Curve as Pairing>::pairing(inner, h);
+
+    let inner = beta_h.into_group() - h.mul(x);
+    let rhs = <Curve as Pairing>::pairing(proof, inner);
+
+    lhs == rhs
+}
+
+/// commitment to f under pp, which must have more powers than f has
+/// coefficients
+pub fn commit(pp: &UniversalParams<Curve>, f: &DensePolynomial<F>) -> G1 {
+    let coeffs: Vec<_> = f.coeffs().iter().map(|c| c.into_bigint()).collect();
+    <Curve as Pairing>::G1::msm_bigint(&pp.powers_of_g[..coeffs.len()], &coeffs).into_affine()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DomainError {
+    /// A domain for zero points was requested.
+    EmptyDomain,
+
+    /// The field has no multiplicative subgroup large enough
+    /// to serve as an FFT domain for `size` points.
+    DomainTooLarge {
+        /// The number of points requested.
+        size: usize,
+    },
+
+    /// `size` points need a domain of exactly their number, but the
+    /// nearest FFT domain has `padded_size` points.
+    NeedsPadding {
+        /// The number of points requested.
+        size: usize,
+        /// The size of the FFT domain they would have to be padded to.
+        padded_size: usize,
+    },
+}
+
+/// the FFT domain for `size` points, and the number of points the caller
+/// must pad with: sizes the field cannot use directly are rounded up to
+/// the next valid FFT size
+pub fn domain_for<Fp: FftField>(size: usize) -> Result<(GeneralEvaluationDomain<Fp>, usize), DomainError> {
+    if size == 0 {
+        return Err(DomainError::EmptyDomain);
+    }
+
+    let domain = GeneralEvaluationDomain::<Fp>::new(size).ok_or(DomainError::DomainTooLarge { size })?;
+    Ok((domain, domain.size() - size))
+}
+
+/// domain_for points that cannot be padded, e.g. a deck whose cards are
+/// the roots of unity of the domain
+pub fn exact_domain_for<Fp: FftField>(size: usize) -> Result<GeneralEvaluationDomain<Fp>, DomainError> {
+    match domain_for(size)? {
+        (domain, 0) => Ok(domain),
+        (domain, _) => Err(DomainError::NeedsPadding { size, padded_size: domain.size() }),
+    }
+}
+
+/// a generator ω of the multiplicative subgroup of size n, or None if
+/// the field has no subgroup of exactly that size
+pub fn subgroup_generator(n: usize) -> Option<F> {
+    exact_domain_for::<F>(n).ok().map(|domain| domain.group_gen())
+}
+
+// v(X) and its commitment, by size and srs_fingerprint of the SRS
+#[cfg(feature = "std")]
+type DeckPolys = Vec<((usize, [u8; 32]), (DensePolynomial<F>, G1))>;
+
+#[cfg(feature = "std")]
+static DECK_POLYS: std::sync::Mutex<DeckPolys> = std::sync::Mutex::new(Vec::new());
+
+/// v(X) with v(ω^i) = ω^i on the subgroup of the given size, i.e. the
+/// unpermuted deck, and its commitment under pp; with `std` it is only
+/// computed once per size and SRS. Fails for a size without a subgroup
+/// of exactly that size
+#[cfg(feature = "std")]
+pub fn deck_poly(pp: &UniversalParams<Curve>, size: usize) -> Result<(DensePolynomial<F>, G1), DomainError> {
+    let key = (size, srs_fingerprint(pp));
+
+    if let Some((_, v)) = DECK_POLYS.lock().unwrap().iter().find(|(k, _)| *k == key) {
+        return Ok(v.clone());
+    }
+
+    let v = interpolate_deck_poly(pp, size)?;
+
+    DECK_POLYS.lock().unwrap().push((key, v.clone()));
+    Ok(v)
+}
+
+/// v(X) with v(ω^i) = ω^i on the subgroup of the given size, i.e. the
+/// unpermuted deck, and its commitment under pp. Fails for a size without
+/// a subgroup of exactly that size
+#[cfg(not(feature = "std"))]
+pub fn deck_poly(pp: &UniversalParams<Curve>, size: usize) -> Result<(DensePolynomial<F>, G1), DomainError> {
+    interpolate_deck_poly(pp, size)
+}
+
+fn interpolate_deck_poly(pp: &UniversalParams<Curve>, size: usize) -> Result<(DensePolynomial<F>, G1), DomainError> {
+    let domain = exact_domain_for::<F>(size)?;
+    let mut powers = Vec::with_capacity(size);
+    let mut cur = F::one();
+    for _ in 0..size {
+        powers.push(cur);
+        cur *= domain.group_gen();
+    }
+
+    let v = Evaluations::from_vec_and_domain(powers, domain).interpolate();
+    let v_com = commit(pp, &v);
+    Ok((v, v_com))
+}
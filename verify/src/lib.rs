@@ -0,0 +1,53 @@
+//! The verification core of pok3r: the proof structures and the checks
+//! on them, with nothing of the networked evaluator. Without the default
+//! `std` feature this crate is `no_std` (it only needs `alloc`), so a
+//! verifier can be embedded where there are no threads or sockets, e.g.
+//! in WASM.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use ark_ec::pairing::{Pairing, PairingOutput};
+
+pub mod combined;
+pub mod encryption;
+pub mod format;
+pub mod kzg;
+pub mod permutation;
+pub mod sigma;
+
+pub use combined::CombinedTranscript;
+pub use encryption::{is_valid_public_key, verify_masked_commitments, EncryptProof, EncryptTranscript};
+pub use format::{FieldEncoding, HashConfig, ProofFormatError};
+pub use kzg::{kzg_check, UniversalParams};
+pub use permutation::{
+    check_permutation_relation, verify_permutation_argument, verify_permutation_argument_in,
+    verify_permutation_relation,
+    PermutationProof, PERMUTATION_EVAL_PROOFS,
+};
+pub use sigma::{
+    local_verify_sigma_proof, verify_encryption_sigma, verify_encryption_sigma_with, verify_sigma, verify_sigma_proof_unbatched, SigmaProof,
+};
+
+pub type Curve = ark_bls12_377::Bls12_377;
+pub type F = ark_bls12_377::Fr;
+pub type G1 = <Curve as Pairing>::G1Affine;
+pub type G2 = <Curve as Pairing>::G2Affine;
+pub type Gt = PairingOutput<Curve>;
+
+/// number of positions the deck is permuted over
+pub const PERM_SIZE: usize = 256;
+
+/// prints why a check failed when there is somewhere to print it to
+#[cfg(feature = "std")]
+macro_rules! check_failed {
+    ($($arg:tt)*) => { std::println!($($arg)*) };
+}
+
+#[cfg(not(feature = "std"))]
+macro_rules! check_failed {
+    ($($arg:tt)*) => { let _ = core::format_args!($($arg)*); };
+}
+
+pub(crate) use check_failed;
@@ -0,0 +1,371 @@
+//! The proof that the committed deck f(X) is a permutation of the
+//! canonical deck v(X), and its verifier.
+
+use alloc::{format, string::String, vec, vec::Vec};
+use ark_ec::CurveGroup;
+use ark_ff::{Field, One};
+use ark_poly::{univariate::DensePolynomial, DenseUVPolynomial, Polynomial};
+use core::fmt;
+
+use crate::combined::CombinedTranscript;
+use crate::format::*;
+use crate::kzg::{self, kzg_check, DomainError, UniversalParams};
+use crate::{check_failed, Curve, F, G1};
+
+/// number of KZG openings in a PermutationProof, see PermutationProof::eval_proof
+pub const PERMUTATION_EVAL_PROOFS: usize = 5;
+
+/// what verifying a PermutationProof derives from it before checking openings
+struct PermutationChallenges {
+    /// generator of the subgroup of deck_size
+    w: F,
+    /// the unpermuted deck v(X)
+    v: DensePolynomial<F>,
+    hash1: F,
+    g_com: G1,
+    hash2: F,
+}
+
+/// PermutationProof is a structure for the permutation proofs
+#[derive(Clone)]
+pub struct PermutationProof {
+    pub y1: F,
+    pub y2: F,
+    pub y3: F,
+    pub y4: F,
+    pub y5: F,
+    pub pi_1: G1,
+    pub pi_2: G1,
+    pub pi_3: G1,
+    pub pi_4: G1,
+    pub pi_5: G1,
+    pub f_com: G1,
+    pub q_com: G1,
+    pub t_com: G1,
+    /// number of cards permuted, i.e. the size of the subgroup that f(X),
+    /// v(X) and the vanishing polynomial are defined over
+    pub deck_size: usize,
+    /// hash of the card each root of unity stands for, as the parties
+    /// agreed on it; it goes into y1, so parties that disagree on the
+    /// mapping produce a proof that does not verify
+    pub mapping_commitment: [u8; 32],
+}
+
+impl PermutationProof {
+    /// the opened values y1, .., y5, in the order check_permutation_relation
+    /// takes them
+    pub fn opened_values(&self) -> [F; 5] {
+        [self.y1, self.y2, self.y3, self.y4, self.y5]
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes);
+        write_len(&mut bytes, self.deck_size);
+        write_element(&mut bytes, &self.mapping_commitment);
+
+        for y in [&self.y1, &self.y2, &self.y3, &self.y4, &self.y5] {
+            write_element(&mut bytes, y);
+        }
+        for g in [&self.pi_1, &self.pi_2, &self.pi_3, &self.pi_4, &self.pi_5, &self.f_com, &self.q_com, &self.t_com] {
+            write_element(&mut bytes, g);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofFormatError> {
+        let reader = &mut &bytes[..];
+        read_header(reader)?;
+        let deck_size = read_len(reader)?;
+        let mapping_commitment = read_element(reader)?;
+
+        let proof = PermutationProof {
+            y1: read_element(reader)?,
+            y2: read_element(reader)?,
+            y3: read_element(reader)?,
+            y4: read_element(reader)?,
+            y5: read_element(reader)?,
+            pi_1: read_element(reader)?,
+            pi_2: read_element(reader)?,
+            pi_3: read_element(reader)?,
+            pi_4: read_element(reader)?,
+            pi_5: read_element(reader)?,
+            f_com: read_element(reader)?,
+            q_com: read_element(reader)?,
+            t_com: read_element(reader)?,
+            deck_size,
+            mapping_commitment,
+        };
+        finish(reader, proof)
+    }
+
+    pub fn fingerprint(&self) -> [u8; 16] {
+        fingerprint_of(&self.to_bytes())
+    }
+
+    /// re-derives the Fiat-Shamir challenges (y1, y2) from the published
+    /// proof, exactly as verify_permutation_argument does, so an auditor
+    /// can check they were not cherry-picked by the prover; fails for a
+    /// deck size without a subgroup of exactly that size
+    pub fn recompute_challenges(&self, pp: &UniversalParams<Curve>) -> Result<(F, F), DomainError> {
+        let v_com = permutation_v_com(pp, self.deck_size)?;
+
+        let y1 = permutation_challenge_1(&v_com, &self.f_com, &self.mapping_commitment);
+        let g_com = self.g_com(pp, &y1);
+        let y2 = permutation_challenge_2(&v_com, &self.f_com, &self.q_com, &self.t_com, &g_com);
+
+        Ok((y1, y2))
+    }
+
+    /// the bytes hashed into y1 and then into y2, labeled "y1/.." and
+    /// "y2/..", for diffing against another implementation's transcript;
+    /// fails like recompute_challenges
+    pub fn transcript_dump(&self, pp: &UniversalParams<Curve>) -> Result<Vec<(String, Vec<u8>)>, DomainError> {
+        let v_com = permutation_v_com(pp, self.deck_size)?;
+
+        let transcript_1 = permutation_transcript_1(&v_com, &self.f_com, &self.mapping_commitment);
+        let y1 = hash_transcript(&transcript_1, b"", 1)[0];
+        let g_com = self.g_com(pp, &y1);
+        let transcript_2 = permutation_transcript_2(&v_com, &self.f_com, &self.q_com, &self.t_com, &g_com);
+
+        let y1_chunks = transcript_1.into_iter().map(|(label, bytes)| (format!("y1/{}", label), bytes));
+        let y2_chunks = transcript_2.into_iter().map(|(label, bytes)| (format!("y2/{}", label), bytes));
+        Ok(y1_chunks.chain(y2_chunks).collect())
+    }
+
+    /// g(X) = f(X) + y1, so its commitment follows from f_com: the constant
+    /// polynomial y1 is committed in the same SRS basis as f_com (y1 times
+    /// pp.powers_of_g[0]), which makes the sum a commitment to f(X) + y1.
+    /// Verifiers derive g_com this way rather than reading it from the proof
+    pub fn g_com(&self, pp: &UniversalParams<Curve>, y1: &F) -> G1 {
+        let const_y1 = DensePolynomial::from_coefficients_vec(vec![*y1]);
+        (self.f_com + kzg::commit(pp, &const_y1)).into_affine()
+    }
+
+    /// the i-th KZG opening of the proof, as (commitment, point, value, proof),
+    /// which kzg_check accepts on its own:
+    /// 0: t(X) at w^{n-1}, 1: t(X) at hash2, 2: t(X) at hash2 / w,
+    /// 3: g(X) at hash2, 4: q(X) at hash2,
+    /// with w generating the subgroup of n = deck_size and hash2 the second
+    /// challenge. None for i >= PERMUTATION_EVAL_PROOFS, or a deck size pp
+    /// or the field does not support
+    pub fn eval_proof(&self, pp: &UniversalParams<Curve>, i: usize) -> Option<(G1, F, F, G1)> {
+        self.eval_proofs(pp)?.get(i).copied()
+    }
+
+    /// all openings of eval_proof, deriving the challenges once
+    pub fn eval_proofs(&self, pp: &UniversalParams<Curve>) -> Option<[(G1, F, F, G1); PERMUTATION_EVAL_PROOFS]> {
+        Some(self.openings(&self.challenges(pp, None)?))
+    }
+
+    fn challenges(
+        &self,
+        pp: &UniversalParams<Curve>,
+        mut transcript: Option<&mut CombinedTranscript>
+    ) -> Option<PermutationChallenges> {
+        // every size-dependent value below follows from the proof's deck size,
+        // which needs a subgroup of that size and as many SRS powers
+        if self.deck_size > pp.powers_of_g.len() {
+            return None;
+        }
+        let w = kzg::subgroup_generator(self.deck_size)?;
+
+        // v(X), the same one the prover committed to
+        let (v, v_com) = kzg::deck_poly(pp, self.deck_size).ok()?;
+
+        let hash1 = match transcript.as_deref_mut() {
+            Some(transcript) => transcript.permutation_challenge_1(&v_com, &self.f_com, &self.mapping_commitment),
+            None => permutation_challenge_1(&v_com, &self.f_com, &self.mapping_commitment),
+        };
+
+        // g_com is not part of the proof: deriving it as f_com + const(hash1)
+        // is what binds the opening of g(X) at hash2 to f(X) + hash1. A
+        // transmitted g_com would need exactly this equality check anyway,
+        // and any other g(X) would be opened against the wrong commitment
+        let g_com = self.g_com(pp, &hash1);
+
+        let hash2 = match transcript {
+            Some(transcript) => transcript.permutation_challenge_2(&self.q_com, &self.t_com, &g_com),
+            None => permutation_challenge_2(&v_com, &self.f_com, &self.q_com, &self.t_com, &g_com),
+        };
+
+        Some(PermutationChallenges { w, v, hash1, g_com, hash2 })
+    }
+
+    fn openings(&self, c: &PermutationChallenges) -> [(G1, F, F, G1); PERMUTATION_EVAL_PROOFS] {
+        let w63 = c.w.pow([self.deck_size as u64 - 1]);
+        [
+            (self.t_com, w63, self.y1, self.pi_1),
+            (self.t_com, c.hash2, self.y2, self.pi_2),
+            (self.t_com, c.hash2 / c.w, self.y3, self.pi_3),
+            (c.g_com, c.hash2, self.y4, self.pi_4),
+            (self.q_com, c.hash2, self.y5, self.pi_5),
+        ]
+    }
+}
+
+/// prints only a fingerprint and the size, not the group elements
+impl fmt::Debug for PermutationProof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PermutationProof")
+            .field("fingerprint", &fingerprint_hex(&self.fingerprint()))
+            .field("bytes", &self.to_bytes().len())
+            .finish()
+    }
+}
+
+/// commitment to v(X), which takes the value ω^i at ω^i for the
+/// deck_size-th root of unity ω
+fn permutation_v_com(pp: &UniversalParams<Curve>, deck_size: usize) -> Result<G1, DomainError> {
+    kzg::deck_poly(pp, deck_size).map(|(_, v_com)| v_com)
+}
+
+fn permutation_transcript_1(v_com: &G1, f_com: &G1, mapping_commitment: &[u8; 32]) -> Vec<(String, Vec<u8>)> {
+    vec![labeled("mapping", mapping_commitment), labeled("v_com", v_com), labeled("f_com", f_com)]
+}
+
+fn permutation_transcript_2(v_com: &G1, f_com: &G1, q_com: &G1, t_com: &G1, g_com: &G1) -> Vec<(String, Vec<u8>)> {
+    vec![
+        labeled("v_com", v_com),
+        labeled("f_com", f_com),
+        labeled("q_com", q_com),
+        labeled("t_com", t_com),
+        labeled("g_com", g_com),
+    ]
+}
+
+/// γ1 = FSHash(mapping, v_com, f_com), the challenge shifting f(X) into g(X)
+pub fn permutation_challenge_1(v_com: &G1, f_com: &G1, mapping_commitment: &[u8; 32]) -> F {
+    hash_transcript(&permutation_transcript_1(v_com, f_com, mapping_commitment), b"", 1)[0]
+}
+
+/// γ2 = FSHash(v_com, f_com, q_com, t_com, g_com), the evaluation point
+pub fn permutation_challenge_2(v_com: &G1, f_com: &G1, q_com: &G1, t_com: &G1, g_com: &G1) -> F {
+    hash_transcript(&permutation_transcript_2(v_com, f_com, q_com, t_com, g_com), b"", 1)[0]
+}
+
+pub fn verify_permutation_argument(
+    pp: &UniversalParams<Curve>,
+    perm_proof: &PermutationProof,
+) -> bool {
+    verify_permutation_argument_in(pp, perm_proof, None)
+}
+
+/// verify_permutation_argument with the challenges drawn from transcript,
+/// for a proof made as part of a combined proof; None for a proof on its own
+pub fn verify_permutation_argument_in(
+    pp: &UniversalParams<Curve>,
+    perm_proof: &PermutationProof,
+    transcript: Option<&mut CombinedTranscript>,
+) -> bool {
+    let mut b = true;
+
+    let challenges = match perm_proof.challenges(pp, transcript) {
+        Some(challenges) => challenges,
+        None => return false,
+    };
+
+    // Check all evaluation proofs
+    for (commitment, point, value, proof) in perm_proof.openings(&challenges) {
+        b &= kzg_check(pp, &commitment, &point, &value, &proof);
+    }
+
+    b &= check_permutation_relation(
+        perm_proof.deck_size,
+        challenges.v.evaluate(&challenges.hash2),
+        challenges.hash1,
+        challenges.hash2,
+        perm_proof.opened_values()
+    );
+
+    b
+}
+
+/// the checks of verify_permutation_argument other than the KZG openings:
+/// whether the opened values satisfy check_permutation_relation. With each
+/// of perm_proof.eval_proofs checked as well, e.g. batched with the openings
+/// of other proofs, this amounts to verify_permutation_argument
+pub fn verify_permutation_relation(
+    pp: &UniversalParams<Curve>,
+    perm_proof: &PermutationProof,
+) -> bool {
+    let challenges = match perm_proof.challenges(pp, None) {
+        Some(challenges) => challenges,
+        None => return false,
+    };
+
+    check_permutation_relation(
+        perm_proof.deck_size,
+        challenges.v.evaluate(&challenges.hash2),
+        challenges.hash1,
+        challenges.hash2,
+        perm_proof.opened_values()
+    )
+}
+
+/// the algebraic checks of verify_permutation_argument on the opened
+/// values, once their KZG proofs are checked:
+/// [y1, .., y5] = [t(w^{n-1}), t(hash2), t(hash2 / w), g(hash2), q(hash2)]
+/// for the n = deck_size cards, with v_at_hash2 = v(hash2)
+pub fn check_permutation_relation(
+    deck_size: usize,
+    v_at_hash2: F,
+    hash1: F,
+    hash2: F,
+    [y1, y2, y3, y4, y5]: [F; 5],
+) -> bool {
+    // Check 1 : y2 * (v(hash2) + hash1) - y3 * y4 = y5 * (hash2^k - 1)
+    let tmp1 = y2 * (v_at_hash2 + hash1);
+    let tmp2 = y3 * y4;
+    let tmp3 = y5 * (hash2.pow([deck_size as u64]) - F::one());
+
+    if tmp1 - tmp2 != tmp3 {
+        check_failed!("VerifyPerm - Check 1 failed");
+        return false;
+    }
+
+    // Check 2 : y1 = 1
+    if !y1.is_one() {
+        check_failed!("VerifyPerm - Check 2 failed");
+        return false;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deck_size 2 and hash2 = 3, so hash2^k - 1 = 8; with hash1 = 1 and
+    // v(hash2) = 2, y2 = 4 and y3 = y4 = 2 give 4 * 3 - 2 * 2 = 8 = y5 * 8
+    fn satisfied() -> [F; 5] {
+        [F::from(1u64), F::from(4u64), F::from(2u64), F::from(2u64), F::from(1u64)]
+    }
+
+    fn check(deck_size: usize, y: [F; 5]) -> bool {
+        check_permutation_relation(deck_size, F::from(2u64), F::from(1u64), F::from(3u64), y)
+    }
+
+    #[test]
+    fn test_permutation_relation_satisfied() {
+        assert!(check(2, satisfied()));
+    }
+
+    #[test]
+    fn test_permutation_relation_violated() {
+        // check 1: y5 * 8 no longer matches
+        let mut y = satisfied();
+        y[4] = F::from(2u64);
+        assert!(!check(2, y));
+
+        // check 2: t(w^{n-1}) must be 1
+        let mut y = satisfied();
+        y[0] = F::from(2u64);
+        assert!(!check(2, y));
+
+        // the relation holds only at the deck size it was computed for
+        assert!(!check(4, satisfied()));
+    }
+}
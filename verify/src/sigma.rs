@@ -0,0 +1,413 @@
+//! Sigma protocols over G1, G2 and Gt: statements, proofs and the
+//! verifier, with the encryption statement as an instance.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use ark_ec::{AffineRepr, CurveGroup, Group};
+use ark_std::ops::Mul;
+use ark_std::Zero;
+use core::fmt;
+
+use crate::format::*;
+use crate::{check_failed, F, G1, G2, Gt};
+
+/// SigmaProof is a structure for the sigma protocol proof
+#[derive(Clone)]
+pub struct SigmaProof {
+    pub a1: G1,
+    pub a2: G2,
+    pub a3: Gt,
+    pub x: F,
+    pub y: F,
+}
+
+impl SigmaProof {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes);
+
+        write_element(&mut bytes, &self.a1);
+        write_element(&mut bytes, &self.a2);
+        write_element(&mut bytes, &self.a3);
+        write_element(&mut bytes, &self.x);
+        write_element(&mut bytes, &self.y);
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofFormatError> {
+        let reader = &mut &bytes[..];
+        read_header(reader)?;
+
+        let proof = SigmaProof {
+            a1: read_element(reader)?,
+            a2: read_element(reader)?,
+            a3: read_element(reader)?,
+            x: read_element(reader)?,
+            y: read_element(reader)?,
+        };
+        finish(reader, proof)
+    }
+
+    pub fn fingerprint(&self) -> [u8; 16] {
+        fingerprint_of(&self.to_bytes())
+    }
+}
+
+/// prints only a fingerprint and the size, not the group elements
+impl fmt::Debug for SigmaProof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SigmaProof")
+            .field("fingerprint", &fingerprint_hex(&self.fingerprint()))
+            .field("bytes", &self.to_bytes().len())
+            .finish()
+    }
+}
+
+/// an element of any of the groups a sigma statement can be over; Gt
+/// elements are boxed, being many times the size of the others
+#[derive(Clone, Debug, PartialEq)]
+pub enum SigmaElement {
+    G1(G1),
+    G2(G2),
+    Gt(Box<Gt>),
+}
+
+impl SigmaElement {
+    /// self^s, in the group of self
+    pub fn scale(&self, s: &F) -> SigmaElement {
+        match self {
+            SigmaElement::G1(g) => SigmaElement::G1(g.mul(s).into_affine()),
+            SigmaElement::G2(g) => SigmaElement::G2(g.mul(s).into_affine()),
+            SigmaElement::Gt(g) => SigmaElement::Gt(Box::new(g.mul(s))),
+        }
+    }
+
+    /// self · other, or None if the two are in different groups
+    pub fn combine(&self, other: &SigmaElement) -> Option<SigmaElement> {
+        match (self, other) {
+            (SigmaElement::G1(a), SigmaElement::G1(b)) => Some(SigmaElement::G1((*a + *b).into_affine())),
+            (SigmaElement::G2(a), SigmaElement::G2(b)) => Some(SigmaElement::G2((*a + *b).into_affine())),
+            (SigmaElement::Gt(a), SigmaElement::Gt(b)) => Some(SigmaElement::Gt(Box::new(**a + **b))),
+            _ => None,
+        }
+    }
+
+    pub fn same_group(&self, other: &SigmaElement) -> bool {
+        core::mem::discriminant(self) == core::mem::discriminant(other)
+    }
+
+    pub fn as_g1(&self) -> Option<G1> {
+        match self { SigmaElement::G1(g) => Some(*g), _ => None }
+    }
+
+    pub fn as_g2(&self) -> Option<G2> {
+        match self { SigmaElement::G2(g) => Some(*g), _ => None }
+    }
+
+    pub fn as_gt(&self) -> Option<Gt> {
+        match self { SigmaElement::Gt(g) => Some(**g), _ => None }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            SigmaElement::G1(g) => write_element(&mut bytes, g),
+            SigmaElement::G2(g) => write_element(&mut bytes, g),
+            SigmaElement::Gt(g) => write_element(&mut bytes, &**g),
+        }
+        bytes
+    }
+
+    /// to_bytes behind a tag for the group, so that elements of different
+    /// groups never serialize alike
+    fn to_tagged_bytes(&self) -> Vec<u8> {
+        let tag = match self {
+            SigmaElement::G1(_) => 1u8,
+            SigmaElement::G2(_) => 2u8,
+            SigmaElement::Gt(_) => 3u8,
+        };
+        let mut bytes = vec![tag];
+        bytes.extend_from_slice(&self.to_bytes());
+        bytes
+    }
+}
+
+/// one relation of a sigma statement: the product over the terms
+/// (base, w) of base^{witness w} equals target
+#[derive(Clone, Debug)]
+pub struct SigmaRelation {
+    pub terms: Vec<(SigmaElement, usize)>,
+    pub target: SigmaElement,
+}
+
+impl SigmaRelation {
+    /// the left hand side with values in place of the witnesses, or None
+    /// if the relation is not well formed for values
+    pub fn evaluate(&self, values: &[F]) -> Option<SigmaElement> {
+        let mut terms = self.terms.iter().map(|(base, w)| values.get(*w).map(|v| base.scale(v)));
+        let mut acc = terms.next()??;
+        for term in terms {
+            acc = acc.combine(&term?)?;
+        }
+        Some(acc)
+    }
+}
+
+/// a set of relations, all over the same num_witnesses shared witnesses,
+/// proven together by prove_sigma and checked by verify_sigma
+#[derive(Clone, Debug)]
+pub struct SigmaStatement {
+    pub num_witnesses: usize,
+    pub relations: Vec<SigmaRelation>,
+}
+
+impl SigmaStatement {
+    pub fn new(num_witnesses: usize) -> Self {
+        SigmaStatement { num_witnesses, relations: Vec::new() }
+    }
+
+    /// adds the relation prod_j terms[j].0^{witness terms[j].1} = target
+    pub fn relation(mut self, terms: Vec<(SigmaElement, usize)>, target: SigmaElement) -> Self {
+        self.relations.push(SigmaRelation { terms, target });
+        self
+    }
+
+    /// every relation has at least one term, only refers to witnesses of
+    /// the statement, and has its bases in the group of its target
+    pub fn is_well_formed(&self) -> bool {
+        self.relations.iter().all(|relation| {
+            !relation.terms.is_empty() && relation.terms.iter().all(|(base, w)| {
+                *w < self.num_witnesses && base.same_group(&relation.target)
+            })
+        })
+    }
+
+    /// the statement as it is absorbed into the challenge: the number of
+    /// witnesses, then per relation its terms (base, witness) and target
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.num_witnesses as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.relations.len() as u64).to_le_bytes());
+        for relation in &self.relations {
+            bytes.extend_from_slice(&(relation.terms.len() as u64).to_le_bytes());
+            for (base, w) in &relation.terms {
+                bytes.extend_from_slice(&base.to_tagged_bytes());
+                bytes.extend_from_slice(&(*w as u64).to_le_bytes());
+            }
+            bytes.extend_from_slice(&relation.target.to_tagged_bytes());
+        }
+        bytes
+    }
+}
+
+/// proof for a SigmaStatement: a commitment per relation and a response
+/// per witness
+#[derive(Clone, Debug)]
+pub struct SigmaStatementProof {
+    pub commitments: Vec<SigmaElement>,
+    pub responses: Vec<F>,
+}
+
+impl SigmaStatementProof {
+    /// the Fiat-Shamir challenge for commitments to a proof of statement;
+    /// it absorbs the whole statement, bases and targets, so a prover
+    /// cannot pick a target after seeing the challenge
+    pub fn challenge(statement: &SigmaStatement, commitments: &[SigmaElement]) -> F {
+        let mut bytes = b"pok3r/sigma".to_vec();
+        bytes.extend_from_slice(&statement.to_bytes());
+        for commitment in commitments {
+            bytes.extend_from_slice(&commitment.to_tagged_bytes());
+        }
+        fs_hash(vec![&bytes], 1)[0]
+    }
+
+    /// the challenge of the encryption proof format, SigmaProof, which
+    /// hashes only the commitments; kept so existing encryption proofs
+    /// still verify. It is sound only because the targets of the
+    /// encryption statement are batched with scalars that hash the whole
+    /// transcript, and must not be used for any other statement
+    pub fn legacy_challenge(commitments: &[SigmaElement]) -> F {
+        Self::legacy_challenge_with(HashConfig::Sha256, commitments)
+    }
+
+    /// legacy_challenge drawn with hash, as in a CombinedTranscript made
+    /// with_hash
+    pub fn legacy_challenge_with(hash: HashConfig, commitments: &[SigmaElement]) -> F {
+        let bytes: Vec<Vec<u8>> = commitments.iter().map(|c| c.to_bytes()).collect();
+        fs_hash_with(hash, bytes.iter().map(|b| b.as_slice()).collect(), 1)[0]
+    }
+}
+
+impl From<&SigmaProof> for SigmaStatementProof {
+    fn from(sigma: &SigmaProof) -> Self {
+        SigmaStatementProof {
+            commitments: vec![
+                SigmaElement::G1(sigma.a1),
+                SigmaElement::G2(sigma.a2),
+                SigmaElement::Gt(Box::new(sigma.a3))
+            ],
+            responses: vec![sigma.x, sigma.y],
+        }
+    }
+}
+
+/// the other way round, for proofs of the shape of the encryption statement
+impl TryFrom<&SigmaStatementProof> for SigmaProof {
+    type Error = ProofFormatError;
+
+    fn try_from(proof: &SigmaStatementProof) -> Result<Self, Self::Error> {
+        match (proof.commitments.as_slice(), proof.responses.as_slice()) {
+            ([SigmaElement::G1(a1), SigmaElement::G2(a2), SigmaElement::Gt(a3)], [x, y]) => {
+                Ok(SigmaProof { a1: *a1, a2: *a2, a3: **a3, x: *x, y: *y })
+            },
+            _ => Err(ProofFormatError::Malformed),
+        }
+    }
+}
+
+/// checks, for every relation i of statement, that the relation holds
+/// with the responses in place of the witnesses and a_i * target^gamma
+/// in place of its target
+pub fn verify_sigma(statement: &SigmaStatement, proof: &SigmaStatementProof) -> bool {
+    let gamma = SigmaStatementProof::challenge(statement, &proof.commitments);
+    verify_sigma_with_challenge(statement, proof, &gamma)
+}
+
+/// verify_sigma for a proof in the encryption proof format, whose
+/// challenge is SigmaStatementProof::legacy_challenge
+pub fn verify_encryption_sigma(statement: &SigmaStatement, sigma: &SigmaProof) -> bool {
+    verify_encryption_sigma_with(HashConfig::Sha256, statement, sigma)
+}
+
+/// verify_encryption_sigma with the challenge drawn with hash, see
+/// SigmaStatementProof::legacy_challenge_with
+pub fn verify_encryption_sigma_with(hash: HashConfig, statement: &SigmaStatement, sigma: &SigmaProof) -> bool {
+    let proof = SigmaStatementProof::from(sigma);
+    let gamma = SigmaStatementProof::legacy_challenge_with(hash, &proof.commitments);
+    verify_sigma_with_challenge(statement, &proof, &gamma)
+}
+
+fn verify_sigma_with_challenge(statement: &SigmaStatement, proof: &SigmaStatementProof, gamma: &F) -> bool {
+    if !statement.is_well_formed()
+        || proof.commitments.len() != statement.relations.len()
+        || proof.responses.len() != statement.num_witnesses {
+        return false;
+    }
+
+    let mut b = true;
+    for (i, (relation, commitment)) in statement.relations.iter().zip(&proof.commitments).enumerate() {
+        let lhs = relation.evaluate(&proof.responses);
+        let rhs = relation.target.scale(gamma).combine(commitment);
+        if lhs.is_none() || lhs != rhs {
+            check_failed!("SigmaProof - Check {} fail", i + 1);
+            b = false;
+        }
+    }
+
+    b
+}
+
+/// the statement of the encryption sigma proof, over the witnesses
+/// x = sum_i s_i z_i and y = r:
+///   C^x = D_batch, g^y = c_1 and gt^x * e_batch^y = c2_batch
+pub fn encryption_sigma_statement(
+    c: &G1, d_batch: &G1, 
+    g: &G2, c_1: &G2,
+    e_batch: &Gt, c2_batch: &Gt
+) -> SigmaStatement {
+    SigmaStatement::new(2)
+        .relation(vec![(SigmaElement::G1(*c), 0)], SigmaElement::G1(*d_batch))
+        .relation(vec![(SigmaElement::G2(*g), 1)], SigmaElement::G2(*c_1))
+        .relation(
+            vec![(SigmaElement::Gt(Box::new(Gt::generator())), 0), (SigmaElement::Gt(Box::new(*e_batch)), 1)],
+            SigmaElement::Gt(Box::new(*c2_batch))
+        )
+}
+
+// Batch the bases before calling this
+// Verifies the encryption sigma proof, as an instance of verify_encryption_sigma
+pub fn local_verify_sigma_proof(
+    c: &G1, d_batch: &G1, 
+    g: &G2, c_1: &G2,
+    e_batch: &Gt, c2_batch: &Gt,
+    sigma: &SigmaProof
+) -> bool {
+    let statement = encryption_sigma_statement(c, d_batch, g, c_1, e_batch, c2_batch);
+    verify_encryption_sigma(&statement, sigma)
+}
+
+/// sum_i s_i * elements[i], e.g. D_batch of the masked commitments d_i
+pub fn batch_g1(elements: &[G1], s: &[F]) -> G1 {
+    let mut batch = G1::zero().into_group();
+    for (e_i, s_i) in elements.iter().zip(s) {
+        batch += e_i.mul(s_i);
+    }
+    batch.into_affine()
+}
+
+/// sum_i s_i * elements[i], e.g. c2_batch of the c2_i of the ciphertexts
+pub fn batch_gt(elements: &[Gt], s: &[F]) -> Gt {
+    let mut batch = Gt::zero();
+    for (e_i, s_i) in elements.iter().zip(s) {
+        batch += e_i.mul(s_i);
+    }
+    batch
+}
+
+/// local_verify_sigma_proof on the values of every card instead of their
+/// batches: the masked commitments d_is, e_per_id[i] = e(H(id_i), pk) and
+/// the c2_is of the ciphertexts, batched here with the scalars s. Fails
+/// unless there is one of each for every scalar
+#[allow(clippy::too_many_arguments)]
+pub fn verify_sigma_proof_unbatched(
+    c: &G1, d_is: &[G1],
+    g: &G2, c_1: &G2,
+    e_per_id: &[Gt], c2_is: &[Gt],
+    s: &[F],
+    sigma: &SigmaProof
+) -> bool {
+    if d_is.len() != s.len() || e_per_id.len() != s.len() || c2_is.len() != s.len() {
+        return false;
+    }
+
+    local_verify_sigma_proof(
+        c, &batch_g1(d_is, s),
+        g, c_1,
+        &batch_gt(e_per_id, s), &batch_gt(c2_is, s),
+        sigma
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::Field;
+
+    // a proof for g^w = target with the target picked after the
+    // challenge: commit to a, take gamma and solve for the target
+    fn forged(response: F, a: G1, gamma: F) -> SigmaElement {
+        let g = G1::generator();
+        let target = (g.mul(response) - a).mul(gamma.inverse().unwrap());
+        SigmaElement::G1(target.into_affine())
+    }
+
+    #[test]
+    fn test_challenge_binds_the_statement() {
+        let g = G1::generator();
+        let a = g.mul(F::from(7u64)).into_affine();
+        let response = F::from(11u64);
+        let proof = SigmaStatementProof {
+            commitments: vec![SigmaElement::G1(a)],
+            responses: vec![response],
+        };
+
+        // with only the commitments hashed, the forgery goes through
+        let gamma = SigmaStatementProof::legacy_challenge(&proof.commitments);
+        let statement = SigmaStatement::new(1)
+            .relation(vec![(SigmaElement::G1(g), 0)], forged(response, a, gamma));
+        assert!(verify_sigma_with_challenge(&statement, &proof, &gamma));
+
+        // the statement's challenge changes with the target it is solved for
+        assert!(!verify_sigma(&statement, &proof));
+    }
+}
@@ -0,0 +1,17 @@
+use std::process::Command;
+
+/// the crate must keep building without std, i.e. without its default
+/// features; a target dir of its own keeps this from waiting on the lock
+/// of the build running this test
+#[test]
+fn test_builds_without_default_features() {
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--no-default-features", "--manifest-path"])
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .arg("--target-dir")
+        .arg(concat!(env!("CARGO_TARGET_TMPDIR"), "/no_std"))
+        .status()
+        .expect("failed to run cargo");
+
+    assert!(status.success(), "pok3r-verify does not build with default-features = false");
+}